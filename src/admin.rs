@@ -0,0 +1,57 @@
+//! Server commands for whoever runs it, `ban`, `unban`, `bans`, `setmotd`
+//! and `setrules`. They're typed at the console, or sent from anywhere with
+//! `--send` and `--admin-key`, which the server only takes when the key
+//! matches `admin_key` in its config.
+
+use crate::{bans, config, notice};
+
+/// Runs `line` if it's an admin command, giving back what to show for it.
+pub fn run(line: &str) -> Option<String> {
+    let line = line.trim();
+    let (command, rest) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    };
+    // The rest keeps its case, being names and text for the players
+    let answer = match command.to_ascii_lowercase().as_str() {
+        "setmotd" => {
+            notice::set_motd(rest);
+            format!("MOTD set to {:?}", notice::motd())
+        },
+        "setrules" => {
+            notice::set_rules(rest);
+            format!("Rules set to {:?}", notice::rules())
+        },
+        "unban" => match bans::unban(rest) {
+            Ok(true) => format!("Unbanned {}", rest),
+            Ok(false) => format!("{} isn't banned", rest),
+            Err(e) => format!("Unable to save bans: {}", e),
+        },
+        "bans" if bans::list().is_empty() => "No bans".to_string(),
+        "bans" => bans::list().iter().map(|ban| format!("{} - {}", ban.key, ban.reason)).collect::<Vec<_>>().join("\n"),
+        "ban" => {
+            // ban KEY [REASON], the key being a name or address
+            let mut words = rest.splitn(2, char::is_whitespace);
+            let key = words.next().unwrap_or("");
+            let reason = words.next().map_or("banned by the server", str::trim);
+            if key.is_empty() {
+                "ban needs a name or address".to_string()
+            } else if let Err(e) = bans::ban(key, reason) {
+                format!("Unable to save bans: {}", e)
+            } else {
+                format!("Banned {}: {}", key, reason)
+            }
+        },
+        _ => return None,
+    };
+    Some(answer)
+}
+
+/// Whether `key` is the server's admin key. Servers without one take admin
+/// commands at the console only.
+pub fn authorized(key: &str) -> bool {
+    let expected = config::get().admin_key.as_bytes();
+    // Compared all the way through, so timing doesn't give away how much matched
+    !expected.is_empty() && expected.len() == key.len()
+        && expected.iter().zip(key.as_bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
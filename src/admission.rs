@@ -0,0 +1,88 @@
+//! Turning away new games and players once a server has as many as it's set
+//! up for, or is getting more requests than it can keep up with. Whoever is
+//! already playing keeps going, only `newgame` and `joingame` are refused.
+
+#[cfg(feature = "server")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "server")]
+use serde_json::json;
+
+#[cfg(feature = "server")]
+use crate::{clock, config};
+
+/// What every refusal starts with, for clients to tell them from a game ID.
+#[cfg(feature = "native")]
+pub const SERVER_FULL: &str = "Server full";
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
+pub struct Admission {
+    /// Start of the second requests are being counted for
+    window_start: Instant,
+    window_packets: u32,
+    /// Requests in the last whole second
+    last_rate: u32,
+}
+
+#[cfg(feature = "server")]
+impl Default for Admission {
+    fn default() -> Admission {
+        Admission {
+            window_start: clock::now(),
+            window_packets: 0,
+            last_rate: 0,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl Admission {
+
+    /// Counts a request that came in.
+    pub fn count_packet(&mut self) {
+        let elapsed = clock::since(self.window_start);
+        if elapsed >= Duration::from_secs(1) {
+            // Nothing at all for a while leaves nothing to go by
+            self.last_rate = if elapsed < Duration::from_secs(2) { self.window_packets } else { 0 };
+            self.window_packets = 0;
+            self.window_start = clock::now();
+        }
+        self.window_packets += 1;
+    }
+
+    /// Requests a second, the last whole second's or this one's so far if
+    /// it's already past that.
+    pub fn packet_rate(&self) -> u32 {
+        self.last_rate.max(self.window_packets)
+    }
+
+    /// Why another game, if `new_game`, or another player can't be let in
+    /// with `games` running and `players` playing, if they can't.
+    pub fn refusal(&self, games: usize, players: usize, new_game: bool) -> Option<String> {
+        let config = config::get();
+        if new_game && games >= config.max_games {
+            Some(format!("{}: {} games running", SERVER_FULL, games))
+        } else if players >= config.max_total_players {
+            Some(format!("{}: {} players", SERVER_FULL, players))
+        } else if self.packet_rate() >= config.max_packets_per_second {
+            Some(format!("{}: too busy right now", SERVER_FULL))
+        } else {
+            None
+        }
+    }
+
+    /// How much of each cap is in use, for `serverinfo`.
+    pub fn utilization(&self, games: usize, players: usize) -> serde_json::Value {
+        let config = config::get();
+        json!({
+            "games": games,
+            "max_games": config.max_games,
+            "players": players,
+            "max_players": config.max_total_players,
+            "packets_per_second": self.packet_rate(),
+            "max_packets_per_second": config.max_packets_per_second,
+            "full": self.refusal(games, players, true).is_some(),
+        })
+    }
+}
@@ -0,0 +1,150 @@
+//! Which clip a hero's sprite is playing, and where it is in it. Hero sheets
+//! only hold a walk cycle for each way the hero can face, so the other clips
+//! are made from those frames: idle breathes on the standing frame, a swing
+//! runs through the walk frames lunging towards where it lands, and the
+//! victory pose turns to the camera and hops.
+
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use crate::{pack, PLAYER_CELL_HEIGHT};
+
+/// How long a swing's clip plays, a little past the swing itself.
+const ATTACK_MILLIS: u64 = 250;
+/// How far the sprite lunges at the height of a swing, in pixels.
+const LUNGE_PIXELS: f32 = 5.0;
+/// Seconds per breath while standing still.
+const BREATH_SECONDS: f32 = 2.4;
+/// How much taller the sprite gets breathing in.
+const BREATH_STRETCH: f32 = 0.03;
+/// Seconds per hop of the victory pose, and how high the hops go.
+const HOP_SECONDS: f32 = 0.6;
+const HOP_PIXELS: f32 = 8.0;
+/// Sheet row facing the camera.
+const FACING_CAMERA: f32 = 0.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clip {
+    Idle,
+    Walk,
+    Attack,
+    Victory,
+}
+
+/// How the clip moves and stretches the sprite this frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Pose {
+    /// Sheet row to draw instead of the way the player faces
+    pub facing: Option<f32>,
+    pub dx: f32,
+    pub dy: f32,
+    /// Multiplies the sprite's height
+    pub stretch: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animator {
+    clip: Clip,
+    started: Instant,
+    last_frame: Instant,
+    /// Frames across a sheet
+    frames: f32,
+    frame_duration: Duration,
+}
+
+/// Played the way the pack, or the usual, says hero sheets are.
+impl Default for Animator {
+    fn default() -> Animator {
+        let animation = pack::animation();
+        Animator {
+            clip: Clip::Idle,
+            started: Instant::now(),
+            last_frame: Instant::now(),
+            frames: animation.frames as f32,
+            frame_duration: Duration::from_millis(animation.frame_millis),
+        }
+    }
+}
+
+impl Animator {
+
+    pub fn frames(&self) -> f32 {
+        self.frames
+    }
+
+    /// Switches to the clip for what the player is up to. Winning beats
+    /// everything, and a swing plays through once it's started.
+    pub fn choose(&mut self, walking: bool, attacked_at: Option<Instant>, won: bool) {
+        let swinging = attacked_at.is_some_and(|at| at.elapsed() < Duration::from_millis(ATTACK_MILLIS));
+        let next = if won {
+            Clip::Victory
+        } else if swinging {
+            Clip::Attack
+        } else if walking {
+            Clip::Walk
+        } else {
+            Clip::Idle
+        };
+        // Another swing starts the clip over
+        let swung_again = next == Clip::Attack && attacked_at.is_some_and(|at| at > self.started);
+        if next != self.clip || swung_again {
+            self.clip = next;
+            self.started = attacked_at.filter(|_| next == Clip::Attack).unwrap_or_else(Instant::now);
+            self.last_frame = Instant::now();
+        }
+    }
+
+    /// The frame after `frame` in the clip, as the fraction across the sheet
+    /// it starts at.
+    pub fn advance(&mut self, frame: f32) -> f32 {
+        match self.clip {
+            Clip::Idle => 0.0,
+            Clip::Attack => {
+                let progress = self.started.elapsed().as_millis() as f32 / ATTACK_MILLIS as f32;
+                (progress * self.frames).floor().min(self.frames - 1.0) / self.frames
+            },
+            // Marches on the spot, at half the pace of walking
+            Clip::Walk | Clip::Victory => {
+                let duration = if self.clip == Clip::Victory { self.frame_duration * 2 } else { self.frame_duration };
+                if self.last_frame.elapsed() <= duration {
+                    return frame
+                }
+                self.last_frame = Instant::now();
+                // Counted in whole frames, adding up thirds or sixths can fall just short of 1.0
+                let next = (frame * self.frames).round() + 1.0;
+                if next >= self.frames { 0.0 } else { next / self.frames }
+            },
+        }
+    }
+
+    /// How the sprite is moved for the clip, `facing` being the sheet row.
+    pub fn pose(&self, facing: f32) -> Pose {
+        let seconds = self.started.elapsed().as_secs_f32();
+        let still = Pose { facing: None, dx: 0.0, dy: 0.0, stretch: 1.0 };
+        match self.clip {
+            Clip::Walk => still,
+            Clip::Idle => {
+                let stretch = 1.0 + BREATH_STRETCH * (0.5 - 0.5 * (seconds / BREATH_SECONDS * 2.0 * PI).cos());
+                // Grows upwards, the feet staying put
+                Pose { dy: -(stretch - 1.0) * PLAYER_CELL_HEIGHT, stretch, ..still }
+            },
+            Clip::Attack => {
+                let progress = (seconds * 1000.0 / ATTACK_MILLIS as f32).min(1.0);
+                let lunge = LUNGE_PIXELS * (progress * PI).sin();
+                // Rows are down, up, left and right
+                let (dx, dy) = match (facing * 4.0).round() as u32 {
+                    1 => (0.0, -lunge),
+                    2 => (-lunge, 0.0),
+                    3 => (lunge, 0.0),
+                    _ => (0.0, lunge),
+                };
+                Pose { dx, dy, ..still }
+            },
+            Clip::Victory => Pose {
+                facing: Some(FACING_CAMERA),
+                dy: -HOP_PIXELS * (seconds / HOP_SECONDS * PI).sin().abs(),
+                ..still
+            },
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Textures the game needs, loaded up front behind a loading screen. Missing
+//! files are all reported together instead of panicking on the first one, and
+//! debug builds pick up files changed on disk while the game runs.
+//!
+//! Files are looked up in a `textures/` directory, which is added as a ggez
+//! resource path. With the `embedded-assets` feature the textures the game
+//! ships with are built in as well, so it still boots with them missing, and
+//! files on disk are still preferred so they can be swapped for others. An
+//! asset pack picked with `--pack` comes before either.
+
+#[cfg(feature = "client")]
+use ggez::{graphics, Context, GameError, GameResult};
+
+#[cfg(feature = "client")]
+use std::collections::HashMap;
+use std::env;
+#[cfg(feature = "client")]
+use std::fs;
+use std::path;
+use std::sync::OnceLock;
+#[cfg(feature = "client")]
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "client")]
+use crate::{pack, skin, SCREEN_SIZE};
+
+/// Textures the game can't run without, by name and file.
+#[cfg(feature = "client")]
+const REQUIRED_TEXTURES: [(&str, &str); 2] = [
+    (skin::DEFAULT_SHEET, "hero.png"),
+    ("potion", "potion.png"),
+];
+
+/// Copies of the textures the game ships with, for when the files are gone.
+#[cfg(all(feature = "client", feature = "embedded-assets"))]
+const BUILT_IN_TEXTURES: [(&str, &[u8]); 3] = [
+    ("hero.png", include_bytes!("../textures/hero.png")),
+    ("potion.png", include_bytes!("../textures/potion.png")),
+    ("tile.png", include_bytes!("../textures/tile.png")),
+];
+
+/// How often debug builds look for changed files.
+#[cfg(feature = "client")]
+const HOT_RELOAD_CHECK_MILLIS: u64 = 1000;
+
+static TEXTURES_DIR: OnceLock<path::PathBuf> = OnceLock::new();
+
+/// Reads textures from `dir` rather than looking for them, as `--assets`
+/// asks. Has to come before anything is loaded.
+#[cfg(feature = "client")]
+pub fn set_textures_dir(dir: path::PathBuf) {
+    let _ = TEXTURES_DIR.set(dir);
+}
+
+/// Where the textures are read from. Unless set, the first `textures`
+/// directory found in the source tree when run with cargo, next to the
+/// executable, in the platform's data directory or in the working directory.
+pub fn textures_dir() -> path::PathBuf {
+    TEXTURES_DIR.get_or_init(|| {
+        let mut candidates = vec![];
+        if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+            candidates.push(path::PathBuf::from(manifest_dir).join("textures"));
+        }
+        if let Some(exe_dir) = env::current_exe().ok().as_deref().and_then(path::Path::parent) {
+            candidates.push(exe_dir.join("textures"));
+        }
+        if let Some(data_dir) = data_dir() {
+            candidates.push(data_dir.join("textures"));
+        }
+        candidates.into_iter()
+            .find(|dir| dir.is_dir())
+            .unwrap_or_else(|| path::PathBuf::from("./textures"))
+    }).clone()
+}
+
+/// Where an installed game keeps its data, by the platform's conventions.
+fn data_dir() -> Option<path::PathBuf> {
+    let base = if cfg!(windows) {
+        path::PathBuf::from(env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        path::PathBuf::from(env::var_os("HOME")?).join("Library").join("Application Support")
+    } else {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(data_home) => path::PathBuf::from(data_home),
+            None => path::PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+        }
+    };
+    Some(base.join("item_wars"))
+}
+
+/// The built in copy of `file` when it couldn't be loaded with `error`, or
+/// the error again if there isn't one.
+#[cfg(all(feature = "client", feature = "embedded-assets"))]
+fn built_in(ctx: &mut Context, file: &str, error: GameError) -> GameResult<graphics::Image> {
+    match BUILT_IN_TEXTURES.iter().find(|(name, _)| *name == file) {
+        Some((_, bytes)) => {
+            println!("Unable to load {} ({}), using the built in one", file, error);
+            graphics::Image::from_bytes(ctx, bytes)
+        },
+        None => Err(error),
+    }
+}
+
+#[cfg(all(feature = "client", not(feature = "embedded-assets")))]
+fn built_in(_ctx: &mut Context, _file: &str, error: GameError) -> GameResult<graphics::Image> {
+    Err(error)
+}
+
+/// The texture in `file`, from the asset pack if it has one, else from disk
+/// or built in.
+#[cfg(feature = "client")]
+pub fn image(ctx: &mut Context, file: &str) -> GameResult<graphics::Image> {
+    match in_pack(file) {
+        Some(bytes) => graphics::Image::from_bytes(ctx, &bytes),
+        None => graphics::Image::new(ctx, format!("/{}", file)).or_else(|e| built_in(ctx, file, e)),
+    }
+}
+
+#[cfg(feature = "client")]
+fn in_pack(file: &str) -> Option<Vec<u8>> {
+    pack::get()?.read(&format!("textures/{}", file))
+}
+
+#[cfg(feature = "client")]
+fn modified(file: &str) -> Option<SystemTime> {
+    fs::metadata(textures_dir().join(file)).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct Assets {
+    /// Texture name to the texture, eg. `hero`, `hero_knight` or `potion`
+    pub textures: HashMap<String, graphics::Image>,
+    /// Texture name to its file and when that was last changed
+    files: HashMap<String, (String, Option<SystemTime>)>,
+    last_check: Instant,
+}
+
+#[cfg(feature = "client")]
+impl Assets {
+
+    /// Loads the required textures and any `hero_*.png` variants, drawing
+    /// progress as it goes.
+    pub fn load(ctx: &mut Context) -> GameResult<Assets> {
+        let mut wanted: Vec<(String, String)> = REQUIRED_TEXTURES.iter()
+            .map(|(name, file)| (name.to_string(), file.to_string()))
+            .collect();
+        // Hero variants are optional, anyone missing one sees a tinted default hero instead
+        let mut variants: Vec<String> = match fs::read_dir(textures_dir()) {
+            Ok(entries) => entries.flatten().map(|entry| entry.file_name().to_string_lossy().to_string()).collect(),
+            Err(_e) => vec![],
+        };
+        if let Some(pack) = pack::get() {
+            variants.extend(pack.files("textures"));
+        }
+        variants.retain(|file| file.starts_with("hero_") && file.ends_with(".png"));
+        variants.sort();
+        variants.dedup();
+        wanted.extend(variants.into_iter().map(|file| (file.trim_end_matches(".png").to_string(), file)));
+
+        let mut assets = Assets {
+            textures: HashMap::new(),
+            files: HashMap::new(),
+            last_check: Instant::now(),
+        };
+        let required = REQUIRED_TEXTURES.len();
+        for (index, (name, file)) in wanted.iter().enumerate() {
+            Assets::draw_progress(ctx, index, wanted.len(), file)?;
+            match image(ctx, file) {
+                Ok(texture) => {
+                    assets.textures.insert(name.clone(), texture);
+                    // Packs are swapped by restarting, only loose files are watched
+                    if in_pack(file).is_none() {
+                        assets.files.insert(name.clone(), (file.clone(), modified(file)));
+                    }
+                },
+                Err(e) if index < required => {
+                    return Err(GameError::ResourceLoadError(format!("Unable to load {}: {}", file, e)))
+                },
+                Err(e) => println!("Unable to load hero sheet {}: {}", file, e),
+            }
+        }
+        Assets::draw_progress(ctx, wanted.len(), wanted.len(), "")?;
+        Ok(assets)
+    }
+
+    /// Loading bar in the middle of the screen, with the file being loaded under it.
+    fn draw_progress(ctx: &mut Context, loaded: usize, total: usize, file: &str) -> GameResult<()> {
+        let (width, height) = SCREEN_SIZE;
+        graphics::clear(ctx, [0.0, 0.0, 0.0, 1.0].into());
+        let back = graphics::Rect::new(width / 4.0, height / 2.0 - 8.0, width / 2.0, 16.0);
+        let mut bar = graphics::MeshBuilder::new();
+        bar.rectangle(graphics::DrawMode::stroke(2.0), back, [1.0, 1.0, 1.0, 1.0].into())?;
+        if loaded > 0 {
+            let filled = graphics::Rect::new(back.x, back.y, back.w * loaded as f32 / total.max(1) as f32, back.h);
+            bar.rectangle(graphics::DrawMode::fill(), filled, [1.0, 0.85, 0.2, 1.0].into())?;
+        }
+        let bar = bar.build(ctx)?;
+        graphics::draw(ctx, &bar, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: format!("Loading {}", file),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 20.0, y: 20.0 }),
+            });
+        graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: back.x, y: back.y + 24.0 }, None);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        graphics::present(ctx)
+    }
+
+    /// Keeps an eye on `file` for `reload_changed`, loaded by someone else
+    /// to begin with.
+    pub fn watch(&mut self, name: &str, file: &str) {
+        self.files.insert(name.to_string(), (file.to_string(), modified(file)));
+    }
+
+    /// Reloads textures whose files changed since they were loaded, every
+    /// second or so in debug builds. Returns the names of the ones reloaded.
+    pub fn reload_changed(&mut self, ctx: &mut Context) -> Vec<String> {
+        if !cfg!(debug_assertions) || self.last_check.elapsed() < Duration::from_millis(HOT_RELOAD_CHECK_MILLIS) {
+            return vec![]
+        }
+        self.last_check = Instant::now();
+        let mut reloaded = vec![];
+        for (name, (file, loaded_at)) in self.files.iter_mut() {
+            let changed_at = modified(file);
+            if changed_at.is_none() || changed_at == *loaded_at {
+                continue;
+            }
+            // Half written files fail to load, they're tried again on the next check
+            match graphics::Image::new(ctx, format!("/{}", file)) {
+                Ok(texture) => {
+                    println!("Reloaded {}", file);
+                    self.textures.insert(name.clone(), texture);
+                    *loaded_at = changed_at;
+                    reloaded.push(name.clone());
+                },
+                Err(e) => println!("Unable to reload {}: {}", file, e),
+            }
+        }
+        reloaded
+    }
+}
@@ -0,0 +1,39 @@
+//! Sprites cut from shared sheets (tiles, potions, hero skins) are batched up
+//! per sheet and drawn together, one draw call per sheet rather than one per
+//! sprite.
+
+use ggez::{graphics, Context, GameResult};
+use graphics::spritebatch::SpriteBatch;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct SpriteBatches {
+    /// Sheet name to its batch, kept between frames so the buffers get reused
+    batches: HashMap<String, SpriteBatch>,
+    /// Sheets in the order they were first added to since the last draw
+    order: Vec<String>,
+}
+
+impl SpriteBatches {
+
+    /// Queues a sprite from `image`, known as `sheet`, to be drawn on the next `draw`.
+    pub fn add(&mut self, sheet: &str, image: &graphics::Image, param: graphics::DrawParam) {
+        let batch = self.batches.entry(sheet.to_string()).or_insert_with(|| SpriteBatch::new(image.clone()));
+        if batch.get_sprites().is_empty() {
+            self.order.push(sheet.to_string());
+        }
+        batch.add(param);
+    }
+
+    /// Draws everything queued, sheet by sheet, and empties the batches.
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        for sheet in self.order.drain(..) {
+            if let Some(batch) = self.batches.get_mut(&sheet) {
+                graphics::draw(ctx, batch, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                batch.clear();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+//! Players kept off a server, by name or address. Bans are made and lifted
+//! with the `ban` and `unban` admin commands, and kept in `bans.json` so
+//! they last across restarts. Anyone banned gets turned away from `joingame`
+//! with the reason, and taken out of any game they're in.
+
+use std::env;
+use std::fs;
+use std::path;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+static BANS: RwLock<Vec<Ban>> = RwLock::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    /// A player name or IP address
+    pub key: String,
+    pub reason: String,
+}
+
+/// Where bans are kept.
+pub fn bans_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("bans.json");
+        path
+    } else {
+        path::PathBuf::from("./bans.json")
+    }
+}
+
+/// Reads the bans kept from earlier runs, if there are any.
+pub fn load() -> Result<usize, String> {
+    let path = bans_path();
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_e) => return Ok(0),
+    };
+    let bans: Vec<Ban> = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let count = bans.len();
+    if let Ok(mut kept) = BANS.write() {
+        *kept = bans;
+    }
+    Ok(count)
+}
+
+fn save(bans: &[Ban]) -> Result<(), String> {
+    let path = bans_path();
+    let contents = serde_json::to_string_pretty(bans).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Bans `key` for `reason`, replacing any ban it already had.
+pub fn ban(key: &str, reason: &str) -> Result<(), String> {
+    let mut bans = BANS.write().map_err(|e| e.to_string())?;
+    bans.retain(|ban| !ban.key.eq_ignore_ascii_case(key));
+    bans.push(Ban { key: key.to_string(), reason: reason.to_string() });
+    save(&bans)
+}
+
+/// Lifts the ban on `key`, giving back whether there was one.
+pub fn unban(key: &str) -> Result<bool, String> {
+    let mut bans = BANS.write().map_err(|e| e.to_string())?;
+    let count = bans.len();
+    bans.retain(|ban| !ban.key.eq_ignore_ascii_case(key));
+    if bans.len() == count {
+        return Ok(false)
+    }
+    save(&bans).map(|_| true)
+}
+
+pub fn list() -> Vec<Ban> {
+    BANS.read().map(|bans| bans.clone()).unwrap_or_default()
+}
+
+/// The reason whichever of `keys` is banned was, if any of them is.
+pub fn reason<'a>(keys: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let bans = BANS.read().ok()?;
+    keys.into_iter()
+        .filter(|key| !key.is_empty())
+        .find_map(|key| bans.iter().find(|ban| ban.key.eq_ignore_ascii_case(key)))
+        .map(|ban| ban.reason.clone())
+}
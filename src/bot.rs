@@ -0,0 +1,175 @@
+//! Practice opponent, a small behavior tree the server runs in place of a
+//! second player.
+
+use crate::{Direction, NetworkedGame, PlayerState, Position};
+
+#[cfg(feature = "client")]
+pub const BOT_NAME: &str = "Bot";
+
+/// HP under which the bot stops fighting and looks after itself.
+const RETREAT_HP: i64 = 30;
+/// Potions closer than this are worth a detour mid fight.
+const POTION_DETOUR: f32 = 160.0;
+/// Close enough on an axis to stop steering along it, so it doesn't jitter.
+const STEER_DEAD_ZONE: f32 = 4.0;
+
+/// What the bot does this tick.
+#[derive(Debug, Default)]
+pub struct Decision {
+    /// Keys held down
+    pub dir: Direction,
+    /// Stand still facing this way and swing
+    pub aim: Option<Direction>,
+}
+
+/// What the nodes get to look at.
+struct Blackboard<'a> {
+    bot: &'a PlayerState,
+    /// Closest enemy still standing and visible
+    target: Option<&'a PlayerState>,
+    /// Closest potion on the ground
+    potion: Option<Position>,
+}
+
+enum Node {
+    /// Runs children until one succeeds
+    Selector(Vec<Node>),
+    /// Runs children until one fails
+    Sequence(Vec<Node>),
+    Condition(fn(&Blackboard) -> bool),
+    Action(fn(&Blackboard, &mut Decision) -> bool),
+}
+
+impl Node {
+    fn run(&self, board: &Blackboard, decision: &mut Decision) -> bool {
+        match self {
+            Node::Selector(children) => children.iter().any(|child| child.run(board, decision)),
+            Node::Sequence(children) => children.iter().all(|child| child.run(board, decision)),
+            Node::Condition(check) => check(board),
+            Node::Action(act) => act(board, decision),
+        }
+    }
+}
+
+/// Retreat when hurt, swing when close, grab nearby potions, chase otherwise.
+fn behavior() -> Node {
+    Node::Selector(vec![
+        Node::Sequence(vec![
+            Node::Condition(low_hp),
+            Node::Selector(vec![Node::Action(seek_potion), Node::Action(retreat)]),
+        ]),
+        Node::Sequence(vec![Node::Condition(target_in_reach), Node::Action(attack)]),
+        Node::Sequence(vec![Node::Condition(potion_nearby), Node::Action(seek_potion)]),
+        Node::Action(chase),
+    ])
+}
+
+/// Works out what the bot with `id` does next.
+pub fn think(game: &NetworkedGame, id: &str) -> Decision {
+    let mut decision = Decision::default();
+    let bot = match game.players.iter().find(|p| p.id == id) {
+        Some(bot) => bot,
+        None => return decision,
+    };
+    let target = game.players.iter()
+        .filter(|p| p.id != bot.id && p.hp > 0 && !p.is_invisible())
+        .filter(|p| bot.team.is_none() || p.team != bot.team)
+        .min_by(|a, b| distance(&bot.body, &a.body).partial_cmp(&distance(&bot.body, &b.body)).unwrap());
+    let potion = game.potions.iter()
+        .map(|potion| potion.pos)
+        .min_by(|a, b| distance(&bot.body, a).partial_cmp(&distance(&bot.body, b)).unwrap());
+    let board = Blackboard { bot, target, potion };
+    behavior().run(&board, &mut decision);
+    decision
+}
+
+fn center(pos: &Position) -> (f32, f32) {
+    (pos.x + pos.w / 2.0, pos.y + pos.h / 2.0)
+}
+
+fn distance(from: &Position, to: &Position) -> f32 {
+    let (fx, fy) = center(from);
+    let (tx, ty) = center(to);
+    ((tx - fx).powi(2) + (ty - fy).powi(2)).sqrt()
+}
+
+/// Keys to hold to head from `from` towards `to`.
+fn towards(from: &Position, to: &Position) -> Direction {
+    let (fx, fy) = center(from);
+    let (tx, ty) = center(to);
+    Direction {
+        up: ty < fy - STEER_DEAD_ZONE,
+        down: ty > fy + STEER_DEAD_ZONE,
+        left: tx < fx - STEER_DEAD_ZONE,
+        right: tx > fx + STEER_DEAD_ZONE,
+    }
+}
+
+/// Single direction facing `to`, along whichever axis it's further on.
+fn facing(from: &Position, to: &Position) -> Direction {
+    let (fx, fy) = center(from);
+    let (tx, ty) = center(to);
+    let (dx, dy) = (tx - fx, ty - fy);
+    Direction {
+        up: dy.abs() > dx.abs() && dy < 0.0,
+        down: dy.abs() > dx.abs() && dy >= 0.0,
+        left: dx.abs() >= dy.abs() && dx < 0.0,
+        right: dx.abs() >= dy.abs() && dx >= 0.0,
+    }
+}
+
+fn low_hp(board: &Blackboard) -> bool {
+    board.bot.hp < RETREAT_HP
+}
+
+fn target_in_reach(board: &Blackboard) -> bool {
+    match board.target {
+        Some(target) => board.bot.reach_towards(&facing(&board.bot.body, &target.body)) == target.body,
+        None => false,
+    }
+}
+
+fn potion_nearby(board: &Blackboard) -> bool {
+    board.potion.is_some_and(|potion| distance(&board.bot.body, &potion) < POTION_DETOUR)
+}
+
+fn attack(board: &Blackboard, decision: &mut Decision) -> bool {
+    match board.target {
+        Some(target) if board.bot.can_attack() => {
+            decision.aim = Some(facing(&board.bot.body, &target.body));
+            true
+        },
+        _ => false,
+    }
+}
+
+fn seek_potion(board: &Blackboard, decision: &mut Decision) -> bool {
+    match board.potion {
+        Some(potion) => {
+            decision.dir = towards(&board.bot.body, &potion);
+            true
+        },
+        None => false,
+    }
+}
+
+fn retreat(board: &Blackboard, decision: &mut Decision) -> bool {
+    match board.target {
+        Some(target) => {
+            let away = towards(&board.bot.body, &target.body);
+            decision.dir = Direction { up: away.down, down: away.up, left: away.right, right: away.left };
+            true
+        },
+        None => false,
+    }
+}
+
+fn chase(board: &Blackboard, decision: &mut Decision) -> bool {
+    match board.target {
+        Some(target) => {
+            decision.dir = towards(&board.bot.body, &target.body);
+            true
+        },
+        None => false,
+    }
+}
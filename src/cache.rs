@@ -0,0 +1,29 @@
+//! Text and meshes kept between frames, only rebuilt when what they show
+//! changes.
+
+use ggez::GameResult;
+
+/// A `T` built from a key, kept until it's asked for with a different key.
+#[derive(Debug, Clone)]
+pub struct Cached<K, T> {
+    built: Option<(K, T)>,
+}
+
+impl<K, T> Default for Cached<K, T> {
+    fn default() -> Cached<K, T> {
+        Cached { built: None }
+    }
+}
+
+impl<K: PartialEq, T> Cached<K, T> {
+
+    /// The value for `key`, calling `build` for it only if the key changed.
+    pub fn get(&mut self, key: K, build: impl FnOnce(&K) -> GameResult<T>) -> GameResult<&T> {
+        let stale = self.built.as_ref().is_none_or(|(built_for, _)| *built_for != key);
+        if stale {
+            let value = build(&key)?;
+            self.built = Some((key, value));
+        }
+        Ok(&self.built.as_ref().unwrap().1)
+    }
+}
@@ -0,0 +1,149 @@
+//! Camera mapping world coordinates onto the window, so maps can be larger
+//! than the screen.
+
+use ggez::{graphics, Context, GameResult};
+use graphics::Rect;
+use rand::Rng;
+
+use crate::Position;
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    /// World position of the top left corner of the view.
+    pub x: f32,
+    pub y: f32,
+    view_width: f32,
+    view_height: f32,
+    /// Seconds of shaking left
+    shake: f32,
+    /// How much bigger than life the world is drawn, only changed while spectating
+    zoom: f32,
+}
+
+/// What the camera does while spectating.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectatorView {
+    /// Panned around by hand
+    Free,
+    /// Stays on the player in this slot, counting from us
+    Follow(usize),
+}
+
+/// Furthest the view is knocked from where it should be at the start of a shake.
+const SHAKE_PIXELS: f32 = 6.0;
+const SHAKE_SECONDS: f32 = 0.25;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.0;
+/// Zoom gained or lost per mouse wheel notch
+const ZOOM_STEP: f32 = 0.1;
+
+impl Camera {
+
+    pub fn new(view_width: f32, view_height: f32) -> Camera {
+        Camera {
+            x: 0.0,
+            y: 0.0,
+            view_width,
+            view_height,
+            shake: 0.0,
+            zoom: 1.0,
+        }
+    }
+
+    /// World size of the view at the current zoom.
+    fn view_size(&self) -> (f32, f32) {
+        (self.view_width / self.zoom, self.view_height / self.zoom)
+    }
+
+    /// Centers the view on `target`, without showing anything past the map
+    /// edges. Maps smaller than the view are centered instead.
+    pub fn follow(&mut self, target: &Position, map_width: f32, map_height: f32) {
+        let (view_width, view_height) = self.view_size();
+        self.x = Camera::clamp_axis(target.x + target.w / 2.0 - view_width / 2.0, view_width, map_width);
+        self.y = Camera::clamp_axis(target.y + target.h / 2.0 - view_height / 2.0, view_height, map_height);
+    }
+
+    /// Moves the view by `dx`, `dy` world pixels, staying on the map.
+    pub fn pan(&mut self, dx: f32, dy: f32, map_width: f32, map_height: f32) {
+        let (view_width, view_height) = self.view_size();
+        self.x = Camera::clamp_axis(self.x + dx, view_width, map_width);
+        self.y = Camera::clamp_axis(self.y + dy, view_height, map_height);
+    }
+
+    /// Zooms in for positive `notches` of the mouse wheel and out for
+    /// negative ones, keeping the middle of the view where it is.
+    pub fn zoom_by(&mut self, notches: f32) {
+        let (view_width, view_height) = self.view_size();
+        let (center_x, center_y) = (self.x + view_width / 2.0, self.y + view_height / 2.0);
+        self.zoom = (self.zoom + notches * ZOOM_STEP).clamp(MIN_ZOOM, MAX_ZOOM);
+        let (view_width, view_height) = self.view_size();
+        self.x = center_x - view_width / 2.0;
+        self.y = center_y - view_height / 2.0;
+    }
+
+    pub fn reset_zoom(&mut self) {
+        self.zoom = 1.0;
+    }
+
+    /// Starts shaking, dying down over a quarter of a second.
+    pub fn shake(&mut self) {
+        self.shake = SHAKE_SECONDS;
+    }
+
+    pub fn update(&mut self, elapsed: f32) {
+        self.shake = (self.shake - elapsed).max(0.0);
+    }
+
+    fn clamp_axis(position: f32, view: f32, map: f32) -> f32 {
+        if map <= view {
+            (map - view) / 2.0
+        } else {
+            position.max(0.0).min(map - view)
+        }
+    }
+
+    /// Everything drawn until `pop` is positioned in world coordinates,
+    /// by moving the screen over the world. Hands back the screen
+    /// coordinates `pop` puts back.
+    pub fn push(&self, ctx: &mut Context) -> GameResult<Rect> {
+        let (mut x, mut y) = (self.x, self.y);
+        if self.shake > 0.0 {
+            let mut rng = rand::thread_rng();
+            let strength = SHAKE_PIXELS * self.shake / SHAKE_SECONDS;
+            x += rng.gen_range(-strength, strength);
+            y += rng.gen_range(-strength, strength);
+        }
+        let screen = graphics::screen_coordinates(ctx);
+        // Snap to whole pixels so tiles don't shimmer while scrolling
+        let world = Rect::new(
+            screen.x / self.zoom + x.round(),
+            screen.y / self.zoom + y.round(),
+            screen.w / self.zoom,
+            screen.h / self.zoom,
+        );
+        graphics::set_screen_coordinates(ctx, world)?;
+        Ok(screen)
+    }
+
+    pub fn pop(&self, ctx: &mut Context, screen: Rect) -> GameResult<()> {
+        graphics::set_screen_coordinates(ctx, screen)
+    }
+}
+
+/// Screen coordinates fitting a `width` x `height` virtual screen into a
+/// window of any size, scaled evenly and centered with bars on the sides
+/// that don't fit.
+pub fn letterbox(width: f32, height: f32, window_width: f32, window_height: f32) -> Rect {
+    let scale = (window_width / width).min(window_height / height);
+    let visible_width = window_width / scale;
+    let visible_height = window_height / scale;
+    Rect::new((width - visible_width) / 2.0, (height - visible_height) / 2.0, visible_width, visible_height)
+}
+
+/// Where a point in the window, in pixels as mouse events give them, is in
+/// screen coordinates.
+pub fn window_to_screen(ctx: &Context, x: f32, y: f32) -> (f32, f32) {
+    let screen = graphics::screen_coordinates(ctx);
+    let (width, height) = graphics::drawable_size(ctx);
+    (screen.x + x / width * screen.w, screen.y + y / height * screen.h)
+}
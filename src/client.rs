@@ -0,0 +1,6 @@
+// The game: the same code as the dedicated server, but with a window to play
+// in. With the `server` feature it can also host, for --practice and --listen.
+
+fn main() -> Result<(), String> {
+    item_wars::run(false)
+}
@@ -0,0 +1,27 @@
+//! The time as the rules of play see it. It's the real time, except while a
+//! recorded session is replayed, when it's the time each request and tick
+//! was recorded at. That way cooldowns, staggers, votes and move checks come
+//! out the same however fast the replay runs.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// The recorded time, on the thread replaying a session
+    static REPLAYED: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+pub fn now() -> Instant {
+    REPLAYED.with(|replayed| replayed.get()).unwrap_or_else(Instant::now)
+}
+
+/// How long it's been since `at`, like `Instant::elapsed`.
+pub fn since(at: Instant) -> Duration {
+    now().saturating_duration_since(at)
+}
+
+/// Stops the clock at `at` on this thread, or starts it again with `None`.
+#[cfg(feature = "server")]
+pub fn set(at: Option<Instant>) {
+    REPLAYED.with(|replayed| replayed.set(at));
+}
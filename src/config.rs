@@ -0,0 +1,298 @@
+//! Gameplay tunables, read from `config.toml` next to `textures/` once at
+//! startup so server operators and modders can change them without
+//! recompiling. Anything left out keeps its built in value, and single values
+//! can be overridden on the command line with `--set name=value`.
+//!
+//! Movement is checked by the server, so the movement values are handed out
+//! to everyone joining and clients predict with the server's, not their own.
+
+use std::env;
+use std::fs;
+use std::path;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+use crate::console::Registry;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+/// Movement values from the server we joined, if any.
+static SERVER_TUNABLES: RwLock<Option<Tunables>> = RwLock::new(None);
+
+/// Where the config is read from.
+pub fn config_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("config.toml");
+        path
+    } else {
+        path::PathBuf::from("./config.toml")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Speed before any acceleration, in pixels per update
+    pub move_speed: f32,
+    /// Acceleration built up by moving, added on to `move_speed`
+    pub starting_accel: f32,
+    pub top_accel: f32,
+    /// Acceleration gained per update while moving
+    pub accel: f32,
+    /// How fast acceleration is lost after letting go, on ground without
+    /// a friction of its own
+    pub friction: f32,
+    /// Upwards speed a jump starts with and the gravity pulling it back
+    pub jump_velocity: f32,
+    pub jump_gravity: f32,
+    /// Simulation steps a second, on the client and in movement checks
+    pub updates_per_second: f32,
+    pub server_tick_millis: u64,
+    /// How often the client asks for the whole world
+    pub world_sync_millis: u64,
+    /// Port connected to when `--server` is left out
+    pub port: u16,
+    /// Players in a game unless its rules say otherwise
+    pub max_players: usize,
+    /// How long an attack, dash or jump pressed a moment too early is held
+    /// on to, going off as soon as it can
+    pub input_buffer_millis: u64,
+    /// Games a server runs at once before `newgame` is turned away
+    pub max_games: usize,
+    /// Players across all of a server's games before joining is turned away
+    pub max_total_players: usize,
+    /// Requests a second a server takes before it stops letting in new games
+    /// and players, to leave room for the ones it has
+    pub max_packets_per_second: u32,
+    /// Message of the day, shown next to the server in listings and in the
+    /// lobby
+    pub motd: String,
+    /// House rules, shown in the lobby
+    pub server_rules: String,
+    /// Take games that clients move here after their server went away, as
+    /// a standby relay
+    pub accept_migrations: bool,
+    /// Key `--send` has to come with for admin commands, eg. `ban`. Left
+    /// empty, they're only taken at the server's console.
+    pub admin_key: String,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            move_speed: 1.0,
+            starting_accel: 0.4,
+            top_accel: 5.0,
+            accel: 0.2,
+            friction: 5.0,
+            jump_velocity: 0.2,
+            jump_gravity: 0.04,
+            updates_per_second: 60.0,
+            server_tick_millis: 16,
+            world_sync_millis: 100,
+            port: 7878,
+            max_players: 2,
+            input_buffer_millis: 100,
+            max_games: 64,
+            max_total_players: 128,
+            max_packets_per_second: 20_000,
+            motd: String::new(),
+            server_rules: String::new(),
+            accept_migrations: false,
+            admin_key: String::new(),
+        }
+    }
+}
+
+impl Config {
+
+    /// Reads `config.toml` if there is one, then the asset pack's stats, and
+    /// applies `overrides`, each `name=value`.
+    pub fn load<'a>(overrides: impl IntoIterator<Item = &'a str>) -> Result<Config, String> {
+        let path = config_path();
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+        let mut table: toml::value::Table = toml::from_str(&contents)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        // An asset pack's stats stand in for the file's, --set still wins
+        if let Some(pack) = crate::pack::get() {
+            table.extend(pack.manifest.stats.clone());
+        }
+        for set in overrides {
+            let (name, value) = match set.find('=') {
+                Some(index) => (set[..index].trim(), set[index + 1..].trim()),
+                None => return Err(format!("--set {} should be name=value", set)),
+            };
+            // Read as TOML, so numbers come out as numbers
+            let parsed: toml::value::Table = toml::from_str(&format!("value = {}", value))
+                .map_err(|e| format!("--set {}: {}", set, e))?;
+            table.insert(name.to_string(), parsed["value"].clone());
+        }
+        let config: Config = toml::Value::Table(table).try_into()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.tunables().validate()?;
+        if self.max_players < 2 || self.max_players > crate::rules::MAX_PLAYERS_LIMIT {
+            return Err(format!("max_players must be between 2 and {}", crate::rules::MAX_PLAYERS_LIMIT))
+        }
+        if self.server_tick_millis == 0 {
+            return Err("server_tick_millis must be above 0".to_string())
+        }
+        if self.max_games == 0 || self.max_total_players == 0 || self.max_packets_per_second == 0 {
+            return Err("max_games, max_total_players and max_packets_per_second must be above 0".to_string())
+        }
+        Ok(())
+    }
+
+    pub fn tunables(&self) -> Tunables {
+        Tunables {
+            move_speed: self.move_speed,
+            starting_accel: self.starting_accel,
+            top_accel: self.top_accel,
+            accel: self.accel,
+            friction: self.friction,
+            jump_velocity: self.jump_velocity,
+            jump_gravity: self.jump_gravity,
+            updates_per_second: self.updates_per_second,
+        }
+    }
+}
+
+/// The part of the config movement is simulated with, which has to be the
+/// same on the server and every client. See `Config` for what each does.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tunables {
+    pub move_speed: f32,
+    pub starting_accel: f32,
+    pub top_accel: f32,
+    pub accel: f32,
+    pub friction: f32,
+    pub jump_velocity: f32,
+    pub jump_gravity: f32,
+    pub updates_per_second: f32,
+}
+
+impl Tunables {
+
+    /// Also run on ones from a server, which could send anything.
+    pub fn validate(&self) -> Result<(), String> {
+        // Any faster and an update would be under a millisecond
+        if !self.updates_per_second.is_finite() || self.updates_per_second <= 0.0 || self.updates_per_second > 1000.0 {
+            return Err("updates_per_second must be above 0 and at most 1000".to_string())
+        }
+        if !self.jump_velocity.is_finite() || !self.jump_gravity.is_finite() || self.jump_velocity <= 0.0 || self.jump_gravity <= 0.0 {
+            return Err("jump_velocity and jump_gravity must be above 0".to_string())
+        }
+        Ok(())
+    }
+
+    /// The value called `name`, as in the config file.
+    #[cfg(feature = "client")]
+    pub fn get(&self, name: &str) -> Result<f32, String> {
+        match name {
+            "move_speed" => Ok(self.move_speed),
+            "starting_accel" => Ok(self.starting_accel),
+            "top_accel" => Ok(self.top_accel),
+            "accel" => Ok(self.accel),
+            "friction" => Ok(self.friction),
+            "jump_velocity" => Ok(self.jump_velocity),
+            "jump_gravity" => Ok(self.jump_gravity),
+            "updates_per_second" => Ok(self.updates_per_second),
+            _ => Err(format!("No movement value {}", name)),
+        }
+    }
+
+    /// Changes the value called `name`, for trying things out in the sandbox.
+    /// `validate` afterwards.
+    #[cfg(feature = "client")]
+    pub fn set(&mut self, name: &str, value: f32) -> Result<(), String> {
+        let field = match name {
+            "move_speed" => &mut self.move_speed,
+            "starting_accel" => &mut self.starting_accel,
+            "top_accel" => &mut self.top_accel,
+            "accel" => &mut self.accel,
+            "friction" => &mut self.friction,
+            "jump_velocity" => &mut self.jump_velocity,
+            "jump_gravity" => &mut self.jump_gravity,
+            "updates_per_second" => &mut self.updates_per_second,
+            _ => return Err(format!("No movement value {}", name)),
+        };
+        *field = value;
+        Ok(())
+    }
+
+    /// Peak of a single jump, what the launch velocity and gravity come to.
+    pub fn jump_height(&self) -> f32 {
+        self.jump_velocity * self.jump_velocity / (2.0 * self.jump_gravity)
+    }
+
+    /// Updates a single jump is in the air for, up to its peak and back.
+    #[cfg(feature = "server")]
+    pub fn jump_updates(&self) -> f32 {
+        2.0 * self.jump_velocity / self.jump_gravity
+    }
+
+    #[cfg(feature = "server")]
+    pub fn millis_per_update(&self) -> u64 {
+        (1000.0 / self.updates_per_second) as u64
+    }
+
+    /// Movement is simulated in steps of exactly this long, drawing runs as
+    /// fast as the display and interpolates in between.
+    #[cfg(feature = "client")]
+    pub fn simulation_step_seconds(&self) -> f32 {
+        1.0 / self.updates_per_second
+    }
+}
+
+/// Makes `config` the one `get` gives back. Only the first call counts.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The config loaded at startup, or the built in one before that.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Simulates movement with the server's values from now on.
+pub fn use_server_tunables(tunables: Tunables) {
+    *SERVER_TUNABLES.write().unwrap() = Some(tunables);
+}
+
+/// What to simulate movement with: the server's values once we've joined
+/// one, our own until then and on the server itself.
+pub fn tunables() -> Tunables {
+    SERVER_TUNABLES.read().unwrap().unwrap_or_else(|| get().tunables())
+}
+
+/// Changes a movement value live, `["friction", "0.5"]`, or says what it is
+/// when there's no value. Only for offline play, where the server shares the
+/// process and so picks the change up too.
+#[cfg(feature = "client")]
+pub fn tune(args: &[&str]) -> Result<String, String> {
+    let (name, value) = match args {
+        [name] => (*name, None),
+        [name, value] => (*name, Some(*value)),
+        _ => return Err("Usage: tune NAME [VALUE]".to_string()),
+    };
+    let mut tunables = tunables();
+    let value = match value {
+        Some(value) => value.parse::<f32>().map_err(|_| format!("{} isn't a number", value))?,
+        None => return tunables.get(name).map(|value| format!("{} is {}", name, value)),
+    };
+    tunables.set(name, value)?;
+    tunables.validate()?;
+    use_server_tunables(tunables);
+    Ok(format!("{} set to {}", name, value))
+}
+
+#[cfg(feature = "client")]
+pub fn console_commands<S>(registry: &mut Registry<S>) {
+    registry.register("tune", "tune NAME [VALUE]", |_, args| tune(args));
+}
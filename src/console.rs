@@ -0,0 +1,146 @@
+//! Developer console, opened with `controls::CONSOLE_KEY` in debug builds and
+//! the sandbox. Commands live in a registry any module can add to, taking
+//! the words typed after the command's name and answering with a line for
+//! the log. `help` lists what's there.
+
+use ggez::{graphics, Context, GameResult};
+
+use graphics::Rect;
+
+/// Lines of the log kept and shown.
+const LOG_KEPT: usize = 14;
+const LINE_HEIGHT: f32 = 14.0;
+
+/// Runs a command on `S` with the words after its name.
+pub type Run<S> = fn(&mut S, &[&str]) -> Result<String, String>;
+
+pub struct Command<S> {
+    pub name: &'static str,
+    /// How it's typed, eg. `tp X Y`
+    pub usage: &'static str,
+    pub run: Run<S>,
+}
+
+pub struct Registry<S> {
+    commands: Vec<Command<S>>,
+}
+
+impl<S> Default for Registry<S> {
+    fn default() -> Registry<S> {
+        Registry { commands: vec![] }
+    }
+}
+
+impl<S> Registry<S> {
+
+    /// Adds a command, replacing any other of the same name.
+    pub fn register(&mut self, name: &'static str, usage: &'static str, run: Run<S>) {
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(Command { name, usage, run });
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Command<S>> {
+        self.commands.iter().find(|command| command.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn usages(&self) -> Vec<&'static str> {
+        let mut usages: Vec<&'static str> = self.commands.iter().map(|command| command.usage).collect();
+        usages.sort_unstable();
+        usages
+    }
+}
+
+pub struct Console<S> {
+    pub registry: Registry<S>,
+    /// What's being typed, while it's open
+    pub input: Option<String>,
+    /// What was typed and answered, oldest first
+    log: Vec<String>,
+}
+
+impl<S> Console<S> {
+
+    pub fn new(registry: Registry<S>) -> Console<S> {
+        Console {
+            registry,
+            input: None,
+            log: vec![],
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn print(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_KEPT {
+            self.log.remove(0);
+        }
+    }
+
+    /// Takes a typed character, leaving out the tilde that opened it.
+    pub fn type_char(&mut self, character: char) {
+        if let Some(input) = &mut self.input {
+            if !character.is_control() && character != '`' && character != '~' {
+                input.push(character);
+            }
+        }
+    }
+
+    /// Works out what the typed line asks for, logging it. Gives back the
+    /// command to run and the words to run it with, or `None` when there's
+    /// nothing to run, the answer already being logged.
+    pub fn submit(&mut self) -> Option<(Run<S>, Vec<String>)> {
+        let line = self.input.as_mut().map(std::mem::take)?;
+        let words: Vec<String> = line.split_whitespace().map(|word| word.to_string()).collect();
+        let name = words.first()?.clone();
+        self.print(format!("> {}", line.trim()));
+        if name.eq_ignore_ascii_case("help") {
+            let usages = self.registry.usages().join(", ");
+            self.print(format!("help, {}", usages));
+            return None
+        }
+        match self.registry.find(&name) {
+            Some(command) => Some((command.run, words[1..].to_vec())),
+            None => {
+                self.print(format!("Unknown command {}, try help", name));
+                None
+            },
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let input = match &self.input {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let height = (LOG_KEPT + 1) as f32 * LINE_HEIGHT + 8.0;
+        let back = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            Rect::new(0.0, 0.0, crate::SCREEN_SIZE.0, height),
+            graphics::Color::new(0.0, 0.0, 0.0, 0.8),
+        )?;
+        graphics::draw(ctx, &back, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let prompt = format!("] {}_", input);
+        for (index, line) in self.log.iter().chain(std::iter::once(&prompt)).enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(graphics::Color::new(0.8, 1.0, 0.8, 1.0)),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 12.0, y: 12.0 }),
+                });
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: 6.0, y: 4.0 + index as f32 * LINE_HEIGHT }, None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )
+    }
+}
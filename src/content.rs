@@ -0,0 +1,60 @@
+//! Checking everyone in a game plays with the same content. Clients send
+//! hashes of their map, rules and asset pack when they're ready, and the
+//! server holds them up against its own map and rules and against each
+//! other's packs. A locally changed map or a client from another version
+//! otherwise only shows up as players quietly disagreeing on where things
+//! are, which rollback can't recover from.
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::MatchRules;
+use crate::{map, transfer};
+#[cfg(feature = "client")]
+use crate::pack;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContentHashes {
+    pub map: u64,
+    pub rules: u64,
+    /// 0 without an asset pack
+    pub pack: u64,
+}
+
+impl ContentHashes {
+
+    /// Hashes of map `map` and `rules` as we have them, and of our asset pack.
+    #[cfg(feature = "client")]
+    pub fn local(map: &str, rules: &MatchRules) -> ContentHashes {
+        ContentHashes {
+            pack: pack::get().map_or(0, |pack| pack.checksum),
+            ..ContentHashes::server(map, rules)
+        }
+    }
+
+    /// The same, for the server to check clients' against. Servers don't draw
+    /// anything, so whatever pack it has doesn't count.
+    pub fn server(map: &str, rules: &MatchRules) -> ContentHashes {
+        ContentHashes {
+            map: map::source(map).map_or(0, |source| transfer::checksum(source.as_bytes())),
+            rules: transfer::checksum(serde_json::to_string(rules).unwrap_or_default().as_bytes()),
+            pack: 0,
+        }
+    }
+
+    /// What about these differs from the `server`'s, and from the pack of
+    /// someone else already in, if anyone is.
+    #[cfg(feature = "server")]
+    pub fn differences(&self, server: &ContentHashes, others_pack: Option<u64>) -> Vec<&'static str> {
+        let mut differences = vec![];
+        if self.map != server.map {
+            differences.push("map");
+        }
+        if self.rules != server.rules {
+            differences.push("rules");
+        }
+        if others_pack.is_some_and(|pack| pack != self.pack) {
+            differences.push("asset pack");
+        }
+        differences
+    }
+}
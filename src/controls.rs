@@ -0,0 +1,336 @@
+//! Rebindable controls, read from `controls.json` next to `textures/` and
+//! changed on the settings screen.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+use std::time::{Duration, Instant};
+
+use ggez::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// Opens and closes the settings screen, can't be rebound.
+pub const SETTINGS_KEY: KeyCode = KeyCode::F1;
+/// Quits the game, can't be rebound.
+pub const QUIT_KEY: KeyCode = KeyCode::Escape;
+/// Toggles fullscreen, can't be rebound.
+pub const FULLSCREEN_KEY: KeyCode = KeyCode::F11;
+/// Shows and hides the performance overlay, can't be rebound.
+pub const PERF_KEY: KeyCode = KeyCode::F3;
+/// Saves a screenshot, can't be rebound.
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+/// Toggle the name plates, the box behind them and the HUD bars, for clean
+/// screenshots and streams. Can't be rebound.
+pub const NAME_PLATES_KEY: KeyCode = KeyCode::F5;
+pub const NAME_BOX_KEY: KeyCode = KeyCode::F6;
+pub const HUD_KEY: KeyCode = KeyCode::F7;
+/// Switches to the caster HUD and back, can't be rebound.
+pub const CASTER_HUD_KEY: KeyCode = KeyCode::F10;
+/// Opens the chat box and sends what's typed in it, can't be rebound.
+pub const CHAT_KEY: KeyCode = KeyCode::Return;
+/// Sandbox only: put a potion or a crate down in front of us, and switch
+/// infinite HP and MP on and off. Can't be rebound.
+pub const SPAWN_POTION_KEY: KeyCode = KeyCode::F8;
+pub const SPAWN_CRATE_KEY: KeyCode = KeyCode::F9;
+pub const GOD_MODE_KEY: KeyCode = KeyCode::F4;
+/// Opens the developer console in debug builds and the sandbox, can't be
+/// rebound.
+pub const CONSOLE_KEY: KeyCode = KeyCode::Grave;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Jump,
+    Attack,
+    Block,
+    Dash,
+    Sprint,
+    PlaceTrap,
+}
+
+/// Every action, in the order the settings screen lists them.
+pub const ACTIONS: [Action; 10] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Jump,
+    Action::Attack,
+    Action::Block,
+    Action::Dash,
+    Action::Sprint,
+    Action::PlaceTrap,
+];
+
+impl Action {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Jump => "Jump",
+            Action::Attack => "Attack",
+            Action::Block => "Block",
+            Action::Dash => "Dash",
+            Action::Sprint => "Sprint",
+            Action::PlaceTrap => "Place trap",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Action::Up => KeyCode::W,
+            Action::Down => KeyCode::S,
+            Action::Left => KeyCode::A,
+            Action::Right => KeyCode::D,
+            Action::Jump => KeyCode::Space,
+            Action::Attack => KeyCode::J,
+            Action::Block => KeyCode::K,
+            Action::Dash => KeyCode::LShift,
+            Action::Sprint => KeyCode::LControl,
+            Action::PlaceTrap => KeyCode::L,
+        }
+    }
+}
+
+/// Keys that can be bound, by the name they go by in `controls.json`.
+const KEY_NAMES: [(&str, KeyCode); 52] = [
+    ("A", KeyCode::A), ("B", KeyCode::B), ("C", KeyCode::C), ("D", KeyCode::D),
+    ("E", KeyCode::E), ("F", KeyCode::F), ("G", KeyCode::G), ("H", KeyCode::H),
+    ("I", KeyCode::I), ("J", KeyCode::J), ("K", KeyCode::K), ("L", KeyCode::L),
+    ("M", KeyCode::M), ("N", KeyCode::N), ("O", KeyCode::O), ("P", KeyCode::P),
+    ("Q", KeyCode::Q), ("R", KeyCode::R), ("S", KeyCode::S), ("T", KeyCode::T),
+    ("U", KeyCode::U), ("V", KeyCode::V), ("W", KeyCode::W), ("X", KeyCode::X),
+    ("Y", KeyCode::Y), ("Z", KeyCode::Z),
+    ("0", KeyCode::Key0), ("1", KeyCode::Key1), ("2", KeyCode::Key2), ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4), ("5", KeyCode::Key5), ("6", KeyCode::Key6), ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8), ("9", KeyCode::Key9),
+    ("Up", KeyCode::Up), ("Down", KeyCode::Down), ("Left", KeyCode::Left), ("Right", KeyCode::Right),
+    ("Space", KeyCode::Space), ("Return", KeyCode::Return), ("Tab", KeyCode::Tab), ("Back", KeyCode::Back),
+    ("LShift", KeyCode::LShift), ("RShift", KeyCode::RShift),
+    ("LControl", KeyCode::LControl), ("RControl", KeyCode::RControl),
+    ("LAlt", KeyCode::LAlt), ("RAlt", KeyCode::RAlt),
+    ("Comma", KeyCode::Comma), ("Period", KeyCode::Period),
+];
+
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(_, k)| *k == key).map(|(name, _)| *name)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, key)| *key)
+}
+
+/// Where the bindings are saved.
+pub fn controls_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("controls.json");
+        path
+    } else {
+        path::PathBuf::from("./controls.json")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    keys: HashMap<Action, KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            keys: ACTIONS.iter().map(|action| (*action, action.default_key())).collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+
+    /// Reads `controls.json`, eg. `{"attack":"F","dash":"RShift"}`. Actions it
+    /// leaves out get their default key unless something else took it, and
+    /// are left unbound if it did.
+    pub fn load() -> KeyBindings {
+        let contents = match fs::read_to_string(controls_path()) {
+            Ok(contents) => contents,
+            Err(_e) => return KeyBindings::default(),
+        };
+        let saved: HashMap<Action, String> = match serde_json::from_str(&contents) {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("Ignoring {}: {}", controls_path().display(), e);
+                return KeyBindings::default()
+            },
+        };
+        let mut bindings = KeyBindings { keys: HashMap::new() };
+        // Apply in a fixed order so conflicts always resolve the same way
+        for action in ACTIONS.iter() {
+            if let Some(name) = saved.get(action) {
+                let result = match key_from_name(name) {
+                    Some(key) => bindings.rebind(*action, key),
+                    None => Err(format!("unknown key {}", name)),
+                };
+                if let Err(e) = result {
+                    println!("Not binding {}: {}", action.title(), e);
+                }
+            }
+        }
+        for action in ACTIONS.iter() {
+            if bindings.key(*action).is_none() && bindings.action(action.default_key()).is_none() {
+                bindings.keys.insert(*action, action.default_key());
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let saved: HashMap<Action, &str> = self.keys.iter()
+            .filter_map(|(action, key)| key_name(*key).map(|name| (*action, name)))
+            .collect();
+        let contents = serde_json::to_string_pretty(&saved)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(controls_path(), contents)
+    }
+
+    pub fn key(&self, action: Action) -> Option<KeyCode> {
+        self.keys.get(&action).copied()
+    }
+
+    /// The action bound to `key`, if any.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.keys.iter().find(|(_, k)| **k == key).map(|(action, _)| *action)
+    }
+
+    /// Binds `key` to `action`, refusing reserved keys and ones another
+    /// action already uses.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) -> Result<(), String> {
+        let reserved = [
+            SETTINGS_KEY, QUIT_KEY, FULLSCREEN_KEY, PERF_KEY, SCREENSHOT_KEY, NAME_PLATES_KEY, NAME_BOX_KEY, HUD_KEY, CHAT_KEY,
+            SPAWN_POTION_KEY, SPAWN_CRATE_KEY, GOD_MODE_KEY, CONSOLE_KEY,
+        ];
+        if reserved.contains(&key) {
+            return Err("F1, F3-F9, F11, F12, Escape, Return and ` are reserved".to_string())
+        }
+        let name = key_name(key).ok_or_else(|| "that key can't be bound".to_string())?;
+        match self.action(key) {
+            Some(other) if other != action => Err(format!("{} is already used for {}", name, other.title())),
+            _ => {
+                self.keys.insert(action, key);
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Presses that couldn't go off yet, like an attack still cooling down, kept
+/// for a moment so they still count if they can by then.
+#[derive(Debug, Clone, Default)]
+pub struct InputBuffer {
+    pressed: Vec<(Action, Instant)>,
+}
+
+impl InputBuffer {
+
+    pub fn press(&mut self, action: Action) {
+        self.pressed.retain(|(pressed, _)| *pressed != action);
+        self.pressed.push((action, Instant::now()));
+    }
+
+    /// Whether `action` was pressed within `window` and not yet taken.
+    pub fn take(&mut self, action: Action, window: Duration) -> bool {
+        self.pressed.retain(|(_, at)| at.elapsed() < window);
+        match self.pressed.iter().position(|(pressed, _)| *pressed == action) {
+            Some(index) => {
+                self.pressed.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pressed.clear();
+    }
+}
+
+/// Two taps of a direction closer together than this dash that way.
+const DOUBLE_TAP_MILLIS: u64 = 250;
+
+/// Turns key presses into what the player does, going by the input
+/// preferences in the settings: whether sprint is held or toggled, whether
+/// holding jump keeps jumping, and whether double tapping a direction dashes.
+#[derive(Debug, Clone, Default)]
+pub struct InputMapper {
+    held: HashSet<Action>,
+    /// Sprint switched on by a tap, when sprint toggles
+    sprint_toggled: bool,
+    /// The last direction tapped and when, to spot a double tap
+    last_tap: Option<(Action, Instant)>,
+}
+
+impl InputMapper {
+
+    /// Takes a press of `action`, returning the actions it sets off: itself,
+    /// and a dash when it's the second tap of a direction. The key repeat of
+    /// one already held sets off nothing.
+    pub fn press(&mut self, action: Action, settings: &Settings) -> Vec<Action> {
+        if !self.held.insert(action) {
+            return vec![]
+        }
+        let mut fired = vec![action];
+        match action {
+            Action::Sprint if settings.toggle_sprint => self.sprint_toggled = !self.sprint_toggled,
+            Action::Up | Action::Down | Action::Left | Action::Right if settings.double_tap_dash => {
+                let double = self.last_tap.is_some_and(|(tapped, at)| {
+                    tapped == action && at.elapsed() < Duration::from_millis(DOUBLE_TAP_MILLIS)
+                });
+                if double {
+                    fired.push(Action::Dash);
+                    self.last_tap = None;
+                } else {
+                    self.last_tap = Some((action, Instant::now()));
+                }
+            },
+            _ => (),
+        }
+        fired
+    }
+
+    /// Takes a release of `action`, returning whether it was held.
+    pub fn release(&mut self, action: Action) -> bool {
+        self.held.remove(&action)
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    pub fn sprinting(&self, settings: &Settings) -> bool {
+        if settings.toggle_sprint {
+            self.sprint_toggled
+        } else {
+            self.held(Action::Sprint)
+        }
+    }
+
+    /// Whether jump is held down with jumping again on landing turned on.
+    pub fn repeating_jump(&self, settings: &Settings) -> bool {
+        settings.auto_jump && self.held(Action::Jump)
+    }
+
+    /// Lets go of everything, sprint toggled on included.
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.sprint_toggled = false;
+        self.last_tap = None;
+    }
+}
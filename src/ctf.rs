@@ -0,0 +1,104 @@
+//! Teams and flags for Capture the Flag.
+
+#[cfg(feature = "client")]
+use ggez::{graphics, Context, GameResult};
+#[cfg(feature = "client")]
+use graphics::Rect;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Position;
+
+#[cfg(feature = "native")]
+pub const TEAMS: usize = 2;
+#[cfg(feature = "native")]
+pub const TEAM_NAMES: [&str; TEAMS] = ["red", "blue"];
+#[cfg(feature = "client")]
+pub const TEAM_COLORS: [[f32; 4]; TEAMS] = [[0.9, 0.2, 0.2, 1.0], [0.2, 0.4, 1.0, 1.0]];
+
+/// Players per game, two on each team.
+#[cfg(feature = "native")]
+pub const CTF_PLAYERS: usize = 4;
+/// Captures that win the game.
+pub const CTF_SCORE_LIMIT: f32 = 3.0;
+/// Speed multiplier while carrying the enemy flag.
+pub const FLAG_CARRIER_SPEED: f32 = 0.7;
+
+#[cfg(feature = "native")]
+const FLAG_WIDTH: f32 = 24.0;
+#[cfg(feature = "native")]
+const FLAG_HEIGHT: f32 = 32.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flag {
+    pub team: usize,
+    /// Where the flag goes back to when returned or captured
+    pub home: Position,
+    pub pos: Position,
+    /// Number of the enemy player running off with it
+    pub carrier: Option<u32>,
+}
+
+impl Flag {
+
+    /// Flag standing in the middle of `base`.
+    #[cfg(feature = "server")]
+    pub fn new(team: usize, base: &Position) -> Flag {
+        let home = Position {
+            x: base.x + (base.w - FLAG_WIDTH) / 2.0,
+            y: base.y + (base.h - FLAG_HEIGHT) / 2.0,
+            w: FLAG_WIDTH,
+            h: FLAG_HEIGHT,
+        };
+        Flag {
+            team,
+            home,
+            pos: home,
+            carrier: None,
+        }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn at_home(&self) -> bool {
+        self.carrier.is_none() && self.pos.x == self.home.x && self.pos.y == self.home.y
+    }
+
+    #[cfg(feature = "server")]
+    pub fn return_home(&mut self) {
+        self.pos = self.home;
+        self.carrier = None;
+    }
+
+    /// Draws the flag on the ground. Carried flags are drawn by their carrier.
+    #[cfg(feature = "client")]
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.carrier.is_some() {
+            return Ok(())
+        }
+        draw_banner(ctx, self.pos.x, self.pos.y, self.team)
+    }
+}
+
+/// A pole with a pennant in the team's color, its top left corner at `x`, `y`.
+#[cfg(feature = "client")]
+pub fn draw_banner(ctx: &mut Context, x: f32, y: f32, team: usize) -> GameResult<()> {
+    let pole = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        Rect::new(x, y, 3.0, FLAG_HEIGHT),
+        [0.5, 0.35, 0.2, 1.0].into(),
+    )?;
+    graphics::draw(ctx, &pole, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+    let pennant = graphics::Mesh::new_polygon(
+        ctx,
+        graphics::DrawMode::fill(),
+        &[
+            ggez::mint::Point2 { x: x + 3.0, y },
+            ggez::mint::Point2 { x: x + FLAG_WIDTH, y: y + 7.0 },
+            ggez::mint::Point2 { x: x + 3.0, y: y + 14.0 },
+        ],
+        TEAM_COLORS[team % TEAMS].into(),
+    )?;
+    graphics::draw(ctx, &pennant, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Damage numbers that float up off whoever got hit and fade out. Critical
+//! hits get bigger, gold ones. Moved and aged by the systems in `ecs`.
+
+use ggez::{graphics, Context, GameResult};
+use hecs::World;
+
+use crate::ecs::{Lifetime, Position, Velocity};
+use crate::events::GameEvent;
+use crate::palette::{self, Palette};
+
+/// Seconds a number stays up.
+const LIFETIME: f32 = 0.9;
+/// Pixels per second they rise by.
+const RISE_SPEED: f32 = 40.0;
+const SIZE: f32 = 16.0;
+const CRIT_SIZE: f32 = 26.0;
+const COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const CRIT_COLOR: [f32; 3] = [1.0, 0.8, 0.2];
+
+/// Text for an entity, drawn centered on its position.
+#[derive(Debug, Clone, Copy)]
+struct DamageNumber {
+    amount: i64,
+    crit: bool,
+}
+
+/// Pops `amount` up centered on `x`, `y`.
+pub fn spawn(world: &mut World, x: f32, y: f32, amount: i64, crit: bool) {
+    world.spawn((
+        Position { x, y },
+        Velocity { x: 0.0, y: -RISE_SPEED },
+        Lifetime::new(LIFETIME),
+        DamageNumber { amount, crit },
+    ));
+}
+
+/// A number over whoever got hit.
+pub fn on_event(world: &mut World, event: &GameEvent) {
+    if let GameEvent::Hit { body, damage, crit, .. } = event {
+        spawn(world, body.x + body.w / 2.0, body.y, *damage, *crit);
+    }
+}
+
+pub fn draw(ctx: &mut Context, world: &World, palette: &Palette) -> GameResult<()> {
+    let mut query = world.query::<(&Position, &Lifetime, &DamageNumber)>();
+    let mut any = false;
+    for (_, (position, lifetime, number)) in query.iter() {
+        let (size, [r, g, b], label) = if number.crit {
+            (CRIT_SIZE, CRIT_COLOR, format!("{}!", number.amount))
+        } else {
+            (SIZE, COLOR, number.amount.to_string())
+        };
+        let alpha = 1.0 - lifetime.progress();
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: label,
+                color: Some(graphics::Color::new(r, g, b, alpha)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: size, y: size }),
+            });
+        let width = text.width(ctx);
+        palette::queue_text(ctx, &text, ggez::mint::Point2 { x: position.x - width / 2.0, y: position.y - size / 2.0 }, palette);
+        any = true;
+    }
+    if !any {
+        return Ok(())
+    }
+    graphics::draw_queued_text(
+            ctx,
+            graphics::DrawParam::new()
+                .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+            None,
+            graphics::FilterMode::Linear,
+        )?;
+    Ok(())
+}
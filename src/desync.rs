@@ -0,0 +1,88 @@
+//! Recent world snapshots from the server next to where we had ourselves at
+//! the time, written out for a look when the two drift apart. Prediction
+//! keeps us a little ahead of the server anyway, so only drift well past
+//! what latency explains counts as a desync.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use ggez::{Context, GameError, GameResult};
+use serde::Serialize;
+
+/// Snapshots kept, several seconds' worth at the default sync rate.
+const KEPT: usize = 120;
+/// Further apart than this, in pixels, is a desync.
+pub const DESYNC_PIXELS: f32 = 96.0;
+/// Desyncs soon after one that was dumped are most likely the same one.
+const DUMP_COOLDOWN_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize)]
+struct Entry<S, P> {
+    /// Since the history started
+    millis: u128,
+    /// As the server sent it
+    snapshot: S,
+    /// Ours when it came in
+    predicted: P,
+}
+
+#[derive(Serialize)]
+struct Dump<'a, S, P> {
+    reason: &'a str,
+    entries: &'a VecDeque<Entry<S, P>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotHistory<S, P> {
+    started: Instant,
+    /// Oldest first
+    entries: VecDeque<Entry<S, P>>,
+    last_dump: Option<Instant>,
+}
+
+impl<S, P> Default for SnapshotHistory<S, P> {
+    fn default() -> SnapshotHistory<S, P> {
+        SnapshotHistory {
+            started: Instant::now(),
+            entries: VecDeque::new(),
+            last_dump: None,
+        }
+    }
+}
+
+impl<S: Serialize, P: Serialize> SnapshotHistory<S, P> {
+
+    pub fn record(&mut self, snapshot: S, predicted: P) {
+        self.entries.push_back(Entry { millis: self.started.elapsed().as_millis(), snapshot, predicted });
+        if self.entries.len() > KEPT {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The last snapshot that came in.
+    pub fn latest(&self) -> Option<&S> {
+        self.entries.back().map(|entry| &entry.snapshot)
+    }
+
+    /// Whether being `distance` pixels from the server is a desync that
+    /// hasn't just been dumped.
+    pub fn is_new_desync(&self, distance: f32) -> bool {
+        distance > DESYNC_PIXELS && self.last_dump.is_none_or(|at| at.elapsed() >= Duration::from_secs(DUMP_COOLDOWN_SECONDS))
+    }
+
+    /// Writes everything kept to `desyncs/` in the user config directory,
+    /// giving back where.
+    pub fn dump(&mut self, ctx: &mut Context, reason: &str) -> GameResult<String> {
+        self.last_dump = Some(Instant::now());
+        let taken = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |t| t.as_millis());
+        let file = format!("/desyncs/desync_{}.json", taken);
+        let contents = serde_json::to_vec_pretty(&Dump { reason, entries: &self.entries })
+            .map_err(|e| GameError::CustomError(e.to_string()))?;
+        ggez::filesystem::create_dir(ctx, "/desyncs")?;
+        ggez::filesystem::create(ctx, &file)?.write_all(&contents)?;
+        Ok(ggez::filesystem::user_config_dir(ctx).join(file.trim_start_matches('/')).display().to_string())
+    }
+}
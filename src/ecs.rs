@@ -0,0 +1,73 @@
+//! Entities, kept in a hecs `World` and run by systems rather than each kind
+//! having its own list and update loop. So far it only holds what the client
+//! makes for itself, particles and damage numbers, which share moving and
+//! aging here and are drawn by their own modules.
+//!
+//! Gameplay state hasn't moved over yet. Players, crates, items, traps and
+//! flags are still plain serde structs in `NetworkedGame`, sent whole in
+//! every world snapshot, recorded and replayed, and stepped by `sim` on the
+//! server and in the client's prediction. Moving them here needs replicated
+//! components to serialize in a stable order and movement, collision, pickup
+//! and drawing rewritten as systems on both sides, which is still to do.
+
+use hecs::{Entity, World};
+
+/// Where an entity is, in world pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Pixels per second.
+#[derive(Debug, Clone, Copy)]
+pub struct Velocity {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Entities with one of these are despawned once they're `lifetime` old.
+#[derive(Debug, Clone, Copy)]
+pub struct Lifetime {
+    /// Seconds since it was spawned
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+impl Lifetime {
+
+    pub fn new(lifetime: f32) -> Lifetime {
+        Lifetime { age: 0.0, lifetime }
+    }
+
+    /// How far through its life it is, 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+}
+
+/// Runs every system that doesn't draw, `elapsed` seconds on.
+pub fn update(world: &mut World, elapsed: f32) {
+    movement(world, elapsed);
+    aging(world, elapsed);
+}
+
+fn movement(world: &mut World, elapsed: f32) {
+    for (_, (position, velocity)) in world.query_mut::<(&mut Position, &Velocity)>() {
+        position.x += velocity.x * elapsed;
+        position.y += velocity.y * elapsed;
+    }
+}
+
+fn aging(world: &mut World, elapsed: f32) {
+    let mut expired: Vec<Entity> = vec![];
+    for (entity, lifetime) in world.query_mut::<&mut Lifetime>() {
+        lifetime.age += elapsed;
+        if lifetime.age >= lifetime.lifetime {
+            expired.push(entity);
+        }
+    }
+    for entity in expired {
+        let _ = world.despawn(entity);
+    }
+}
@@ -0,0 +1,220 @@
+//! Gear that occupies an equipment slot and changes a player's stats. Worn
+//! gear is drawn over the hero sprite so the opponent can see it.
+
+#[cfg(feature = "client")]
+use ggez::{graphics, Context, GameResult};
+#[cfg(feature = "client")]
+use graphics::Rect;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Position;
+#[cfg(feature = "client")]
+use crate::ITEM_BLINK_SECONDS;
+
+#[cfg(feature = "server")]
+pub const EQUIPMENT_WIDTH: f32 = 28.0;
+#[cfg(feature = "server")]
+pub const EQUIPMENT_HEIGHT: f32 = 28.0;
+
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    Weapon,
+    Offhand,
+    Feet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EquipmentKind {
+    Sword,
+    Shield,
+    Boots,
+}
+
+#[cfg(feature = "server")]
+pub const ALL_EQUIPMENT: [EquipmentKind; 3] = [EquipmentKind::Sword, EquipmentKind::Shield, EquipmentKind::Boots];
+
+impl EquipmentKind {
+
+    #[cfg(feature = "server")]
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentKind::Sword => "Sword",
+            EquipmentKind::Shield => "Shield",
+            EquipmentKind::Boots => "Boots",
+        }
+    }
+
+    #[cfg(feature = "native")]
+    pub fn slot(&self) -> Slot {
+        match self {
+            EquipmentKind::Sword => Slot::Weapon,
+            EquipmentKind::Shield => Slot::Offhand,
+            EquipmentKind::Boots => Slot::Feet,
+        }
+    }
+
+    pub fn strength_bonus(&self) -> i64 {
+        match self {
+            EquipmentKind::Sword => 4,
+            _ => 0,
+        }
+    }
+
+    /// Taken off every hit.
+    #[cfg(feature = "server")]
+    pub fn defense_bonus(&self) -> i64 {
+        match self {
+            EquipmentKind::Shield => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            EquipmentKind::Boots => 1.15,
+            _ => 1.0,
+        }
+    }
+
+    /// Draws the item with its top left corner at `x`, `y`, `size` pixels square.
+    #[cfg(feature = "client")]
+    pub fn draw_icon(&self, ctx: &mut Context, x: f32, y: f32, size: f32) -> GameResult<()> {
+        match self {
+            EquipmentKind::Sword => {
+                let blade = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(x + size * 0.42, y, size * 0.16, size * 0.7),
+                    [0.85, 0.85, 0.9, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &blade, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                let guard = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(x + size * 0.2, y + size * 0.65, size * 0.6, size * 0.1),
+                    [0.6, 0.45, 0.1, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &guard, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                let hilt = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(x + size * 0.44, y + size * 0.75, size * 0.12, size * 0.25),
+                    [0.4, 0.25, 0.1, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &hilt, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            },
+            EquipmentKind::Shield => {
+                let center = ggez::mint::Point2 { x: x + size / 2.0, y: y + size / 2.0 };
+                let face = graphics::Mesh::new_circle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    center,
+                    size / 2.0,
+                    0.5,
+                    [0.2, 0.35, 0.8, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &face, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                let rim = graphics::Mesh::new_circle(
+                    ctx,
+                    graphics::DrawMode::stroke(2.0),
+                    center,
+                    size / 2.0,
+                    0.5,
+                    [0.8, 0.7, 0.2, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &rim, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            },
+            EquipmentKind::Boots => {
+                for boot_x in [x + size * 0.05, x + size * 0.55].iter() {
+                    let shaft = graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::fill(),
+                        Rect::new(*boot_x, y + size * 0.3, size * 0.25, size * 0.5),
+                        [0.45, 0.25, 0.1, 1.0].into(),
+                    )?;
+                    graphics::draw(ctx, &shaft, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                    let foot = graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::fill(),
+                        Rect::new(*boot_x, y + size * 0.75, size * 0.4, size * 0.2),
+                        [0.35, 0.2, 0.05, 1.0].into(),
+                    )?;
+                    graphics::draw(ctx, &foot, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Draws the worn item over the hero sprite standing at `body`.
+    #[cfg(feature = "client")]
+    pub fn draw_worn(&self, ctx: &mut Context, body: &Position) -> GameResult<()> {
+        match self.slot() {
+            Slot::Weapon => self.draw_icon(ctx, body.x + body.w - 6.0, body.y + 4.0, 18.0),
+            Slot::Offhand => self.draw_icon(ctx, body.x - 10.0, body.y + 10.0, 16.0),
+            Slot::Feet => self.draw_icon(ctx, body.x + 6.0, body.y + body.h - 16.0, 20.0),
+        }
+    }
+}
+
+/// Gear lying on the ground.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquipmentDrop {
+    pub pos: Position,
+    pub kind: EquipmentKind,
+    /// Seconds since it appeared
+    pub age: f32,
+    /// Seconds it lies around before disappearing, forever when `None`
+    pub lifetime: Option<f32>,
+}
+
+impl EquipmentDrop {
+
+    #[cfg(feature = "server")]
+    pub fn new(x: f32, y: f32, kind: EquipmentKind) -> EquipmentDrop {
+        EquipmentDrop {
+            pos: Position { x, y, w: EQUIPMENT_WIDTH, h: EQUIPMENT_HEIGHT },
+            kind,
+            age: 0.0,
+            lifetime: None,
+        }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn expired(&self) -> bool {
+        self.lifetime.is_some_and(|lifetime| self.age >= lifetime)
+    }
+
+    /// Blinks on and off when about to disappear, like potions do.
+    #[cfg(feature = "client")]
+    fn hidden_by_blink(&self) -> bool {
+        match self.lifetime {
+            Some(lifetime) if lifetime - self.age < ITEM_BLINK_SECONDS => {
+                let millis = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |t| t.as_millis());
+                millis % 400 < 200
+            },
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.hidden_by_blink() {
+            return Ok(())
+        }
+        let glow = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            ggez::mint::Point2 { x: self.pos.x + self.pos.w / 2.0, y: self.pos.y + self.pos.h / 2.0 },
+            self.pos.w / 2.0 + 4.0,
+            0.5,
+            [1.0, 1.0, 0.6, 0.3].into(),
+        )?;
+        graphics::draw(ctx, &glow, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        self.kind.draw_icon(ctx, self.pos.x, self.pos.y, self.pos.w)
+    }
+}
@@ -0,0 +1,62 @@
+//! What happened in the game, as far as the feedback goes. Working out what
+//! happened, mostly from world snapshots, only emits these, and the sounds,
+//! particles, damage numbers, kill feed, chat and camera each react to the
+//! ones they care about once a frame. New feedback hooks in there rather than
+//! into the gameplay code.
+
+use crate::Position;
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// Someone lost HP
+    Hit {
+        body: Position,
+        damage: i64,
+        crit: bool,
+        /// Down to 0 HP
+        fatal: bool,
+        on_us: bool,
+    },
+    /// Someone got hurt as our swing went through, taken as it landing
+    HitLanded,
+    Death {
+        name: String,
+        /// The blow that ended the game
+        final_blow: bool,
+    },
+    /// Our combo went up to this
+    Combo(u32),
+    /// A potion was picked up from here
+    Pickup(Position),
+    /// A line for the kill feed
+    Feed(String),
+    /// Shown in the middle of the screen
+    Announcement(String),
+    /// Everyone's ready and the fighting starts
+    RoundStart,
+    /// The opponent's checksum didn't match our simulation of them, and
+    /// they were put back where the server has them
+    Corrected,
+    ChatReceived {
+        from: String,
+        text: String,
+        spectator: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventBus {
+    queue: Vec<GameEvent>,
+}
+
+impl EventBus {
+
+    pub fn emit(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+
+    /// Everything emitted since the last time, oldest first.
+    pub fn drain(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.queue)
+    }
+}
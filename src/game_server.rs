@@ -0,0 +1,1329 @@
+//! The server: the games it hosts, the requests it answers over UDP and
+//! WebSocket, and the ticks that move every game along.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "client")]
+use crossbeam_channel::bounded;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::{rngs::StdRng, SeedableRng};
+use serde_json::json;
+
+use crate::admission::Admission;
+use crate::content::ContentHashes;
+use crate::ctf::Flag;
+use crate::map::{Map, Terrain};
+use crate::mode::GameMode;
+use crate::profiling::Profiler;
+use crate::rules::MatchRules;
+use crate::scripting::Scripting;
+use crate::servers::{GameFilter, GamePage};
+use crate::skin::Skin;
+use crate::status::{StatusEffect, StatusKind};
+use crate::{
+    admin, bans, bot, clock, config, ctf, map, migration, mode, names, net, notice, observer, rollback, session, shutdown, sim,
+    skin, transfer, websocket,
+};
+use crate::{Crate, Direction, Loot, NetActions, NetworkedGame, PlayerState, Position, Potion, PotionType, Trap};
+use crate::{
+    AFK_FORFEIT_SECONDS, BLOCK_MP_PER_SECOND, CRATES_GENERATED, CRATE_HEIGHT, CRATE_WIDTH, DASH_MP_COST,
+    MAP_VOTE_CANDIDATES, MOVE_VALIDATION_SLACK_TICKS, PLAYER_CELL_HEIGHT, PLAYER_CELL_WIDTH, PLAYER_MAX_HP,
+    PLAYER_MAX_MP, POTION_HEIGHT, POTION_WIDTH, PROTOCOL_VERSION, SHUTDOWN_GRACE_MILLIS, SPIKES_SLOW, SPIKES_SLOW_SECONDS,
+    SPRINT_SPEED_MULTIPLIER, SUDDEN_DEATH_DAMAGE_PER_SECOND, WEBSOCKET_POLL_MILLIS,
+};
+
+pub struct GameServer {
+    hostname: String,
+    games: Vec<NetworkedGame>,
+    game_count: String,
+    maps: HashMap<String, Map>,
+    pub profiler: Profiler,
+    /// Turn away names with profanity in them
+    pub filter_names: bool,
+    /// Requests that came in over WebSocket, when listening for them
+    pub websocket_requests: Option<crossbeam_channel::Receiver<websocket::Request>>,
+    /// Read-only HTTP requests for live games, when taking them
+    pub observer_requests: Option<crossbeam_channel::Receiver<observer::Request>>,
+    /// Scripts the games' rules have asked for
+    scripting: Scripting,
+    /// Counts requests, and turns away new games and players when full
+    admission: Admission,
+    /// When the server came up, for its uptime
+    started: Instant,
+    /// Where the requests and ticks go when recording a session
+    recorder: Option<session::Recorder>,
+    /// Everything left to chance, from map shuffles and crits to player IDs,
+    /// so a session replays the same from the seed it was recorded with
+    rng: StdRng,
+}
+
+/// Where the answer to a request goes, back the way the request came.
+pub enum Reply<'a> {
+    Udp(&'a UdpSocket, SocketAddr),
+    WebSocket(crossbeam_channel::Sender<String>),
+    /// Sent on as usual, with a copy kept for the session being recorded
+    Tee(&'a Reply<'a>, crossbeam_channel::Sender<String>),
+    /// Kept to hold up against a recorded session, as if from where the
+    /// recorded request came from
+    Replay(crossbeam_channel::Sender<String>, Option<IpAddr>),
+}
+
+impl Reply<'_> {
+
+    /// Where the request came from, when it's known.
+    fn address(&self) -> Option<IpAddr> {
+        match self {
+            Reply::Udp(_, addr) => Some(addr.ip()),
+            Reply::WebSocket(_) => None,
+            Reply::Tee(reply, _) => reply.address(),
+            Reply::Replay(_, address) => *address,
+        }
+    }
+
+    fn send(&self, answer: &[u8]) {
+        match self {
+            Reply::Udp(socket, addr) => {
+                let _ = socket.send_to(answer, addr);
+            },
+            Reply::WebSocket(sender) | Reply::Replay(sender, _) => {
+                let _ = sender.send(String::from_utf8_lossy(answer).to_string());
+            },
+            Reply::Tee(reply, copies) => {
+                reply.send(answer);
+                let _ = copies.send(String::from_utf8_lossy(answer).to_string());
+            },
+        }
+    }
+}
+
+impl GameServer {
+
+    pub fn new(hostname: String) -> GameServer {
+        GameServer {
+            hostname,
+            games: vec![],
+            game_count: "0".to_string(),
+            maps: HashMap::new(),
+            profiler: Profiler::default(),
+            filter_names: true,
+            websocket_requests: None,
+            observer_requests: None,
+            scripting: Scripting::default(),
+            admission: Admission::default(),
+            started: Instant::now(),
+            recorder: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Starts recording a session into `recorder`, from a fresh seed it keeps
+    /// for replaying.
+    pub fn record(&mut self, mut recorder: session::Recorder) {
+        let seed = rand::random();
+        recorder.record(&session::Entry::Seed { seed });
+        self.rng = StdRng::seed_from_u64(seed);
+        self.recorder = Some(recorder);
+    }
+
+    /// Why the sender of a request is banned, if they are, going by the
+    /// name of the player with the ID `player`, or `player` itself when
+    /// `joining` since it's the name they want, and `address`.
+    fn ban_reason(&self, player: &str, joining: bool, address: Option<IpAddr>) -> Option<String> {
+        let name = self.games.iter().flat_map(|g| g.players.iter()).find(|p| p.id == player).map(|p| p.name.as_str())
+            .or(if joining { Some(player) } else { None });
+        let address = address.map(|address| address.to_string());
+        bans::reason([name, address.as_deref()].iter().flatten().copied())
+    }
+
+    /// Games still running and the people in them, bots not counting, for
+    /// the server's caps.
+    fn load(&self) -> (usize, usize) {
+        let running: Vec<&NetworkedGame> = self.games.iter().filter(|g| !g.completed).collect();
+        let players = running.iter().map(|g| g.players.iter().filter(|p| !p.bot).count()).sum();
+        (running.len(), players)
+    }
+
+    fn load_map<'a>(maps: &'a mut HashMap<String, Map>, name: &str) -> Option<&'a Map> {
+        if !maps.contains_key(name) {
+            match Map::load(name) {
+                Ok(map) => {
+                    maps.insert(name.to_string(), map);
+                },
+                Err(e) => {
+                    println!("Unable to load map {}: {}", name, e);
+                    return None
+                }
+            }
+        }
+        maps.get(name)
+    }
+
+    /// Picks the voted map, moves everyone to its spawn points, and starts the game.
+    fn start_game(game: &mut NetworkedGame, maps: &mut HashMap<String, Map>, rng: &mut impl Rng) {
+        game.map = game.tally_map_votes();
+        if let Some(map) = GameServer::load_map(maps, &game.map) {
+            for (index, player) in game.players.iter_mut().enumerate() {
+                if let Some(spawn) = map.spawn_point(index) {
+                    player.body.x = spawn.x;
+                    player.body.y = spawn.y;
+                }
+            }
+            game.crates = map.crates.iter().map(|pos| Crate::new(*pos)).collect();
+            if game.crates.is_empty() {
+                for _ in 0..CRATES_GENERATED {
+                    if let Some(pos) = map.random_free_position(CRATE_WIDTH, CRATE_HEIGHT, rng) {
+                        game.crates.push(Crate::new(pos));
+                    }
+                }
+            }
+            if game.mode == GameMode::KingOfTheHill {
+                game.zone = Some(map.zone(0));
+            }
+            if game.mode == GameMode::CaptureTheFlag {
+                game.flags = (0..ctf::TEAMS).map(|team| Flag::new(team, &map.base(team))).collect();
+            }
+        }
+        if game.mode.has_teams() {
+            for team in ctf::TEAM_NAMES.iter() {
+                game.scores.insert(team.to_string(), 0.0);
+            }
+        } else {
+            for player in game.players.iter() {
+                game.scores.insert(player.score_key(), 0.0);
+            }
+        }
+        println!("Starting {} game {} on map {}", game.mode.name(), game.session_id, game.map);
+        for player in game.players.iter_mut() {
+            player.last_input = Some(clock::now());
+        }
+        game.log_event(format!("{} on {} - fight!", game.mode.name(), game.map), true);
+        game.started = true;
+    }
+
+    /// Adds `name` at the next spawn point if there's room, starting the map
+    /// vote or the game once it's full. Gives back the ID they were given.
+    fn join(game: &mut NetworkedGame, maps: &mut HashMap<String, Map>, name: &str, skin: Skin, bot: bool, rng: &mut impl Rng) -> Option<String> {
+        if game.players.len() >= game.max_players() {
+            return None
+        }
+        let spawn = maps.get(&game.map).and_then(|m| m.spawn_point(game.players.len()));
+        let player_pos = if let Some(spawn) = spawn {
+            Position { x: spawn.x, y: spawn.y, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT }
+        } else if game.players.is_empty() {
+            Position { x: 100.0, y: 250.0, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT }
+        } else {
+            Position { x: 500.0, y: 250.0, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT }
+        };
+        let mut new_player = PlayerState::new(name.to_string(), player_pos);
+        new_player.id = GameServer::player_id(rng);
+        new_player.rejoin_check = migration::rejoin_check(&new_player.id);
+        // Only ever reused for someone who left before anyone else joined,
+        // taking their vote with them
+        new_player.number = game.players.iter().map(|p| p.number).max().unwrap_or(0) + 1;
+        new_player.skin = GameServer::distinct_skin(game, &new_player.id, skin);
+        // Teams alternate, matching the spawn points they alternate between
+        if game.mode.has_teams() {
+            new_player.team = Some(game.players.len() % ctf::TEAMS);
+        }
+        // Bots are ready from the start and never vote
+        new_player.bot = bot;
+        new_player.ready = bot;
+        let id = new_player.id.clone();
+        game.players.push(new_player);
+        if game.players.len() == game.max_players() {
+            if game.map_candidates.len() > 1 {
+                println!("Game {} voting between {:?}", game.session_id, game.map_candidates);
+                game.vote_started = Some(clock::now());
+            } else {
+                GameServer::start_game(game, maps, rng);
+            }
+        }
+        Some(id)
+    }
+
+    /// Runs a server on a free loopback port with a game against a bot
+    /// already waiting in it, for practicing offline, with the sandbox
+    /// hotkeys working if `sandbox`. Gives back the server's address and the
+    /// game id.
+    #[cfg(feature = "client")]
+    pub fn practice(sandbox: bool) -> (String, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host = socket.local_addr().unwrap().to_string();
+        let game_id = GameServer::spawn(host.clone(), socket, GameServer::serve, move |server| {
+            let game_id = server.new_game(GameMode::default(), MatchRules::default());
+            if let Some(game) = server.games.iter_mut().find(|g| g.session_id == game_id) {
+                game.sandbox = sandbox;
+                GameServer::join(game, &mut server.maps, bot::BOT_NAME, Skin::default(), true, &mut server.rng);
+            }
+            Ok(game_id)
+        });
+        (host, game_id.unwrap())
+    }
+
+    /// Makes a server for `hostname` on a thread of its own and sets it up
+    /// with `setup`, then, unless that failed, has `run` serve `socket` with
+    /// it. Gives back what `setup` did.
+    #[cfg(feature = "client")]
+    fn spawn<T: Send + 'static>(
+        hostname: String,
+        socket: UdpSocket,
+        run: fn(&mut GameServer, UdpSocket),
+        setup: impl FnOnce(&mut GameServer) -> std::result::Result<T, String> + Send + 'static,
+    ) -> std::result::Result<T, String> {
+        let (started_sender, started) = bounded(1);
+        // Maps hold textures, so the server has to be made on its own thread
+        std::thread::spawn(move || {
+            let mut server = GameServer::new(hostname);
+            let result = setup(&mut server);
+            let failed = result.is_err();
+            let _ = started_sender.send(result);
+            if !failed {
+                run(&mut server, socket);
+            }
+        });
+        started.recv().map_err(|e| e.to_string())?
+    }
+
+    /// Carries on `game`, moved here from a server that went away, as
+    /// `game_id`. Its players join again by name with `rejoingame`, and the
+    /// first to move it here wins.
+    fn import(&mut self, game_id: &str, mut game: NetworkedGame) -> std::result::Result<(), String> {
+        if self.games.iter().any(|g| g.session_id == game_id) {
+            return Err(format!("Game {} is already here", game_id))
+        }
+        self.scripting.load(&game.rules.scripts)?;
+        GameServer::load_map(&mut self.maps, &game.map);
+        game.session_id = game_id.to_string();
+        for player in game.players.iter_mut() {
+            // Player IDs never leave a server, bots just get new ones
+            player.id = if player.bot { GameServer::player_id(&mut self.rng) } else { String::new() };
+            player.last_input = Some(clock::now());
+        }
+        println!("Took over {} game {} on map {}", game.mode.name(), game_id, game.map);
+        self.games.push(game);
+        Ok(())
+    }
+
+    /// Starts a server on `address` carrying on `game` as `game_id`, for
+    /// when the one it was on went away and we're next in line to host it.
+    #[cfg(feature = "client")]
+    pub fn resume(address: String, game_id: String, game: NetworkedGame) -> std::result::Result<(), String> {
+        let socket = UdpSocket::bind(&address).map_err(|e| format!("Unable to host on {}: {}", address, e))?;
+        GameServer::spawn(address, socket, GameServer::serve, move |server| server.import(&game_id, game))
+    }
+
+    /// Hosts on `address` from a thread of its own, with a new `mode` game
+    /// waiting for us to join, for playing on the server we're hosting.
+    /// Gives back the address to connect to and the game id.
+    #[cfg(feature = "client")]
+    pub fn listen(address: String, mode: GameMode, filter_names: bool) -> std::result::Result<(String, String), String> {
+        let socket = UdpSocket::bind(&address).map_err(|e| format!("Unable to host on {}: {}", address, e))?;
+        let port = socket.local_addr().map_err(|e| e.to_string())?.port();
+        let game_id = GameServer::spawn(address, socket, GameServer::host_on, move |server| {
+            server.filter_names = filter_names;
+            Ok(server.new_game(mode, MatchRules::default()))
+        })?;
+        Ok((format!("127.0.0.1:{}", port), game_id))
+    }
+
+    /// Lets every bot in `game` decide on and carry out its next move.
+    fn tick_bots(game: &mut NetworkedGame, map: &Map, scripting: &Scripting, elapsed: f32, rng: &mut impl Rng) {
+        // They wait for everyone to be ready, like the players do
+        if !game.players.iter().all(|p| p.ready) {
+            return
+        }
+        let bots: Vec<String> = game.players.iter().filter(|p| p.bot && p.hp > 0).map(|p| p.id.clone()).collect();
+        for id in bots {
+            let decision = bot::think(game, &id);
+            let attacking = decision.aim.is_some();
+            // The one before may have just broken a crate
+            sim::sync_obstacles(&mut game.collisions.crates, &game.crates);
+            if let Some(player) = game.players.iter_mut().find(|p| p.id == id) {
+                match decision.aim {
+                    Some(aim) => {
+                        player.dir = Direction::default();
+                        player.last_dir = aim;
+                    },
+                    None => player.dir = decision.dir,
+                }
+                player.update(elapsed, !player.staggered, map, &game.collisions.crates);
+                player.last_move = Some(clock::now());
+                player.last_input = Some(clock::now());
+            }
+            if attacking {
+                sim::attack(game, scripting, &id, rng);
+            }
+        }
+    }
+
+    /// A new player ID, which is all the player is known by in their
+    /// messages from then on. Never sent to anyone else. Made from the
+    /// server's seeded generator, so a replayed session hands out the same ones.
+    fn player_id(rng: &mut impl Rng) -> String {
+        uuid::Builder::from_bytes(rng.gen()).set_variant(uuid::Variant::RFC4122).set_version(uuid::Version::Random).build().to_string()
+    }
+
+    /// Recolors `skin` until it differs from everyone else's in the game.
+    fn distinct_skin(game: &NetworkedGame, player_id: &str, mut skin: Skin) -> Skin {
+        for _ in 0..skin::SKIN_COLORS.len() {
+            if !game.players.iter().any(|p| p.id != player_id && p.skin == skin) {
+                break
+            }
+            skin.next_color();
+        }
+        skin
+    }
+
+    pub fn host(&mut self) -> std::result::Result<(), String> {
+        //let listener = TcpListener::bind(self.hostname.clone()).unwrap();
+        let socket = UdpSocket::bind(&self.hostname).map_err(|e| format!("Unable to host on {}: {}", self.hostname, e))?;
+        self.host_on(socket);
+        Ok(())
+    }
+
+    /// Serves on `socket` until shut down. Whoever started the server is
+    /// left running, a client hosting with `--listen` still has to leave.
+    fn host_on(&mut self, socket: UdpSocket) {
+        match bans::load() {
+            Ok(0) => (),
+            Ok(count) => println!("Loaded {} bans", count),
+            Err(e) => println!("Unable to load bans: {}", e),
+        }
+
+         // threaded game checking one thread per game
+        // if Instant::now() - last_server_update > Duration::from_millis(16) {
+        //    for game in self.games.iter_mut() {
+        //        if game.started {
+        //           let mut cloned_mut_vec = game.players.clone();
+        //           let mut player1 =  cloned_mut_vec.first_mut().unwrap().clone();
+        //           let mut player2 =  cloned_mut_vec.last_mut().unwrap().clone();
+        //           if player1.clone().body == player2.clone().body {
+        //               player1.is_hit = true;
+        //               player2.is_hit = true;
+        //               //println!("{:?}", player1.last_dir);
+        //               //println!("HIT");
+        //           } else {
+        //               player1.is_hit = false;
+        //               player2.is_hit = false;
+        //           }
+        //        }   
+        //    }
+        //    last_server_update = Instant::now();
+        //}
+        self.serve(socket);
+        self.profiler.save();
+        println!("Server stopped");
+    }
+
+    /// Answers requests on `socket` and runs the game tick until a shutdown
+    /// is asked for and the players have had time to hear about it.
+    fn serve(&mut self, socket: UdpSocket) {
+        // Wake up for the game tick even when nobody is sending anything, and
+        // often enough to not keep anyone on a WebSocket waiting
+        let wait = match self.websocket_requests {
+            Some(_) => WEBSOCKET_POLL_MILLIS.min(config::get().server_tick_millis),
+            None => config::get().server_tick_millis,
+        };
+        socket.set_read_timeout(Some(Duration::from_millis(wait))).unwrap();
+        let mut last_server_update = Instant::now();
+        let mut closing_since: Option<Instant> = None;
+        loop {
+            if closing_since.is_none() && shutdown::requested() {
+                self.close_games();
+                closing_since = Some(Instant::now());
+            }
+            if closing_since.is_some_and(|since| since.elapsed() >= Duration::from_millis(SHUTDOWN_GRACE_MILLIS)) {
+                return
+            }
+            let mut buf = [0; 65_000];
+            let received = {
+                puffin::profile_scope!("recv");
+                socket.recv_from(&mut buf)
+            };
+            if let Ok((amt, src)) = received {
+                let result = String::from_utf8_lossy(&buf[..amt]).to_string();
+                let amt = result.len();
+                self.answer(result, &Reply::Udp(&socket, src), amt);
+            }
+            let websocket_requests: Vec<websocket::Request> = self.websocket_requests.iter().flat_map(|r| r.try_iter()).collect();
+            for (request, answers) in websocket_requests {
+                let amt = request.len();
+                self.answer(request, &Reply::WebSocket(answers), amt);
+            }
+            let observer_requests: Vec<observer::Request> = self.observer_requests.iter().flat_map(|r| r.try_iter()).collect();
+            for (path, answer) in observer_requests {
+                let _ = answer.send(observer::answer(&self.games, &path));
+            }
+
+            let elapsed = last_server_update.elapsed();
+            if elapsed >= Duration::from_millis(config::get().server_tick_millis) {
+                // Ticks with no games to run make no difference to a replay
+                let recorder = if self.games.is_empty() { None } else { self.recorder.take() };
+                match recorder {
+                    Some(mut recorder) => {
+                        let now = Instant::now();
+                        clock::set(Some(now));
+                        self.tick(elapsed);
+                        clock::set(None);
+                        recorder.record(&session::Entry::Tick { at: recorder.at(now), nanos: elapsed.as_nanos() as u64 });
+                        self.recorder = Some(recorder);
+                    },
+                    None => self.tick(elapsed),
+                }
+                last_server_update = Instant::now();
+                // A frame per game tick, with the requests answered since the last one
+                self.profiler.new_frame();
+            }
+        }
+    }
+
+    /// Handles a request, keeping it and its answers when recording.
+    fn answer(&mut self, request: String, reply: &Reply, amt: usize) {
+        let mut recorder = match self.recorder.take() {
+            Some(recorder) => recorder,
+            None => return self.handle_connection(request, reply, amt),
+        };
+        let recorded = request.get(0..amt).unwrap_or(&request).to_string();
+        let address = reply.address();
+        let (copies, answers) = crossbeam_channel::unbounded();
+        // The time stands still while it's handled, as it does on a replay
+        let now = Instant::now();
+        clock::set(Some(now));
+        self.handle_connection(request, &Reply::Tee(reply, copies), amt);
+        clock::set(None);
+        recorder.record(&session::Entry::Request {
+            at: recorder.at(now),
+            request: recorded,
+            address: address.map(|address| address.to_string()),
+            answers: answers.try_iter().collect(),
+        });
+        self.recorder = Some(recorder);
+    }
+
+    /// Plays a recorded session back through a fresh server, giving the
+    /// requests answered differently this time.
+    pub fn replay(entries: &[session::Entry]) -> Vec<session::Mismatch> {
+        let mut gameserver = GameServer::new("localhost".to_string());
+        let mut mismatches = vec![];
+        let started = Instant::now();
+        for (index, entry) in entries.iter().enumerate() {
+            match entry {
+                session::Entry::Request { at, request, address, answers } => {
+                    clock::set(Some(started + Duration::from_nanos(*at)));
+                    let (sender, replayed) = crossbeam_channel::unbounded();
+                    let address = address.as_ref().and_then(|address| address.parse().ok());
+                    gameserver.handle_connection(request.clone(), &Reply::Replay(sender, address), request.len());
+                    let replayed: Vec<String> = replayed.try_iter().collect();
+                    if &replayed != answers {
+                        mismatches.push(session::Mismatch {
+                            entry: index + 1,
+                            request: request.clone(),
+                            recorded: answers.clone(),
+                            replayed,
+                        });
+                    }
+                },
+                session::Entry::Tick { at, nanos } => {
+                    clock::set(Some(started + Duration::from_nanos(*at)));
+                    gameserver.tick(Duration::from_nanos(*nanos));
+                },
+                session::Entry::Seed { seed } => gameserver.rng = StdRng::seed_from_u64(*seed),
+            }
+        }
+        clock::set(None);
+        mismatches
+    }
+
+    /// Ends every game for the server shutting down, without a winner.
+    fn close_games(&mut self) {
+        println!("Shutting down, closing {} games", self.games.len());
+        for game in self.games.iter_mut() {
+            game.server_closing = true;
+            game.completed = true;
+        }
+    }
+
+    /// Runs the match clock. When time is up whoever is ahead on score, or on
+    /// HP in modes without one, wins, and a tie goes into sudden death.
+    fn tick_clock(game: &mut NetworkedGame, elapsed: f32) {
+        game.clock += elapsed;
+        if let Some(sudden_death) = game.sudden_death.as_mut() {
+            *sudden_death += elapsed;
+            return
+        }
+        if game.clock < game.rules.time_limit {
+            return
+        }
+        let mut standings: Vec<(String, f32)> = if game.score_limit().is_some() {
+            game.scores.iter().map(|(key, score)| (game.scorer_name(key), *score)).collect()
+        } else {
+            game.players.iter().map(|p| (p.name.clone(), p.hp as f32)).collect()
+        };
+        standings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        match (standings.first(), standings.get(1)) {
+            (Some(first), Some(second)) if first.1 > second.1 => {
+                println!("Time is up in game {}", game.session_id);
+                let winner = first.0.clone();
+                game.finish(Some(winner));
+            },
+            _ => {
+                println!("Game {} is going into sudden death", game.session_id);
+                game.sudden_death = Some(0.0);
+                game.log_event("Sudden death!".to_string(), true);
+            },
+        }
+    }
+
+    /// Scoring and win conditions of the game mode.
+    fn tick_mode(game: &mut NetworkedGame, map: &Map, elapsed: f32, rng: &mut impl Rng) {
+        // In sudden death the first one down loses, whatever the mode
+        if game.sudden_death.is_some() {
+            if let Some(fallen) = game.players.iter().find(|p| p.hp == 0) {
+                let winner = match fallen.team {
+                    Some(team) => Some(ctf::TEAM_NAMES[(team + 1) % ctf::TEAMS].to_string()),
+                    None => game.players.iter()
+                        .filter(|p| p.hp > 0)
+                        .max_by_key(|p| p.hp)
+                        .map(|p| p.name.clone()),
+                };
+                game.finish(winner);
+                return
+            }
+        }
+        match game.mode {
+            GameMode::Duel => {
+                let alive: Vec<&PlayerState> = game.players.iter().filter(|p| p.hp > 0).collect();
+                if game.players.len() > 1 && alive.len() <= 1 {
+                    let winner = alive.first().map(|p| p.name.clone());
+                    game.finish(winner);
+                }
+            },
+            GameMode::KingOfTheHill => {
+                game.zone_timer += elapsed;
+                if game.zone_timer >= mode::KOTH_ZONE_MOVE_SECONDS && map.zones.len() > 1 {
+                    game.zone_index += 1;
+                    game.zone = Some(map.zone(game.zone_index));
+                    game.zone_timer = 0.0;
+                }
+                let zone = match game.zone {
+                    Some(zone) => zone,
+                    None => return,
+                };
+                game.sync_collisions();
+                let holders: Vec<&PlayerState> = game.collisions.players.query(&zone).into_iter()
+                    .map(|index| &game.players[index])
+                    .filter(|p| p.hp > 0)
+                    .collect();
+                // Contested zones score for nobody
+                if holders.len() == 1 {
+                    let (key, holder) = (holders[0].score_key(), holders[0].name.clone());
+                    let limit = game.score_limit();
+                    let score = game.scores.entry(key).or_insert(0.0);
+                    *score += elapsed;
+                    if limit.is_some_and(|limit| *score >= limit) {
+                        game.finish(Some(holder));
+                    }
+                }
+            },
+            GameMode::CaptureTheFlag => GameServer::tick_flags(game, map),
+            GameMode::CollectionRace => {
+                // The fallen scatter part of their haul and get back in the race
+                let mut dropped = vec![];
+                for (index, player) in game.players.iter_mut().enumerate().filter(|(_, p)| p.hp == 0) {
+                    let score = game.scores.entry(player.score_key()).or_insert(0.0);
+                    let lost = score.min(mode::RACE_DEATH_DROP);
+                    *score -= lost;
+                    for item in 0..lost as usize {
+                        let angle = item as f32 * 2.094;
+                        let pos = Position {
+                            x: player.body.x + angle.cos() * 40.0,
+                            y: player.body.y + angle.sin() * 40.0,
+                            w: POTION_WIDTH,
+                            h: POTION_HEIGHT,
+                        };
+                        dropped.push(Loot::Potion(Potion::dropped(pos, PotionType::random(rng))));
+                    }
+                    println!("{} was killed and dropped {} item(s)", player.name, lost);
+                    player.respawn(map.spawn_point(index));
+                }
+                for loot in dropped {
+                    game.drop_loot(loot);
+                }
+                let limit = game.score_limit().unwrap_or(mode::RACE_SCORE_LIMIT);
+                let winner = game.scores.iter().find(|(_, score)| **score >= limit).map(|(key, _)| game.scorer_name(key));
+                if winner.is_some() {
+                    game.finish(winner);
+                }
+            },
+        }
+    }
+
+    /// Capture the Flag pickups, returns, and captures.
+    fn tick_flags(game: &mut NetworkedGame, map: &Map) {
+        let players = &mut game.players;
+        let flags = &mut game.flags;
+        let collisions = &mut game.collisions;
+        // Flags follow their carrier, and stay where the carrier went down
+        for flag in flags.iter_mut() {
+            if let Some(carrier) = flag.carrier {
+                match players.iter().find(|p| p.number == carrier && p.hp > 0) {
+                    Some(p) => {
+                        flag.pos.x = p.body.x;
+                        flag.pos.y = p.body.y;
+                    },
+                    None => flag.carrier = None,
+                }
+            }
+        }
+        collisions.flags.sync(flags.iter().map(|flag| Some(flag.pos)));
+        let mut captures = vec![];
+        for player in players.iter().filter(|p| p.hp > 0) {
+            let team = match player.team {
+                Some(t) => t,
+                None => continue,
+            };
+            for index in collisions.flags.query(&player.body) {
+                let flag = &mut flags[index];
+                if flag.carrier.is_some() {
+                    continue
+                }
+                if flag.team != team {
+                    flag.carrier = Some(player.number);
+                } else if !flag.at_home() {
+                    flag.return_home();
+                    collisions.flags.update(index, Some(flag.pos));
+                }
+            }
+            // Only scores with our own flag safe at home
+            let own_flag_home = flags.iter().any(|f| f.team == team && f.at_home());
+            if own_flag_home && player.body == map.base(team) {
+                if let Some(index) = flags.iter().position(|f| f.carrier == Some(player.number)) {
+                    flags[index].return_home();
+                    collisions.flags.update(index, Some(flags[index].pos));
+                    captures.push((team, player.name.clone()));
+                }
+            }
+        }
+        for player in players.iter_mut() {
+            player.carrying_flag = flags.iter().find(|f| f.carrier == Some(player.number)).map(|f| f.team);
+        }
+        for (team, name) in captures {
+            let team_name = ctf::TEAM_NAMES[team].to_string();
+            println!("{} captured the flag for {}", name, team_name);
+            let limit = game.score_limit();
+            let score = game.scores.entry(team_name.clone()).or_insert(0.0);
+            *score += 1.0;
+            if limit.is_some_and(|limit| *score >= limit) {
+                game.finish(Some(team_name));
+            }
+        }
+    }
+
+    /// Server side simulation of every running game.
+    pub fn tick(&mut self, elapsed: Duration) {
+        puffin::profile_function!();
+        // Games only move mid-game, so the successor's address goes out no sooner
+        for game in self.games.iter_mut() {
+            let playing = game.started && !game.completed && !game.server_closing;
+            game.successor = if playing { migration::successor(game) } else { None };
+        }
+        for game in self.games.iter_mut().filter(|g| g.started && !g.completed) {
+            let map = match GameServer::load_map(&mut self.maps, &game.map) {
+                Some(m) => m,
+                None => continue,
+            };
+            GameServer::tick_clock(game, elapsed.as_secs_f32());
+            if game.completed {
+                continue
+            }
+            let afk_timeout = Duration::from_secs_f32(game.rules.afk_timeout);
+            let mut forfeited = None;
+            for player in game.players.iter_mut() {
+                let idle = player.last_input.map_or(Duration::from_secs(0), clock::since);
+                if idle >= afk_timeout && !player.afk {
+                    println!("{} is AFK in game {}", player.name, game.session_id);
+                }
+                player.afk = idle >= afk_timeout;
+                if idle >= afk_timeout + Duration::from_secs_f32(AFK_FORFEIT_SECONDS) {
+                    forfeited = Some(player.id.clone());
+                }
+            }
+            if let Some(id) = forfeited {
+                game.forfeit(&id);
+                continue
+            }
+            let safe_area = NetworkedGame::safe_area(game.sudden_death, map);
+            for player in game.players.iter_mut() {
+                let mut damage_per_second = map.hazard_damage(&player.body);
+                if let Some(safe_area) = safe_area {
+                    if !safe_area.contains(player.body.x + player.body.w / 2.0, player.body.y + player.body.h / 2.0) {
+                        damage_per_second += SUDDEN_DEATH_DAMAGE_PER_SECOND;
+                    }
+                }
+                player.burning = damage_per_second > 0.0;
+                if player.burning {
+                    player.hazard_damage += damage_per_second * elapsed.as_secs_f32();
+                    let damage = player.hazard_damage.floor();
+                    player.hazard_damage -= damage;
+                    player.hp = (player.hp - damage as i64).max(0);
+                } else {
+                    player.hazard_damage = 0.0;
+                }
+                if map.on_spikes(&player.body) && !player.jumping {
+                    player.add_status(StatusEffect::new(StatusKind::Slow, SPIKES_SLOW, SPIKES_SLOW_SECONDS));
+                }
+                player.tick_statuses(elapsed.as_secs_f32());
+            }
+
+            // Holding the guard up costs mana until it runs out
+            for player in game.players.iter_mut() {
+                if player.blocking {
+                    player.block_drain += BLOCK_MP_PER_SECOND * elapsed.as_secs_f32();
+                    let drain = player.block_drain.floor();
+                    player.block_drain -= drain;
+                    player.mp = (player.mp - drain as i64).max(0);
+                    if player.mp == 0 {
+                        player.blocking = false;
+                    }
+                } else {
+                    player.block_drain = 0.0;
+                }
+                player.staggered = player.stagger_until.is_some_and(|until| clock::now() < until);
+                if !player.combo_alive() {
+                    player.combo = 0;
+                }
+                player.regenerate(elapsed.as_secs_f32());
+                // Bots wear theirs down as they move, everyone else's is kept here
+                if !player.bot {
+                    player.tick_timers(elapsed.as_secs_f32());
+                    player.update_stamina(elapsed.as_secs_f32());
+                }
+                if player.god_mode {
+                    player.hp = PLAYER_MAX_HP;
+                    player.mp = PLAYER_MAX_MP;
+                }
+            }
+
+            GameServer::tick_bots(game, map, &self.scripting, elapsed.as_secs_f32(), &mut self.rng);
+            sim::tick_items(game, map, elapsed.as_secs_f32(), &mut self.rng);
+            GameServer::tick_mode(game, map, elapsed.as_secs_f32(), &mut self.rng);
+
+            sim::pick_up(game, &self.scripting);
+            game.trace.record(elapsed.as_secs_f32(), game.clock, &game.players);
+
+            if !game.rules.scripts.is_empty() {
+                let players = game.players.iter().map(|p| {
+                    let mut player = rhai::Map::new();
+                    player.insert("name".into(), p.name.clone().into());
+                    player.insert("hp".into(), p.hp.into());
+                    player.insert("mp".into(), p.mp.into());
+                    player.insert("level".into(), (p.level as i64).into());
+                    player.insert("score".into(), (game.scores.get(&p.score_key()).copied().unwrap_or(0.0) as f64).into());
+                    player.into()
+                }).collect();
+                let actions = self.scripting.on_tick(&game.rules.scripts, game.clock, players);
+                game.apply_script_actions(actions);
+            }
+        }
+    }
+
+    fn new_game(&mut self, mode: GameMode, rules: MatchRules) -> String {
+        let mut count = self.game_count.parse::<i32>().unwrap();
+        count += 1;
+        self.game_count = count.to_string();
+        let mut map_candidates = map::available_maps();
+        map_candidates.shuffle(&mut self.rng);
+        map_candidates.truncate(MAP_VOTE_CANDIDATES);
+        let game = NetworkedGame::new(self.game_count.clone(), map_candidates, mode, rules);
+        GameServer::load_map(&mut self.maps, &game.map);
+        let session_id = game.session_id.clone();
+        self.games.push(game);
+        session_id
+    }
+
+    pub fn handle_connection(&mut self, request: String, reply: &Reply, amt: usize) {
+        puffin::profile_function!();
+        // Anything short of all four parts isn't one of ours, and gets no answer.
+        // The player is the ID the server gave them, or the name they want when joining
+        let (game_id, player, action, meta) = match net::parse_request(request.get(0..amt).unwrap_or(&request)) {
+            Some(keys) => keys,
+            None => return,
+        };
+        let command = action.parse::<usize>().map_or(NetActions::Unknown, NetActions::from_usize);
+        self.admission.count_packet();
+
+        if let Some(reason) = self.ban_reason(player, command == NetActions::Joingame, reply.address()) {
+            // Out of whatever game they were in, and kept out
+            for game in self.games.iter_mut().filter(|g| g.players.iter().any(|p| p.id == player)) {
+                game.leave(player);
+            }
+            reply.send(json!({"error": format!("Banned: {}", reason)}).to_string().as_bytes());
+            return
+        }
+
+        // Anything but polling counts as being at the keyboard
+        let inputs = [NetActions::Sendposition, NetActions::Attack, NetActions::Block, NetActions::PlaceTrap];
+        if inputs.contains(&command) {
+            if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                if let Some(game_player) = game.players.iter_mut().find(|p| p.id == player) {
+                    game_player.last_input = Some(clock::now());
+                    game_player.afk = false;
+                }
+            }
+        }
+
+        match command {
+            NetActions::Newgame => {
+                let (games, players) = self.load();
+                if let Some(refusal) = self.admission.refusal(games, players, true) {
+                    println!("Refused new game: {}", refusal);
+                    reply.send(refusal.as_bytes());
+                    return
+                }
+                let created = MatchRules::from_meta(meta)
+                    .and_then(|(mode, rules)| self.scripting.load(&rules.scripts).map(|_| (mode, rules)));
+                match created {
+                    Ok((mode, rules)) => {
+                        let game_id = self.new_game(mode, rules);
+                        reply.send(game_id.as_bytes());
+                    },
+                    Err(e) => {
+                        println!("Invalid new game {}: {}", meta, e);
+                        reply.send(format!("Invalid game rules: {}", e).as_bytes());
+                    },
+                }
+            },
+            NetActions::Gameinfo => {
+                if let Some(game) = self.games.iter().find(|g| g.session_id == game_id) {
+                    let players: Vec<&String> = game.players.iter().map(|p| &p.name).collect();
+                    let result = json!({
+                        "session_id": game.session_id,
+                        "mode": game.mode.name(),
+                        "map": game.map,
+                        "players": players,
+                        "max_players": game.max_players(),
+                        "score_limit": game.score_limit(),
+                        "rules": game.rules,
+                        "started": game.started,
+                        "completed": game.completed,
+                    });
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Serverinfo => {
+                let (games, players) = self.load();
+                let mut info = self.admission.utilization(games, players);
+                info["version"] = json!(env!("CARGO_PKG_VERSION"));
+                info["protocol"] = json!(PROTOCOL_VERSION);
+                info["uptime_seconds"] = json!(self.started.elapsed().as_secs());
+                info["motd"] = json!(notice::motd());
+                info["rules"] = json!(notice::rules());
+                reply.send(info.to_string().as_bytes());
+            },
+            NetActions::Listgames => {
+                let filter: GameFilter = serde_json::from_str(meta).unwrap_or_default();
+                let game_info: Vec<Vec<String>> = self.games.iter().filter(|game| !game.started).filter(|game| {
+                    let maps: Vec<&str> = std::iter::once(&game.map).chain(game.map_candidates.iter()).map(|map| map.as_str()).collect();
+                    filter.matches(game.mode.name(), &maps, game.players.len(), game.max_players())
+                }).map(|game| {
+                    vec![game.session_id.clone(), game.players.len().to_string(), game.mode.name().to_string(), game.max_players().to_string(), game.map.clone()]
+                }).collect();
+
+                let result = json!(GamePage::of(game_info, filter.page));
+                reply.send(result.to_string().as_bytes());
+            },
+            NetActions::Getworld => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if game.voting() && game.vote_finished() {
+                        GameServer::start_game(game, &mut self.maps, &mut self.rng);
+                    }
+                    let world = {
+                        puffin::profile_scope!("serialize world");
+                        json!(game).to_string()
+                    };
+                    reply.send(world.as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Joingame => {
+                let (games, players) = self.load();
+                if let Some(refusal) = self.admission.refusal(games, players, false) {
+                    println!("Refused {:?} joining game {}: {}", player, game_id, refusal);
+                    reply.send(json!({"error": refusal}).to_string().as_bytes());
+                    return
+                }
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    let checked = names::check(player, game.players.iter().map(|p| p.name.as_str()), self.filter_names);
+                    if let Err(e) = checked {
+                        println!("Refused {:?} joining game {}: {}", player, game.session_id, e);
+                        reply.send(json!({"error": format!("Invalid player name: {}", e)}).to_string().as_bytes());
+                    } else if let Some(id) = GameServer::join(game, &mut self.maps, player, Skin::from_meta(meta), false, &mut self.rng) {
+                        if let Some(joined) = game.players.iter_mut().find(|p| p.id == id) {
+                            joined.address = reply.address().map(|address| address.to_string());
+                        }
+                        reply.send(json!({"player_id": id, "world": game, "tunables": config::get().tunables()}).to_string().as_bytes());
+                    } else {
+                        println!("game {:?} is full", game.session_id);
+                        reply.send(json!({"error": "Game is full"}).to_string().as_bytes());
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                    reply.send(json!({"error": format!("No game {}", game_id)}).to_string().as_bytes());
+                }
+            },
+            NetActions::Ready => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    // Clients that don't send hashes aren't checked
+                    let differences = match serde_json::from_str::<ContentHashes>(meta) {
+                        Ok(content) => {
+                            let server = ContentHashes::server(&game.map, &game.rules);
+                            let others_pack = game.players.iter().filter(|p| p.id != player).find_map(|p| p.content).map(|c| c.pack);
+                            content.differences(&server, others_pack)
+                        },
+                        Err(_e) => vec![],
+                    };
+                    let name = game.players.iter().find(|p| p.id == player).map(|p| p.name.clone()).unwrap_or_default();
+                    let warning = format!("{} has a different {}", name, differences.join(", "));
+                    // Rollback needs everyone simulating the same thing, anywhere else it's a heads up
+                    if !differences.is_empty() && game.rules.rollback {
+                        println!("Not starting game {}: {}", game.session_id, warning);
+                        reply.send(json!({"ready": false, "error": warning}).to_string().as_bytes());
+                        return
+                    }
+                    let mut newly_ready = false;
+                    for game_player in  game.players.iter_mut() {
+                        if game_player.id == player {
+                            newly_ready = !game_player.ready;
+                            game_player.ready = true;
+                            game_player.content = serde_json::from_str(meta).ok();
+                        }
+                    }
+                    if newly_ready && !differences.is_empty() {
+                        game.log_event(warning, true);
+                    }
+                    let ready = game.players.iter().filter(|p| p.ready).count() == game.max_players();
+                    let result = json!({"ready": ready});
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Sendposition => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if let Some(player) = game.players.iter_mut().find(|p| p.id == player) {
+                        let update_player: Vec<f32> = serde_json::from_str(meta).unwrap_or_default();
+                        // Garbled or short updates are dropped
+                        let (x, y, dir, jumping, frame, last_dir) = match update_player[..] {
+                            [x, y, dir, jumping, frame, last_dir, ..] => (x, y, dir, jumping, frame, last_dir),
+                            _ => return,
+                        };
+                        let mut next = player.body;
+                        next.x = x;
+                        next.y = y;
+                        // Nobody outruns the terrain they're leaving or entering
+                        let terrain = match self.maps.get(&game.map) {
+                            Some(map) => {
+                                let from = map.terrain_at(&player.body);
+                                let to = map.terrain_at(&next);
+                                if from.speed > to.speed { from } else { to }
+                            },
+                            None => Terrain::default(),
+                        };
+                        // Dashes cost mana, so the server has the final say on them
+                        let wants_dash = update_player.get(6).is_some_and(|d| *d != 0.0);
+                        if wants_dash && !player.dash_active() && player.can_dash() {
+                            player.since_dash = Some(Duration::ZERO);
+                            player.mp -= DASH_MP_COST;
+                        }
+                        player.dashing = wants_dash && player.dash_active();
+                        // Stamina is kept by the server, the client can only say it has less
+                        player.stamina = update_player.get(7).map_or(player.stamina, |s| s.clamp(0.0, player.stamina));
+                        player.sprint_held = update_player.get(8).is_some_and(|s| *s != 0.0);
+                        let sprinting = player.sprint_held && player.stamina > 0.0;
+                        let moved = (next.x - player.body.x).abs().max((next.y - player.body.y).abs());
+                        let now = clock::now();
+                        // The first move is from the spawn point, so gets one update's worth
+                        let ticks = player.last_move
+                            .map_or(1.0, |last_move| (now - last_move).as_millis() as f32 / config::tunables().millis_per_update() as f32);
+                        // Slows are only enforced by the client, since it hears
+                        // about them a snapshot later than the server applies them
+                        let sprint = if sprinting { SPRINT_SPEED_MULTIPLIER } else { 1.0 };
+                        let multiplier = player.speed_multiplier().max(1.0) * sprint;
+                        let allowed = (ticks + MOVE_VALIDATION_SLACK_TICKS) * PlayerState::max_step(terrain, player.dash_active()) * multiplier;
+                        let walled = self.maps.get(&game.map).is_some_and(|map| map.is_blocked(&next));
+                        if player.staggered && moved > 0.0 {
+                            player.corrections += 1;
+                        } else if walled {
+                            println!("Rejected move of {} into a wall", player.name);
+                            player.corrections += 1;
+                        } else if moved <= allowed {
+                            player.body = next;
+                            player.last_move = Some(now);
+                        } else {
+                            println!("Rejected move of {} by {:.1}px, allowed {:.1}px", player.name, moved, allowed);
+                            player.corrections += 1;
+                        }
+                        player.dir = Direction::from(dir);
+                        player.claim_jump(jumping != 0.0, update_player.get(9).copied().unwrap_or(0.0), game.rules.double_jump);
+                        player.animation_frame = frame;
+                        player.last_dir = Direction::from(last_dir);
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Votemap => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    // Votes are kept by number, the world goes out to everyone
+                    let voter = game.players.iter().find(|p| p.id == player).map(|p| p.number);
+                    let valid = !game.started
+                        && voter.is_some()
+                        && game.map_candidates.iter().any(|m| m == meta);
+                    if let (Some(voter), true) = (voter, valid) {
+                        game.map_votes.insert(voter, meta.to_string());
+                        if game.voting() && game.vote_finished() {
+                            GameServer::start_game(game, &mut self.maps, &mut self.rng);
+                        }
+                    }
+                    let result = json!({"voted": valid});
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Attack => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if game.started {
+                        sim::attack(game, &self.scripting, player, &mut self.rng);
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Block => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if let Some(player) = game.players.iter_mut().find(|p| p.id == player) {
+                        player.set_blocking(meta == "1" && player.mp > 0);
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::PlaceTrap => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if let Some(player) = game.players.iter_mut().find(|p| p.id == player) {
+                        if game.started && player.traps > 0 {
+                            player.traps -= 1;
+                            game.traps.push(Trap::new(player));
+                        }
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Setskin => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if !game.started {
+                        let skin = GameServer::distinct_skin(game, player, Skin::from_meta(meta));
+                        if let Some(player) = game.players.iter_mut().find(|p| p.id == player) {
+                            player.skin = skin;
+                        }
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Leavegame => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    game.leave(player);
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::GetopponentName => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    if let Some(opponent) = game.opponent_of(player) {
+                        reply.send(opponent.name.as_bytes());
+                    }
+                }
+            },
+            NetActions::Getopponent => {
+                if let Some(game) = self.games.iter().find(|g| g.session_id == game_id) {
+                    if let Some(player) = game.opponent_of(player) {
+                        let result = json!({"opponent": vec![player.body.x,
+                                                             player.body.y,
+                                                             player.dir.clone().into(),
+                                                             player.jumping as usize as f32,
+                                                             player.current_accel,
+                                                             player.animation_frame,
+                                                             player.dashing as usize as f32,
+                                                             player.stamina]});
+                        reply.send(result.to_string().as_bytes());
+                    } else {
+                       println!("Invalid Player {}", player);
+                    }
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Sendinputs => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    let number = match game.players.iter().find(|p| p.id == player) {
+                        Some(player) if game.rules.rollback => player.number,
+                        _ => return,
+                    };
+                    let message: serde_json::Value = serde_json::from_str(meta).unwrap_or_default();
+                    let from = message["from"].as_u64().unwrap_or(0) as usize;
+                    let want = message["want"].as_u64().unwrap_or(0) as usize;
+                    let sent: Vec<rollback::FrameInput> = serde_json::from_value(message["inputs"].clone()).unwrap_or_default();
+                    if let Ok(checksum) = serde_json::from_value::<(u32, u64)>(message["checksum"].clone()) {
+                        game.checksums.insert(number, checksum);
+                    }
+                    // Resent inputs overlap what's already stored, gaps wait for a resend
+                    let stored = game.inputs.entry(number).or_default();
+                    if from <= stored.len() {
+                        let known = stored.len() - from;
+                        stored.extend(sent.iter().skip(known));
+                    }
+                    let stored = stored.len();
+                    let opponent_inputs = game.opponent_of(player)
+                        .and_then(|opponent| game.inputs.get(&opponent.number))
+                        .map(|inputs| inputs.iter().skip(want).take(rollback::MAX_INPUTS_PER_MESSAGE).copied().collect())
+                        .unwrap_or_else(Vec::new);
+                    let opponent_checksum = game.opponent_of(player).and_then(|opponent| game.checksums.get(&opponent.number));
+                    let result = json!({"from": want, "inputs": opponent_inputs, "stored": stored, "checksum": opponent_checksum});
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Sendchat => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    game.say(player, meta);
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Muteplayer => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    let result = match game.toggle_mute(player, meta.trim()) {
+                        Ok(muted) => json!({"muted": muted}),
+                        Err(e) => json!({"error": e}),
+                    };
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Rejoingame => {
+                // Only into a spot a moved game is still holding for them, with
+                // the ID they had before it moved
+                let check = migration::rejoin_check(player);
+                let result = match self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    Some(game) => match game.players.iter_mut().find(|p| p.rejoin_check == check && p.id.is_empty() && !p.bot) {
+                        Some(rejoined) => {
+                            rejoined.id = GameServer::player_id(&mut self.rng);
+                            rejoined.rejoin_check = migration::rejoin_check(&rejoined.id);
+                            rejoined.address = reply.address().map(|address| address.to_string());
+                            rejoined.last_input = Some(clock::now());
+                            println!("{} rejoined game {}", rejoined.name, game_id);
+                            json!({"player_id": rejoined.id})
+                        },
+                        None => json!({"error": format!("No spot to rejoin in game {}", game_id)}),
+                    },
+                    None => json!({"error": format!("No game {}", game_id)}),
+                };
+                reply.send(result.to_string().as_bytes());
+            },
+            NetActions::Importgame => {
+                let result = if !config::get().accept_migrations {
+                    json!({"error": "This server doesn't take games from other servers"})
+                } else {
+                    match serde_json::from_str::<NetworkedGame>(meta) {
+                        Ok(game) => match self.import(game_id, game) {
+                            Ok(()) => json!({"imported": game_id}),
+                            Err(e) => json!({"error": e}),
+                        },
+                        Err(e) => json!({"error": format!("Invalid game: {}", e)}),
+                    }
+                };
+                reply.send(result.to_string().as_bytes());
+            },
+            NetActions::Sandbox => {
+                if let Some(game) = self.games.iter_mut().find(|g| g.session_id == game_id) {
+                    let result = match game.sandbox_command(player, meta.trim(), &mut self.rng) {
+                        Ok(done) => json!({"done": done}),
+                        Err(e) => json!({"error": e}),
+                    };
+                    reply.send(result.to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            NetActions::Matchtrace => {
+                // Too long for a packet once the game has gone on a while
+                let result = match self.games.iter().find(|g| g.session_id == game_id) {
+                    Some(game) => {
+                        let contents = json!(game.trace).to_string();
+                        match transfer::chunk(&contents, meta.trim().parse().unwrap_or(0)) {
+                            Some(data) => json!({
+                                "chunks": transfer::chunk_count(&contents),
+                                "checksum": transfer::checksum(contents.as_bytes()),
+                                "data": data,
+                            }),
+                            None => json!({"error": format!("No chunk {} of the trace of game {}", meta, game_id)}),
+                        }
+                    },
+                    None => json!({"error": format!("No game {}", game_id)}),
+                };
+                reply.send(result.to_string().as_bytes());
+            },
+            NetActions::Getmap => {
+                // Only maps the server would offer, and only by name
+                let (name, index) = meta.split_once(':').unwrap_or((meta, "0"));
+                let result = if !map::valid_name(name) || !map::available_maps().iter().any(|m| m == name) {
+                    json!({"error": format!("No map {}", name)})
+                } else {
+                    match map::source(name) {
+                        Ok(contents) => match transfer::chunk(&contents, index.trim().parse().unwrap_or(0)) {
+                            Some(data) => json!({
+                                "chunks": transfer::chunk_count(&contents),
+                                "checksum": transfer::checksum(contents.as_bytes()),
+                                "data": data,
+                            }),
+                            None => json!({"error": format!("No chunk {} of map {}", index, name)}),
+                        },
+                        Err(e) => json!({"error": format!("Unable to read map {}: {}", name, e)}),
+                    }
+                };
+                reply.send(result.to_string().as_bytes());
+            },
+            NetActions::Admin => {
+                // The admin key goes where a player ID would
+                let answer = if !admin::authorized(player) {
+                    "Not authorized".to_string()
+                } else {
+                    admin::run(meta).unwrap_or_else(|| format!("No admin command {}", meta))
+                };
+                reply.send(answer.as_bytes());
+            },
+            NetActions::Getchat => {
+                if let Some(game) = self.games.iter().find(|g| g.session_id == game_id) {
+                    let seen = meta.parse::<u32>().unwrap_or(0);
+                    reply.send(json!(game.chat_for(player, seen)).to_string().as_bytes());
+                } else {
+                    println!("Invalid Game {}", game_id);
+                }
+            },
+            _ => {
+                reply.send("Invalid Command".as_bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,2300 @@
+//! The client's side of a game: what it knows of the world, the threads
+//! talking to the server, and ggez's event loop drawing it all.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::bounded;
+use ggez::conf::FullscreenType;
+use ggez::event::{Axis, GamepadId, KeyCode, KeyMods, MouseButton};
+use ggez::{event, graphics, Context, GameResult};
+use graphics::{GlBackendSpec, ImageGeneric, Rect};
+use serde_json::json;
+
+use crate::assets::Assets;
+use crate::atlas::SpriteBatches;
+use crate::camera::{Camera, SpectatorView};
+use crate::console::{Console, Registry};
+use crate::content::ContentHashes;
+use crate::controls::{Action, InputBuffer, InputMapper, KeyBindings};
+use crate::ctf::Flag;
+use crate::desync::SnapshotHistory;
+use crate::equipment::EquipmentDrop;
+use crate::events::{EventBus, GameEvent};
+use crate::heatmap::Heatmap;
+use crate::killcam::{History, Replay};
+use crate::map::Map;
+use crate::menu::{MenuItem, PauseMenu};
+use crate::mode::GameMode;
+use crate::perf::Perf;
+use crate::physics::SpatialHash;
+use crate::profiling::Profiler;
+use crate::reckoning::DeadReckoning;
+use crate::rollback::Rollback;
+use crate::rules::MatchRules;
+use crate::servers::GameFilter;
+use crate::settings::{Setting, Settings, SettingsPage, SettingsScreen};
+use crate::skin::Skin;
+use crate::sounds::Sounds;
+use crate::touch::{TouchControls, Touched};
+use crate::{
+    admission, camera, config, controls, ctf, damage_numbers, ecs, map, menu, migration, net, notice, particles,
+    rollback, servers, settings, sim, skin, touch, trace, transfer,
+};
+use crate::{
+    ChatMessage, Crate, Direction, Hud, ItemSpawn, NetworkedGame, PlateStyle, Player, PlayerState, Position, Potion,
+    Trap,
+};
+use crate::{
+    ANNOUNCEMENT_SECONDS, CHAT_LINES, CHAT_MAX_CHARS, COMBO_POPUP_MILLIS, CORRECTION_ICON_MILLIS, FEED_LINES,
+    GRID_CELL_SIZE, HIT_STOP_MILLIS, LOW_HP_FLASH, MAX_SIMULATION_STEPS, NET_GAME_READY_CHECK,
+    NET_GAME_START_CHECK_MILLIS, NET_MILLIS_PER_UPDATE, PLAYER_CELL_HEIGHT, PLAYER_CELL_WIDTH, ROLLBACK_RESYNC_PIXELS,
+    SCREEN_SIZE, SPECTATOR_PAN_SPEED, STICK_DEAD_ZONE, TILES_ASSET, TOAST_SECONDS,
+};
+
+/// Our inputs from a frame, the frame the opponent's are wanted from and our
+/// latest `(frame, checksum)`, for the rollback thread to send.
+type OutgoingInputs = (u32, Vec<rollback::FrameInput>, u32, Option<(u32, u64)>);
+/// The opponent's inputs from a frame, how many of ours the server has and
+/// the opponent's latest `(frame, checksum)`.
+type IncomingInputs = (u32, Vec<rollback::FrameInput>, u32, Option<(u32, u64)>);
+
+pub struct GameState {
+    player: Player,
+    opponent: Player,
+    /// Potions on the ground, as of the last world snapshot
+    loot: Vec<Potion>,
+    equipment: Vec<EquipmentDrop>,
+    crates: Vec<Crate>,
+    /// The crates still standing, hashed for movement to bump into
+    obstacles: SpatialHash,
+    traps: Vec<Trap>,
+    item_spawn: Option<ItemSpawn>,
+    potion_texture: ImageGeneric<GlBackendSpec>,
+    mode: GameMode,
+    score_limit: Option<f32>,
+    scores: HashMap<String, f32>,
+    zone: Option<Position>,
+    flags: Vec<Flag>,
+    sudden_death: Option<f32>,
+    /// Server's match clock as of the last snapshot
+    clock: f32,
+    clock_synced: Instant,
+    time_limit: f32,
+    /// Whether the rules allow a second jump in the air
+    double_jump: bool,
+    /// As the server sent them, hashed to check against its own
+    rules: MatchRules,
+    /// Players past the first opponent, only known from world snapshots
+    others: Vec<Player>,
+    map: Map,
+    camera: Camera,
+    /// What the camera does once we're only watching, `None` while playing
+    spectator: Option<SpectatorView>,
+    /// Pan keys held down while spectating
+    spectator_pan: Direction,
+    /// Where everyone was over the last few snapshots, for the kill cam
+    history: History,
+    /// The killing blow being played back
+    replay: Option<Replay>,
+    server: String,
+    game_id: String,
+    started: bool,
+    ready: bool,
+    gameover: bool,
+    /// The server told us it's shutting down
+    server_closing: bool,
+    last_net_update: Instant,
+    last_ready_check: Instant,
+    hud: Hud,
+    lobby: Option<NetworkedGame>,
+    /// The server's message of the day and rules, for the lobby
+    server_notice: Vec<String>,
+    announcement: Option<(String, Instant)>,
+    /// Brief confirmation at the bottom of the screen, eg. where a screenshot went
+    toast: Option<(String, Instant)>,
+    /// Our combo as of the last hit that added to it
+    combo_popup: Option<(u32, Instant)>,
+    /// Saves the next frame before it's presented
+    screenshot_requested: bool,
+    /// Recent kills and pickups, oldest first
+    feed: Vec<(String, Instant)>,
+    /// Id of the last server event shown
+    last_event: u32,
+    /// Recent chat lines, oldest first
+    chat: Vec<(String, Instant)>,
+    /// What we're typing, while the chat box is open
+    chat_input: Option<String>,
+    chat_receiver: crossbeam_channel::Receiver<Vec<ChatMessage>>,
+    controls: KeyBindings,
+    settings: Settings,
+    /// Open while the settings or controls are being changed
+    settings_screen: Option<SettingsScreen>,
+    /// Open while paused, under the settings screen if that's opened from it
+    pause_menu: Option<PauseMenu>,
+    /// Round trip of the last world snapshot
+    ping: Option<Duration>,
+    /// Particles and damage numbers, run by the systems in `ecs`
+    effects: hecs::World,
+    sounds: Sounds,
+    batches: SpriteBatches,
+    /// Movement is frozen until then after landing a hit
+    hit_stop_until: Option<Instant>,
+    perf: Perf,
+    pub profiler: Profiler,
+    /// Playing in the offline sandbox, where the sandbox keys and `/set` work
+    pub sandbox: bool,
+    /// Developer console, in debug builds and the sandbox
+    console: Console<GameState>,
+    /// What happened this frame, for the sounds, particles, feed and camera
+    events: EventBus,
+    /// Recent world snapshots and where we had ourselves as each came in
+    snapshots: SnapshotHistory<NetworkedGame, PlayerState>,
+    /// Dumps the snapshots on the next update, asked for from the console
+    dump_requested: bool,
+    /// Standby server to move the game to if ours goes away mid-game
+    pub relay: Option<String>,
+    /// When the last world snapshot came in, to notice the server going away
+    last_world: Instant,
+    /// Where the game went, while it's being moved to another server
+    migration: Option<crossbeam_channel::Receiver<std::result::Result<migration::Moved, String>>>,
+    /// The match trace on its way once the game is over
+    trace_receiver: Option<crossbeam_channel::Receiver<trace::Trace>>,
+    /// Where everyone went over the match, shown under the result
+    heatmap: Option<Heatmap>,
+    /// Maps on their way from the server, with where to hear how it went
+    map_downloads: Vec<(String, crossbeam_channel::Receiver<std::result::Result<(), String>>)>,
+    /// Maps the server couldn't give us
+    failed_maps: Vec<String>,
+    assets: Assets,
+    player_receiver: crossbeam_channel::Receiver<Vec<f32>>,
+    /// World snapshots and how long each took to come back
+    world_receiver: crossbeam_channel::Receiver<(NetworkedGame, Duration)>,
+    player_pos_sender: crossbeam_channel::Sender<PlayerState>,
+    /// Carries the opponent on between positions from the server
+    opponent_reckoning: DeadReckoning,
+    /// Set for duels with the `rollback` rule, the opponent then being
+    /// simulated from their inputs instead
+    rollback: Option<Rollback<PlayerState>>,
+    /// Jumps and dashes since the last simulation step, for our next input
+    presses: rollback::FrameInput,
+    /// Attacks and dashes pressed before they could go off
+    buffered: InputBuffer,
+    /// Keys held down, turned into movement, sprint and jumps
+    input: InputMapper,
+    /// The on-screen stick and buttons, when they're turned on
+    touch: TouchControls,
+    /// Our inputs from a frame, and the frame the opponent's are wanted from
+    inputs_sender: crossbeam_channel::Sender<OutgoingInputs>,
+    /// The opponent's inputs from a frame, and how many of ours the server has
+    inputs_receiver: crossbeam_channel::Receiver<IncomingInputs>,
+    inputs_stored: u32,
+    /// Our checksum at the last `CHECKSUM_INTERVAL`th frame, for the
+    /// opponent to check their simulation of us by
+    own_checksum: Option<(u32, u64)>,
+    /// The opponent's checksum didn't match, so they're put where the next
+    /// world snapshot has them
+    resync_requested: bool,
+    /// When the opponent was last corrected that way, for the warning icon
+    corrected: Option<Instant>,
+    /// Left stick position, x then y
+    stick: (f32, f32),
+}
+
+impl GameState {
+
+    /// Joins the game as `player`, giving back the ID to send from then on
+    /// and the world, or why the server wouldn't have us. Movement is
+    /// simulated with the server's values from then on.
+    fn join_game(host: String, player: String, game_id: String, skin: &Skin) -> std::result::Result<(String, NetworkedGame), String> {
+        let msg = "joingame".to_string();
+        let result = net::send_message(host, game_id, player, msg, skin.to_meta(), true)
+            .ok_or_else(|| "no answer from the server".to_string())?;
+        let mut joined: serde_json::Value = serde_json::from_str(&result).map_err(|e| e.to_string())?;
+        if let Some(error) = joined["error"].as_str() {
+            return Err(error.to_string())
+        }
+        let id = joined["player_id"].as_str().ok_or_else(|| "no player ID from the server".to_string())?.to_string();
+        let world = serde_json::from_value(joined["world"].take()).map_err(|e| e.to_string())?;
+        let tunables: config::Tunables = serde_json::from_value(joined["tunables"].take())
+            .map_err(|e| format!("no movement values from the server: {}", e))?;
+        tunables.validate().map_err(|e| format!("bad movement values from the server: {}", e))?;
+        config::use_server_tunables(tunables);
+        Ok((id, world))
+    }
+
+    /// The first game on `host` still waiting for players, or a new one
+    /// made there for others to find the same way.
+    pub fn find_or_create_game(host: String) -> std::result::Result<String, String> {
+        let filter = GameFilter { open: true, ..GameFilter::default() };
+        let listed = servers::list_games(&host, &filter).ok_or_else(|| "no answer from the server".to_string())?;
+        if let Some(game) = listed.games.first() {
+            println!("Joining game {}", game[0]);
+            return Ok(game[0].clone())
+        }
+        let game_id = net::send_message(host, "".to_string(), "".to_string(), "newgame".to_string(), "".to_string(), true)
+            .ok_or_else(|| "no answer from the server".to_string())?;
+        if game_id.starts_with("Invalid") || game_id.starts_with(admission::SERVER_FULL) {
+            return Err(game_id)
+        }
+        println!("Created game {}, waiting for players", game_id);
+        Ok(game_id)
+    }
+
+    fn send_ready(server: String, player: String, game_id: String, content: &ContentHashes) -> String {
+        let msg = "ready".to_string();
+        net::send_message(server, game_id, player, msg, json!(content).to_string(), true).unwrap()
+    }
+
+    fn get_opponent(server: String, player: String, game_id: String) -> Option<Vec<f32>> {
+        let result = match net::send_message(server, game_id, player, "getopponent".to_string(), "".to_string(), true) {
+            Some(r) => r,
+            None => {
+                return None;
+            },
+        };
+        if let Ok(opponent) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(opponent_array) = opponent["opponent"].as_array() {
+                let opponent_vec: Vec<f32> = opponent_array.iter().map(|p| p.as_f64().unwrap() as f32 ).collect();
+                return Some(opponent_vec);
+       
+            }
+        }
+        None
+    }
+
+    /// Sends our inputs from frame `from` and our latest checksum, getting
+    /// back the opponent's inputs from frame `want`, how many of ours the
+    /// server has now and the opponent's latest checksum.
+    fn send_inputs(server: String, player: String, game_id: String, outgoing: OutgoingInputs) -> Option<IncomingInputs> {
+        let (from, inputs, want, checksum) = outgoing;
+        let meta = json!({"from": from, "inputs": inputs, "want": want, "checksum": checksum}).to_string();
+        let result = net::send_message(server, game_id, player, "sendinputs".to_string(), meta, true)?;
+        let reply: serde_json::Value = serde_json::from_str(&result).ok()?;
+        let inputs = serde_json::from_value(reply["inputs"].clone()).ok()?;
+        let checksum = serde_json::from_value(reply["checksum"].clone()).ok();
+        Some((reply["from"].as_u64()? as u32, inputs, reply["stored"].as_u64()? as u32, checksum))
+    }
+
+    fn vote_map(server: String, player: String, game_id: String, map: String) -> bool {
+        let msg = "votemap".to_string();
+        match net::send_message(server, game_id, player, msg, map, true) {
+            Some(result) => {
+                let vote_result: serde_json::Value = serde_json::from_str(&result).unwrap_or_default();
+                vote_result["voted"].as_bool().unwrap_or(false)
+            },
+            None => false,
+        }
+    }
+
+    /// The server's version, caps, message of the day and rules.
+    fn get_server_info(server: String) -> Option<serde_json::Value> {
+        let result = net::send_message(server, "".to_string(), "".to_string(), "serverinfo".to_string(), "".to_string(), true)?;
+        serde_json::from_str(&result).ok()
+    }
+
+    fn get_opponent_name(server: String, player: String, game_id: String) -> String {
+        let msg = "getopponentname".to_string();
+        net::send_message(server, game_id, player, msg, "".to_string(), true).unwrap()
+    }
+
+    pub fn get_world_state(server: String, player: String, game_id: String) -> Option<NetworkedGame> {
+        let msg = "getworld".to_string();
+        let result = net::send_message(server, game_id, player, msg, "".to_string(), true)?;
+        puffin::profile_scope!("deserialize world");
+        match serde_json::from_str(&result) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                println!("Error in getting world: {} {}", e, result);
+                None
+            }
+        }
+    }
+
+    fn send_attack(server: String, player: String, game_id: String) {
+        net::send_message(server, game_id, player, "attack".to_string(), "".to_string(), false);
+    }
+
+    fn send_block(server: String, player: String, game_id: String, blocking: bool) {
+        let meta = if blocking { "1" } else { "0" };
+        net::send_message(server, game_id, player, "block".to_string(), meta.to_string(), false);
+    }
+
+    fn send_chat(server: String, player: String, game_id: String, text: String) {
+        net::send_message(server, game_id, player, "sendchat".to_string(), text, false);
+    }
+
+    /// Downloads map `name` from the server if we don't have it, so custom
+    /// maps don't have to be handed around by hand. Only its tileset image
+    /// still has to be.
+    fn ensure_map(server: String, player: String, game_id: String, name: &str) -> std::result::Result<(), String> {
+        if map::source(name).is_ok() {
+            return Ok(())
+        }
+        println!("Downloading map {} from {}", name, server);
+        let downloaded = transfer::download(|index| {
+            let answer = net::send_message(server.clone(), game_id.clone(), player.clone(), "getmap".to_string(), format!("{}:{}", name, index), true)?;
+            serde_json::from_str(&answer).ok()
+        });
+        downloaded.and_then(|contents| map::save(name, &contents).map_err(|e| e.to_string()))
+    }
+
+    /// Starts downloading map `name` on its own thread, unless it's here,
+    /// on its way or failed already.
+    fn download_map(&mut self, name: &str) {
+        let known = map::source(name).is_ok()
+            || self.failed_maps.iter().any(|failed| failed == name)
+            || self.map_downloads.iter().any(|(downloading, _)| downloading == name);
+        if known {
+            return
+        }
+        let (server, player, game_id, map_name) = (self.server.clone(), self.player.id.clone(), self.game_id.clone(), name.to_string());
+        let (sender, receiver) = bounded(1);
+        std::thread::spawn(move || {
+            let _ = sender.send(GameState::ensure_map(server, player, game_id, &map_name));
+        });
+        self.map_downloads.push((name.to_string(), receiver));
+    }
+
+    /// Takes in the downloads that have finished, keeping which failed.
+    fn check_map_downloads(&mut self) {
+        let mut finished = vec![];
+        self.map_downloads.retain(|(name, receiver)| match receiver.try_recv() {
+            Ok(result) => {
+                finished.push((name.clone(), result));
+                false
+            },
+            Err(crossbeam_channel::TryRecvError::Empty) => true,
+            Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                finished.push((name.clone(), Err("the download stopped".to_string())));
+                false
+            },
+        });
+        for (name, result) in finished {
+            if let Err(e) = result {
+                println!("Unable to download map {}: {}", name, e);
+                self.failed_maps.push(name);
+            }
+        }
+    }
+
+    /// Leaves a game whose map we can't get, back to the menu.
+    fn map_failed(&mut self, name: &str, error: &str) {
+        let message = format!("Unable to load map {}: {}", name, error);
+        println!("{}", message);
+        self.announcement = Some((message, Instant::now()));
+        self.gameover = true;
+        GameState::send_leave(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+        if self.settings_screen.is_some() {
+            self.toggle_settings();
+        }
+        self.open_pause_menu(PauseMenu::confirming_quit());
+    }
+
+    /// Has the server mute `name` in our game, which it only does if we're
+    /// the host. Whether they're muted now, or why not.
+    fn mute_player(server: String, player: String, game_id: String, name: String) -> Option<std::result::Result<bool, String>> {
+        let result = net::send_message(server, game_id, player, "muteplayer".to_string(), name, true)?;
+        let result: serde_json::Value = serde_json::from_str(&result).ok()?;
+        match result["error"].as_str() {
+            Some(e) => Some(Err(e.to_string())),
+            None => Some(Ok(result["muted"].as_bool().unwrap_or(false))),
+        }
+    }
+
+    /// Has the sandbox server carry out `command`, see
+    /// `NetworkedGame::sandbox_command`.
+    fn send_sandbox(&self, command: &str) -> std::result::Result<String, String> {
+        let result = net::send_message(self.server.clone(), self.game_id.clone(), self.player.id.clone(), "sandbox".to_string(), command.to_string(), true)
+            .ok_or_else(|| "No answer from the server".to_string())?;
+        let result: serde_json::Value = serde_json::from_str(&result).map_err(|e| e.to_string())?;
+        match result["error"].as_str() {
+            Some(e) => Err(e.to_string()),
+            None => Ok(result["done"].as_str().unwrap_or("").to_string()),
+        }
+    }
+
+    /// A sandbox hotkey, toasting how it went.
+    fn sandbox_command(&mut self, command: &str) {
+        let message = self.send_sandbox(command).unwrap_or_else(|e| e);
+        self.toast = Some((message, Instant::now()));
+    }
+
+    /// The console's own commands, with `tune` from `config`.
+    fn console_commands() -> Registry<GameState> {
+        let mut registry: Registry<GameState> = Registry::default();
+        registry.register("spawn", "spawn potion|crate [TYPE] [X Y]", |state, args| state.send_sandbox(&args.join(" ")));
+        // HP and MP are the server's, anything else is a movement value
+        registry.register("set", "set hp|mp|NAME VALUE", |state, args| match args.first() {
+            Some(&"hp") | Some(&"mp") => state.send_sandbox(&args.join(" ")),
+            _ => config::tune(args),
+        });
+        registry.register("god", "god", |state, _| state.send_sandbox("god"));
+        registry.register("tp", "tp X Y", |state, args| {
+            let done = state.send_sandbox(&format!("tp {}", args.join(" ")))?;
+            // The server checked them already
+            state.player.body.x = args.first().and_then(|x| x.parse().ok()).unwrap_or(state.player.body.x);
+            state.player.body.y = args.get(1).and_then(|y| y.parse().ok()).unwrap_or(state.player.body.y);
+            Ok(done)
+        });
+        registry.register("netstat", "netstat", |state, _| Ok(state.netstat()));
+        registry.register("dumpsnapshots", "dumpsnapshots", |state, _| {
+            state.dump_requested = true;
+            Ok("Saving snapshots...".to_string())
+        });
+        config::console_commands(&mut registry);
+        registry
+    }
+
+    fn netstat(&self) -> String {
+        let ping = self.ping.map_or("-".to_string(), |ping| format!("{}ms", ping.as_millis()));
+        let rollback = self.rollback.as_ref().map_or("off".to_string(), |rollback| format!("confirmed to frame {}", rollback.confirmed()));
+        format!("{} game {} as {}, ping {}, rollback {}", self.server, self.game_id, self.player.id, ping, rollback)
+    }
+
+    fn console_allowed(&self) -> bool {
+        cfg!(debug_assertions) || self.sandbox
+    }
+
+    /// Typing in the console, Return runs the line and the console key or
+    /// Escape closes it.
+    fn console_key(&mut self, keycode: KeyCode) {
+        match keycode {
+            KeyCode::Back => {
+                if let Some(input) = &mut self.console.input {
+                    input.pop();
+                }
+            },
+            controls::CONSOLE_KEY | controls::QUIT_KEY => self.console.input = None,
+            controls::CHAT_KEY => {
+                if let Some((run, words)) = self.console.submit() {
+                    let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+                    let answer = run(self, &words).unwrap_or_else(|e| e);
+                    self.console.print(answer);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Chat after message `seen`, whatever we're allowed to read of it.
+    fn get_chat(server: String, player: String, game_id: String, seen: u32) -> Option<Vec<ChatMessage>> {
+        let result = net::send_message(server, game_id, player, "getchat".to_string(), seen.to_string(), true)?;
+        serde_json::from_str(&result).ok()
+    }
+
+    fn send_skin(server: String, player: String, game_id: String, skin: &Skin) {
+        net::send_message(server, game_id, player, "setskin".to_string(), skin.to_meta(), false);
+    }
+
+    /// Lobby choice of the next color or sprite sheet.
+    fn change_skin(&mut self, next_sheet: bool) {
+        let mut skin = self.player.skin.clone();
+        if next_sheet {
+            let mut sheets: Vec<&String> = self.assets.textures.keys().filter(|k| k.starts_with(skin::DEFAULT_SHEET)).collect();
+            sheets.sort();
+            let current = sheets.iter().position(|s| **s == skin.sheet).unwrap_or(0);
+            if let Some(sheet) = sheets.get((current + 1) % sheets.len().max(1)) {
+                skin.sheet = sheet.to_string();
+            }
+        } else {
+            skin.next_color();
+        }
+        GameState::send_skin(self.server.clone(), self.player.id.clone(), self.game_id.clone(), &skin);
+        self.player.set_skin(skin, &self.assets.textures);
+    }
+
+    /// Opens the settings screen, or closes it and saves any changes. Keys
+    /// held down when it opens are let go of.
+    fn toggle_settings(&mut self) {
+        if self.settings_screen.take().is_some() {
+            if let Err(e) = self.controls.save() {
+                println!("Unable to save controls to {}: {}", controls::controls_path().display(), e);
+            }
+            self.save_settings();
+            return
+        }
+        self.release_keys();
+        self.settings_screen = Some(SettingsScreen::default());
+    }
+
+    /// Lets go of everything held down, for when a menu takes over the keys.
+    fn release_keys(&mut self) {
+        self.player.dir = Direction::default();
+        self.player.sprint_held = false;
+        self.input.clear();
+        self.touch = TouchControls::default();
+        if self.player.blocking {
+            self.player.set_blocking(false);
+            GameState::send_block(self.server.clone(), self.player.id.clone(), self.game_id.clone(), false);
+        }
+    }
+
+    fn open_pause_menu(&mut self, menu: PauseMenu) {
+        self.release_keys();
+        self.pause_menu = Some(menu);
+    }
+
+    /// Moves around the pause menu, or answers whether to quit.
+    fn pause_key(&mut self, ctx: &mut Context, keycode: KeyCode) {
+        let menu = match self.pause_menu.as_mut() {
+            Some(menu) => menu,
+            None => return,
+        };
+        if menu.confirming_quit {
+            match keycode {
+                KeyCode::Y | KeyCode::Return => self.quit(ctx),
+                KeyCode::N | controls::QUIT_KEY => menu.confirming_quit = false,
+                _ => (),
+            }
+            return
+        }
+        let items = menu::MENU_ITEMS.len();
+        match keycode {
+            KeyCode::Up => menu.selected = (menu.selected + items - 1) % items,
+            KeyCode::Down => menu.selected = (menu.selected + 1) % items,
+            controls::QUIT_KEY => self.pause_menu = None,
+            KeyCode::Return => match menu.item() {
+                MenuItem::Resume => self.pause_menu = None,
+                MenuItem::Settings => self.toggle_settings(),
+                MenuItem::Quit => menu.confirming_quit = true,
+            },
+            _ => (),
+        }
+    }
+
+    /// Tells the server we're going, so it can hand out the win or our spot
+    /// in the lobby, and closes the game.
+    fn quit(&mut self, ctx: &mut Context) {
+        GameState::send_leave(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+        event::quit(ctx);
+    }
+
+    /// Ends the game for the server going away and offers to quit.
+    fn server_closed(&mut self) {
+        if self.server_closing {
+            return
+        }
+        self.server_closing = true;
+        self.gameover = true;
+        let message = "The server is shutting down".to_string();
+        println!("{}", message);
+        self.announcement = Some((message, Instant::now()));
+        if self.settings_screen.is_some() {
+            self.toggle_settings();
+        }
+        self.open_pause_menu(PauseMenu::confirming_quit());
+    }
+
+    fn send_leave(server: String, player: String, game_id: String) {
+        net::send_message(server, game_id, player, "leavegame".to_string(), "".to_string(), false);
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save() {
+            println!("Unable to save settings to {}: {}", settings::settings_path().display(), e);
+        }
+    }
+
+    /// Moves around the settings screen, changing options or taking the new
+    /// key for the selected action.
+    fn settings_key(&mut self, ctx: &mut Context, keycode: KeyCode) {
+        let screen = match self.settings_screen.as_mut() {
+            Some(screen) => screen,
+            None => return,
+        };
+        if screen.rebinding {
+            let action = controls::ACTIONS[screen.selected];
+            screen.rebinding = false;
+            screen.message = if keycode == controls::QUIT_KEY {
+                None
+            } else {
+                self.controls.rebind(action, keycode).err()
+            };
+            return
+        }
+        let rows = screen.rows();
+        match keycode {
+            KeyCode::Up => screen.selected = (screen.selected + rows - 1) % rows,
+            KeyCode::Down => screen.selected = (screen.selected + 1) % rows,
+            KeyCode::Tab => {
+                screen.page = match screen.page {
+                    SettingsPage::Options => SettingsPage::Controls,
+                    SettingsPage::Controls => SettingsPage::Options,
+                };
+                screen.selected = 0;
+                screen.message = None;
+            },
+            KeyCode::Return if screen.page == SettingsPage::Controls => {
+                screen.rebinding = true;
+                screen.message = None;
+            },
+            KeyCode::Left | KeyCode::Right | KeyCode::Return if screen.page == SettingsPage::Options => {
+                let setting = settings::SETTINGS[screen.selected];
+                self.settings.change(setting, keycode != KeyCode::Left);
+                self.apply_setting(ctx, setting);
+            },
+            _ => (),
+        }
+    }
+
+    /// Puts a changed setting into effect straight away, where ggez can.
+    fn apply_setting(&mut self, ctx: &mut Context, setting: Setting) {
+        let applied = match setting {
+            Setting::Fullscreen => {
+                let fullscreen = if self.settings.fullscreen { FullscreenType::Desktop } else { FullscreenType::Windowed };
+                graphics::set_fullscreen(ctx, fullscreen)
+            },
+            Setting::Resolution if !self.settings.fullscreen => {
+                let (width, height) = self.settings.window_size();
+                graphics::set_drawable_size(ctx, width, height)
+            },
+            _ => Ok(()),
+        };
+        if let Err(e) = applied {
+            println!("Unable to change {}: {}", setting.title(), e);
+        }
+    }
+
+    fn send_place_trap(server: String, player: String, game_id: String) {
+        net::send_message(server, game_id, player, "placetrap".to_string(), "".to_string(), false);
+    }
+
+    fn send_position(server: String, player: PlayerState, game_id: String) {
+        let meta = player.position_meta();
+        net::send_message(server, game_id, player.id, "sendposition".to_string(), meta, false);
+    }
+
+    /// One frame of the opponent under rollback, run again whenever a guess
+    /// at their input turns out wrong.
+    fn simulate_opponent(opponent: &mut PlayerState, input: rollback::FrameInput, step: f32, double_jump: bool, map: &Map, obstacles: &SpatialHash) {
+        opponent.apply_input(input, double_jump);
+        opponent.update(step, !opponent.staggered, map, obstacles);
+    }
+
+    /// Swaps inputs with the opponent under rollback, simulating them again
+    /// from wherever theirs went differently than guessed.
+    fn exchange_inputs(&mut self) {
+        let rollback = match &mut self.rollback {
+            Some(rollback) => rollback,
+            None => return,
+        };
+        if let Ok((from, inputs, stored, checksum)) = self.inputs_receiver.try_recv() {
+            self.inputs_stored = stored;
+            let step = config::tunables().simulation_step_seconds();
+            let (double_jump, map) = (self.double_jump, &self.map);
+            let obstacles = &self.obstacles;
+            rollback.receive(from, &inputs, &mut self.opponent.state, |opponent, input| {
+                GameState::simulate_opponent(opponent, input, step, double_jump, map, obstacles)
+            });
+            if let Some((frame, checksum)) = checksum {
+                if rollback.matches(frame, checksum) == Some(false) && !self.resync_requested {
+                    let message = format!("Desync with {} at tick {}, resyncing from the server", self.opponent.name, frame);
+                    println!("{}", message);
+                    self.console.print(message.clone());
+                    self.resync_requested = true;
+                }
+            }
+        }
+        let outgoing = (self.inputs_stored, rollback.local_since(self.inputs_stored).to_vec(), rollback.confirmed(), self.own_checksum);
+        let _ = self.inputs_sender.try_send(outgoing);
+    }
+
+    /// Puts the opponent where a world snapshot has them after their
+    /// checksum didn't match, starting the simulation over from there.
+    fn resync_opponent(&mut self, world: &NetworkedGame) {
+        if !self.resync_requested {
+            return
+        }
+        let theirs = match world.players.iter().find(|p| p.number == self.opponent.number) {
+            Some(theirs) => theirs,
+            None => return,
+        };
+        if let Some(rollback) = &mut self.rollback {
+            self.opponent.body = theirs.body;
+            self.opponent.dir = theirs.dir.clone();
+            self.opponent.jumping = theirs.jumping;
+            self.opponent.jump_offset = theirs.jump_offset;
+            self.opponent.dashing = theirs.dashing;
+            self.opponent.stamina = theirs.stamina;
+            rollback.resync();
+            self.resync_requested = false;
+            self.events.emit(GameEvent::Corrected);
+        }
+    }
+
+    fn receive_chat(&mut self) {
+        if let Ok(messages) = self.chat_receiver.try_recv() {
+            for message in messages {
+                self.events.emit(GameEvent::ChatReceived { from: message.from, text: message.text, spectator: message.spectator });
+            }
+        }
+    }
+
+    /// Hands what happened since the last frame to everything that shows it.
+    fn dispatch_events(&mut self, ctx: &mut Context) {
+        let volume = self.settings.master_volume * self.settings.sfx_volume;
+        for event in self.events.drain() {
+            particles::on_event(&mut self.effects, &event);
+            damage_numbers::on_event(&mut self.effects, &event);
+            self.sounds.on_event(ctx, &event, volume);
+            match event {
+                GameEvent::Hit { on_us: true, .. } => {
+                    self.combo_popup = None;
+                    if self.settings.screen_shake {
+                        self.camera.shake();
+                    }
+                },
+                GameEvent::HitLanded if self.settings.hit_stop => {
+                    self.hit_stop_until = Some(Instant::now() + Duration::from_millis(HIT_STOP_MILLIS));
+                },
+                GameEvent::Combo(combo) => self.combo_popup = Some((combo, Instant::now())),
+                // Our death, or the blow that ends the game, gets replayed
+                GameEvent::Death { name, final_blow } if self.settings.kill_cam
+                    && (name == self.player.name || (final_blow && self.replay.is_none())) => {
+                    self.replay = self.history.replay(&name);
+                },
+                GameEvent::Feed(text) => {
+                    self.feed.push((text, Instant::now()));
+                    if self.feed.len() > FEED_LINES {
+                        self.feed.drain(0..self.feed.len() - FEED_LINES);
+                    }
+                },
+                GameEvent::Announcement(text) => self.announcement = Some((text, Instant::now())),
+                GameEvent::RoundStart => self.announcement = Some(("Fight!".to_string(), Instant::now())),
+                GameEvent::Corrected => self.corrected = Some(Instant::now()),
+                GameEvent::ChatReceived { from, text, spectator } => {
+                    if self.settings.is_muted(&from) {
+                        continue
+                    }
+                    let from = if spectator { format!("[spectator] {}", from) } else { from };
+                    self.chat.push((format!("{}: {}", from, text), Instant::now()));
+                    if self.chat.len() > CHAT_LINES {
+                        self.chat.drain(0..self.chat.len() - CHAT_LINES);
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Swings if we can, returning whether we did.
+    fn attack(&mut self) -> bool {
+        if !self.player.can_attack() {
+            return false
+        }
+        self.player.last_attack = Some(Instant::now());
+        GameState::send_attack(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+        true
+    }
+
+    /// Dashes if we can, returning whether we did.
+    fn dash(&mut self) -> bool {
+        if !self.player.can_dash() {
+            return false
+        }
+        self.player.start_dash();
+        self.presses |= rollback::DASH;
+        self.player.dashing
+    }
+
+    /// Carries out a press of `action`, from a key or a touch button.
+    fn press_action(&mut self, action: Action) {
+        for fired in self.input.press(action, &self.settings) {
+            match fired {
+                Action::Left | Action::Right | Action::Up | Action::Down => {
+                    self.player.dir = Direction::held(&self.input);
+                    self.player.throttle = 1.0;
+                },
+                Action::Dash => {
+                    if self.ready && !self.dash() {
+                        self.buffered.press(Action::Dash);
+                    }
+                },
+                Action::Block => {
+                    if self.ready && !self.player.blocking && self.player.mp > 0 {
+                        self.player.set_blocking(true);
+                        GameState::send_block(self.server.clone(), self.player.id.clone(), self.game_id.clone(), true);
+                    }
+                },
+                Action::PlaceTrap => {
+                    if self.ready && self.player.traps > 0 {
+                        GameState::send_place_trap(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+                    }
+                },
+                Action::Attack => {
+                    if self.ready && !self.attack() {
+                        self.buffered.press(Action::Attack);
+                    }
+                },
+                Action::Jump => {
+                    self.player.jump(self.double_jump);
+                    self.presses |= rollback::JUMP;
+                },
+                Action::Sprint => self.player.sprint_held = self.input.sprinting(&self.settings),
+            }
+        }
+    }
+
+    /// Carries out letting go of `action`.
+    fn release_action(&mut self, action: Action) {
+        if !self.input.release(action) {
+            return
+        }
+        match action {
+            Action::Left | Action::Right | Action::Up | Action::Down => self.player.dir = Direction::held(&self.input),
+            Action::Sprint => self.player.sprint_held = self.input.sprinting(&self.settings),
+            Action::Block if self.player.blocking => {
+                self.player.set_blocking(false);
+                GameState::send_block(self.server.clone(), self.player.id.clone(), self.game_id.clone(), false);
+            },
+            _ => ()
+        };
+    }
+
+    /// Moves the way a stick, a gamepad's or the touch one, is pushed, -1.0 -
+    /// 1.0 each way with pushing it away positive.
+    fn steer(&mut self, x: f32, y: f32) {
+        self.player.dir.left = x < -STICK_DEAD_ZONE;
+        self.player.dir.right = x > STICK_DEAD_ZONE;
+        self.player.dir.up = y > STICK_DEAD_ZONE;
+        self.player.dir.down = y < -STICK_DEAD_ZONE;
+        // Letting go keeps the last push so the player glides to a stop as usual
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude > STICK_DEAD_ZONE {
+            self.player.throttle = ((magnitude - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)).min(1.0);
+        }
+    }
+
+    /// Whether the touch controls are up and taking presses.
+    fn touch_active(&self) -> bool {
+        self.settings.touch_controls && self.ready && self.spectator.is_none() && self.replay.is_none()
+            && self.settings_screen.is_none() && self.pause_menu.is_none() && self.chat_input.is_none() && !self.console.is_open()
+    }
+
+    /// Lets off attacks and dashes pressed a moment before they could go.
+    fn fire_buffered(&mut self) {
+        let window = Duration::from_millis(config::get().input_buffer_millis);
+        if self.player.can_attack() && self.buffered.take(Action::Attack, window) {
+            self.attack();
+        }
+        if self.player.can_dash() && self.buffered.take(Action::Dash, window) {
+            self.dash();
+        }
+    }
+
+    /// Opens the chat box, letting go of whatever we were holding since the
+    /// keys won't be let go of until it's closed.
+    fn open_chat(&mut self) {
+        self.chat_input = Some(String::new());
+        self.player.dir = Direction::default();
+        self.player.sprint_held = false;
+        self.spectator_pan = Direction::default();
+        self.buffered.clear();
+        self.input.clear();
+    }
+
+    /// Carries out `/mute NAME`, which hides someone's chat from us from now
+    /// on, and `/gamemute NAME`, which has the host mute them for everyone.
+    /// Either one again undoes it. Returns whether `text` was a command.
+    fn chat_command(&mut self, text: &str) -> bool {
+        let (command, name) = match text.split_once(' ') {
+            Some((command, name)) => (command, name.trim()),
+            None => (text, ""),
+        };
+        let message = match command {
+            "/mute" if !name.is_empty() => {
+                let muted = self.settings.toggle_mute(name);
+                self.save_settings();
+                format!("{} {}", if muted { "Muted" } else { "Unmuted" }, name)
+            },
+            "/gamemute" if !name.is_empty() => {
+                match GameState::mute_player(self.server.clone(), self.player.id.clone(), self.game_id.clone(), name.to_string()) {
+                    Some(Ok(true)) => format!("Muted {} for everyone", name),
+                    Some(Ok(false)) => format!("Unmuted {} for everyone", name),
+                    Some(Err(e)) => e,
+                    None => "No answer from the server".to_string(),
+                }
+            },
+            "/mute" | "/gamemute" => format!("Usage: {} NAME", command),
+            "/set" if self.sandbox => match config::tune(&name.split_whitespace().collect::<Vec<&str>>()) {
+                Ok(set) => set,
+                Err(e) => e,
+            },
+            _ => return false,
+        };
+        self.toast = Some((message, Instant::now()));
+        true
+    }
+
+    /// Typing in the chat box, Return sends and Escape gives up.
+    fn chat_key(&mut self, keycode: KeyCode) {
+        let input = match &mut self.chat_input {
+            Some(input) => input,
+            None => return,
+        };
+        match keycode {
+            KeyCode::Back => {
+                input.pop();
+            },
+            KeyCode::Escape => self.chat_input = None,
+            controls::CHAT_KEY => {
+                if let Some(text) = self.chat_input.take().filter(|text| !text.trim().is_empty()) {
+                    if !self.chat_command(text.trim()) {
+                        GameState::send_chat(self.server.clone(), self.player.id.clone(), self.game_id.clone(), text);
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Goes back to the game after the kill cam, the victim only starting
+    /// to fade out now.
+    fn end_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            let victim = self.player_mut(&replay.victim);
+            if victim.view.died_at.is_some() {
+                victim.view.died_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Moves everyone to where the kill cam has them, alive, handing back
+    /// where they really are for `restore_bodies`.
+    fn replay_bodies(&mut self) -> Vec<(Position, Option<Position>, Option<Instant>)> {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => return vec![],
+        };
+        let mut live = vec![];
+        for player in std::iter::once(&mut self.player).chain(std::iter::once(&mut self.opponent)).chain(self.others.iter_mut()) {
+            live.push((player.body, player.view.previous_body, player.view.died_at));
+            if let Some(body) = replay.body(&player.name) {
+                player.body = body;
+                player.view.previous_body = None;
+                player.view.died_at = None;
+            }
+        }
+        live
+    }
+
+    fn restore_bodies(&mut self, live: Vec<(Position, Option<Position>, Option<Instant>)>) {
+        let players = std::iter::once(&mut self.player).chain(std::iter::once(&mut self.opponent)).chain(self.others.iter_mut());
+        for (player, (body, previous_body, died_at)) in players.zip(live) {
+            player.body = body;
+            player.view.previous_body = previous_body;
+            player.view.died_at = died_at;
+        }
+    }
+
+    /// We only watch once we're down for good or the game is over.
+    fn spectating(&self) -> bool {
+        self.ready && (self.gameover || self.player.hp <= 0)
+    }
+
+    /// Everyone the spectator camera can follow, us first, by slot.
+    fn watched_players(&self) -> impl Iterator<Item = &Player> {
+        std::iter::once(&self.player).chain(std::iter::once(&self.opponent)).chain(self.others.iter())
+    }
+
+    /// Starts watching whoever's still standing when we go down, and goes
+    /// back to following ourselves when we're back.
+    fn update_spectator(&mut self) {
+        let spectating = self.spectating();
+        if spectating && self.spectator.is_none() {
+            let standing = self.watched_players().position(|p| p.hp > 0 && p.name != self.player.name);
+            self.spectator = Some(standing.map_or(SpectatorView::Free, SpectatorView::Follow));
+            self.player.dir = Direction::default();
+        } else if !spectating && self.spectator.is_some() {
+            self.spectator = None;
+            self.spectator_pan = Direction::default();
+            self.camera.reset_zoom();
+        }
+    }
+
+    /// Number keys follow a player, movement and arrow keys pan the camera
+    /// around freely.
+    fn spectator_key(&mut self, keycode: KeyCode, pressed: bool) {
+        let slot = match keycode {
+            KeyCode::Key1 => Some(0),
+            KeyCode::Key2 => Some(1),
+            KeyCode::Key3 => Some(2),
+            KeyCode::Key4 => Some(3),
+            KeyCode::Key5 => Some(4),
+            KeyCode::Key6 => Some(5),
+            KeyCode::Key7 => Some(6),
+            KeyCode::Key8 => Some(7),
+            KeyCode::Key9 => Some(8),
+            _ => None,
+        };
+        if let Some(slot) = slot {
+            if pressed && slot < self.watched_players().count() {
+                self.spectator = Some(SpectatorView::Follow(slot));
+            }
+            return;
+        }
+        let held = match (keycode, self.controls.action(keycode)) {
+            (KeyCode::Up, _) | (_, Some(Action::Up)) => &mut self.spectator_pan.up,
+            (KeyCode::Down, _) | (_, Some(Action::Down)) => &mut self.spectator_pan.down,
+            (KeyCode::Left, _) | (_, Some(Action::Left)) => &mut self.spectator_pan.left,
+            (KeyCode::Right, _) | (_, Some(Action::Right)) => &mut self.spectator_pan.right,
+            _ => return,
+        };
+        *held = pressed;
+        if pressed {
+            self.spectator = Some(SpectatorView::Free);
+        }
+    }
+
+    /// Saves what's been drawn so far this frame to a timestamped PNG in
+    /// `screenshots/` under the user config dir, returning where it went.
+    fn save_screenshot(ctx: &mut Context) -> GameResult<String> {
+        let taken = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |t| t.as_millis());
+        let file = format!("/screenshots/screenshot_{}.png", taken);
+        ggez::filesystem::create_dir(ctx, "/screenshots")?;
+        graphics::screenshot(ctx)?.encode(ctx, graphics::ImageFormat::Png, &file)?;
+        Ok(ggez::filesystem::user_config_dir(ctx).join(file.trim_start_matches('/')).display().to_string())
+    }
+
+    /// Swaps in textures changed on disk.
+    fn apply_reloaded(&mut self, reloaded: &[String]) {
+        if reloaded.iter().any(|name| name == TILES_ASSET) {
+            self.map.reload_tiles();
+        }
+        if let Some(potion_texture) = self.assets.textures.get("potion") {
+            self.potion_texture = potion_texture.clone();
+            for potion in self.loot.iter_mut() {
+                potion.texture = Some(potion_texture.clone());
+            }
+        }
+        // Batches hold on to the sheet they were made from
+        self.batches = SpriteBatches::default();
+        let textures = self.assets.textures.clone();
+        for player in std::iter::once(&mut self.player).chain(std::iter::once(&mut self.opponent)).chain(self.others.iter_mut()) {
+            let skin = player.skin.clone();
+            player.set_skin(skin, &textures);
+        }
+    }
+
+    /// Picks up skin changes, including the server recoloring ours to not
+    /// clash with the opponent.
+    fn apply_skins(&mut self, world: &NetworkedGame) {
+        let textures = self.assets.textures.clone();
+        for world_player in world.players.iter() {
+            let skin = world_player.skin.clone();
+            let player = self.player_mut(&world_player.name);
+            if skin != player.skin {
+                player.set_skin(skin, &textures);
+            }
+        }
+    }
+
+    /// Draws the sprites queued on `batches`, then the name plate text
+    /// queued along with them.
+    fn draw_batched(ctx: &mut Context, batches: &mut SpriteBatches) -> GameResult<()> {
+        batches.draw(ctx)?;
+        graphics::draw_queued_text(
+            ctx,
+            graphics::DrawParam::new()
+                .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+            None,
+            graphics::FilterMode::Linear,
+        )
+    }
+
+    /// Our copy of the named player. Whoever isn't us or the opponent ends up
+    /// in `others`, added on first sight.
+    fn player_mut(&mut self, name: &str) -> &mut Player {
+        if name == self.player.name {
+            return &mut self.player
+        }
+        if self.opponent.name.is_empty() {
+            self.opponent.name = name.to_string();
+        }
+        if name == self.opponent.name {
+            return &mut self.opponent
+        }
+        match self.others.iter().position(|p| p.name == name) {
+            Some(index) => &mut self.others[index],
+            None => {
+                let mut other = Player::new(name.to_string(), Position { x: 0.0, y: 0.0, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT }, None);
+                other.set_skin(Skin::default(), &self.assets.textures);
+                self.others.push(other);
+                self.others.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Keeps the snapshot, dumping what's kept when the server has us
+    /// somewhere else entirely. Respawns and the dead are left out, the
+    /// server moving those on its own.
+    fn check_desync(&mut self, ctx: &mut Context, world: &NetworkedGame) {
+        self.snapshots.record(world.clone(), (*self.player).clone());
+        let ours = match world.players.iter().find(|p| p.name == self.player.name) {
+            Some(ours) => ours,
+            None => return,
+        };
+        if ours.respawns != self.player.respawns || ours.hp == 0 || self.player.hp == 0 {
+            return
+        }
+        let distance = (ours.body.x - self.player.body.x).hypot(ours.body.y - self.player.body.y);
+        if self.snapshots.is_new_desync(distance) {
+            self.dump_snapshots(ctx, &format!("desync, the server has us {:.0} pixels away", distance));
+        }
+    }
+
+    /// Asks for where everyone went over the match, for the heatmap.
+    fn fetch_trace(&mut self) {
+        let (host, game_id) = (self.server.clone(), self.game_id.clone());
+        let (sender, receiver) = bounded(1);
+        std::thread::spawn(move || {
+            let trace = net::match_trace(host, game_id).and_then(|trace| serde_json::from_str(&trace).map_err(|e| e.to_string()));
+            match trace {
+                Ok(trace) => {
+                    let _ = sender.send(trace);
+                },
+                Err(e) => println!("Unable to get the match trace: {}", e),
+            }
+        });
+        self.trace_receiver = Some(receiver);
+    }
+
+    /// Moves the game elsewhere once the server has gone quiet mid-game, and
+    /// picks up where it went once it's there.
+    fn check_migration(&mut self) {
+        if let Some(moved) = self.migration.as_ref().and_then(|migration| migration.try_recv().ok()) {
+            self.migration = None;
+            self.last_world = Instant::now();
+            let toast = match moved {
+                Ok(moved) => {
+                    let toast = format!("The host left, game moved to {}", moved.host);
+                    self.server = moved.host;
+                    self.game_id = moved.game_id;
+                    self.player.id = moved.player_id;
+                    toast
+                },
+                Err(e) => format!("Unable to move the game: {}", e),
+            };
+            println!("{}", toast);
+            self.toast = Some((toast, Instant::now()));
+            return
+        }
+        let playing = self.started && self.ready && !self.gameover && !self.server_closing;
+        if playing && self.migration.is_none() && self.last_world.elapsed() >= Duration::from_millis(migration::MIGRATION_TIMEOUT_MILLIS) {
+            self.migrate();
+        }
+    }
+
+    /// Takes the game somewhere else, carrying on from the last snapshot.
+    fn migrate(&mut self) {
+        let world = match self.snapshots.latest() {
+            Some(world) => world.clone(),
+            None => return,
+        };
+        let (host, game_id, player_id) = (self.server.clone(), self.game_id.clone(), self.player.id.clone());
+        let (name, relay) = (self.player.name.clone(), self.relay.clone());
+        println!("Lost the server, moving game {} elsewhere", game_id);
+        self.toast = Some(("Lost the server, moving the game...".to_string(), Instant::now()));
+        let (sender, receiver) = bounded(1);
+        std::thread::spawn(move || {
+            let moved = GameState::move_game(&host, &game_id, &player_id, &name, relay, world);
+            if let Ok(moved) = &moved {
+                migration::record(&host, &game_id, &player_id, moved.clone());
+            }
+            let _ = sender.send(moved);
+        });
+        self.migration = Some(receiver);
+    }
+
+    /// Takes `world`, the last we saw of game `game_id` on `host`, to `relay`
+    /// or whoever's next in line to host it, and joins it there again as
+    /// `name`, who was `player_id` on `host`.
+    fn move_game(host: &str, game_id: &str, player_id: &str, name: &str, relay: Option<String>, world: NetworkedGame) -> std::result::Result<migration::Moved, String> {
+        let moved_id = migration::session_id(host, game_id);
+        let moved_to = match relay {
+            Some(relay) => {
+                // Whoever gets there first moves it, the rest find it there
+                net::send_message(relay.clone(), moved_id.clone(), name.to_string(), "importgame".to_string(), json!(world).to_string(), true)
+                    .ok_or_else(|| format!("no answer from {}", relay))?;
+                relay
+            },
+            None => {
+                let port = migration::port(host).ok_or_else(|| format!("no port in {}", host))?;
+                let successor = world.successor.clone().ok_or_else(|| "nobody left to host it".to_string())?;
+                if successor.name == name {
+                    migration::host(port, &moved_id, world.clone())?
+                } else {
+                    let address: IpAddr = successor.address.parse().map_err(|_e| format!("no address for {}", successor.name))?;
+                    SocketAddr::new(address, port).to_string()
+                }
+            },
+        };
+        let player_id = migration::rejoin(&moved_to, &moved_id, player_id)?;
+        Ok(migration::Moved { host: moved_to, game_id: moved_id, player_id })
+    }
+
+    fn dump_snapshots(&mut self, ctx: &mut Context, reason: &str) {
+        let toast = match self.snapshots.dump(ctx, reason) {
+            Ok(path) => format!("Saved snapshots to {} ({})", path, reason),
+            Err(e) => format!("Unable to save snapshots: {}", e),
+        };
+        println!("{}", toast);
+        self.console.print(toast.clone());
+        self.toast = Some((toast, Instant::now()));
+    }
+
+    /// Takes the server owned state of both players from a world snapshot.
+    fn apply_world(&mut self, world: &NetworkedGame) {
+        puffin::profile_function!();
+        self.apply_skins(world);
+        self.history.record(world.players.iter().map(|p| (p.name.clone(), p.body)).collect());
+        let final_blow = world.completed && !self.gameover;
+        let mut events = vec![];
+        let mut combo = None;
+        let (mut swung, mut landed) = (false, false);
+        for world_player in world.players.iter() {
+            let is_local = world_player.name == self.player.name;
+            let is_other = !is_local && world_player.name != self.opponent.name;
+            let player = self.player_mut(&world_player.name);
+            if is_other {
+                // No position stream for these, the snapshot is all we get
+                player.body = world_player.body;
+                player.view.observe(player.body);
+                player.dir = world_player.dir.clone();
+                player.jumping = world_player.jumping;
+                player.dashing = world_player.dashing;
+            }
+            if !is_local {
+                player.stamina = world_player.stamina;
+            }
+            player.number = world_player.number;
+            player.team = world_player.team;
+            player.carrying_flag = world_player.carrying_flag;
+            let crit_taken = world_player.crits_taken != player.crits_taken;
+            if world_player.hp < player.hp {
+                landed |= !is_local;
+                events.push(GameEvent::Hit {
+                    body: player.body,
+                    damage: player.hp - world_player.hp,
+                    crit: crit_taken,
+                    fatal: world_player.hp == 0,
+                    on_us: is_local,
+                });
+            }
+            if world_player.hits_taken != player.hits_taken {
+                player.view.hit_at = Some(Instant::now());
+                player.hits_taken = world_player.hits_taken;
+            }
+            if crit_taken {
+                player.view.crit_at = Some(Instant::now());
+                player.crits_taken = world_player.crits_taken;
+            }
+            if world_player.hp == 0 && player.hp > 0 {
+                player.view.died_at = Some(Instant::now());
+                events.push(GameEvent::Death { name: world_player.name.clone(), final_blow });
+            }
+            if world_player.respawns != player.respawns {
+                player.view.respawned_at = Some(Instant::now());
+                player.view.died_at = None;
+                player.stamina = world_player.stamina;
+                player.respawns = world_player.respawns;
+            }
+            player.hp = world_player.hp;
+            player.mp = world_player.mp;
+            player.str = world_player.str;
+            if world_player.level > player.level {
+                player.view.leveled_at = Some(Instant::now());
+            }
+            player.level = world_player.level;
+            player.xp = world_player.xp;
+            player.burning = world_player.burning;
+            player.staggered = world_player.staggered;
+            player.statuses = world_player.statuses.clone();
+            player.traps = world_player.traps;
+            player.equipment = world_player.equipment.clone();
+            player.afk = world_player.afk;
+            if !is_local || (!world_player.blocking && world_player.mp == 0) {
+                player.blocking = world_player.blocking;
+            }
+            if world_player.attack_count != player.attack_count {
+                swung |= is_local;
+                // Our own swings are already shown when the key is pressed
+                if !is_local {
+                    player.last_attack = Some(Instant::now());
+                }
+                player.attack_count = world_player.attack_count;
+            }
+            if is_local && world_player.combo > player.combo && world_player.combo > 1 {
+                combo = Some(world_player.combo);
+            }
+            player.combo = world_player.combo;
+        }
+        if let Some(combo) = combo {
+            events.push(GameEvent::Combo(combo));
+        }
+        if swung && landed {
+            events.push(GameEvent::HitLanded);
+        }
+        // Potions gone from under someone's feet were picked up, not expired
+        for potion in self.loot.iter() {
+            let still_there = world.potions.iter().any(|p| p.pos.x == potion.pos.x && p.pos.y == potion.pos.y);
+            if !still_there && world.players.iter().any(|p| p.body == potion.pos) {
+                events.push(GameEvent::Pickup(potion.pos));
+            }
+        }
+        self.crates = world.crates.clone();
+        sim::sync_obstacles(&mut self.obstacles, &self.crates);
+        self.traps = world.traps.iter().filter(|trap| trap.owner == self.player.number).cloned().collect();
+        self.loot = world.potions.iter().map(|potion| {
+            let mut potion = potion.clone();
+            potion.texture = Some(self.potion_texture.clone());
+            potion
+        }).collect();
+        self.equipment = world.equipment.clone();
+        self.item_spawn = world.item_spawn.clone();
+        self.scores = world.scores.clone();
+        self.zone = world.zone;
+        self.flags = world.flags.clone();
+        let last_event = self.last_event;
+        for event in world.events.iter().filter(|e| e.id > last_event) {
+            if event.announce {
+                events.push(GameEvent::Announcement(event.text.clone()));
+            } else {
+                events.push(GameEvent::Feed(event.text.clone()));
+            }
+            self.last_event = event.id;
+        }
+        for event in events {
+            self.events.emit(event);
+        }
+        self.sudden_death = world.sudden_death;
+        self.clock = world.clock;
+        self.clock_synced = Instant::now();
+        if world.server_closing {
+            self.server_closed();
+        }
+        if world.completed && !self.gameover {
+            self.gameover = true;
+            let message = match &world.winner {
+                Some(winner) if *winner == self.player.name => "You win!".to_string(),
+                Some(winner) if self.player.team.is_some_and(|t| ctf::TEAM_NAMES[t] == winner) => "Your team wins!".to_string(),
+                Some(winner) => format!("{} wins!", winner),
+                None => "Draw!".to_string(),
+            };
+            println!("{}", message);
+            if let Some(winner) = &world.winner {
+                for player in std::iter::once(&mut self.player).chain(std::iter::once(&mut self.opponent)).chain(self.others.iter_mut()) {
+                    player.view.won = player.name == *winner || player.team.is_some_and(|t| ctf::TEAM_NAMES[t] == winner);
+                }
+            }
+            self.announcement = Some((message, Instant::now()));
+            self.fetch_trace();
+        }
+        if let Some(world_player) = world.players.iter().find(|p| p.name == self.player.name) {
+            self.player.follow_corrections(&world_player.body, world_player.corrections);
+        }
+    }
+
+    pub fn new(player_name: String, host: String, game_id: String, skin: Skin, settings: Settings, mut assets: Assets) -> std::result::Result<Self, String> {
+        let (player_id, game_state) = GameState::join_game(host.clone(), player_name.clone(), game_id.clone(), &skin)
+            .map_err(|e| format!("Unable to join game {}: {}", game_id, e))?;
+
+        // Nothing's on screen yet, so the map can be waited for
+        let map = GameState::ensure_map(host.clone(), player_id.clone(), game_id.clone(), &game_state.map)
+            .and_then(|_| Map::load(&game_state.map).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Unable to load map {}: {}", game_state.map, e))?;
+        let server_notice = GameState::get_server_info(host.clone()).map_or_else(Vec::new, |info| notice::lobby_lines(&info));
+        assets.watch(TILES_ASSET, map.tileset_image());
+
+        let mut player_pos = Position { x: 100.0, y: 100.0, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT };
+        let mut opponent_pos = Position { x: 100.0, y: 100.0, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT };
+        let potion_texture = assets.textures["potion"].clone();
+        for game_state_player in game_state.players.iter() {
+            if game_state_player.name != player_name.clone() {
+                opponent_pos.x = game_state_player.body.x;
+                opponent_pos.y = game_state_player.body.y;
+            } else {
+                player_pos.x = game_state_player.body.x;
+                player_pos.y = game_state_player.body.y;
+            }
+        }
+        let mut player = Player::new(player_name, player_pos, None);
+        player.id = player_id;
+        let mut opponent = Player::new("".to_string(), opponent_pos, None);
+        for game_state_player in game_state.players.iter() {
+            if game_state_player.name == player.name {
+                player.set_skin(game_state_player.skin.clone(), &assets.textures);
+            }
+        }
+        opponent.set_skin(Skin::default(), &assets.textures);
+
+        let (s, r) = bounded(1);
+        let (player_pos_sender, player_pos_receiver) = bounded(1);
+        let (world_sender, world_receiver) = bounded(1);
+        let (chat_sender, chat_receiver) = bounded(1);
+        let (inputs_sender, inputs_to_send) = bounded::<OutgoingInputs>(1);
+        let (inputs_received, inputs_receiver) = bounded(1);
+        let rollback = game_state.rules.rollback && game_state.mode == GameMode::Duel;
+
+        let game_state = GameState {
+            player: player.clone(),
+            opponent,
+            server: host.clone(),
+            game_id: game_id.clone(),
+            loot: vec![],
+            equipment: vec![],
+            crates: vec![],
+            obstacles: SpatialHash::default(),
+            traps: vec![],
+            item_spawn: None,
+            potion_texture,
+            mode: game_state.mode,
+            score_limit: game_state.score_limit(),
+            scores: HashMap::new(),
+            zone: None,
+            flags: vec![],
+            sudden_death: None,
+            clock: 0.0,
+            clock_synced: Instant::now(),
+            time_limit: game_state.rules.time_limit,
+            double_jump: game_state.rules.double_jump,
+            rules: game_state.rules.clone(),
+            others: vec![],
+            map,
+            camera: Camera::new(SCREEN_SIZE.0, SCREEN_SIZE.1),
+            spectator: None,
+            spectator_pan: Direction::default(),
+            history: History::default(),
+            replay: None,
+            hud: Hud::new(),
+            lobby: None,
+            server_notice,
+            announcement: None,
+            toast: None,
+            combo_popup: None,
+            screenshot_requested: false,
+            feed: vec![],
+            last_event: 0,
+            chat: vec![],
+            chat_input: None,
+            chat_receiver,
+            controls: KeyBindings::load(),
+            settings,
+            settings_screen: None,
+            pause_menu: None,
+            ping: None,
+            effects: hecs::World::new(),
+            sounds: Sounds::default(),
+            batches: SpriteBatches::default(),
+            perf: Perf::default(),
+            profiler: Profiler::default(),
+            sandbox: false,
+            console: Console::new(GameState::console_commands()),
+            events: EventBus::default(),
+            snapshots: SnapshotHistory::default(),
+            dump_requested: false,
+            relay: None,
+            last_world: Instant::now(),
+            migration: None,
+            trace_receiver: None,
+            heatmap: None,
+            map_downloads: vec![],
+            failed_maps: vec![],
+            hit_stop_until: None,
+            gameover: false,
+            server_closing: false,
+            started: false,
+            last_net_update: Instant::now(),
+            last_ready_check: Instant::now(),
+            ready: false,
+            assets,
+            player_receiver: r,
+            world_receiver,
+            player_pos_sender,
+            opponent_reckoning: DeadReckoning::default(),
+            rollback: if rollback { Some(Rollback::new(PlayerState::sync_checksum)) } else { None },
+            presses: 0,
+            buffered: InputBuffer::default(),
+            input: InputMapper::default(),
+            touch: TouchControls::default(),
+            inputs_sender,
+            inputs_receiver,
+            inputs_stored: 0,
+            own_checksum: None,
+            resync_requested: false,
+            corrected: None,
+            stick: (0.0, 0.0),
+        };
+
+        let threaded_host_pos = host.clone();
+        let threaded_game_id = game_id.clone();
+
+        if rollback {
+            let (host, player_id, game_id) = (host.clone(), player.id.clone(), game_id.clone());
+            std::thread::spawn(move || {
+                while let Ok(outgoing) = inputs_to_send.recv() {
+                    if let Some(reply) = GameState::send_inputs(host.clone(), player_id.clone(), game_id.clone(), outgoing) {
+                        let _ = inputs_received.try_send(reply);
+                    }
+                }
+            });
+        }
+
+        std::thread::spawn(move || {
+            loop {
+                match player_pos_receiver.recv() {
+                    Ok(msg) => {
+                        GameState::send_position(threaded_host_pos.clone(), msg.clone(), threaded_game_id.clone());
+                    },
+                    Err(_e_) => {
+
+                    }
+                }
+            }
+        });
+        std::thread::spawn(move || {
+            let mut last_net_update = Instant::now();
+            let mut last_world_sync = Instant::now();
+            let mut last_chat = 0;
+            loop {
+                // Server owned stats like hp come from the full world snapshot
+                if Instant::now() - last_world_sync >= Duration::from_millis(config::get().world_sync_millis) {
+                    let requested = Instant::now();
+                    if let Some(world) = GameState::get_world_state(host.clone(), player.id.clone(), game_id.clone()) {
+                        let _ = world_sender.try_send((world, requested.elapsed()));
+                    }
+                    // Asked for again until they're taken, so none are skipped
+                    if let Some(messages) = GameState::get_chat(host.clone(), player.id.clone(), game_id.clone(), last_chat) {
+                        let newest = messages.last().map(|m| m.id);
+                        if let (Some(newest), Ok(())) = (newest, chat_sender.try_send(messages)) {
+                            last_chat = newest;
+                        }
+                    }
+                    last_world_sync = Instant::now();
+                }
+                if Instant::now() - last_net_update >= Duration::from_millis(NET_MILLIS_PER_UPDATE) {
+                   
+                    //let get_world = GameState::get_world_state(threaded_host.clone(), threaded_player.name.clone(), game_id.clone()).unwrap();
+                    if let Some(opponent) = GameState::get_opponent(host.clone(), player.id.clone(), game_id.clone()) {
+                        match s.send(opponent) {
+                            Ok(_) => (),
+                            Err (e) => {
+                                println!("{:?}", e);
+                            },
+                        }
+                    }
+                    last_net_update = Instant::now();
+                }
+            }
+        });
+        Ok(game_state)
+    }
+
+    /// Game logic for one update, timed for the performance overlay.
+    fn tick(&mut self, ctx: &mut Context) -> GameResult {
+        let reloaded = self.assets.reload_changed(ctx);
+        if !reloaded.is_empty() {
+            self.apply_reloaded(&reloaded);
+        }
+        if self.replay.as_ref().is_some_and(|replay| replay.finished()) {
+            self.end_replay();
+        }
+        // Taken every frame, even in the lobby, so time spent there isn't caught up on later
+        let mut steps = 0;
+        while ggez::timer::check_update_time(ctx, config::tunables().updates_per_second as u32) {
+            steps += 1;
+        }
+        // Chat goes on in the lobby too
+        self.receive_chat();
+        self.check_map_downloads();
+        if !self.started {
+            // Left before it started, for want of the map
+            if self.gameover {
+                return Ok(())
+            }
+            if Instant::now() - self.last_net_update >= Duration::from_millis(NET_GAME_START_CHECK_MILLIS) {
+                let get_world = match GameState::get_world_state(self.server.clone(), self.player.id.clone(), self.game_id.clone()) {
+                    Some(world) => world,
+                    None => {
+                        // Asked again next time round
+                        self.last_net_update = Instant::now();
+                        return Ok(())
+                    },
+                };
+                if get_world.server_closing {
+                    self.server_closed();
+                    return Ok(())
+                }
+                if !get_world.started {
+                    println!("Waiting for game {} to start...", self.game_id.clone());
+                    self.apply_skins(&get_world);
+                    // Whichever map wins the vote is on hand by the time the game starts
+                    if self.lobby.is_none() {
+                        for name in get_world.map_candidates.iter() {
+                            self.download_map(name);
+                        }
+                    }
+                    self.lobby = Some(get_world);
+                    self.last_net_update = Instant::now();
+                    return Ok(())
+                } else {
+                    // Get opponent name
+                    let opponent_name = GameState::get_opponent_name(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+                    self.opponent.name = opponent_name;
+                    let opponent_name = self.opponent.name.clone();
+                    self.others.retain(|p| p.name != opponent_name);
+                    self.apply_skins(&get_world);
+                    // Switch to the map that won the vote and its spawn points
+                    if get_world.map != self.map.name {
+                        if self.failed_maps.contains(&get_world.map) {
+                            self.map_failed(&get_world.map, "the server couldn't send it");
+                            return Ok(())
+                        }
+                        if map::source(&get_world.map).is_err() {
+                            // Started once the map is here, asking again next time round
+                            self.download_map(&get_world.map);
+                            self.last_net_update = Instant::now();
+                            return Ok(())
+                        }
+                        match Map::load(&get_world.map) {
+                            Ok(map) => self.map = map,
+                            Err(e) => {
+                                self.map_failed(&get_world.map, &e.to_string());
+                                return Ok(())
+                            },
+                        }
+                        self.assets.watch(TILES_ASSET, self.map.tileset_image());
+                    }
+                    for world_player in get_world.players.iter() {
+                        if world_player.name == self.player.name {
+                            self.player.body.x = world_player.body.x;
+                            self.player.body.y = world_player.body.y;
+                        } else if world_player.name == self.opponent.name {
+                            self.opponent.body.x = world_player.body.x;
+                            self.opponent.body.y = world_player.body.y;
+                        }
+                    }
+                    if get_world.map_candidates.len() > 1 {
+                        println!("Map {} won the vote!", get_world.map);
+                    }
+                    self.lobby = None;
+                    println!("Game started!");
+                    self.started = true
+                }
+            } else {
+                return Ok(())
+            }
+        } 
+
+        if let Ok((world, ping)) = self.world_receiver.try_recv() {
+            self.ping = Some(ping);
+            self.last_world = Instant::now();
+            self.check_desync(ctx, &world);
+            self.resync_opponent(&world);
+            self.apply_world(&world);
+        }
+        self.check_migration();
+        if let Some(trace) = self.trace_receiver.as_ref().and_then(|trace| trace.try_recv().ok()) {
+            self.trace_receiver = None;
+            self.heatmap = Some(Heatmap::new(trace));
+        }
+        if self.dump_requested {
+            self.dump_requested = false;
+            self.dump_snapshots(ctx, "dumpsnapshots");
+        }
+
+        // Get opponent
+        if let Ok(net_opponent) = self.player_receiver.try_recv() {
+            if let Some(rollback) = &mut self.rollback {
+                // Simulated from their inputs, the server's copy only matters
+                // once it's somewhere else entirely, like after a respawn
+                let (x, y) = (net_opponent[0], net_opponent[1]);
+                if (x - self.opponent.body.x).hypot(y - self.opponent.body.y) > ROLLBACK_RESYNC_PIXELS {
+                    self.opponent.body.x = x;
+                    self.opponent.body.y = y;
+                    rollback.resync();
+                }
+            } else {
+                let shown = (self.opponent.body.x, self.opponent.body.y);
+                self.opponent.body.x = net_opponent[0];
+                self.opponent.body.y = net_opponent[1];
+                let body = self.opponent.body;
+                self.opponent.view.observe(body);
+                let terrain = self.map.terrain_at(&body);
+                let max_speed = PlayerState::max_step(terrain, true) * config::tunables().updates_per_second;
+                self.opponent_reckoning.receive(body.x, body.y, shown, max_speed);
+                self.opponent.dir = Direction::from(net_opponent[2]);
+                self.opponent.jumping = net_opponent[3] != 0.0;
+                self.opponent.current_accel = net_opponent[4];
+                self.opponent.dashing = net_opponent.get(6).is_some_and(|d| *d != 0.0);
+                self.opponent.stamina = net_opponent.get(7).copied().unwrap_or(self.opponent.stamina);
+            }
+        }
+        if self.started && self.rollback.is_none() {
+            if let Some((x, y)) = self.opponent_reckoning.position() {
+                self.opponent.body.x = x;
+                self.opponent.body.y = y;
+            }
+        }
+
+        // Countdown till all players read
+        if !self.ready && Instant::now() - self.last_ready_check >= Duration::from_millis(NET_GAME_READY_CHECK) {
+            let content = ContentHashes::local(&self.map.name, &self.rules);
+            let ready_result: serde_json::Value = serde_json::from_str(&GameState::send_ready(self.server.clone(), self.player.id.clone(), self.game_id.clone(), &content)).unwrap();
+            if let Some(e) = ready_result["error"].as_str() {
+                self.toast = Some((format!("Can't start: {}", e), Instant::now()));
+            }
+            if let Some(ready) = ready_result["ready"].as_bool() {
+                self.ready = ready;
+                if ready {
+                    println!("Game ready!");
+                    self.events.emit(GameEvent::RoundStart);
+                }
+                return Ok(())
+            }
+            self.last_ready_check = Instant::now();
+            return Ok(())
+        } else if !self.ready {
+            return Ok(())
+        }
+
+        self.update_spectator();
+        self.exchange_inputs();
+        self.fire_buffered();
+        if self.input.repeating_jump(&self.settings) && !self.player.jumping {
+            self.player.jump(self.double_jump);
+            self.presses |= rollback::JUMP;
+        }
+
+        // Send pos
+        // Fixed steps keep movement the same however unevenly frames come in
+        let step = config::tunables().simulation_step_seconds();
+        let hit_stopped = self.hit_stop_until.is_some_and(|until| Instant::now() < until);
+        let was_jumping = self.player.jumping;
+        for _ in 0..steps.min(MAX_SIMULATION_STEPS) {
+            if !self.gameover && !hit_stopped {
+                let obstacles = &self.obstacles;
+                let input = self.player.frame_input(self.presses);
+                self.presses = 0;
+                if let Some(rollback) = &self.rollback {
+                    if rollback.frame() % rollback::CHECKSUM_INTERVAL == 0 {
+                        self.own_checksum = Some((rollback.frame(), self.player.sync_checksum()));
+                    }
+                }
+                self.player.update(step, !self.player.staggered, &self.map, obstacles);
+                if let Some(rollback) = &mut self.rollback {
+                    let (double_jump, map) = (self.double_jump, &self.map);
+                    self.opponent.view.step(&self.opponent.state);
+                    rollback.advance(&mut self.opponent.state, input, |opponent, input| {
+                        GameState::simulate_opponent(opponent, input, step, double_jump, map, obstacles)
+                    });
+                } else {
+                    self.opponent.update(step, false, &self.map, obstacles);
+                }
+            }
+            let dashing = std::iter::once(&self.player).chain(std::iter::once(&self.opponent)).chain(self.others.iter())
+                .filter(|p| p.dashing && !p.is_invisible())
+                .map(|p| (p.body.x + p.body.w / 2.0, p.body.y + p.body.h))
+                .collect::<Vec<_>>();
+            for (x, y) in dashing {
+                particles::spawn(&mut self.effects, x, y, particles::DASH_TRAIL);
+            }
+            ecs::update(&mut self.effects, step);
+            self.camera.update(step);
+            if self.spectator == Some(SpectatorView::Free) {
+                let pan = &self.spectator_pan;
+                let dx = (pan.right as i32 - pan.left as i32) as f32 * SPECTATOR_PAN_SPEED * step;
+                let dy = (pan.down as i32 - pan.up as i32) as f32 * SPECTATOR_PAN_SPEED * step;
+                self.camera.pan(dx, dy, self.map.pixel_width(), self.map.pixel_height());
+            }
+            self.player.drain_bars(step);
+            self.opponent.drain_bars(step);
+            for other in self.others.iter_mut() {
+                other.drain_bars(step);
+            }
+        }
+        //if Instant::now() - self.last_pos_send >= Duration::from_millis(SEND_POS_MILLIS_PER_UPDATE) && (self.player.is_moving() || self.player.jumping) {
+        // One more once we land, so the server doesn't think we're still in the air
+        let landed = was_jumping && !self.player.jumping;
+        if self.player.is_moving() || self.player.jumping || self.player.dashing || landed {
+            let _ = self.player_pos_sender.send(self.player.state.clone());
+            //self.last_pos_send = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+impl event::EventHandler<ggez::GameError> for GameState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        puffin::profile_scope!("update");
+        let started = Instant::now();
+        let result = self.tick(ctx);
+        self.dispatch_events(ctx);
+        self.perf.record_update(started.elapsed());
+        result
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        puffin::profile_scope!("draw");
+        self.perf.record_frame(ggez::timer::delta(ctx));
+        graphics::clear(ctx, [0.0, 0.5, 0.0, 1.0].into());
+        // How far into the next simulation step we are
+        let alpha = (ggez::timer::remaining_update_time(ctx).as_secs_f32() / config::tunables().simulation_step_seconds()).min(1.0);
+        let plates = PlateStyle { names: self.settings.name_plates, name_box: self.settings.name_box, palette: self.settings.color_mode };
+
+        // World space
+        let live_bodies = self.replay_bodies();
+        let followed = match self.spectator {
+            _ if self.replay.is_some() => {
+                let victim = self.replay.as_ref().map_or("", |replay| &replay.victim);
+                self.watched_players().find(|p| p.name == victim).map(|p| p.drawn_body(alpha))
+            },
+            Some(SpectatorView::Free) => None,
+            Some(SpectatorView::Follow(slot)) => self.watched_players().nth(slot).map(|p| p.drawn_body(alpha)),
+            None => Some(self.player.drawn_body(alpha)),
+        };
+        if let Some(followed) = followed {
+            self.camera.follow(&followed, self.map.pixel_width(), self.map.pixel_height());
+        }
+        let screen = self.camera.push(ctx)?;
+        self.map.draw(ctx, graphics::DrawParam::default())?;
+        if self.ready {
+            if let Some(zone) = self.zone {
+                let holders = [&self.player, &self.opponent].iter().filter(|p| p.hp > 0 && p.body == zone).count();
+                let color = match holders {
+                    0 => graphics::Color::new(1.0, 0.85, 0.2, 0.25),
+                    1 => graphics::Color::new(0.2, 1.0, 0.3, 0.3),
+                    _ => graphics::Color::new(1.0, 0.2, 0.2, 0.3),
+                };
+                let zone_fill = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), zone.into(), color)?;
+                graphics::draw(ctx, &zone_fill, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                let zone_outline = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::stroke(2.0),
+                    zone.into(),
+                    graphics::Color::new(color.r, color.g, color.b, 0.9),
+                )?;
+                graphics::draw(ctx, &zone_outline, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+            // Then we tell the player and the items to draw themselves
+            if self.mode == GameMode::CaptureTheFlag {
+                for team in 0..ctf::TEAMS {
+                    let base = graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::stroke(3.0),
+                        self.map.base(team).into(),
+                        ctf::TEAM_COLORS[team].into(),
+                    )?;
+                    graphics::draw(ctx, &base, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                }
+                for flag in self.flags.iter() {
+                    flag.draw(ctx)?;
+                }
+            }
+            for map_crate in self.crates.iter() {
+                map_crate.draw(ctx)?;
+            }
+            if let Some(item_spawn) = &self.item_spawn {
+                item_spawn.draw(ctx)?;
+            }
+            for potion in self.loot.iter() {
+                potion.draw_ring(ctx, self.settings.palette())?;
+                potion.draw(&mut self.batches);
+            }
+            // Potions go down before the players walk over them
+            self.batches.draw(ctx)?;
+            for drop in self.equipment.iter() {
+                drop.draw(ctx)?;
+            }
+            for trap in self.traps.iter() {
+                trap.draw(ctx)?;
+            }
+            if !self.opponent.is_invisible() {
+                self.opponent.draw(ctx, alpha, plates, &mut self.batches)?;
+            }
+            for other in self.others.iter_mut().filter(|p| !p.is_invisible()) {
+                other.draw(ctx, alpha, plates, &mut self.batches)?;
+            }
+            GameState::draw_batched(ctx, &mut self.batches)?;
+            if !self.opponent.is_invisible() {
+                self.opponent.draw_over(ctx, alpha)?;
+            }
+            for other in self.others.iter_mut().filter(|p| !p.is_invisible()) {
+                other.draw_over(ctx, alpha)?;
+            }
+            if let Some(safe) = NetworkedGame::safe_area(self.sudden_death, &self.map) {
+                // Darken everything outside the closing safe area
+                let (map_w, map_h) = (self.map.pixel_width(), self.map.pixel_height());
+                let outside = [
+                    Rect::new(0.0, 0.0, map_w, safe.y),
+                    Rect::new(0.0, safe.y + safe.h, map_w, map_h - safe.y - safe.h),
+                    Rect::new(0.0, safe.y, safe.x, safe.h),
+                    Rect::new(safe.x + safe.w, safe.y, map_w - safe.x - safe.w, safe.h),
+                ];
+                for area in outside.iter().filter(|r| r.w > 0.0 && r.h > 0.0) {
+                    let storm = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), *area, [0.6, 0.0, 0.0, 0.35].into())?;
+                    graphics::draw(ctx, &storm, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                }
+                let edge = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(3.0), safe.into(), [1.0, 0.2, 0.1, 0.9].into())?;
+                graphics::draw(ctx, &edge, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+            self.player.draw(ctx, alpha, plates, &mut self.batches)?;
+            GameState::draw_batched(ctx, &mut self.batches)?;
+            self.player.draw_over(ctx, alpha)?;
+            particles::draw(ctx, &self.effects)?;
+            damage_numbers::draw(ctx, &self.effects, self.settings.palette())?;
+        }
+        self.camera.pop(ctx, screen)?;
+        self.restore_bodies(live_bodies);
+
+        // Screen space
+
+        if let Some(lobby) = &self.lobby {
+            self.hud.draw_lobby(ctx, lobby, self.player.number, &self.server_notice)?;
+        }
+
+        let caster = self.ready && self.settings.caster_hud;
+        if self.ready && self.settings.hud && !caster {
+            self.hud.draw(ctx, &self.player, self.settings.color_mode)?;
+        }
+        if self.ready {
+            // Counts on between snapshots, stopping at the end of the game
+            let clock = if self.gameover {
+                self.clock
+            } else {
+                self.clock + self.clock_synced.elapsed().as_secs_f32()
+            };
+            if caster {
+                let players = [&self.player, &self.opponent];
+                let scores = self.score_limit.map(|_| players.map(|p| self.scores.get(&p.score_key()).copied().unwrap_or(0.0)));
+                self.hud.draw_caster(ctx, players, scores, (self.time_limit - clock).max(0.0), self.sudden_death.is_some(), self.settings.palette())?;
+            } else {
+                self.hud.draw_clock(ctx, (self.time_limit - clock).max(0.0), self.sudden_death.is_some())?;
+            }
+            if let (Some(limit), false) = (self.score_limit, caster) {
+                self.hud.draw_scores(ctx, &self.scores, &self.player.score_key(), limit)?;
+            }
+        }
+
+        if self.replay.is_some() {
+            self.hud.draw_warning(ctx, "KILL CAM", self.settings.palette())?;
+        } else if self.player.afk && !self.gameover {
+            self.hud.draw_warning(ctx, "You are AFK - move or forfeit!", self.settings.palette())?;
+        } else if let Some(afk) = std::iter::once(&self.opponent).chain(self.others.iter()).find(|p| p.afk && !self.gameover) {
+            self.hud.draw_warning(ctx, &format!("{} is AFK", afk.name), self.settings.palette())?;
+        } else if self.sudden_death.is_some() && !self.gameover {
+            self.hud.draw_warning(ctx, "SUDDEN DEATH - next hit wins", self.settings.palette())?;
+        }
+
+        if self.ready {
+            if self.settings.low_hp_flash && self.player.hp > 0 && self.player.hp < LOW_HP_FLASH && !self.gameover {
+                self.hud.draw_low_hp_vignette(ctx)?;
+            }
+            self.hud.draw_feed(ctx, &self.feed)?;
+            if let Some((combo, shown)) = self.combo_popup.filter(|(_, shown)| shown.elapsed() < Duration::from_millis(COMBO_POPUP_MILLIS)) {
+                self.hud.draw_combo(ctx, combo, shown.elapsed(), self.settings.palette())?;
+            }
+        }
+        if !caster {
+            self.hud.draw_chat(ctx, &self.chat, self.chat_input.as_deref())?;
+        }
+        if self.touch_active() {
+            touch::draw(ctx, &self.touch)?;
+        }
+
+        if let (Some(heatmap), None, true) = (self.heatmap.as_mut(), &self.replay, self.gameover) {
+            heatmap.draw(ctx, &mut self.map)?;
+        }
+        if let (Some((announcement, shown)), None) = (&self.announcement, &self.replay) {
+            // The result stays up once the game is over
+            if self.gameover || shown.elapsed() < Duration::from_secs(ANNOUNCEMENT_SECONDS) {
+                self.hud.draw_announcement(ctx, announcement, self.settings.palette())?;
+            }
+        }
+
+        if let (Some(ping), true) = (self.ping, self.settings.show_ping) {
+            self.hud.draw_ping(ctx, ping)?;
+        }
+
+        if self.corrected.is_some_and(|corrected| corrected.elapsed() < Duration::from_millis(CORRECTION_ICON_MILLIS)) {
+            self.hud.draw_correction(ctx)?;
+        }
+
+        if self.perf.shown {
+            let counts = [
+                ("players", 2 + self.others.len()),
+                ("potions", self.loot.len()),
+                ("gear", self.equipment.len()),
+                ("traps", self.traps.len()),
+                ("crates", self.crates.len()),
+                ("particles", particles::count(&self.effects)),
+            ];
+            self.perf.draw(ctx, 6.0, GRID_CELL_SIZE + 4.0, ggez::timer::fps(ctx), &counts)?;
+        }
+
+        self.console.draw(ctx)?;
+
+        if let Some(screen) = &self.settings_screen {
+            self.hud.draw_settings(ctx, &self.settings, &self.controls, screen)?;
+        } else if let Some(menu) = &self.pause_menu {
+            self.hud.draw_pause_menu(ctx, menu)?;
+        }
+         
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let toast = match GameState::save_screenshot(ctx) {
+                Ok(path) => format!("Saved {}", path),
+                Err(e) => format!("Unable to save screenshot: {}", e),
+            };
+            println!("{}", toast);
+            self.toast = Some((toast, Instant::now()));
+        } else if let Some((toast, _)) = self.toast.as_ref().filter(|(_, shown)| shown.elapsed() < Duration::from_secs(TOAST_SECONDS)) {
+            self.hud.draw_toast(ctx, toast)?;
+        } else if let Some(view) = self.spectator {
+            let watching = match view {
+                SpectatorView::Free => "Free camera".to_string(),
+                SpectatorView::Follow(slot) => format!("Watching {}", self.watched_players().nth(slot).map_or("", |p| &p.name)),
+            };
+            self.hud.draw_toast(ctx, &format!("{} - 1-9 follow, move to pan, wheel to zoom", watching))?;
+        }
+
+        {
+            puffin::profile_scope!("present");
+            graphics::present(ctx)?;
+        }
+        // A frame per update and draw
+        self.profiler.new_frame();
+        ggez::timer::yield_now();
+        Ok(())
+    }
+
+    fn key_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        keycode: KeyCode,
+        _keymod: KeyMods,
+    ) {
+        if self.settings_screen.is_some() || self.pause_menu.is_some() || self.chat_input.is_some() || self.console.is_open() {
+            return;
+        }
+        if self.spectator.is_some() {
+            self.spectator_key(keycode, false);
+            return;
+        }
+        if let Some(action) = self.controls.action(keycode) {
+            self.release_action(action);
+        }
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+        if let Some(input) = &mut self.chat_input {
+            if !character.is_control() && input.chars().count() < CHAT_MAX_CHARS {
+                input.push(character);
+            }
+        }
+        self.console.type_char(character);
+    }
+
+    /// key_down_event gets fired when a key gets pressed.
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymod: KeyMods,
+        _repeat: bool,
+    ) {
+        if keycode == controls::FULLSCREEN_KEY {
+            self.settings.change(Setting::Fullscreen, true);
+            self.apply_setting(ctx, Setting::Fullscreen);
+            self.save_settings();
+            return;
+        }
+        if keycode == controls::SETTINGS_KEY {
+            self.toggle_settings();
+            return;
+        }
+        if keycode == controls::PERF_KEY {
+            self.perf.toggle();
+            return;
+        }
+        if keycode == controls::SCREENSHOT_KEY {
+            self.screenshot_requested = true;
+            return;
+        }
+        let shown = match keycode {
+            controls::NAME_PLATES_KEY => Some(Setting::NamePlates),
+            controls::NAME_BOX_KEY => Some(Setting::NameBox),
+            controls::HUD_KEY => Some(Setting::Hud),
+            controls::CASTER_HUD_KEY => Some(Setting::CasterHud),
+            _ => None,
+        };
+        if let Some(setting) = shown {
+            self.settings.change(setting, true);
+            self.save_settings();
+            self.toast = Some((format!("{} {}", setting.title(), self.settings.describe(setting)), Instant::now()));
+            return;
+        }
+        if self.settings_screen.is_some() {
+            self.settings_key(ctx, keycode);
+            return;
+        }
+        if self.pause_menu.is_some() {
+            self.pause_key(ctx, keycode);
+            return;
+        }
+        if self.chat_input.is_some() {
+            self.chat_key(keycode);
+            return;
+        }
+        if self.console.is_open() {
+            self.console_key(keycode);
+            return;
+        }
+        if keycode == controls::CONSOLE_KEY && self.console_allowed() && self.replay.is_none() {
+            self.release_keys();
+            self.console.input = Some(String::new());
+            return;
+        }
+        if keycode == controls::CHAT_KEY && self.replay.is_none() {
+            self.open_chat();
+            return;
+        }
+        if keycode == controls::QUIT_KEY {
+            self.open_pause_menu(PauseMenu::default());
+            return;
+        }
+        // Any key skips the kill cam
+        if self.replay.is_some() {
+            self.end_replay();
+            return;
+        }
+        if !self.started {
+            let choice = match keycode {
+                KeyCode::Key1 => Some(0),
+                KeyCode::Key2 => Some(1),
+                KeyCode::Key3 => Some(2),
+                _ => None,
+            };
+            match keycode {
+                KeyCode::C => self.change_skin(false),
+                KeyCode::V => self.change_skin(true),
+                _ => (),
+            }
+            if let (Some(choice), Some(lobby)) = (choice, &self.lobby) {
+                if let Some(map) = lobby.map_candidates.get(choice) {
+                    if !GameState::vote_map(self.server.clone(), self.player.id.clone(), self.game_id.clone(), map.clone()) {
+                        println!("Vote for {} was not accepted", map);
+                    }
+                }
+            }
+            return;
+        }
+        if self.spectator.is_some() {
+            self.spectator_key(keycode, true);
+            return;
+        }
+        if self.sandbox {
+            let command = match keycode {
+                controls::SPAWN_POTION_KEY => Some("potion"),
+                controls::SPAWN_CRATE_KEY => Some("crate"),
+                controls::GOD_MODE_KEY => Some("god"),
+                _ => None,
+            };
+            if let Some(command) = command {
+                self.sandbox_command(command);
+                return;
+            }
+        }
+        if let Some(action) = self.controls.action(keycode) {
+            self.press_action(action);
+        }
+    }
+
+    /// Pauses when we're tabbed away from, so nothing is left held down.
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        if !gained && self.pause_menu.is_none() && self.settings_screen.is_none() {
+            self.open_pause_menu(PauseMenu::default());
+        }
+    }
+
+    /// Closing the window asks first, and a second close quits anyway.
+    fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+        if self.pause_menu.as_ref().is_some_and(|menu| menu.confirming_quit) {
+            GameState::send_leave(self.server.clone(), self.player.id.clone(), self.game_id.clone());
+            return false
+        }
+        if self.settings_screen.is_some() {
+            self.toggle_settings();
+        }
+        self.open_pause_menu(PauseMenu::confirming_quit());
+        true
+    }
+
+    /// Zooms the spectator camera.
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+        if self.spectator.is_some() {
+            self.camera.zoom_by(y);
+        }
+    }
+
+    /// Keeps the whole screen in view whatever the window's size and shape.
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        let coordinates = camera::letterbox(SCREEN_SIZE.0, SCREEN_SIZE.1, width, height);
+        if let Err(e) = graphics::set_screen_coordinates(ctx, coordinates) {
+            println!("Unable to rescale to {}x{}: {}", width, height, e);
+        }
+    }
+
+    /// The left stick moves the player, slower the less it's pushed.
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, _id: GamepadId) {
+        if !self.started || self.settings_screen.is_some() || self.pause_menu.is_some() {
+            return;
+        }
+        match axis {
+            Axis::LeftStickX => self.stick.0 = value,
+            Axis::LeftStickY => self.stick.1 = value,
+            _ => return,
+        }
+        let (x, y) = self.stick;
+        self.steer(x, y);
+    }
+
+    /// Presses on the touch controls.
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button != MouseButton::Left || !self.touch_active() {
+            return;
+        }
+        let (x, y) = camera::window_to_screen(ctx, x, y);
+        match self.touch.press(x, y) {
+            Some(Touched::Stick(x, y)) => self.steer(x, y),
+            Some(Touched::Button(action)) => self.press_action(action),
+            None => (),
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if !self.touch_active() {
+            return;
+        }
+        let (x, y) = camera::window_to_screen(ctx, x, y);
+        if let Some((x, y)) = self.touch.drag(x, y) {
+            self.steer(x, y);
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        if button != MouseButton::Left {
+            return;
+        }
+        match self.touch.release() {
+            Some(Touched::Stick(x, y)) => self.steer(x, y),
+            Some(Touched::Button(action)) => self.release_action(action),
+            None => (),
+        }
+    }
+}
+
@@ -0,0 +1,124 @@
+//! Heatmap shown under the result once a match is over: where each player
+//! spent their time and where the deaths happened, from the server's match
+//! trace, drawn over a miniature of the arena.
+
+use ggez::{graphics, Context, GameResult};
+use glam::Vec2;
+
+use crate::map::Map;
+use crate::trace::Trace;
+use crate::SCREEN_SIZE;
+
+/// Room the miniature is fitted into, below the result.
+const AREA: graphics::Rect = graphics::Rect { x: 120.0, y: 200.0, w: 400.0, h: 200.0 };
+/// Arena pixels per heatmap cell, each way.
+const CELL_SIZE: f32 = 32.0;
+/// A color per player, in name order.
+const PLAYER_COLORS: [[f32; 3]; 5] = [
+    [1.0, 0.3, 0.2],
+    [0.2, 0.6, 1.0],
+    [0.3, 1.0, 0.3],
+    [1.0, 0.85, 0.2],
+    [0.8, 0.4, 1.0],
+];
+/// How far a death's cross reaches from its middle, on screen.
+const DEATH_MARK: f32 = 4.0;
+
+pub struct Heatmap {
+    trace: Trace,
+    /// Cells and death marks, in screen coordinates once the arena is known
+    mesh: Option<graphics::Mesh>,
+}
+
+impl Heatmap {
+
+    pub fn new(trace: Trace) -> Heatmap {
+        Heatmap { trace, mesh: None }
+    }
+
+    /// Scale and top left corner fitting the arena into `AREA`, centered.
+    fn placement(map: &Map) -> (f32, f32, f32) {
+        let scale = (AREA.w / map.pixel_width()).min(AREA.h / map.pixel_height());
+        let x = (SCREEN_SIZE.0 - map.pixel_width() * scale) / 2.0;
+        let y = AREA.y + (AREA.h - map.pixel_height() * scale) / 2.0;
+        (scale, x, y)
+    }
+
+    fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.trace.players.keys().collect();
+        names.sort();
+        names
+    }
+
+    /// `None` when nobody was traced, as in a game that never got going.
+    fn build(&self, ctx: &mut Context, map: &Map) -> GameResult<Option<graphics::Mesh>> {
+        if self.trace.players.is_empty() && self.trace.deaths.is_empty() {
+            return Ok(None)
+        }
+        let (scale, left, top) = Heatmap::placement(map);
+        let columns = (map.pixel_width() / CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = (map.pixel_height() / CELL_SIZE).ceil().max(1.0) as usize;
+        let grids: Vec<Vec<u32>> = self.names().iter().map(|name| {
+            let mut grid = vec![0; columns * rows];
+            for [_, x, y] in self.trace.players[*name].iter() {
+                let column = ((x / CELL_SIZE).max(0.0) as usize).min(columns - 1);
+                let row = ((y / CELL_SIZE).max(0.0) as usize).min(rows - 1);
+                grid[row * columns + column] += 1;
+            }
+            grid
+        }).collect();
+        let most = grids.iter().flatten().copied().max().unwrap_or(0).max(1) as f32;
+
+        let mut mesh = graphics::MeshBuilder::new();
+        for (index, grid) in grids.iter().enumerate() {
+            let [r, g, b] = PLAYER_COLORS[index % PLAYER_COLORS.len()];
+            for (cell, count) in grid.iter().enumerate().filter(|(_, count)| **count > 0) {
+                let rect = graphics::Rect::new(
+                    left + (cell % columns) as f32 * CELL_SIZE * scale,
+                    top + (cell / columns) as f32 * CELL_SIZE * scale,
+                    CELL_SIZE * scale,
+                    CELL_SIZE * scale,
+                );
+                // Anywhere they went at all shows, the most visited the strongest
+                let alpha = 0.15 + 0.6 * *count as f32 / most;
+                mesh.rectangle(graphics::DrawMode::fill(), rect, [r, g, b, alpha].into())?;
+            }
+        }
+        for [_, x, y] in self.trace.deaths.iter() {
+            let (x, y) = (left + x * scale, top + y * scale);
+            for (width, color) in [(4.0, [0.0, 0.0, 0.0, 1.0]), (2.0, [1.0, 1.0, 1.0, 1.0])].iter() {
+                mesh.line(&[[x - DEATH_MARK, y - DEATH_MARK], [x + DEATH_MARK, y + DEATH_MARK]], *width, (*color).into())?;
+                mesh.line(&[[x - DEATH_MARK, y + DEATH_MARK], [x + DEATH_MARK, y - DEATH_MARK]], *width, (*color).into())?;
+            }
+        }
+        mesh.build(ctx).map(Some)
+    }
+
+    /// Draws the arena scaled down with the heatmap over it, and who's who
+    /// underneath.
+    pub fn draw(&mut self, ctx: &mut Context, map: &mut Map) -> GameResult<()> {
+        if self.mesh.is_none() {
+            self.mesh = self.build(ctx, map)?;
+        }
+        let mesh = match &self.mesh {
+            Some(mesh) => mesh,
+            None => return Ok(()),
+        };
+        let (scale, left, top) = Heatmap::placement(map);
+        let frame = graphics::Rect::new(left - 2.0, top - 2.0, map.pixel_width() * scale + 4.0, map.pixel_height() * scale + 4.0);
+        let back = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), frame, [0.0, 0.0, 0.0, 0.8].into())?;
+        graphics::draw(ctx, &back, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        map.draw(ctx, graphics::DrawParam::new().dest(Vec2::new(left, top)).scale(Vec2::new(scale, scale)))?;
+        graphics::draw(ctx, mesh, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        let mut legend = graphics::Text::default();
+        for (index, name) in self.names().into_iter().enumerate() {
+            let [r, g, b] = PLAYER_COLORS[index % PLAYER_COLORS.len()];
+            legend.add(graphics::TextFragment::new(format!("{}  ", name)).color(graphics::Color::new(r, g, b, 1.0)));
+        }
+        legend.add(graphics::TextFragment::new(format!("X {} deaths", self.trace.deaths.len())));
+        let legend_x = (SCREEN_SIZE.0 - legend.width(ctx)) / 2.0;
+        graphics::draw(ctx, &legend, (ggez::mint::Point2 { x: legend_x, y: top + map.pixel_height() * scale + 6.0 },))
+    }
+}
@@ -0,0 +1,97 @@
+//! Kill cam: where everyone was over the last few snapshots is kept, and when
+//! a killing blow lands it's played back in slow motion before the game goes
+//! on to the respawn or the results.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::Position;
+
+/// How far back the replay goes, in seconds.
+const KEPT_SECONDS: f32 = 2.5;
+/// Replay speed, 0.5 being half speed.
+const SLOW_MOTION: f32 = 0.5;
+/// Seconds the last frame is held for before the replay ends.
+const HOLD_SECONDS: f32 = 0.75;
+
+/// Where each player was at one snapshot, by name.
+type Bodies = Vec<(String, Position)>;
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// Oldest first
+    snapshots: VecDeque<(Instant, Bodies)>,
+}
+
+impl History {
+
+    pub fn record(&mut self, bodies: Bodies) {
+        let now = Instant::now();
+        self.snapshots.push_back((now, bodies));
+        while self.snapshots.front().is_some_and(|(at, _)| now.duration_since(*at).as_secs_f32() > KEPT_SECONDS) {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// A replay of what's been kept, centered on `victim`.
+    pub fn replay(&self, victim: &str) -> Option<Replay> {
+        let (first, _) = self.snapshots.front()?;
+        let frames = self.snapshots.iter()
+            .map(|(at, bodies)| (at.duration_since(*first).as_secs_f32(), bodies.clone()))
+            .collect();
+        Some(Replay {
+            victim: victim.to_string(),
+            frames,
+            started: Instant::now(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Who the camera stays on
+    pub victim: String,
+    /// Seconds from the first snapshot, and where everyone was
+    frames: Vec<(f32, Bodies)>,
+    started: Instant,
+}
+
+impl Replay {
+
+    /// Seconds into the recording being shown.
+    fn time(&self) -> f32 {
+        self.started.elapsed().as_secs_f32() * SLOW_MOTION
+    }
+
+    pub fn finished(&self) -> bool {
+        let length = self.frames.last().map_or(0.0, |(at, _)| *at);
+        self.started.elapsed() >= Duration::from_secs_f32(length / SLOW_MOTION + HOLD_SECONDS)
+    }
+
+    /// Where `name` was at this point of the replay, between the snapshots
+    /// either side of it.
+    pub fn body(&self, name: &str) -> Option<Position> {
+        let time = self.time();
+        let find = |bodies: &Bodies| bodies.iter().find(|(n, _)| n == name).map(|(_, body)| *body);
+        let next = self.frames.iter().position(|(at, _)| *at >= time);
+        match next {
+            Some(0) => find(&self.frames[0].1),
+            Some(index) => {
+                let (from_at, from) = &self.frames[index - 1];
+                let (to_at, to) = &self.frames[index];
+                let (from, to) = match (find(from), find(to)) {
+                    (Some(from), Some(to)) => (from, to),
+                    (from, to) => return to.or(from),
+                };
+                let t = (time - from_at) / (to_at - from_at).max(f32::EPSILON);
+                Some(Position {
+                    x: from.x + (to.x - from.x) * t,
+                    y: from.y + (to.y - from.y) * t,
+                    w: to.w,
+                    h: to.h,
+                })
+            },
+            None => self.frames.last().and_then(|(_, bodies)| find(bodies)),
+        }
+    }
+}
@@ -0,0 +1,3792 @@
+// Author: @justmike2000
+// Repo: https://github.com/justmike2000/item_wars/
+
+#[cfg(feature = "client")]
+use ggez::{event, graphics, Context, GameResult};
+#[cfg(feature = "client")]
+use ggez::conf::FullscreenType;
+#[cfg(feature = "client")]
+use graphics::{GlBackendSpec, ImageGeneric, Rect};
+#[cfg(feature = "client")]
+use glam::*;
+
+use std::time::Duration;
+#[cfg(feature = "native")]
+use std::time::Instant;
+use std::env;
+use std::path;
+use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::io::{self};
+
+use serde::{Deserialize, Serialize};
+use clap::App;
+#[cfg(feature = "native")]
+use rand::Rng;
+#[cfg(feature = "server")]
+use rand::seq::SliceRandom;
+
+#[cfg(feature = "server")]
+mod admin;
+mod admission;
+#[cfg(feature = "client")]
+mod animation;
+mod assets;
+#[cfg(feature = "server")]
+mod bans;
+#[cfg(feature = "client")]
+mod atlas;
+#[cfg(feature = "server")]
+mod bot;
+#[cfg(feature = "client")]
+mod cache;
+#[cfg(feature = "client")]
+mod camera;
+mod clock;
+pub mod config;
+#[cfg(feature = "client")]
+mod console;
+#[cfg(feature = "native")]
+mod content;
+#[cfg(feature = "client")]
+mod controls;
+mod ctf;
+#[cfg(feature = "client")]
+mod damage_numbers;
+#[cfg(feature = "client")]
+mod desync;
+#[cfg(feature = "client")]
+mod ecs;
+mod equipment;
+#[cfg(feature = "client")]
+mod events;
+#[cfg(feature = "server")]
+mod game_server;
+#[cfg(feature = "client")]
+mod game_state;
+#[cfg(feature = "client")]
+mod heatmap;
+#[cfg(feature = "client")]
+mod killcam;
+#[cfg(feature = "server")]
+mod logging;
+pub mod map;
+mod migration;
+#[cfg(feature = "client")]
+mod menu;
+mod mode;
+#[cfg(feature = "native")]
+mod names;
+pub mod net;
+mod notice;
+#[cfg(feature = "server")]
+mod observer;
+mod pack;
+#[cfg(feature = "client")]
+mod palette;
+#[cfg(feature = "client")]
+mod particles;
+#[cfg(feature = "client")]
+mod perf;
+pub mod physics;
+#[cfg(feature = "native")]
+mod profiling;
+#[cfg(feature = "client")]
+mod reckoning;
+mod rules;
+pub mod rollback;
+mod scripting;
+mod servers;
+#[cfg(feature = "server")]
+mod session;
+#[cfg(feature = "client")]
+mod settings;
+#[cfg(feature = "server")]
+mod shutdown;
+mod sim;
+mod skin;
+#[cfg(feature = "client")]
+mod sounds;
+mod status;
+#[cfg(feature = "client")]
+mod touch;
+#[cfg(feature = "native")]
+mod trace;
+pub mod transfer;
+#[cfg(feature = "server")]
+mod websocket;
+
+#[cfg(feature = "client")]
+use animation::Animator;
+#[cfg(feature = "client")]
+use assets::Assets;
+#[cfg(feature = "client")]
+use atlas::SpriteBatches;
+#[cfg(feature = "client")]
+use cache::Cached;
+#[cfg(feature = "server")]
+use content::ContentHashes;
+#[cfg(feature = "client")]
+use controls::{Action, InputMapper, KeyBindings};
+use ctf::Flag;
+use equipment::{EquipmentDrop, EquipmentKind};
+#[cfg(feature = "server")]
+use equipment::{ALL_EQUIPMENT, EQUIPMENT_HEIGHT, EQUIPMENT_WIDTH};
+#[cfg(feature = "server")]
+use game_server::GameServer;
+#[cfg(feature = "client")]
+use game_state::GameState;
+#[cfg(feature = "native")]
+use map::Map;
+#[cfg(feature = "client")]
+use menu::PauseMenu;
+#[cfg(feature = "client")]
+use physics::SpatialHash;
+#[cfg(feature = "native")]
+use profiling::Profiler;
+#[cfg(feature = "client")]
+use palette::Palette;
+use mode::GameMode;
+use rules::MatchRules;
+use servers::GameFilter;
+#[cfg(feature = "client")]
+use settings::{Settings, SettingsPage, SettingsScreen};
+use skin::Skin;
+use status::StatusEffect;
+#[cfg(feature = "native")]
+use status::StatusKind;
+
+// The first thing we want to do is set up some constants that will help us out later.
+
+// Everything is laid out for this size and scaled to fit the window
+#[cfg(feature = "client")]
+const SCREEN_SIZE: (f32, f32) = (640.0, 480.0);
+#[cfg(feature = "client")]
+const GRID_CELL_SIZE: f32 = 32.0;
+
+pub const PLAYER_MAX_HP: i64 = 100;
+const PLAYER_MAX_MP: i64 = 30;
+const PLAYER_MAX_STR: i64 = 10;
+// How far up a jump has to be, as a fraction of its peak, to clear swings and traps
+#[cfg(feature = "server")]
+const JUMP_DODGE_HEIGHT: f32 = 0.5;
+const PLAYER_CELL_HEIGHT: f32 = 44.0;
+const PLAYER_CELL_WIDTH: f32 = 34.0;
+// Stick pushes smaller than this are ignored
+#[cfg(feature = "client")]
+const STICK_DEAD_ZONE: f32 = 0.2;
+
+#[cfg(feature = "server")]
+const POTION_WIDTH: f32 = 42.0;
+#[cfg(feature = "server")]
+const POTION_HEIGHT: f32 = 42.0;
+#[cfg(feature = "server")]
+const POTION_HEALTH_RESTORE: i64 = 25;
+#[cfg(feature = "server")]
+const POTION_MANA_RESTORE: i64 = 10;
+#[cfg(feature = "server")]
+const POTION_SPEED_BOOST: f32 = 0.5;
+#[cfg(feature = "server")]
+const POTION_STRENGTH_BOOST: f32 = 5.0;
+#[cfg(feature = "server")]
+const POTION_BUFF_SECONDS: f32 = 8.0;
+#[cfg(feature = "server")]
+const POTION_INVISIBILITY_SECONDS: f32 = 5.0;
+
+// Server spawned items come and go to keep the arena moving
+const ITEM_LIFETIME_SECONDS: f32 = 30.0;
+const ITEM_SPAWN_INTERVAL_SECONDS: f32 = 10.0;
+#[cfg(feature = "native")]
+const ITEM_SPAWN_WARNING_SECONDS: f32 = 2.0;
+#[cfg(feature = "client")]
+const ITEM_BLINK_SECONDS: f32 = 5.0;
+#[cfg(feature = "server")]
+const MAX_ITEMS: usize = 4;
+// Odds of gear instead of a potion
+#[cfg(feature = "server")]
+const CRATE_EQUIPMENT_CHANCE: f64 = 0.25;
+#[cfg(feature = "server")]
+const SPAWN_EQUIPMENT_CHANCE: f64 = 0.05;
+
+#[cfg(feature = "server")]
+const TRAP_WIDTH: f32 = 28.0;
+#[cfg(feature = "server")]
+const TRAP_HEIGHT: f32 = 28.0;
+#[cfg(feature = "server")]
+const TRAP_DAMAGE: i64 = 10;
+#[cfg(feature = "server")]
+const TRAP_POISON: f32 = 3.0;
+#[cfg(feature = "server")]
+const TRAP_POISON_SECONDS: f32 = 4.0;
+
+#[cfg(feature = "server")]
+const CRATE_WIDTH: f32 = 32.0;
+#[cfg(feature = "server")]
+const CRATE_HEIGHT: f32 = 32.0;
+#[cfg(feature = "native")]
+const CRATE_MAX_HP: i64 = 20;
+#[cfg(feature = "server")]
+const CRATES_GENERATED: usize = 4;
+
+const DASH_SPEED: f32 = 14.0;
+const DASH_UPDATES: u32 = 8;
+const DASH_COOLDOWN_MILLIS: u64 = 1000;
+const DASH_MP_COST: i64 = 3;
+#[cfg(feature = "client")]
+const DASH_AFTERIMAGES: usize = 5;
+
+const PLAYER_MAX_STAMINA: f32 = 100.0;
+const SPRINT_SPEED_MULTIPLIER: f32 = 1.5;
+const SPRINT_STAMINA_PER_SECOND: f32 = 40.0;
+const JUMP_STAMINA_COST: f32 = 20.0;
+const STAMINA_REGEN_PER_SECOND: f32 = 25.0;
+// Stamina needed back after running out before sprinting or jumping again
+const STAMINA_WINDED_RECOVERY: f32 = 30.0;
+// Out of combat regeneration, which waits this long after the last hit taken
+#[cfg(feature = "server")]
+const HP_REGEN_PER_SECOND: f32 = 1.0;
+#[cfg(feature = "server")]
+const MP_REGEN_PER_SECOND: f32 = 0.5;
+#[cfg(feature = "server")]
+const REGEN_DELAY_SECONDS: f32 = 5.0;
+
+#[cfg(feature = "client")]
+const HIT_FLASH_MILLIS: u64 = 120;
+#[cfg(feature = "client")]
+const CRIT_FLASH_MILLIS: u64 = 250;
+#[cfg(feature = "client")]
+const DEATH_FADE_MILLIS: u64 = 900;
+#[cfg(feature = "client")]
+const RESPAWN_MATERIALIZE_MILLIS: u64 = 600;
+
+// How far the server can put a rollback opponent from where we simulated
+// them before we take its word for it
+#[cfg(feature = "client")]
+const ROLLBACK_RESYNC_PIXELS: f32 = 96.0;
+// How long the warning icon stays up after a rollback desync was corrected
+#[cfg(feature = "client")]
+const CORRECTION_ICON_MILLIS: u64 = 3000;
+
+// How long a remote player keeps walking after last being seen to move, so
+// the cycle doesn't stutter when a snapshot comes without any movement in it
+#[cfg(feature = "client")]
+const REMOTE_WALK_MILLIS: u64 = 200;
+
+// Fraction of a full bar the damage taken off HP and MP bars drains by each second
+#[cfg(feature = "client")]
+const BAR_DRAIN_PER_SECOND: f32 = 0.5;
+
+const ATTACK_REACH: f32 = 24.0;
+const ATTACK_COOLDOWN_MILLIS: u64 = 400;
+#[cfg(feature = "client")]
+const ATTACK_SWING_MILLIS: u64 = 150;
+#[cfg(feature = "server")]
+const DAMAGE_PER_STRENGTH: f32 = 1.0;
+// Defense that halves damage taken
+#[cfg(feature = "server")]
+const DEFENSE_SCALE: f32 = 10.0;
+#[cfg(feature = "server")]
+const CRIT_CHANCE: f64 = 0.1;
+#[cfg(feature = "server")]
+const CRIT_MULTIPLIER: f32 = 1.75;
+// Hits landed within this long of each other chain into a combo
+#[cfg(feature = "server")]
+const COMBO_WINDOW_MILLIS: u64 = 1200;
+// Extra damage for each hit already in the combo, up to the cap
+#[cfg(feature = "server")]
+const COMBO_DAMAGE_BONUS: f32 = 0.1;
+#[cfg(feature = "server")]
+const COMBO_MAX_BONUS_HITS: u32 = 5;
+
+#[cfg(feature = "server")]
+const BLOCK_DAMAGE_FACTOR: f32 = 0.3;
+#[cfg(feature = "server")]
+const BLOCK_MP_PER_SECOND: f32 = 4.0;
+#[cfg(feature = "server")]
+const PARRY_WINDOW_MILLIS: u64 = 150;
+#[cfg(feature = "server")]
+const STAGGER_MILLIS: u64 = 800;
+// Movement freezes this long when our swing lands
+#[cfg(feature = "client")]
+const HIT_STOP_MILLIS: u64 = 70;
+// The screen edges pulse red under this much HP
+#[cfg(feature = "client")]
+const LOW_HP_FLASH: i64 = 25;
+
+// Experience within a match, levels start back at 1 every game
+#[cfg(feature = "server")]
+const XP_PER_KILL: u32 = 50;
+#[cfg(feature = "server")]
+const XP_PER_PICKUP: u32 = 10;
+#[cfg(feature = "native")]
+const XP_PER_LEVEL: u32 = 40;
+#[cfg(feature = "server")]
+const LEVEL_UP_STR: i64 = 1;
+#[cfg(feature = "server")]
+const LEVEL_UP_HP: i64 = 10;
+#[cfg(feature = "server")]
+const LEVEL_UP_MP: i64 = 5;
+#[cfg(feature = "client")]
+const LEVEL_UP_BURST_MILLIS: u64 = 800;
+
+const MATCH_SECONDS: f32 = 180.0;
+const AFK_TIMEOUT_SECONDS: f32 = 30.0;
+// Seconds an AFK player has to come back before forfeiting
+#[cfg(feature = "server")]
+const AFK_FORFEIT_SECONDS: f32 = 15.0;
+// Once time runs out with nobody ahead the arena closes in and the next hit wins
+#[cfg(feature = "native")]
+const SUDDEN_DEATH_SHRINK_PER_SECOND: f32 = 16.0;
+#[cfg(feature = "native")]
+const SUDDEN_DEATH_MIN_SIZE: f32 = 128.0;
+#[cfg(feature = "server")]
+const SUDDEN_DEATH_DAMAGE_PER_SECOND: f32 = 10.0;
+
+#[cfg(feature = "server")]
+const SPIKES_SLOW: f32 = 0.4;
+#[cfg(feature = "server")]
+const SPIKES_SLOW_SECONDS: f32 = 1.0;
+
+// Steps caught up on per frame at most, so a long stall doesn't snowball
+#[cfg(feature = "client")]
+const MAX_SIMULATION_STEPS: u32 = 5;
+#[cfg(feature = "client")]
+const NET_MILLIS_PER_UPDATE: u64 = 1; // 20 ticks
+// updates of leeway given to late or bunched up position packets
+#[cfg(feature = "server")]
+const MOVE_VALIDATION_SLACK_TICKS: f32 = 2.0;
+// Stamina a jump can be short of, for the server's regeneration lagging the client's
+#[cfg(feature = "server")]
+const JUMP_STAMINA_SLACK: f32 = 2.0;
+
+// checks
+#[cfg(feature = "client")]
+const NET_GAME_START_CHECK_MILLIS: u64 = 500;
+#[cfg(feature = "client")]
+const NET_GAME_READY_CHECK: u64 = 100;
+// How long a shutting down server keeps answering, so every client polls
+// at least once more and finds out
+#[cfg(feature = "server")]
+const SHUTDOWN_GRACE_MILLIS: u64 = 2000;
+// Bumped whenever requests or their answers change in a way older clients
+// or servers can't follow, shown in `serverinfo`
+#[cfg(feature = "server")]
+const PROTOCOL_VERSION: u32 = 1;
+
+#[cfg(feature = "server")]
+const MAP_VOTE_CANDIDATES: usize = 3;
+#[cfg(feature = "server")]
+const MAP_VOTE_SECONDS: u64 = 20;
+#[cfg(feature = "client")]
+const ANNOUNCEMENT_SECONDS: u64 = 3;
+#[cfg(feature = "client")]
+const TOAST_SECONDS: u64 = 2;
+// Rows the settings screen fits before it scrolls
+#[cfg(feature = "client")]
+const SETTINGS_VISIBLE_ROWS: usize = 11;
+// The caster HUD's band along the top and each player's bars in it
+#[cfg(feature = "client")]
+const CASTER_BAND_HEIGHT: f32 = 44.0;
+#[cfg(feature = "client")]
+const CASTER_BAR_WIDTH: f32 = 200.0;
+#[cfg(feature = "client")]
+const COMBO_POPUP_MILLIS: u64 = 1200;
+// World pixels a second the spectator camera pans
+#[cfg(feature = "client")]
+const SPECTATOR_PAN_SPEED: f32 = 300.0;
+// Name the map's tileset goes by in the assets, for hot reloading
+#[cfg(feature = "client")]
+const TILES_ASSET: &str = "tiles";
+// Longest a WebSocket request waits for the server to look at it
+#[cfg(feature = "server")]
+const WEBSOCKET_POLL_MILLIS: u64 = 2;
+
+// Server events kept in each snapshot, so a dropped packet doesn't lose any
+#[cfg(feature = "server")]
+const EVENTS_KEPT: usize = 10;
+#[cfg(feature = "client")]
+const FEED_LINES: usize = 5;
+#[cfg(feature = "client")]
+const FEED_SECONDS: u64 = 6;
+// Chat messages each game keeps, what late joiners see when they come in
+#[cfg(feature = "server")]
+const CHAT_KEPT: usize = 20;
+#[cfg(feature = "native")]
+const CHAT_MAX_CHARS: usize = 100;
+#[cfg(feature = "client")]
+const CHAT_LINES: usize = 6;
+#[cfg(feature = "client")]
+const CHAT_SECONDS: u64 = 10;
+
+
+#[derive(PartialOrd, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Position {
+
+    /// Whether the point `x`, `y` is inside, edges included.
+    #[cfg(feature = "server")]
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.w && y >= self.y && y <= self.y + self.h
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<Position> for Rect {
+    fn from(pos: Position) -> Self {
+        Rect { x: pos.x, y: pos.y, w: pos.w, h: pos.h }
+    }
+}
+
+impl PartialEq for Position {
+    fn eq(&self, other: &Self) -> bool {
+        self.x <= other.x + other.w && self.x + self.w >= other.x
+            && self.y <= other.y + other.h && self.y + self.h >= other.y
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Direction {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+impl Direction {
+    /// The directions held down on the keyboard.
+    #[cfg(feature = "client")]
+    fn held(input: &InputMapper) -> Direction {
+        Direction {
+            up: input.held(Action::Up),
+            down: input.held(Action::Down),
+            left: input.held(Action::Left),
+            right: input.held(Action::Right),
+        }
+    }
+
+    fn is_diagonal(&self) -> bool {
+        (self.up || self.down) && (self.left || self.right)
+    }
+}
+
+impl From<Direction> for f32 {
+    fn from(dir: Direction) -> f32 {
+        if dir.up {
+            0.0
+        } else if dir.down {
+            1.0
+        } else if dir.left {
+            2.0
+        } else {
+            3.0
+        }
+    }
+}
+
+impl From<f32> for Direction {
+    fn from(item: f32) -> Self {
+        let error_margin = 1.0;
+        if item == 0.0 {
+            Direction { up: true, down: false, left: false, right: false }
+        } else if (item - 1.0).abs() < error_margin {
+            Direction { up: false, down: true, left: false, right: false }
+        } else if (item - 2.0).abs() < error_margin {
+            Direction { up: false, down: false, left: true, right: false }
+        } else {
+            Direction { up: false, down: false, left: false, right: true }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+enum PotionType {
+    Health,
+    Mana,
+    Speed,
+    Strength,
+    Invisibility,
+    /// Picked up to be placed later
+    Trap,
+}
+
+/// How often each type turns up relative to the others.
+#[cfg(feature = "server")]
+const POTION_SPAWN_WEIGHTS: [(PotionType, u32); 6] = [
+    (PotionType::Health, 30),
+    (PotionType::Mana, 25),
+    (PotionType::Speed, 15),
+    (PotionType::Strength, 15),
+    (PotionType::Trap, 10),
+    (PotionType::Invisibility, 5),
+];
+
+impl PotionType {
+    #[cfg(feature = "server")]
+    fn name(&self) -> &'static str {
+        match self {
+            PotionType::Health => "Health",
+            PotionType::Mana => "Mana",
+            PotionType::Speed => "Speed",
+            PotionType::Strength => "Strength",
+            PotionType::Invisibility => "Invisibility",
+            PotionType::Trap => "Trap",
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn random(rng: &mut impl Rng) -> PotionType {
+        let total: u32 = POTION_SPAWN_WEIGHTS.iter().map(|(_, weight)| weight).sum();
+        let mut roll = rng.gen_range(0, total);
+        for (potion_type, weight) in POTION_SPAWN_WEIGHTS.iter() {
+            if roll < *weight {
+                return potion_type.clone()
+            }
+            roll -= weight;
+        }
+        PotionType::Health
+    }
+
+    /// Top left of the sprite in potion.png, a 3x3 sheet of red, blue, and
+    /// green potions that are full, tipped over, and nearly empty.
+    #[cfg(feature = "client")]
+    fn frame(&self) -> (f32, f32) {
+        match self {
+            PotionType::Health => (0.0, 0.0),
+            PotionType::Mana => (0.0, 0.33),
+            PotionType::Speed => (0.0, 0.66),
+            PotionType::Strength => (0.33, 0.0),
+            PotionType::Invisibility => (0.66, 0.33),
+            PotionType::Trap => (0.33, 0.66),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Potion {
+    pos: Position,
+    potion_type: PotionType,
+    /// Seconds since it appeared
+    #[serde(default)]
+    age: f32,
+    /// Seconds it lies around before disappearing, forever when `None`
+    #[serde(default)]
+    lifetime: Option<f32>,
+    #[cfg(feature = "client")]
+    #[serde(skip_serializing, skip_deserializing)]
+    texture: Option<ImageGeneric<GlBackendSpec>>,
+}
+
+impl Potion {
+
+    /// Dropped by the server, which has no textures.
+    #[cfg(feature = "server")]
+    fn dropped(pos: Position, potion_type: PotionType) -> Self {
+        Potion {
+            pos,
+            potion_type,
+            age: 0.0,
+            lifetime: None,
+            #[cfg(feature = "client")]
+            texture: None,
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn expired(&self) -> bool {
+        self.lifetime.is_some_and(|lifetime| self.age >= lifetime)
+    }
+
+    /// Blinks for the last few seconds before despawning.
+    #[cfg(feature = "client")]
+    fn hidden_by_blink(&self) -> bool {
+        match self.lifetime {
+            Some(lifetime) if lifetime - self.age < ITEM_BLINK_SECONDS => {
+                let millis = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |t| t.as_millis());
+                millis % 400 < 200
+            },
+            _ => false,
+        }
+    }
+
+    /// Ring in the palette's color for the kind of potion, for palettes that
+    /// don't trust the sprite's color to tell them apart.
+    #[cfg(feature = "client")]
+    fn draw_ring(&self, ctx: &mut Context, palette: &Palette) -> GameResult<()> {
+        let rings = match palette.potion_rings {
+            Some(rings) if !self.hidden_by_blink() => rings,
+            _ => return Ok(()),
+        };
+        let color = match self.potion_type {
+            PotionType::Health => rings[0],
+            PotionType::Mana => rings[1],
+            PotionType::Speed => rings[2],
+            _ => return Ok(()),
+        };
+        let ring = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            ggez::mint::Point2 { x: self.pos.x + self.pos.w / 2.0, y: self.pos.y + self.pos.h / 2.0 },
+            self.pos.w / 2.0 + 2.0,
+            0.5,
+            color.into(),
+        )?;
+        graphics::draw(ctx, &ring, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))
+    }
+
+    /// Queues the potion on `batches`, with the other potions.
+    #[cfg(feature = "client")]
+    fn draw(&self, batches: &mut SpriteBatches) {
+        if self.hidden_by_blink() {
+            return
+        }
+
+        //let black_rectangle = graphics::Mesh::new_rectangle(
+        //    ctx,
+        //    graphics::DrawMode::fill(),
+        //    Rect::new(self.pos.x, self.pos.y, self.pos.w, self.pos.h),
+        //    [0.0, 0.0, 0.0, 1.0].into(),
+        //)?;
+        //graphics::draw(ctx, &black_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        //let color = [0.0, 0.0, 1.0, 1.0].into();
+        //let rectangle =
+        //    graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), self.pos.into(), color)?;
+        //graphics::draw(ctx, &rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))
+        let (frame_x, frame_y) = self.potion_type.frame();
+        let param = graphics::DrawParam::new()
+        .src(graphics::Rect {x: frame_x, y: frame_y, w: 0.33, h: 0.33})
+        .dest(Vec2::new(self.pos.x, self.pos.y))
+        //.offset(Vec2::new(0.15, 0.0))
+        .scale(Vec2::new(0.25, 0.25));
+        //.rotation((time % cycle) as f32 / cycle as f32 * 6.28)
+        //.offset(Vec2::new(150.0, 150.0));
+        if let Some(texture) = &self.texture {
+            batches.add("potion", texture, param);
+        }
+    }
+}
+
+/// An item about to appear, announced with a shimmer so players can race for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ItemSpawn {
+    pos: Position,
+    /// Seconds until the item appears
+    remaining: f32,
+}
+
+impl ItemSpawn {
+
+    #[cfg(feature = "client")]
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let progress = 1.0 - (self.remaining / ITEM_SPAWN_WARNING_SECONDS).clamp(0.0, 1.0);
+        let shimmer = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            ggez::mint::Point2 { x: self.pos.x + self.pos.w / 2.0, y: self.pos.y + self.pos.h / 2.0 },
+            4.0 + progress * self.pos.w / 2.0,
+            0.5,
+            graphics::Color::new(1.0, 1.0, 0.7, 0.2 + 0.4 * progress),
+        )?;
+        graphics::draw(ctx, &shimmer, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        Ok(())
+    }
+}
+
+/// A placed trap, only visible to the player who set it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Trap {
+    pos: Position,
+    /// Number of the player who set it
+    owner: u32,
+}
+
+impl Trap {
+
+    #[cfg(feature = "server")]
+    fn new(owner: &PlayerState) -> Self {
+        Trap {
+            pos: Position {
+                x: owner.body.x + (owner.body.w - TRAP_WIDTH) / 2.0,
+                y: owner.body.y + owner.body.h - TRAP_HEIGHT,
+                w: TRAP_WIDTH,
+                h: TRAP_HEIGHT,
+            },
+            owner: owner.number,
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let center = ggez::mint::Point2 { x: self.pos.x + self.pos.w / 2.0, y: self.pos.y + self.pos.h / 2.0 };
+        let trap_circle = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(3.0),
+            center,
+            self.pos.w / 2.0,
+            0.5,
+            graphics::Color::new(0.3, 0.3, 0.3, 0.8),
+        )?;
+        graphics::draw(ctx, &trap_circle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let trap_center = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            center,
+            4.0,
+            0.5,
+            graphics::Color::new(0.3, 0.8, 0.1, 0.8),
+        )?;
+        graphics::draw(ctx, &trap_center, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        Ok(())
+    }
+}
+
+/// Something that appears on the ground for the players to pick up.
+#[cfg(feature = "server")]
+enum Loot {
+    Potion(Potion),
+    Equipment(EquipmentDrop),
+}
+
+#[cfg(feature = "server")]
+impl Loot {
+
+    /// Rolls a random potion, or a piece of gear with `equipment_chance`,
+    /// centered on `area`.
+    fn random(area: &Position, equipment_chance: f64, rng: &mut impl Rng) -> Loot {
+        if rng.gen_bool(equipment_chance) {
+            let kind = *ALL_EQUIPMENT.choose(rng).unwrap();
+            Loot::Equipment(EquipmentDrop::new(
+                area.x + (area.w - EQUIPMENT_WIDTH) / 2.0,
+                area.y + (area.h - EQUIPMENT_HEIGHT) / 2.0,
+                kind,
+            ))
+        } else {
+            let pos = Position {
+                x: area.x + (area.w - POTION_WIDTH) / 2.0,
+                y: area.y + (area.h - POTION_HEIGHT) / 2.0,
+                w: POTION_WIDTH,
+                h: POTION_HEIGHT,
+            };
+            Loot::Potion(Potion::dropped(pos, PotionType::random(rng)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Crate {
+    pos: Position,
+    hp: i64,
+    broken: bool,
+}
+
+impl Crate {
+
+    #[cfg(feature = "server")]
+    fn new(pos: Position) -> Self {
+        Crate {
+            pos: Position { x: pos.x, y: pos.y, w: CRATE_WIDTH, h: CRATE_HEIGHT },
+            hp: CRATE_MAX_HP,
+            broken: false,
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.broken {
+            // Leave some splinters behind
+            for (x, y) in [(4.0, 20.0), (14.0, 24.0), (22.0, 18.0)].iter() {
+                let splinter = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(self.pos.x + x, self.pos.y + y, 8.0, 4.0),
+                    [0.45, 0.3, 0.15, 0.8].into(),
+                )?;
+                graphics::draw(ctx, &splinter, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+            return Ok(())
+        }
+        let crate_rectangle = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            self.pos.into(),
+            [0.6, 0.4, 0.2, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &crate_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let crate_outline = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            self.pos.into(),
+            [0.3, 0.2, 0.1, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &crate_outline, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        if self.hp < CRATE_MAX_HP {
+            let crack = graphics::Mesh::new_line(
+                ctx,
+                &[
+                    ggez::mint::Point2 { x: self.pos.x + 6.0, y: self.pos.y + 4.0 },
+                    ggez::mint::Point2 { x: self.pos.x + 16.0, y: self.pos.y + 16.0 },
+                    ggez::mint::Point2 { x: self.pos.x + 12.0, y: self.pos.y + 28.0 },
+                ],
+                2.0,
+                [0.2, 0.1, 0.05, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &crack, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerState {
+    /// First we have the body of the player, which is a single `Segment`.
+    body: Position,
+    is_hit: bool,
+    /// Then we have the current direction the player is moving. This is
+    /// the direction it will move when `update` is called on it.
+    dir: Direction,
+    last_dir: Direction,
+    ate: Option<Potion>,
+    /// Store the direction that will be used in the `update` after the next `update`
+    /// This is needed so a user can press two directions (eg. left then up)
+    /// before one `update` has happened. It sort of queues up key press input
+    name: String,
+    /// What the server knows the player by in their messages, the name only
+    /// being for show. Kept from everyone else, so nobody can act for them.
+    #[serde(skip_serializing, default)]
+    #[cfg(feature = "native")]
+    id: String,
+    /// What everyone else knows the player by, counting up from 1 as they
+    /// join. Unlike `id` it's no secret, so scores, map votes, rollback
+    /// inputs, traps and flags are all kept under it.
+    #[serde(default)]
+    #[cfg(feature = "native")]
+    number: u32,
+    hp: i64,
+    mp: i64,
+    str: i64,
+    /// Worn down by sprinting and jumping, kept by the client and replicated
+    /// for the opponent's bar
+    #[serde(default)]
+    stamina: f32,
+    #[serde(skip_serializing, skip_deserializing)]
+    sprint_held: bool,
+    /// Ran out of stamina, no sprinting or jumping until some of it is back
+    #[serde(skip_serializing, skip_deserializing)]
+    winded: bool,
+    current_accel: f32,
+    /// How far the stick is pushed, 1.0 on a keyboard
+    #[serde(skip_serializing, skip_deserializing)]
+    throttle: f32,
+    jumping: bool,
+    jump_offset: f32,
+    ready: bool,
+    /// Going up while positive, per update
+    #[serde(skip_serializing, skip_deserializing)]
+    jump_velocity: f32,
+    /// Already jumped again in the air this jump
+    #[serde(skip_serializing, skip_deserializing)]
+    air_jumped: bool,
+    /// When the server last let this player leave the ground
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    jump_started: Option<std::time::Instant>,
+    /// Time since a jump was pressed in the air with nothing left to jump
+    /// with, counted in simulated time so rollback replays it the same
+    #[serde(skip_serializing, skip_deserializing)]
+    jump_buffered: Option<Duration>,
+    /// Standing in lava or on spikes, set by the server
+    burning: bool,
+    /// Hazard damage not yet taken off `hp`, carried between server ticks
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    hazard_damage: f32,
+    /// HP and MP regenerated but not yet added on, carried between server ticks
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    regen: (f32, f32),
+    /// HP as of the last regen tick, to notice hits from anything
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    regen_hp: i64,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    last_damaged: Option<std::time::Instant>,
+    /// Bumped by the server every time it refuses a position, so the client
+    /// knows to snap back to the server's copy
+    corrections: u32,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    last_move: Option<std::time::Instant>,
+    /// Mid dash, replicated so the opponent's afterimages show up
+    dashing: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    dash_updates: u32,
+    /// Time since the last dash, in simulated time like `jump_buffered`
+    #[serde(skip_serializing, skip_deserializing)]
+    since_dash: Option<Duration>,
+    /// Holding up the guard, taking reduced damage
+    blocking: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    block_started: Option<std::time::Instant>,
+    /// MP drained by blocking not yet taken off `mp`
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    block_drain: f32,
+    /// Got parried and can neither move nor attack for a moment
+    staggered: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    stagger_until: Option<std::time::Instant>,
+    statuses: Vec<StatusEffect>,
+    /// Traps picked up and not placed yet
+    traps: u32,
+    /// Worn gear, at most one per slot
+    equipment: Vec<EquipmentKind>,
+    /// No input for a while, forfeits unless back soon
+    #[serde(default)]
+    afk: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    last_input: Option<std::time::Instant>,
+    /// Played by the server, see `bot`
+    #[serde(default)]
+    bot: bool,
+    /// Index into `ctf::TEAM_NAMES` in team modes
+    #[serde(default)]
+    team: Option<usize>,
+    /// Where the player joined from, as the server saw it. Kept from
+    /// everyone else but for the one who'd take the game over, see
+    /// `NetworkedGame::successor`
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    address: Option<String>,
+    /// What a server the game moves to checks the ID a player rejoins with
+    /// against, see `migration::rejoin_check`
+    #[serde(default)]
+    rejoin_check: String,
+    /// Team of the enemy flag being carried
+    #[serde(default)]
+    carrying_flag: Option<usize>,
+    level: u32,
+    /// Experience towards the next level
+    xp: u32,
+    #[serde(default)]
+    skin: Skin,
+    /// Bumped by the server for every swing, so the opponent can see it
+    attack_count: u32,
+    #[serde(skip_serializing, skip_deserializing)]
+    last_attack: Option<std::time::Instant>,
+    /// Hits landed in a row, each within `COMBO_WINDOW_MILLIS` of the last
+    #[serde(default)]
+    combo: u32,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    combo_at: Option<std::time::Instant>,
+    /// Bumped by the server every time the player takes a hit, for the flash
+    #[serde(default)]
+    hits_taken: u32,
+    /// Bumped along with `hits_taken` when the hit was a critical
+    #[serde(default)]
+    crits_taken: u32,
+    /// Bumped by the server on every respawn
+    #[serde(default)]
+    respawns: u32,
+    /// Sent along by the client, so everyone sees the same step of the walk
+    animation_frame: f32,
+    /// What the client said it plays with when it got ready
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    content: Option<ContentHashes>,
+    /// Never loses HP or MP, turned on in the sandbox
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    god_mode: bool,
+}
+
+impl PlayerState {
+    pub fn new(name: String, pos: Position) -> Self {
+        // Our player will initially have a body and one body segment,
+        // and will be moving to the right.
+        PlayerState {
+            name,
+            #[cfg(feature = "native")]
+            id: String::new(),
+            #[cfg(feature = "native")]
+            number: 0,
+            body: pos,
+            dir: Direction::default(),
+            last_dir: Direction::default(),
+            ate: None,
+            current_accel: config::tunables().starting_accel,
+            throttle: 1.0,
+            hp: PLAYER_MAX_HP,
+            mp: PLAYER_MAX_MP,
+            str: PLAYER_MAX_STR,
+            stamina: PLAYER_MAX_STAMINA,
+            sprint_held: false,
+            winded: false,
+            jumping: false,
+            jump_offset: 0.0,
+            jump_velocity: 0.0,
+            air_jumped: false,
+            #[cfg(feature = "server")]
+            jump_started: None,
+            jump_buffered: None,
+            ready: false,
+            burning: false,
+            #[cfg(feature = "server")]
+            hazard_damage: 0.0,
+            #[cfg(feature = "server")]
+            regen: (0.0, 0.0),
+            #[cfg(feature = "server")]
+            regen_hp: PLAYER_MAX_HP,
+            #[cfg(feature = "server")]
+            last_damaged: None,
+            corrections: 0,
+            #[cfg(feature = "server")]
+            last_move: None,
+            dashing: false,
+            dash_updates: 0,
+            since_dash: None,
+            blocking: false,
+            block_started: None,
+            #[cfg(feature = "server")]
+            block_drain: 0.0,
+            staggered: false,
+            #[cfg(feature = "server")]
+            stagger_until: None,
+            statuses: vec![],
+            traps: 0,
+            equipment: vec![],
+            afk: false,
+            #[cfg(feature = "server")]
+            last_input: None,
+            bot: false,
+            team: None,
+            #[cfg(feature = "server")]
+            address: None,
+            rejoin_check: String::new(),
+            carrying_flag: None,
+            level: 1,
+            xp: 0,
+            skin: Skin::default(),
+            attack_count: 0,
+            last_attack: None,
+            combo: 0,
+            #[cfg(feature = "server")]
+            combo_at: None,
+            hits_taken: 0,
+            crits_taken: 0,
+            respawns: 0,
+            animation_frame: 0.0,
+            is_hit: false,
+            #[cfg(feature = "server")]
+            content: None,
+            #[cfg(feature = "server")]
+            god_mode: false,
+        }
+    }
+
+    pub fn body(&self) -> Position {
+        self.body
+    }
+
+    /// Where we are and what we're doing, as `sendposition` tells the server.
+    pub fn position_meta(&self) -> String {
+        let meta = [
+            self.body.x, self.body.y, self.dir.clone().into(), self.jumping as u8 as f32, self.animation_frame,
+            self.last_dir.clone().into(), self.dashing as u8 as f32, self.stamina, self.is_sprinting() as u8 as f32,
+            self.jump_offset,
+        ];
+        serde_json::to_string(&meta).unwrap_or_default()
+    }
+
+    /// Goes back to `body`, where the server has us, if it's refused a move
+    /// or respawned us since we last heard, which bumps `corrections`.
+    pub fn follow_corrections(&mut self, body: &Position, corrections: u32) {
+        if corrections != self.corrections {
+            self.body.x = body.x;
+            self.body.y = body.y;
+            self.current_accel = config::tunables().starting_accel;
+            self.corrections = corrections;
+        }
+    }
+
+    //fn eats(&self, potion: &Potion) -> bool {
+    //    if self.body == potion.pos {
+    //        true
+    //    } else {
+    //        false
+    //    }
+    //}
+
+    /// What's held this frame, along with the `presses` made since the last.
+    #[cfg(feature = "client")]
+    fn frame_input(&self, presses: rollback::FrameInput) -> rollback::FrameInput {
+        let held = [
+            (self.dir.up, rollback::UP),
+            (self.dir.down, rollback::DOWN),
+            (self.dir.left, rollback::LEFT),
+            (self.dir.right, rollback::RIGHT),
+            (self.sprint_held, rollback::SPRINT),
+        ];
+        held.iter().filter(|(on, _)| *on).fold(presses, |input, (_, bit)| input | bit)
+    }
+
+    /// Where we are and what we're doing, summed up for holding up against
+    /// the opponent's simulation of us under rollback. Positions are rounded
+    /// so float noise too small to see doesn't count.
+    #[cfg(feature = "client")]
+    fn sync_checksum(&self) -> u64 {
+        let summary = format!("{} {} {} {}", self.body.x.round(), self.body.y.round(), self.jumping, self.dashing);
+        transfer::checksum(summary.as_bytes())
+    }
+
+    /// Angle of the stars over a staggered player's head, one turn a second.
+    #[cfg(feature = "client")]
+    fn stagger_spin(&self) -> f32 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |t| (t.as_millis() % 1000) as f32 / 1000.0 * std::f32::consts::TAU)
+    }
+
+    /// Adds a status effect, or tops up the duration of one already running.
+    #[cfg(feature = "server")]
+    fn add_status(&mut self, effect: StatusEffect) {
+        match self.statuses.iter_mut().find(|s| s.kind == effect.kind) {
+            Some(existing) => {
+                existing.remaining = existing.remaining.max(effect.remaining);
+                existing.magnitude = existing.magnitude.max(effect.magnitude);
+            },
+            None => self.statuses.push(effect),
+        }
+    }
+
+    /// Runs the status effect clocks, applying poison and regen ticks.
+    #[cfg(feature = "server")]
+    fn tick_statuses(&mut self, elapsed: f32) {
+        for status in self.statuses.iter_mut() {
+            let ticks = status.advance(elapsed) as i64;
+            match status.kind {
+                StatusKind::Poison => self.hp = (self.hp - status.magnitude as i64 * ticks).max(0),
+                StatusKind::Regen => self.hp = (self.hp + status.magnitude as i64 * ticks).min(PLAYER_MAX_HP),
+                _ => (),
+            }
+        }
+        self.statuses.retain(|s| !s.expired());
+    }
+
+    /// Slowly brings back HP and MP once nothing has hurt the player for a
+    /// while. Guarding holds MP back.
+    #[cfg(feature = "server")]
+    fn regenerate(&mut self, elapsed: f32) {
+        if self.hp < self.regen_hp {
+            self.last_damaged = Some(clock::now());
+            self.regen = (0.0, 0.0);
+        }
+        let in_combat = self.last_damaged.is_some_and(|at| clock::since(at) < Duration::from_secs_f32(REGEN_DELAY_SECONDS));
+        if self.hp > 0 && !in_combat {
+            self.regen.0 += HP_REGEN_PER_SECOND * elapsed;
+            if !self.blocking {
+                self.regen.1 += MP_REGEN_PER_SECOND * elapsed;
+            }
+            let (hp, mp) = (self.regen.0.floor(), self.regen.1.floor());
+            self.regen = (self.regen.0 - hp, self.regen.1 - mp);
+            self.hp = (self.hp + hp as i64).min(PLAYER_MAX_HP);
+            self.mp = (self.mp + mp as i64).min(PLAYER_MAX_MP);
+        }
+        self.regen_hp = self.hp;
+    }
+
+    /// What the player's points go under in `NetworkedGame::scores`, their
+    /// team's name in team modes and their number otherwise.
+    #[cfg(feature = "native")]
+    fn score_key(&self) -> String {
+        match self.team {
+            Some(team) => ctf::TEAM_NAMES[team].to_string(),
+            None => self.number.to_string(),
+        }
+    }
+
+    #[cfg(feature = "native")]
+    fn xp_to_next_level(&self) -> u32 {
+        self.level * XP_PER_LEVEL
+    }
+
+    /// Adds experience, leveling up as often as it covers.
+    #[cfg(feature = "server")]
+    fn gain_xp(&mut self, xp: u32) {
+        self.xp += xp;
+        while self.xp >= self.xp_to_next_level() {
+            self.xp -= self.xp_to_next_level();
+            self.level += 1;
+            self.str += LEVEL_UP_STR;
+            self.hp = (self.hp + LEVEL_UP_HP).min(PLAYER_MAX_HP);
+            self.mp = (self.mp + LEVEL_UP_MP).min(PLAYER_MAX_MP);
+            println!("{} reached level {}", self.name, self.level);
+        }
+    }
+
+    /// Back on its feet at `spawn` with full stats. Bumps `corrections` so the
+    /// client drops its own position.
+    #[cfg(feature = "server")]
+    fn respawn(&mut self, spawn: Option<Position>) {
+        if let Some(spawn) = spawn {
+            self.body.x = spawn.x;
+            self.body.y = spawn.y;
+        }
+        self.hp = PLAYER_MAX_HP;
+        self.mp = PLAYER_MAX_MP;
+        self.stamina = PLAYER_MAX_STAMINA;
+        self.statuses.clear();
+        self.blocking = false;
+        self.current_accel = config::tunables().starting_accel;
+        self.corrections += 1;
+        self.respawns += 1;
+    }
+
+    /// Puts on a piece of gear, replacing whatever was in that slot.
+    #[cfg(feature = "server")]
+    fn equip(&mut self, kind: EquipmentKind) {
+        self.equipment.retain(|worn| worn.slot() != kind.slot());
+        self.equipment.push(kind);
+    }
+
+    #[cfg(feature = "native")]
+    fn is_invisible(&self) -> bool {
+        self.statuses.iter().any(|s| s.kind == StatusKind::Invisible)
+    }
+
+    #[cfg(feature = "client")]
+    fn get_animation_direction(&self) -> f32 {
+        if self.dir.up {
+            0.25
+        } else if self.dir.left {
+            0.5
+        } else if self.dir.right {
+            0.75
+        } else if self.dir.down {
+            0.0
+        } else if self.last_dir.left {
+            0.5
+        } else if self.last_dir.right {
+           0.75
+        } else if self.last_dir.up {
+            0.25
+        } else {
+            0.0
+        }
+    }
+
+}
+
+/// How a player looks on this client: sprite, animation, bars and effects,
+/// none of which the server or the other clients ever see. Worked out from
+/// the `PlayerState` each frame.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+struct PlayerView {
+    /// Where the body was before the last simulation step, drawn from
+    previous_body: Option<Position>,
+    /// What the HP and MP bars show, lagging behind on damage so it drains away
+    shown_hp: f32,
+    shown_mp: f32,
+    /// Where the player was on the last few dash updates, oldest first
+    afterimages: Vec<(Position, f32, f32)>,
+    leveled_at: Option<std::time::Instant>,
+    /// Color the sprite is drawn with, worked out from the skin
+    tint: [f32; 3],
+    hit_at: Option<std::time::Instant>,
+    crit_at: Option<std::time::Instant>,
+    died_at: Option<std::time::Instant>,
+    respawned_at: Option<std::time::Instant>,
+    texture: Option<ImageGeneric<GlBackendSpec>>,
+    /// Name of the sheet `texture` is, to batch it with others using it
+    sheet: String,
+    name_text: Cached<String, graphics::Text>,
+    level_text: Cached<u32, graphics::Text>,
+    /// Name plate with the team stripe and bars, relative to the body
+    plate: Cached<PlateKey, graphics::Mesh>,
+    /// Picks and plays the sprite's clip
+    animator: Animator,
+    /// Whether the game went to this player or their team, for the victory pose
+    won: bool,
+    /// How a remote player was last seen moving, none for the local one
+    motion: Option<Motion>,
+}
+
+/// Facing and walking for a player whose keys are only known to their own
+/// client, worked out from how far the body moved between snapshots.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    body: Position,
+    /// Row of the sprite sheet, as `get_animation_direction` gives it
+    facing: f32,
+    moved_at: Option<Instant>,
+}
+
+#[cfg(feature = "client")]
+impl PlayerView {
+    fn new(texture: Option<ImageGeneric<GlBackendSpec>>) -> Self {
+        PlayerView {
+            previous_body: None,
+            shown_hp: PLAYER_MAX_HP as f32,
+            shown_mp: PLAYER_MAX_MP as f32,
+            afterimages: vec![],
+            leveled_at: None,
+            tint: [1.0, 1.0, 1.0],
+            hit_at: None,
+            crit_at: None,
+            died_at: None,
+            respawned_at: None,
+            texture,
+            sheet: skin::DEFAULT_SHEET.to_string(),
+            name_text: Cached::default(),
+            level_text: Cached::default(),
+            plate: Cached::default(),
+            animator: Animator::default(),
+            won: false,
+            motion: None,
+        }
+    }
+
+    /// Takes in where a snapshot puts a remote player.
+    fn observe(&mut self, body: Position) {
+        let mut motion = self.motion.unwrap_or(Motion { body, facing: 0.0, moved_at: None });
+        let (dx, dy) = (body.x - motion.body.x, body.y - motion.body.y);
+        if dx != 0.0 || dy != 0.0 {
+            motion.facing = if dx.abs() > dy.abs() {
+                if dx < 0.0 { 0.5 } else { 0.75 }
+            } else if dy < 0.0 {
+                0.25
+            } else {
+                0.0
+            };
+            motion.moved_at = Some(Instant::now());
+        }
+        motion.body = body;
+        self.motion = Some(motion);
+    }
+
+    fn is_walking(&self, player: &PlayerState) -> bool {
+        match self.motion {
+            Some(motion) => motion.moved_at.is_some_and(|at| at.elapsed() < Duration::from_millis(REMOTE_WALK_MILLIS)),
+            None => player.is_moving(),
+        }
+    }
+
+    fn facing(&self, player: &PlayerState) -> f32 {
+        match self.motion {
+            Some(motion) => motion.facing,
+            None => player.get_animation_direction(),
+        }
+    }
+
+    /// Switches to the sprite sheet and tint of `skin`.
+    fn set_skin(&mut self, skin: &Skin, textures: &HashMap<String, ImageGeneric<GlBackendSpec>>) {
+        let (texture, tint) = skin.texture(textures);
+        self.texture = texture;
+        self.sheet = if textures.contains_key(&skin.sheet) { skin.sheet.clone() } else { skin::DEFAULT_SHEET.to_string() };
+        self.tint = tint;
+    }
+
+    /// Keeps track of where `player` is before it's moved on a step.
+    fn step(&mut self, player: &PlayerState) {
+        self.previous_body = Some(player.body);
+        if player.dashing {
+            self.afterimages.push((player.body, player.animation_frame, self.facing(player)));
+            if self.afterimages.len() > DASH_AFTERIMAGES {
+                self.afterimages.remove(0);
+            }
+        } else if !self.afterimages.is_empty() {
+            self.afterimages.remove(0);
+        }
+    }
+
+    fn animate_frames(&mut self, player: &mut PlayerState) {
+        let walking = self.is_walking(player);
+        self.animator.choose(walking, player.last_attack, self.won);
+        player.animation_frame = self.animator.advance(player.animation_frame);
+    }
+
+    /// Where the body is `alpha` of the way from before the last simulation
+    /// step to now.
+    fn drawn_body(&self, player: &PlayerState, alpha: f32) -> Position {
+        let mut drawn = player.body;
+        if let Some(previous) = self.previous_body {
+            drawn.x = previous.x + (player.body.x - previous.x) * alpha;
+            drawn.y = previous.y + (player.body.y - previous.y) * alpha;
+        }
+        drawn
+    }
+
+    /// Drains the bars towards `hp` and `mp`, healing shows straight away.
+    fn drain_bars(&mut self, player: &PlayerState, elapsed: f32) {
+        let drain = BAR_DRAIN_PER_SECOND * elapsed;
+        self.shown_hp = (self.shown_hp - drain * PLAYER_MAX_HP as f32).max(player.hp as f32);
+        self.shown_mp = (self.shown_mp - drain * PLAYER_MAX_MP as f32).max(player.mp as f32);
+    }
+
+    /// Effects, name plate and bars go straight to the screen, the sprite is
+    /// queued on `batches` to be drawn along with the rest of its sheet.
+    fn draw(&mut self, ctx: &mut Context, player: &mut PlayerState, alpha: f32, plates: PlateStyle, batches: &mut SpriteBatches) -> GameResult<()> {
+        let body = player.body;
+        player.body = self.drawn_body(player, alpha);
+        let drawn = self.draw_at_body(ctx, player, plates, batches);
+        player.body = body;
+        drawn
+    }
+
+    fn draw_at_body(&mut self, ctx: &mut Context, player: &mut PlayerState, plates: PlateStyle, batches: &mut SpriteBatches) -> GameResult<()> {
+        if let Some(ate) = &player.ate {
+            println!("{:?}", ate.pos);
+        }
+        // And then we do the same for the head, instead making it fully red to distinguish it.
+        //let bounding_box_rectangle = graphics::Mesh::new_rectangle(
+        //    ctx,
+        //    graphics::DrawMode::fill(),
+        //    player.body.into(),
+        //    [1.0, 0.5, 0.0, 1.0].into(),
+        //)?;
+        //graphics::draw(ctx, &bounding_box_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        //let black_rectangle = graphics::Mesh::new_rectangle(
+        //    ctx,
+        //    graphics::DrawMode::fill(),
+        //    Rect::new(player.body.x, player.body.y, player.body.w, player.body.h),
+        //    [0.0, 0.0, 0.0, 1.0].into(),
+        //)?;
+        //graphics::draw(ctx, &black_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        if player.burning {
+            let mut rng = rand::thread_rng();
+            for flame in 0..3 {
+                let flame_circle = graphics::Mesh::new_circle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    ggez::mint::Point2 {
+                        x: player.body.x + 5.0 + flame as f32 * 10.0 + rng.gen_range(-2.0, 2.0),
+                        y: player.body.y + 40.0 - rng.gen_range(0.0, 8.0),
+                    },
+                    rng.gen_range(4.0, 7.0),
+                    1.0,
+                    graphics::Color::new(1.0, rng.gen_range(0.2, 0.6), 0.0, 0.8),
+                )?;
+                graphics::draw(ctx, &flame_circle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+        }
+
+        if let Some(last_attack) = player.last_attack {
+            if last_attack.elapsed() < Duration::from_millis(ATTACK_SWING_MILLIS) {
+                let swing_rectangle = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    player.attack_reach().into(),
+                    [1.0, 1.0, 1.0, 0.5].into(),
+                )?;
+                graphics::draw(ctx, &swing_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+        }
+
+        if player.blocking {
+            let shield_circle = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::stroke(3.0),
+                ggez::mint::Point2 { x: player.body.x + player.body.w / 2.0, y: player.body.y + player.body.h / 2.0 },
+                30.0,
+                1.0,
+                graphics::Color::new(0.3, 0.6, 1.0, 0.7),
+            )?;
+            graphics::draw(ctx, &shield_circle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        }
+
+        if player.staggered {
+            // Stars circling the head
+            let spin = player.stagger_spin();
+            for star in 0..3 {
+                let angle = spin + star as f32 * 2.094;
+                let star_circle = graphics::Mesh::new_circle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    ggez::mint::Point2 {
+                        x: player.body.x + player.body.w / 2.0 + angle.cos() * 14.0,
+                        y: player.body.y - 4.0 + angle.sin() * 4.0,
+                    },
+                    3.0,
+                    0.5,
+                    graphics::Color::new(1.0, 0.9, 0.2, 1.0),
+                )?;
+                graphics::draw(ctx, &star_circle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+        }
+
+        if let Some(leveled_at) = self.leveled_at {
+            let burst = leveled_at.elapsed().as_millis() as f32 / LEVEL_UP_BURST_MILLIS as f32;
+            if burst < 1.0 {
+                let center = ggez::mint::Point2 { x: player.body.x + player.body.w / 2.0, y: player.body.y + player.body.h / 2.0 };
+                let ring = graphics::Mesh::new_circle(
+                    ctx,
+                    graphics::DrawMode::stroke(3.0),
+                    center,
+                    10.0 + burst * 40.0,
+                    0.5,
+                    graphics::Color::new(1.0, 0.85, 0.2, 1.0 - burst),
+                )?;
+                graphics::draw(ctx, &ring, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                for ray in 0..8 {
+                    let angle = ray as f32 * 0.785;
+                    let inner = 12.0 + burst * 30.0;
+                    let outer = inner + 10.0;
+                    let ray_line = graphics::Mesh::new_line(
+                        ctx,
+                        &[
+                            ggez::mint::Point2 { x: center.x + angle.cos() * inner, y: center.y + angle.sin() * inner },
+                            ggez::mint::Point2 { x: center.x + angle.cos() * outer, y: center.y + angle.sin() * outer },
+                        ],
+                        2.0,
+                        graphics::Color::new(1.0, 1.0, 0.6, 1.0 - burst),
+                    )?;
+                    graphics::draw(ctx, &ray_line, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+                }
+            }
+        }
+
+        if player.jumping {
+            // Shrinks and fades with height, so the landing can be seen coming
+            let height = (player.jump_offset / config::tunables().jump_height()).min(1.0);
+            let bounding_box_rectangle = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                ggez::mint::Point2 { x: player.body.x + 15.0,  y: player.body.y + 47.0 },
+                14.0 * (1.0 - height * 0.4),
+                1.0,
+                graphics::Color::new(0.0, 0.0, 0.0, 0.45 - height * 0.25),
+            )?;
+            graphics::draw(ctx, &bounding_box_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        }
+
+        let key = (plates.palette, plates.name_box, player.team, player.hp, player.mp, self.shown_hp.ceil() as i64, self.shown_mp.ceil() as i64, player.stamina.ceil() as i64);
+        let plate = self.plate.get(key, |key| PlayerView::build_plate(ctx, key))?;
+        graphics::draw(ctx, plate, (ggez::mint::Point2 { x: player.body.x, y: player.body.y },))?;
+
+        if plates.names {
+            // Queued here, drawn along with everyone else's once the sprites are
+            let player_name = self.name_text.get(player.name.clone(), |name| Ok(graphics::Text::new(graphics::TextFragment {
+                text: name.clone(),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 15.0, y: 15.0 }),
+            })))?;
+            palette::queue_text(ctx, player_name, ggez::mint::Point2 { x: player.body.x - (player.name.chars().count() as f32) + 5.0, y: player.body.y - GRID_CELL_SIZE - 10.0 }, palette::palette(plates.palette));
+            let player_level = self.level_text.get(player.level, |level| Ok(graphics::Text::new(graphics::TextFragment {
+                text: format!("L{}", level),
+                color: Some(graphics::Color::new(1.0, 0.85, 0.2, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 13.0, y: 13.0 }),
+            })))?;
+            palette::queue_text(ctx, player_level, ggez::mint::Point2 { x: player.body.x + 50.0, y: player.body.y - 44.0 }, palette::palette(plates.palette));
+        }
+        self.animate_frames(player);
+        if let Some(player_texture) = &self.texture {
+            for (index, (pos, frame, direction)) in self.afterimages.iter().enumerate() {
+                let alpha = (index + 1) as f32 / (DASH_AFTERIMAGES + 1) as f32 * 0.5;
+                let afterimage = graphics::DrawParam::new()
+                    .src(graphics::Rect {x: *frame, y: *direction, w: 1.0 / self.animator.frames(), h: 0.25})
+                    .dest(Vec2::new(pos.x + 2.0, pos.y - 10.0))
+                    .offset(Vec2::new(0.15, 0.0))
+                    .scale(Vec2::new(0.1, 0.1))
+                    .color(graphics::Color::new(0.6, 0.8, 1.0, alpha));
+                batches.add(&self.sheet, player_texture, afterimage);
+            }
+        }
+        if let Some(respawned_at) = self.respawned_at {
+            let progress = respawned_at.elapsed().as_millis() as f32 / RESPAWN_MATERIALIZE_MILLIS as f32;
+            if progress < 1.0 {
+                // The sprite fades in inside a column of light that narrows as it goes
+                let width = player.body.w * (1.0 - progress) + 4.0;
+                let column = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(player.body.x + (player.body.w - width) / 2.0, player.body.y - 40.0, width, player.body.h + 40.0),
+                    graphics::Color::new(0.7, 0.9, 1.0, 0.6 * (1.0 - progress)),
+                )?;
+                graphics::draw(ctx, &column, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+        }
+        if let Some(player_texture) = &self.texture {
+            batches.add(&self.sheet, player_texture, self.sprite_param(player));
+        }
+        Ok(())
+    }
+
+    /// Whatever goes on top of the sprite, drawn once the batches have been.
+    fn draw_over(&mut self, ctx: &mut Context, player: &mut PlayerState, alpha: f32) -> GameResult<()> {
+        let body = player.body;
+        player.body = self.drawn_body(player, alpha);
+        let drawn = self.draw_over_body(ctx, player);
+        player.body = body;
+        drawn
+    }
+
+    fn draw_over_body(&mut self, ctx: &mut Context, player: &PlayerState) -> GameResult<()> {
+        // Criticals flash gold, and for longer
+        let crit = self.crit_at.filter(|at| at.elapsed() < Duration::from_millis(CRIT_FLASH_MILLIS));
+        let (flashed_at, flash_millis, (r, g, b)) = match crit {
+            Some(crit_at) => (Some(crit_at), CRIT_FLASH_MILLIS, (1.0, 0.8, 0.2)),
+            None => (self.hit_at, HIT_FLASH_MILLIS, (1.0, 1.0, 1.0)),
+        };
+        if let (Some(player_texture), Some(hit_at)) = (&self.texture, flashed_at) {
+            let flash = hit_at.elapsed().as_millis() as f32 / flash_millis as f32;
+            if flash < 1.0 && self.died_at.is_none() {
+                // Drawn again on top, added on, to wash the sprite out
+                let param = self.sprite_param(player);
+                let mut white = player_texture.clone();
+                graphics::Drawable::set_blend_mode(&mut white, Some(graphics::BlendMode::Add));
+                graphics::draw(ctx, &white, param.color(graphics::Color::new(r, g, b, param.color.a * (1.0 - flash))))?;
+            }
+        }
+        for worn in player.equipment.iter() {
+            worn.draw_worn(ctx, &player.body)?;
+        }
+        if let Some(team) = player.carrying_flag {
+            ctf::draw_banner(ctx, player.body.x + player.body.w - 6.0, player.body.y - 24.0, team)?;
+        }
+        Ok(())
+    }
+
+    /// Where and how the current frame of the sprite is drawn.
+    fn sprite_param(&self, player: &PlayerState) -> graphics::DrawParam {
+        // Invisible players only see a ghost of themselves, the opponent isn't drawn at all
+        let mut alpha = if player.is_invisible() { 0.4 } else { 1.0 };
+        let mut sink = 0.0;
+        if let Some(died_at) = self.died_at {
+            // Slumps and fades away
+            let progress = (died_at.elapsed().as_millis() as f32 / DEATH_FADE_MILLIS as f32).min(1.0);
+            alpha *= 1.0 - progress;
+            sink = progress * 8.0;
+        }
+        if let Some(respawned_at) = self.respawned_at {
+            alpha *= (respawned_at.elapsed().as_millis() as f32 / RESPAWN_MATERIALIZE_MILLIS as f32).min(1.0);
+        }
+        let facing = self.facing(player);
+        let pose = self.animator.pose(facing);
+        graphics::DrawParam::new()
+        .src(graphics::Rect {x: player.animation_frame, y: pose.facing.unwrap_or(facing), w: 1.0 / self.animator.frames(), h: 0.25})
+        .dest(Vec2::new(player.body.x + 2.0 + pose.dx, player.body.y - 10.0 + sink + pose.dy))
+        .offset(Vec2::new(0.15, player.jump_offset))
+        .scale(Vec2::new(0.1, 0.1 * pose.stretch))
+        .color(graphics::Color::new(self.tint[0], self.tint[1], self.tint[2], alpha))
+        //.rotation((time % cycle) as f32 / cycle as f32 * 6.28)
+        //.offset(Vec2::new(150.0, 150.0));
+    }
+
+    /// Plate, team stripe and bars in one mesh, around a body at 0, 0.
+    fn build_plate(ctx: &mut Context, key: &PlateKey) -> GameResult<graphics::Mesh> {
+        let (palette, name_box, team, hp, mp, shown_hp, shown_mp, stamina) = *key;
+        let palette = palette::palette(palette);
+        let mut plate = graphics::MeshBuilder::new();
+        if name_box {
+            plate.rectangle(
+                graphics::DrawMode::fill(),
+                Rect::new(-13.0, -45.0, 60.0, 35.0),
+                [0.0, 0.0, 0.0, 1.0].into(),
+            )?;
+        }
+        if let Some(team) = team {
+            plate.rectangle(
+                graphics::DrawMode::fill(),
+                Rect::new(-13.0, -13.0, 60.0, 3.0),
+                ctf::TEAM_COLORS[team % ctf::TEAMS].into(),
+            )?;
+        }
+        stat_bar(
+            &mut plate,
+            Rect::new(-10.0, -26.0, 54.0, 5.0),
+            hp as f32 / PLAYER_MAX_HP as f32,
+            shown_hp as f32 / PLAYER_MAX_HP as f32,
+            palette.hp,
+            palette,
+        )?;
+        stat_bar(
+            &mut plate,
+            Rect::new(-10.0, -20.0, 54.0, 4.0),
+            mp as f32 / PLAYER_MAX_MP as f32,
+            shown_mp as f32 / PLAYER_MAX_MP as f32,
+            palette.mp,
+            palette,
+        )?;
+        let stamina = stamina as f32 / PLAYER_MAX_STAMINA;
+        stat_bar(&mut plate, Rect::new(-10.0, -15.0, 54.0, 2.0), stamina, stamina, palette.stamina, palette)?;
+        plate.build(ctx)
+    }
+}
+
+/// A player as the client has it, the replicated state along with how it's
+/// drawn. Derefs to the state, so game code reads the same as on the server.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+struct Player {
+    state: PlayerState,
+    view: PlayerView,
+}
+
+#[cfg(feature = "client")]
+impl std::ops::Deref for Player {
+    type Target = PlayerState;
+
+    fn deref(&self) -> &PlayerState {
+        &self.state
+    }
+}
+
+#[cfg(feature = "client")]
+impl std::ops::DerefMut for Player {
+    fn deref_mut(&mut self) -> &mut PlayerState {
+        &mut self.state
+    }
+}
+
+#[cfg(feature = "client")]
+impl Player {
+    fn new(name: String, pos: Position, texture: Option<ImageGeneric<GlBackendSpec>>) -> Self {
+        Player {
+            state: PlayerState::new(name, pos),
+            view: PlayerView::new(texture),
+        }
+    }
+
+    fn update(&mut self, elapsed: f32, do_move: bool, map: &Map, obstacles: &SpatialHash) {
+        self.view.step(&self.state);
+        self.state.update(elapsed, do_move, map, obstacles);
+    }
+
+    fn set_skin(&mut self, skin: Skin, textures: &HashMap<String, ImageGeneric<GlBackendSpec>>) {
+        self.view.set_skin(&skin, textures);
+        self.state.skin = skin;
+    }
+
+    fn drawn_body(&self, alpha: f32) -> Position {
+        self.view.drawn_body(&self.state, alpha)
+    }
+
+    fn drain_bars(&mut self, elapsed: f32) {
+        self.view.drain_bars(&self.state, elapsed);
+    }
+
+    fn draw(&mut self, ctx: &mut Context, alpha: f32, plates: PlateStyle, batches: &mut SpriteBatches) -> GameResult<()> {
+        self.view.draw(ctx, &mut self.state, alpha, plates, batches)
+    }
+
+    fn draw_over(&mut self, ctx: &mut Context, alpha: f32) -> GameResult<()> {
+        self.view.draw_over(ctx, &mut self.state, alpha)
+    }
+}
+
+/// What the name plate shows: the palette, whether it has the box behind,
+/// team, HP, MP, the HP and MP the bars are still draining from, and stamina.
+#[cfg(feature = "client")]
+type PlateKey = (usize, bool, Option<usize>, i64, i64, i64, i64, i64);
+
+/// What the HUD's bars show: the palette, HP, MP, the HP and MP the bars
+/// are still draining from, stamina and whether the player is winded.
+#[cfg(feature = "client")]
+type BarsKey = (usize, i64, i64, i64, i64, i64, bool);
+
+/// Which parts of the name plates are drawn and in what colors, from the settings.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy)]
+struct PlateStyle {
+    names: bool,
+    name_box: bool,
+    /// Index into `palette::PALETTES`
+    palette: usize,
+}
+
+/// Adds a stat bar over a dark background to `mesh`, with the part of `fill`
+/// just lost still showing faintly up to `trailing`. Both are fractions of a
+/// full bar.
+#[cfg(feature = "client")]
+fn stat_bar(mesh: &mut graphics::MeshBuilder, rect: Rect, fill: f32, trailing: f32, color: [f32; 4], palette: &Palette) -> GameResult<()> {
+    mesh.rectangle(graphics::DrawMode::fill(), rect, palette.bar_back.into())?;
+    let fill = fill.clamp(0.0, 1.0);
+    let trailing = trailing.max(fill).min(1.0);
+    if trailing > fill {
+        let lost = Rect::new(rect.x + rect.w * fill, rect.y, rect.w * (trailing - fill), rect.h);
+        mesh.rectangle(graphics::DrawMode::fill(), lost, palette.bar_drain.into())?;
+    }
+    if fill > 0.0 {
+        let filled = Rect::new(rect.x, rect.y, rect.w * fill, rect.h);
+        mesh.rectangle(graphics::DrawMode::fill(), filled, color.into())?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[derive(Clone)]
+struct Hud {
+    /// Top and bottom bars and the STR icon, which never change
+    frame: Cached<(), graphics::Mesh>,
+    name_text: Cached<String, graphics::Text>,
+    str_text: Cached<i64, graphics::Text>,
+    /// Level, XP and XP needed
+    level_text: Cached<(u32, u32, u32), graphics::Text>,
+    traps_text: Cached<u32, graphics::Text>,
+    /// HP, MP and stamina bars
+    bars: Cached<BarsKey, graphics::Mesh>,
+    /// Seconds left and whether it's shown red
+    clock_text: Cached<(u32, bool), graphics::Text>,
+}
+
+#[cfg(feature = "client")]
+impl Hud {
+
+    fn new() -> Hud {
+        Hud {
+            frame: Cached::default(),
+            name_text: Cached::default(),
+            str_text: Cached::default(),
+            level_text: Cached::default(),
+            traps_text: Cached::default(),
+            bars: Cached::default(),
+            clock_text: Cached::default(),
+        }
+    }
+
+    fn build_frame(ctx: &mut Context) -> GameResult<graphics::Mesh> {
+        let color = [0.0, 0.0, 0.0, 1.0].into();
+        let top_back = graphics::Rect {
+                x: 0.0,
+                y: 0.0,
+                w: SCREEN_SIZE.0,
+                h: GRID_CELL_SIZE,
+        };
+        let bottom_back = graphics::Rect {
+                x: 0.0,
+                y: SCREEN_SIZE.1 - GRID_CELL_SIZE,
+                w: SCREEN_SIZE.0,
+                h: GRID_CELL_SIZE,
+        };
+        graphics::MeshBuilder::new()
+            .rectangle(graphics::DrawMode::fill(), top_back, color)?
+            .rectangle(graphics::DrawMode::fill(), bottom_back, color)?
+            // A little sword for STR
+            .line(
+                &[
+                    ggez::mint::Point2 { x: 116.0, y: SCREEN_SIZE.1 - 6.0 },
+                    ggez::mint::Point2 { x: 128.0, y: SCREEN_SIZE.1 - GRID_CELL_SIZE + 6.0 },
+                ],
+                3.0,
+                graphics::Color::new(1.0, 1.0, 0.2, 1.0),
+            )?
+            .line(
+                &[
+                    ggez::mint::Point2 { x: 113.0, y: SCREEN_SIZE.1 - 14.0 },
+                    ggez::mint::Point2 { x: 124.0, y: SCREEN_SIZE.1 - 10.0 },
+                ],
+                2.0,
+                graphics::Color::new(1.0, 1.0, 0.2, 1.0),
+            )?
+            .build(ctx)
+    }
+
+    fn build_bars(ctx: &mut Context, key: &BarsKey) -> GameResult<graphics::Mesh> {
+        let (palette, hp, mp, shown_hp, shown_mp, stamina, winded) = *key;
+        let palette = palette::palette(palette);
+        let mut bars = graphics::MeshBuilder::new();
+        stat_bar(
+            &mut bars,
+            Rect::new(6.0, SCREEN_SIZE.1 - GRID_CELL_SIZE + 5.0, 100.0, 10.0),
+            hp as f32 / PLAYER_MAX_HP as f32,
+            shown_hp as f32 / PLAYER_MAX_HP as f32,
+            palette.hp,
+            palette,
+        )?;
+        stat_bar(
+            &mut bars,
+            Rect::new(6.0, SCREEN_SIZE.1 - GRID_CELL_SIZE + 19.0, 100.0, 8.0),
+            mp as f32 / PLAYER_MAX_MP as f32,
+            shown_mp as f32 / PLAYER_MAX_MP as f32,
+            palette.mp,
+            palette,
+        )?;
+        // Greyed out until it's usable again
+        let stamina = stamina as f32 / PLAYER_MAX_STAMINA;
+        let color = if winded { palette.winded } else { palette.stamina };
+        stat_bar(&mut bars, Rect::new(6.0, SCREEN_SIZE.1 - GRID_CELL_SIZE + 28.0, 100.0, 3.0), stamina, stamina, color, palette)?;
+        bars.build(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, player: &Player, palette: usize) -> GameResult<()> {
+        let frame = self.frame.get((), |_| Hud::build_frame(ctx))?;
+        graphics::draw(ctx, frame, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let key = (palette, player.hp, player.mp, player.view.shown_hp.ceil() as i64, player.view.shown_mp.ceil() as i64, player.stamina.ceil() as i64, player.winded);
+        let bars = self.bars.get(key, |key| Hud::build_bars(ctx, key))?;
+        graphics::draw(ctx, bars, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let player_name = self.name_text.get(player.name.clone(), |name| Ok(graphics::Text::new(graphics::TextFragment {
+                text: format!("Player: {}", name),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 30.0, y: 30.0 }),
+            })))?;
+        graphics::queue_text(ctx, player_name, ggez::mint::Point2 { x: 0.0, y: 0.0 }, None);
+        let str_text = self.str_text.get(player.strength(), |strength| Ok(graphics::Text::new(graphics::TextFragment {
+                text: format!("{}", strength),
+                color: Some(graphics::Color::new(1.0, 1.0, 0.2, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 30.0, y: 30.0 }),
+            })))?;
+        graphics::queue_text(ctx, str_text, ggez::mint::Point2 { x: 134.0, y: SCREEN_SIZE.1 - GRID_CELL_SIZE }, None);
+        let level = (player.level, player.xp, player.xp_to_next_level());
+        let level_text = self.level_text.get(level, |(level, xp, needed)| Ok(graphics::Text::new(graphics::TextFragment {
+                text: format!("Lv {} ({}/{} XP)", level, xp, needed),
+                color: Some(graphics::Color::new(1.0, 0.85, 0.2, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 20.0, y: 20.0 }),
+            })))?;
+        graphics::queue_text(ctx, level_text, ggez::mint::Point2 { x: SCREEN_SIZE.0 - 200.0, y: 6.0 }, None);
+        if player.traps > 0 {
+            let traps_text = self.traps_text.get(player.traps, |traps| Ok(graphics::Text::new(graphics::TextFragment {
+                    text: format!("Traps: {}", traps),
+                    color: Some(graphics::Color::new(0.3, 0.8, 0.1, 1.0)),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 30.0, y: 30.0 }),
+                })))?;
+            graphics::queue_text(ctx, traps_text, ggez::mint::Point2 { x: 200.0, y: SCREEN_SIZE.1 - GRID_CELL_SIZE }, None);
+        }
+        // Worn gear in the bottom right corner
+        for (index, worn) in player.equipment.iter().enumerate() {
+            worn.draw_icon(ctx, SCREEN_SIZE.0 - (index + 1) as f32 * 30.0, SCREEN_SIZE.1 - GRID_CELL_SIZE + 2.0, 28.0)?;
+        }
+        // Status effect icons sit just above the stat numbers
+        for (index, status) in player.statuses.iter().enumerate() {
+            let icon_x = 10.0 + index as f32 * 22.0;
+            let icon_y = SCREEN_SIZE.1 - GRID_CELL_SIZE - 12.0;
+            let icon = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                ggez::mint::Point2 { x: icon_x, y: icon_y },
+                9.0,
+                0.5,
+                status.kind.color().into(),
+            )?;
+            graphics::draw(ctx, &icon, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            let icon_text = graphics::Text::new(graphics::TextFragment {
+                text: status.kind.letter().to_string(),
+                color: Some(graphics::Color::new(0.0, 0.0, 0.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 13.0, y: 13.0 }),
+            });
+            graphics::queue_text(ctx, &icon_text, ggez::mint::Point2 { x: icon_x - 4.0, y: icon_y - 7.0 }, None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                    //.rotation(-0.5),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Map vote shown while waiting for the game to start.
+    fn draw_lobby(&self, ctx: &mut Context, game: &NetworkedGame, player_number: u32, notice: &[String]) -> GameResult<()> {
+        let color = [0.0, 0.0, 0.0, 0.8].into();
+        let lobby_back = graphics::Rect {
+                x: GRID_CELL_SIZE * 3.0,
+                y: GRID_CELL_SIZE * 2.0,
+                w: SCREEN_SIZE.0 - GRID_CELL_SIZE * 6.0,
+                h: SCREEN_SIZE.1 - GRID_CELL_SIZE * 4.0,
+        };
+        let lobby_rectangle =
+            graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), lobby_back, color)?;
+        graphics::draw(ctx, &lobby_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        let title = if game.players.len() < game.max_players() {
+            format!("Game {} - waiting for players ({}/{})", game.session_id, game.players.len(), game.max_players())
+        } else {
+            format!("Game {} - vote for a map!", game.session_id)
+        };
+        let mut lines = vec![
+            (title, graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+            (format!("Mode: {}  Players: {}", game.mode.title(), game.max_players()), graphics::Color::new(0.7, 0.7, 0.7, 1.0)),
+            (game.rules.describe(game.mode), graphics::Color::new(0.7, 0.7, 0.7, 1.0)),
+        ];
+        for line in notice {
+            lines.push((line.clone(), graphics::Color::new(1.0, 0.85, 0.4, 1.0)));
+        }
+        let my_vote = game.map_votes.get(&player_number);
+        for (index, candidate) in game.map_candidates.iter().enumerate() {
+            let votes = game.map_votes.values().filter(|vote| *vote == candidate).count();
+            let color = if my_vote == Some(candidate) {
+                graphics::Color::new(1.0, 1.0, 0.2, 1.0)
+            } else {
+                graphics::Color::new(1.0, 1.0, 1.0, 1.0)
+            };
+            lines.push((format!("{}) {} - {} vote(s)", index + 1, candidate, votes), color));
+        }
+        for player in game.players.iter() {
+            let [r, g, b] = skin::SKIN_COLORS[player.skin.color % skin::SKIN_COLORS.len()].1;
+            let team = player.team.map_or("".to_string(), |t| format!(" ({} team)", ctf::TEAM_NAMES[t % ctf::TEAMS]));
+            lines.push((format!("{} - {} {}{}", player.name, player.skin.color_name(), player.skin.sheet, team), graphics::Color::new(r, g, b, 1.0)));
+        }
+        lines.push(("C) change color  V) change hero  F1) controls".to_string(), graphics::Color::new(0.7, 0.7, 0.7, 1.0)));
+        for (index, (line, color)) in lines.iter().enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(*color),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 20.0, y: 20.0 }),
+                });
+            let line_offset = if index == 0 { 0.0 } else { GRID_CELL_SIZE / 2.0 };
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 {
+                x: GRID_CELL_SIZE * 3.5,
+                y: GRID_CELL_SIZE * 2.5 + line_offset + index as f32 * 24.0 },
+                None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Red screen edges pulsing like a heartbeat.
+    fn draw_low_hp_vignette(&self, ctx: &mut Context) -> GameResult<()> {
+        let pulse = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |t| (t.as_millis() % 1000) as f32 / 1000.0);
+        let alpha = 0.15 + (pulse * std::f32::consts::PI).sin() * 0.25;
+        // Bands getting fainter towards the middle
+        for band in 0..6 {
+            let inset = band as f32 * 8.0;
+            let edge = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(8.0),
+                Rect::new(inset + 4.0, inset + 4.0, SCREEN_SIZE.0 - inset * 2.0 - 8.0, SCREEN_SIZE.1 - inset * 2.0 - 8.0),
+                graphics::Color::new(0.8, 0.0, 0.0, alpha * (1.0 - band as f32 / 6.0)),
+            )?;
+            graphics::draw(ctx, &edge, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        }
+        Ok(())
+    }
+
+    /// Round trip to the server, just under the top bar on the right.
+    fn draw_ping(&self, ctx: &mut Context, ping: Duration) -> GameResult<()> {
+        let ping_text = graphics::Text::new(graphics::TextFragment {
+                text: format!("{} ms", ping.as_millis()),
+                color: Some(graphics::Color::new(0.8, 0.8, 0.8, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 16.0, y: 16.0 }),
+            });
+        graphics::queue_text(ctx, &ping_text, ggez::mint::Point2 { x: SCREEN_SIZE.0 - 70.0, y: GRID_CELL_SIZE + 4.0 }, None);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// A small warning triangle left of the ping, after the opponent had to
+    /// be put back where the server has them.
+    fn draw_correction(&self, ctx: &mut Context) -> GameResult<()> {
+        let (x, y) = (SCREEN_SIZE.0 - 96.0, GRID_CELL_SIZE + 4.0);
+        let points = [
+            ggez::mint::Point2 { x: x + 8.0, y },
+            ggez::mint::Point2 { x: x + 16.0, y: y + 16.0 },
+            ggez::mint::Point2 { x, y: y + 16.0 },
+        ];
+        let triangle = graphics::Mesh::new_polygon(ctx, graphics::DrawMode::fill(), &points, graphics::Color::new(0.95, 0.75, 0.1, 1.0))?;
+        graphics::draw(ctx, &triangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        let mark = graphics::Text::new(graphics::TextFragment {
+                text: "!".to_string(),
+                color: Some(graphics::Color::BLACK),
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 12.0, y: 12.0 }),
+            });
+        graphics::draw(ctx, &mark, (ggez::mint::Point2 { x: x + 6.0, y: y + 4.0 },))?;
+        Ok(())
+    }
+
+    /// Options or controls list, with the selected row highlighted.
+    fn draw_settings(&self, ctx: &mut Context, settings: &Settings, controls: &KeyBindings, screen: &SettingsScreen) -> GameResult<()> {
+        let color = [0.0, 0.0, 0.0, 0.9].into();
+        let settings_back = graphics::Rect {
+                x: GRID_CELL_SIZE * 3.0,
+                y: GRID_CELL_SIZE * 2.0,
+                w: SCREEN_SIZE.0 - GRID_CELL_SIZE * 6.0,
+                h: SCREEN_SIZE.1 - GRID_CELL_SIZE * 4.0,
+        };
+        let settings_rectangle =
+            graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), settings_back, color)?;
+        graphics::draw(ctx, &settings_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        let (title, help) = match screen.page {
+            SettingsPage::Options => ("Settings", "Left/Right) change  Tab) controls  F1) close"),
+            SettingsPage::Controls => ("Controls", "Enter) rebind  Tab) settings  F1) close"),
+        };
+        let mut lines = vec![
+            (title.to_string(), graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+            (help.to_string(), graphics::Color::new(0.7, 0.7, 0.7, 1.0)),
+        ];
+        let rows: Vec<String> = match screen.page {
+            SettingsPage::Options => settings::SETTINGS.iter()
+                .map(|setting| format!("{}: {}", setting.title(), settings.describe(*setting)))
+                .collect(),
+            SettingsPage::Controls => controls::ACTIONS.iter().enumerate()
+                .map(|(index, action)| {
+                    let key = if index == screen.selected && screen.rebinding {
+                        "press a key..."
+                    } else {
+                        controls.key(*action).and_then(controls::key_name).unwrap_or("-")
+                    };
+                    format!("{}: {}", action.title(), key)
+                })
+                .collect(),
+        };
+        // Scrolls to keep the selected row in view once there are too many to fit
+        let first = (screen.selected + 1).saturating_sub(SETTINGS_VISIBLE_ROWS);
+        for (index, row) in rows.into_iter().enumerate().skip(first).take(SETTINGS_VISIBLE_ROWS) {
+            let color = if index == screen.selected {
+                graphics::Color::new(1.0, 1.0, 0.2, 1.0)
+            } else {
+                graphics::Color::new(1.0, 1.0, 1.0, 1.0)
+            };
+            lines.push((row, color));
+        }
+        if let Some(message) = &screen.message {
+            lines.push((message.clone(), graphics::Color::new(1.0, 0.3, 0.3, 1.0)));
+        }
+        for (index, (line, color)) in lines.iter().enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(*color),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 20.0, y: 20.0 }),
+                });
+            let line_offset = if index == 0 { 0.0 } else { GRID_CELL_SIZE / 2.0 };
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 {
+                x: GRID_CELL_SIZE * 3.5,
+                y: GRID_CELL_SIZE * 2.5 + line_offset + index as f32 * 24.0 },
+                None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Pause menu items with the selected one highlighted, or the quit question.
+    fn draw_pause_menu(&self, ctx: &mut Context, menu: &PauseMenu) -> GameResult<()> {
+        let menu_back = graphics::Rect {
+                x: SCREEN_SIZE.0 / 2.0 - GRID_CELL_SIZE * 4.0,
+                y: SCREEN_SIZE.1 / 2.0 - GRID_CELL_SIZE * 2.5,
+                w: GRID_CELL_SIZE * 8.0,
+                h: GRID_CELL_SIZE * 5.0,
+        };
+        let menu_rectangle =
+            graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), menu_back, [0.0, 0.0, 0.0, 0.9].into())?;
+        graphics::draw(ctx, &menu_rectangle, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        let mut lines = vec![];
+        if menu.confirming_quit {
+            lines.push(("Quit the game?".to_string(), graphics::Color::new(1.0, 1.0, 1.0, 1.0)));
+            lines.push(("Y) quit  N) stay".to_string(), graphics::Color::new(0.7, 0.7, 0.7, 1.0)));
+        } else {
+            lines.push(("Paused".to_string(), graphics::Color::new(1.0, 1.0, 1.0, 1.0)));
+            for (index, item) in menu::MENU_ITEMS.iter().enumerate() {
+                let color = if index == menu.selected {
+                    graphics::Color::new(1.0, 1.0, 0.2, 1.0)
+                } else {
+                    graphics::Color::new(1.0, 1.0, 1.0, 1.0)
+                };
+                lines.push((item.title().to_string(), color));
+            }
+        }
+        for (index, (line, color)) in lines.iter().enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(*color),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 20.0, y: 20.0 }),
+                });
+            let line_offset = if index == 0 { 0.0 } else { GRID_CELL_SIZE / 2.0 };
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 {
+                x: menu_back.x + GRID_CELL_SIZE / 2.0,
+                y: menu_back.y + GRID_CELL_SIZE / 2.0 + line_offset + index as f32 * 24.0 },
+                None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Score bars in the top bar, ours on top.
+    fn draw_scores(&self, ctx: &mut Context, scores: &HashMap<String, f32>, our_key: &str, limit: f32) -> GameResult<()> {
+        let mut ordered: Vec<(&String, &f32)> = scores.iter().collect();
+        ordered.sort_by_key(|(key, _)| *key != our_key);
+        for (index, (_name, score)) in ordered.iter().take(2).enumerate() {
+            let bar_y = 4.0 + index as f32 * 14.0;
+            let back = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                Rect::new(260.0, bar_y, 150.0, 10.0),
+                [0.3, 0.3, 0.3, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &back, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            let color = if index == 0 { [0.2, 0.9, 0.3, 1.0] } else { [0.9, 0.2, 0.2, 1.0] };
+            let filled = (**score / limit).min(1.0) * 150.0;
+            if filled > 0.0 {
+                let bar = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    Rect::new(260.0, bar_y, filled, 10.0),
+                    color.into(),
+                )?;
+                graphics::draw(ctx, &bar, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Time left in the top bar, red in the last ten seconds and in sudden death.
+    fn draw_clock(&mut self, ctx: &mut Context, remaining: f32, sudden_death: bool) -> GameResult<()> {
+        let seconds = remaining.ceil() as u32;
+        let urgent = sudden_death || seconds <= 10;
+        let clock_text = self.clock_text.get((seconds, urgent), |(seconds, urgent)| {
+            let color = if *urgent {
+                graphics::Color::new(1.0, 0.2, 0.2, 1.0)
+            } else {
+                graphics::Color::new(1.0, 1.0, 1.0, 1.0)
+            };
+            Ok(graphics::Text::new(graphics::TextFragment {
+                text: format!("{}:{:02}", seconds / 60, seconds % 60),
+                color: Some(color),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 24.0, y: 24.0 }),
+            }))
+        })?;
+        graphics::queue_text(ctx, clock_text, ggez::mint::Point2 { x: 205.0, y: 4.0 }, None);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// The caster layout's band along the top: `players` names and bars on
+    /// either side, facing each other, with the time left and their
+    /// `scores`, in modes that keep score, between them.
+    fn draw_caster(&self, ctx: &mut Context, players: [&Player; 2], scores: Option<[f32; 2]>, remaining: f32, sudden_death: bool, palette: &Palette) -> GameResult<()> {
+        let mut band = graphics::MeshBuilder::new();
+        band.rectangle(graphics::DrawMode::fill(), Rect::new(0.0, 0.0, SCREEN_SIZE.0, CASTER_BAND_HEIGHT), [0.0, 0.0, 0.0, 0.85].into())?;
+        for (side, player) in players.iter().enumerate() {
+            let x = if side == 0 { 8.0 } else { SCREEN_SIZE.0 - 8.0 - CASTER_BAR_WIDTH };
+            let stats = [
+                (player.hp as f32 / PLAYER_MAX_HP as f32, player.view.shown_hp / PLAYER_MAX_HP as f32, palette.hp, 24.0, 8.0),
+                (player.mp as f32 / PLAYER_MAX_MP as f32, player.view.shown_mp / PLAYER_MAX_MP as f32, palette.mp, 35.0, 5.0),
+            ];
+            for (fill, trailing, color, y, h) in stats.iter() {
+                if side == 0 {
+                    stat_bar(&mut band, Rect::new(x, *y, CASTER_BAR_WIDTH, *h), *fill, *trailing, *color, palette)?;
+                } else {
+                    // Drains towards the middle, like the left one
+                    band.rectangle(graphics::DrawMode::fill(), Rect::new(x, *y, CASTER_BAR_WIDTH, *h), palette.bar_back.into())?;
+                    let (fill, trailing) = (fill.clamp(0.0, 1.0), trailing.max(*fill).min(1.0));
+                    let right = x + CASTER_BAR_WIDTH;
+                    if trailing > fill {
+                        band.rectangle(graphics::DrawMode::fill(), Rect::new(right - CASTER_BAR_WIDTH * trailing, *y, CASTER_BAR_WIDTH * (trailing - fill), *h), palette.bar_drain.into())?;
+                    }
+                    if fill > 0.0 {
+                        band.rectangle(graphics::DrawMode::fill(), Rect::new(right - CASTER_BAR_WIDTH * fill, *y, CASTER_BAR_WIDTH * fill, *h), (*color).into())?;
+                    }
+                }
+            }
+        }
+        let band = band.build(ctx)?;
+        graphics::draw(ctx, &band, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        let text = |text: String, size: f32, color: graphics::Color| graphics::Text::new(graphics::TextFragment {
+            text,
+            color: Some(color),
+            font: Some(graphics::Font::default()),
+            scale: Some(graphics::PxScale { x: size, y: size }),
+        });
+        let white = graphics::Color::new(1.0, 1.0, 1.0, 1.0);
+        for (side, player) in players.iter().enumerate() {
+            let name = text(format!("{}  Lv {}", player.name, player.level), 18.0, white);
+            let x = if side == 0 { 8.0 } else { SCREEN_SIZE.0 - 8.0 - name.width(ctx) };
+            graphics::queue_text(ctx, &name, ggez::mint::Point2 { x, y: 3.0 }, None);
+        }
+        let seconds = remaining.ceil() as u32;
+        let clock_color = if sudden_death || seconds <= 10 { graphics::Color::new(1.0, 0.2, 0.2, 1.0) } else { white };
+        let clock = text(format!("{}:{:02}", seconds / 60, seconds % 60), 24.0, clock_color);
+        graphics::queue_text(ctx, &clock, ggez::mint::Point2 { x: (SCREEN_SIZE.0 - clock.width(ctx)) / 2.0, y: 1.0 }, None);
+        if let Some(scores) = scores {
+            let score = text(format!("{} - {}", scores[0].floor(), scores[1].floor()), 16.0, graphics::Color::new(1.0, 0.85, 0.2, 1.0));
+            graphics::queue_text(ctx, &score, ggez::mint::Point2 { x: (SCREEN_SIZE.0 - score.width(ctx)) / 2.0, y: 27.0 }, None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Pulsing red text just under the top bar.
+    fn draw_warning(&self, ctx: &mut Context, warning: &str, palette: &Palette) -> GameResult<()> {
+        let pulse = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |t| (t.as_millis() % 1000) as f32 / 1000.0);
+        let alpha = 0.5 + (pulse * std::f32::consts::TAU).sin().abs() * 0.5;
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: warning.to_string(),
+                color: Some(graphics::Color::new(1.0, 0.15, 0.1, alpha)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 24.0, y: 24.0 }),
+            });
+        palette::queue_text(ctx, &text, ggez::mint::Point2 {
+            x: SCREEN_SIZE.0 / 2.0 - (warning.chars().count() as f32 * 6.0),
+            y: GRID_CELL_SIZE + 6.0 },
+            palette);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Kill feed down the top right, lines dropping off after a few seconds.
+    fn draw_feed(&self, ctx: &mut Context, feed: &[(String, Instant)]) -> GameResult<()> {
+        let recent = feed.iter().filter(|(_, shown)| shown.elapsed() < Duration::from_secs(FEED_SECONDS));
+        for (index, (line, _)) in recent.enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(graphics::Color::new(1.0, 1.0, 1.0, 0.9)),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 16.0, y: 16.0 }),
+                });
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 {
+                x: SCREEN_SIZE.0 - text.width(ctx) - 8.0,
+                y: GRID_CELL_SIZE + 4.0 + index as f32 * 18.0 },
+                None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Chat down the bottom left, lines dropping off after a while unless the
+    /// chat box is open, with what's being typed under them.
+    fn draw_chat(&self, ctx: &mut Context, chat: &[(String, Instant)], input: Option<&str>) -> GameResult<()> {
+        let mut lines: Vec<(String, f32)> = chat.iter()
+            .filter(|(_, shown)| input.is_some() || shown.elapsed() < Duration::from_secs(CHAT_SECONDS))
+            .map(|(line, _)| (line.clone(), 0.9))
+            .collect();
+        if let Some(input) = input {
+            lines.push((format!("Say: {}_", input), 1.0));
+        }
+        let bottom = SCREEN_SIZE.1 - GRID_CELL_SIZE - 48.0;
+        for (index, (line, alpha)) in lines.iter().rev().enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(graphics::Color::new(1.0, 1.0, 1.0, *alpha)),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 14.0, y: 14.0 }),
+                });
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: 8.0, y: bottom - index as f32 * 16.0 }, None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Short note on a dark strip just above the bottom bar.
+    fn draw_toast(&self, ctx: &mut Context, toast: &str) -> GameResult<()> {
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: toast.to_string(),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 14.0, y: 14.0 }),
+            });
+        let width = text.width(ctx);
+        let y = SCREEN_SIZE.1 - GRID_CELL_SIZE - 26.0;
+        let strip = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            Rect::new(SCREEN_SIZE.0 / 2.0 - width / 2.0 - 6.0, y - 3.0, width + 12.0, 20.0),
+            graphics::Color::new(0.0, 0.0, 0.0, 0.7),
+        )?;
+        graphics::draw(ctx, &strip, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+        graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: SCREEN_SIZE.0 / 2.0 - width / 2.0, y }, None);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    /// Our running combo, popping in big and fading out once it's been up a while.
+    fn draw_combo(&self, ctx: &mut Context, combo: u32, shown: Duration, palette: &Palette) -> GameResult<()> {
+        let age = shown.as_secs_f32() / Duration::from_millis(COMBO_POPUP_MILLIS).as_secs_f32();
+        let size = 26.0 + 14.0 * (1.0 - age * 6.0).max(0.0);
+        let alpha = ((1.0 - age) * 3.0).min(1.0);
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: format!("{} HIT COMBO", combo),
+                color: Some(graphics::Color::new(1.0, 0.8, 0.2, alpha)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: size, y: size }),
+            });
+        let width = text.width(ctx);
+        palette::queue_text(ctx, &text, ggez::mint::Point2 {
+            x: SCREEN_SIZE.0 * 0.75 - width / 2.0,
+            y: SCREEN_SIZE.1 / 3.0 - size / 2.0 },
+            palette);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+
+    fn draw_announcement(&self, ctx: &mut Context, announcement: &str, palette: &Palette) -> GameResult<()> {
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: announcement.to_string(),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 30.0, y: 30.0 }),
+            });
+        palette::queue_text(ctx, &text, ggez::mint::Point2 {
+            x: SCREEN_SIZE.0 / 2.0 - (announcement.chars().count() as f32 * 7.0),
+            y: SCREEN_SIZE.1 / 2.0 - GRID_CELL_SIZE * 3.0 },
+            palette);
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+}
+
+
+#[derive(PartialEq, Debug)]
+pub enum NetActions {
+    Sendposition,
+    Newgame,
+    Listgames,
+    Ready,
+    Getworld,
+    Joingame,
+    Getopponent,
+    GetopponentName,
+    Votemap,
+    Attack,
+    Block,
+    PlaceTrap,
+    Setskin,
+    Gameinfo,
+    Leavegame,
+    Sendinputs,
+    Sendchat,
+    Getchat,
+    Muteplayer,
+    Getmap,
+    Sandbox,
+    Serverinfo,
+    Rejoingame,
+    Importgame,
+    Matchtrace,
+    Admin,
+    Unknown
+}
+
+impl NetActions {
+    fn from_string(action: String) -> NetActions {
+        if action == "sendposition" {
+            NetActions::Sendposition
+        } else if action == "newgame" {
+            NetActions::Newgame
+        } else if action == "listgames" {
+            NetActions::Listgames
+        } else if action == "ready" {
+            NetActions::Ready
+        } else if action == "getworld" {
+            NetActions::Getworld
+        } else if action == "getopponent" {
+            NetActions::Getopponent
+        } else if action == "joingame" {
+            NetActions::Joingame
+        } else if action == "getopponentname" {
+            NetActions::GetopponentName
+        } else if action == "votemap" {
+            NetActions::Votemap
+        } else if action == "attack" {
+            NetActions::Attack
+        } else if action == "block" {
+            NetActions::Block
+        } else if action == "placetrap" {
+            NetActions::PlaceTrap
+        } else if action == "setskin" {
+            NetActions::Setskin
+        } else if action == "gameinfo" {
+            NetActions::Gameinfo
+        } else if action == "leavegame" {
+            NetActions::Leavegame
+        } else if action == "sendinputs" {
+            NetActions::Sendinputs
+        } else if action == "sendchat" {
+            NetActions::Sendchat
+        } else if action == "getchat" {
+            NetActions::Getchat
+        } else if action == "muteplayer" {
+            NetActions::Muteplayer
+        } else if action == "getmap" {
+            NetActions::Getmap
+        } else if action == "sandbox" {
+            NetActions::Sandbox
+        } else if action == "serverinfo" {
+            NetActions::Serverinfo
+        } else if action == "rejoingame" {
+            NetActions::Rejoingame
+        } else if action == "importgame" {
+            NetActions::Importgame
+        } else if action == "matchtrace" {
+            NetActions::Matchtrace
+        } else if action == "admin" {
+            NetActions::Admin
+        } else {
+            NetActions::Unknown
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn from_usize(action: usize) -> NetActions {
+        if action == 1 {
+            NetActions::Sendposition
+        } else if action == 2 {
+            NetActions::Newgame
+        } else if action == 3 {
+            NetActions::Listgames
+        } else if action == 4 {
+            NetActions::Ready
+        } else if action == 5 {
+            NetActions::Getworld
+        } else if action == 6 {
+            NetActions::Getopponent
+        } else if action == 7 {
+            NetActions::Joingame
+        } else if action == 8 {
+            NetActions::GetopponentName
+        } else if action == 9 {
+            NetActions::Votemap
+        } else if action == 10 {
+            NetActions::Attack
+        } else if action == 11 {
+            NetActions::Block
+        } else if action == 12 {
+            NetActions::PlaceTrap
+        } else if action == 13 {
+            NetActions::Setskin
+        } else if action == 14 {
+            NetActions::Gameinfo
+        } else if action == 15 {
+            NetActions::Leavegame
+        } else if action == 16 {
+            NetActions::Sendinputs
+        } else if action == 17 {
+            NetActions::Sendchat
+        } else if action == 18 {
+            NetActions::Getchat
+        } else if action == 19 {
+            NetActions::Muteplayer
+        } else if action == 20 {
+            NetActions::Getmap
+        } else if action == 21 {
+            NetActions::Sandbox
+        } else if action == 22 {
+            NetActions::Serverinfo
+        } else if action == 23 {
+            NetActions::Rejoingame
+        } else if action == 24 {
+            NetActions::Importgame
+        } else if action == 25 {
+            NetActions::Matchtrace
+        } else if action == 26 {
+            NetActions::Admin
+        } else {
+            NetActions::Unknown
+        }
+    }
+}
+
+impl From<NetActions> for usize {
+    fn from(action: NetActions) -> usize {
+        if action == NetActions::Sendposition {
+            1
+        } else if action == NetActions::Newgame {
+            2
+        } else if action == NetActions::Listgames {
+            3
+        } else if action == NetActions::Ready {
+            4
+        } else if action == NetActions::Getworld {
+            5
+        } else if action == NetActions::Getopponent {
+            6
+        } else if action == NetActions::Joingame {
+            7
+        } else if action == NetActions::GetopponentName {
+            8
+        } else if action == NetActions::Votemap {
+            9
+        } else if action == NetActions::Attack {
+            10
+        } else if action == NetActions::Block {
+            11
+        } else if action == NetActions::PlaceTrap {
+            12
+        } else if action == NetActions::Setskin {
+            13
+        } else if action == NetActions::Gameinfo {
+            14
+        } else if action == NetActions::Leavegame {
+            15
+        } else if action == NetActions::Sendinputs {
+            16
+        } else if action == NetActions::Sendchat {
+            17
+        } else if action == NetActions::Getchat {
+            18
+        } else if action == NetActions::Muteplayer {
+            19
+        } else if action == NetActions::Getmap {
+            20
+        } else if action == NetActions::Sandbox {
+            21
+        } else if action == NetActions::Serverinfo {
+            22
+        } else if action == NetActions::Rejoingame {
+            23
+        } else if action == NetActions::Importgame {
+            24
+        } else if action == NetActions::Matchtrace {
+            25
+        } else if action == NetActions::Admin {
+            26
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkedGame {
+    players: Vec<PlayerState>,
+    session_id: String,
+    map: String,
+    map_candidates: Vec<String>,
+    /// Player number to the map they voted for.
+    map_votes: HashMap<u32, String>,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    vote_started: Option<Instant>,
+    crates: Vec<Crate>,
+    /// Potions lying around waiting to be picked up
+    potions: Vec<Potion>,
+    /// Gear lying around waiting to be picked up
+    equipment: Vec<EquipmentDrop>,
+    traps: Vec<Trap>,
+    item_spawn: Option<ItemSpawn>,
+    /// Seconds since the last item spawn
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    item_spawn_timer: f32,
+    item_lifetime: f32,
+    #[serde(default)]
+    mode: GameMode,
+    #[serde(default)]
+    rules: MatchRules,
+    /// Points in modes that keep score, by `PlayerState::score_key`
+    scores: HashMap<String, f32>,
+    /// King of the Hill capture zone
+    zone: Option<Position>,
+    zone_index: usize,
+    /// Capture the Flag flags, one per team
+    flags: Vec<Flag>,
+    /// Seconds the zone has been in its current spot
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    zone_timer: f32,
+    winner: Option<String>,
+    /// Seconds since the game started
+    clock: f32,
+    /// Seconds into sudden death, once time ran out with everyone standing
+    sudden_death: Option<f32>,
+    /// The last few kills, pickups and announcements, oldest first
+    #[serde(default)]
+    events: Vec<FeedEvent>,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    next_event_id: u32,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    first_blood: bool,
+    started: bool,
+    completed: bool,
+    /// The server is shutting down, ending the game without a winner
+    #[serde(default)]
+    server_closing: bool,
+    /// Who starts a server for the game if this one goes away, and where,
+    /// only given out while the game is being played
+    #[serde(default)]
+    successor: Option<migration::Successor>,
+    /// Every frame of input each player has sent with the `rollback` rule, by
+    /// number, passed on to the opponent
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    inputs: HashMap<u32, Vec<rollback::FrameInput>>,
+    /// Each rollback player's latest checksum of themselves, as `(frame,
+    /// checksum)`, passed on for the opponent to check their simulation by
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    checksums: HashMap<u32, (u32, u64)>,
+    /// The last few chat messages, oldest first. Asked for on their own, as
+    /// not everyone gets to see all of them
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    chat: Vec<ChatMessage>,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    next_chat_id: u32,
+    /// Names the host has muted, whose chat goes nowhere
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    muted: Vec<String>,
+    /// Offline practice where items can be spawned at will
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    sandbox: bool,
+    /// Where everyone has been, asked for on its own with `matchtrace`
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    trace: trace::Trace,
+    #[serde(skip_serializing, skip_deserializing)]
+    #[cfg(feature = "server")]
+    collisions: sim::Collisions,
+}
+
+/// Something that happened in the game, for the kill feed or announced in
+/// the middle of the screen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FeedEvent {
+    /// Counts up from 1 so clients can tell which ones they've seen
+    id: u32,
+    text: String,
+    announce: bool,
+}
+
+/// Something a player said. Spectators talk among themselves unless the
+/// rules have all chat on.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatMessage {
+    /// Counts up from 1 so clients can ask for the ones after what they've seen
+    id: u32,
+    from: String,
+    text: String,
+    spectator: bool,
+}
+
+impl NetworkedGame {
+
+    pub fn new(game_id: String, map_candidates: Vec<String>, mode: GameMode, rules: MatchRules) -> NetworkedGame {
+        //let my_uuid = Uuid::new_v4().to_string();
+
+        NetworkedGame {
+            players: vec![],
+            session_id: game_id,
+            map: map_candidates.first().cloned().unwrap_or_else(|| map::DEFAULT_MAP.to_string()),
+            map_candidates,
+            map_votes: HashMap::new(),
+            #[cfg(feature = "server")]
+            vote_started: None,
+            crates: vec![],
+            potions: vec![],
+            equipment: vec![],
+            traps: vec![],
+            item_spawn: None,
+            #[cfg(feature = "server")]
+            item_spawn_timer: 0.0,
+            item_lifetime: ITEM_LIFETIME_SECONDS,
+            mode,
+            rules,
+            scores: HashMap::new(),
+            zone: None,
+            zone_index: 0,
+            flags: vec![],
+            #[cfg(feature = "server")]
+            zone_timer: 0.0,
+            winner: None,
+            clock: 0.0,
+            sudden_death: None,
+            events: vec![],
+            #[cfg(feature = "server")]
+            next_event_id: 1,
+            #[cfg(feature = "server")]
+            first_blood: false,
+            started: false,
+            completed: false,
+            server_closing: false,
+            successor: None,
+            #[cfg(feature = "server")]
+            inputs: HashMap::new(),
+            #[cfg(feature = "server")]
+            checksums: HashMap::new(),
+            #[cfg(feature = "server")]
+            chat: vec![],
+            #[cfg(feature = "server")]
+            next_chat_id: 1,
+            #[cfg(feature = "server")]
+            muted: vec![],
+            #[cfg(feature = "server")]
+            sandbox: false,
+            #[cfg(feature = "server")]
+            trace: trace::Trace::default(),
+            #[cfg(feature = "server")]
+            collisions: sim::Collisions::default(),
+        }
+    }
+
+    /// Tells everyone about something, in the kill feed or announced if `announce`.
+    #[cfg(feature = "server")]
+    fn log_event(&mut self, text: String, announce: bool) {
+        self.events.push(FeedEvent { id: self.next_event_id, text, announce });
+        self.next_event_id += 1;
+        if self.events.len() > EVENTS_KEPT {
+            self.events.remove(0);
+        }
+    }
+
+    /// Players who are down or whose game is over watch rather than play.
+    #[cfg(feature = "server")]
+    fn spectating(&self, player_id: &str) -> bool {
+        self.completed || self.players.iter().find(|p| p.id == player_id).is_none_or(|p| p.hp <= 0)
+    }
+
+    /// Adds what player `player_id` said to the chat.
+    #[cfg(feature = "server")]
+    fn say(&mut self, player_id: &str, text: &str) {
+        let from = match self.players.iter().find(|p| p.id == player_id) {
+            Some(player) if !self.muted.contains(&player.name) => player.name.clone(),
+            _ => return,
+        };
+        let text: String = text.trim().chars().take(CHAT_MAX_CHARS).collect();
+        if text.is_empty() {
+            return
+        }
+        let spectator = self.spectating(player_id);
+        self.chat.push(ChatMessage { id: self.next_chat_id, from, text, spectator });
+        self.next_chat_id += 1;
+        if self.chat.len() > CHAT_KEPT {
+            self.chat.remove(0);
+        }
+    }
+
+    /// The first player in who isn't a bot, who gets to mute the others.
+    #[cfg(feature = "server")]
+    fn host(&self) -> Option<&PlayerState> {
+        self.players.iter().find(|p| !p.bot)
+    }
+
+    /// Mutes `name` for the whole game if player `player_id` is the host, or
+    /// unmutes them if they were. Returns whether they're muted now.
+    #[cfg(feature = "server")]
+    fn toggle_mute(&mut self, player_id: &str, name: &str) -> std::result::Result<bool, String> {
+        if self.host().is_none_or(|host| host.id != player_id) {
+            return Err("Only the host can mute players".to_string())
+        }
+        let name = match self.players.iter().find(|p| p.name.eq_ignore_ascii_case(name)) {
+            Some(player) if player.id != player_id => player.name.clone(),
+            Some(_) => return Err("The host can't mute themselves".to_string()),
+            None => return Err(format!("No player {}", name)),
+        };
+        if self.muted.contains(&name) {
+            self.muted.retain(|muted| *muted != name);
+            Ok(false)
+        } else {
+            self.muted.push(name);
+            Ok(true)
+        }
+    }
+
+    /// Chat after `seen` that player `player_id` gets to read.
+    #[cfg(feature = "server")]
+    fn chat_for(&self, player_id: &str, seen: u32) -> Vec<&ChatMessage> {
+        let hears_spectators = self.rules.all_chat || self.spectating(player_id);
+        self.chat.iter().filter(|m| m.id > seen && (hears_spectators || !m.spectator)).collect()
+    }
+
+    /// Puts a kill in the feed, announcing it if it's the first of the game.
+    #[cfg(feature = "server")]
+    fn log_kill(&mut self, text: String) {
+        if !self.first_blood {
+            self.first_blood = true;
+            self.log_event("First blood!".to_string(), true);
+        }
+        self.log_event(text, false);
+    }
+
+    /// True once the game is full and the players are picking a map.
+    #[cfg(feature = "server")]
+    fn voting(&self) -> bool {
+        self.vote_started.is_some() && !self.started
+    }
+
+    #[cfg(feature = "server")]
+    fn vote_finished(&self) -> bool {
+        match self.vote_started {
+            Some(vote_started) => {
+                // Bots go along with whatever the players pick
+                self.map_votes.len() >= self.players.iter().filter(|p| !p.bot).count()
+                    || clock::since(vote_started) >= Duration::from_secs(MAP_VOTE_SECONDS)
+            },
+            None => false,
+        }
+    }
+
+    /// Who a `scores` key stands for, the team or the player by name.
+    #[cfg(feature = "server")]
+    fn scorer_name(&self, key: &str) -> String {
+        match self.players.iter().find(|p| p.team.is_none() && p.number.to_string() == key) {
+            Some(player) => player.name.clone(),
+            None => key.to_string(),
+        }
+    }
+
+    /// The player `id` is up against, someone on the other team in team modes.
+    #[cfg(feature = "server")]
+    fn opponent_of(&self, id: &str) -> Option<&PlayerState> {
+        let team = self.players.iter().find(|p| p.id == id).and_then(|p| p.team);
+        self.players.iter()
+            .find(|p| p.id != id && (team.is_none() || p.team != team))
+            .or_else(|| self.players.iter().find(|p| p.id != id))
+    }
+
+    #[cfg(feature = "native")]
+    fn max_players(&self) -> usize {
+        match self.rules.max_players {
+            Some(max_players) => max_players,
+            None if self.mode == GameMode::CaptureTheFlag => ctf::CTF_PLAYERS,
+            None => config::get().max_players,
+        }
+    }
+
+    #[cfg(feature = "native")]
+    fn score_limit(&self) -> Option<f32> {
+        self.rules.score_limit.or_else(|| self.mode.score_limit())
+    }
+
+    /// The part of the map still safe `sudden_death` seconds into sudden
+    /// death, shrinking towards the middle.
+    #[cfg(feature = "native")]
+    fn safe_area(sudden_death: Option<f32>, map: &Map) -> Option<Position> {
+        let sudden_death = sudden_death?;
+        let shrink = sudden_death * SUDDEN_DEATH_SHRINK_PER_SECOND;
+        let w = (map.pixel_width() - shrink * 2.0).max(SUDDEN_DEATH_MIN_SIZE);
+        let h = (map.pixel_height() - shrink * 2.0).max(SUDDEN_DEATH_MIN_SIZE);
+        Some(Position {
+            x: (map.pixel_width() - w) / 2.0,
+            y: (map.pixel_height() - h) / 2.0,
+            w,
+            h,
+        })
+    }
+
+    /// Ends the game, `None` being a draw.
+    #[cfg(feature = "server")]
+    fn finish(&mut self, winner: Option<String>) {
+        match &winner {
+            Some(name) => println!("Game {} won by {}", self.session_id, name),
+            None => println!("Game {} ended in a draw", self.session_id),
+        }
+        self.winner = winner;
+        self.completed = true;
+        // The killing blow lands between ticks
+        self.trace.record(0.0, self.clock, &self.players);
+    }
+
+    /// Ends the game in favor of whoever player `id` was up against.
+    /// Takes player `id` out, freeing their spot before the game starts
+    /// and forfeiting it for them after.
+    #[cfg(feature = "server")]
+    fn leave(&mut self, id: &str) {
+        if !self.started {
+            if let Some(index) = self.players.iter().position(|p| p.id == id) {
+                let left = self.players.remove(index);
+                self.map_votes.remove(&left.number);
+                println!("{} left game {}", left.name, self.session_id);
+            }
+        } else if !self.completed {
+            self.forfeit(id);
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn forfeit(&mut self, id: &str) {
+        let forfeiting = match self.players.iter().find(|p| p.id == id) {
+            Some(player) => player,
+            None => return,
+        };
+        println!("{} forfeits game {}", forfeiting.name, self.session_id);
+        let winner = match forfeiting.team {
+            Some(team) => Some(ctf::TEAM_NAMES[(team + 1) % ctf::TEAMS].to_string()),
+            None => self.opponent_of(id).map(|p| p.name.clone()),
+        };
+        self.finish(winner);
+    }
+
+    /// Carries out a sandbox command of player `id`, from a hotkey or the
+    /// console. Gives back what was done.
+    ///
+    /// ```text
+    /// potion [TYPE] [X Y]   a potion, random unless named, in front of them
+    ///                       unless put somewhere
+    /// crate [X Y]           the same with a crate
+    /// god                   infinite HP and MP on or off
+    /// hp N, mp N            their HP or MP
+    /// tp X Y                moves them
+    /// ```
+    #[cfg(feature = "server")]
+    fn sandbox_command(&mut self, id: &str, command: &str, rng: &mut impl Rng) -> std::result::Result<String, String> {
+        if !self.sandbox {
+            return Err("Only in the sandbox".to_string())
+        }
+        let player = self.players.iter_mut().find(|p| p.id == id).ok_or_else(|| "Not in this game".to_string())?;
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let number = |word: Option<&&str>| -> std::result::Result<f32, String> {
+            let word = word.ok_or_else(|| format!("{} needs more numbers", command))?;
+            word.parse::<f32>().map_err(|_| format!("{} isn't a number", word))
+        };
+        let front = player.attack_reach();
+        // Centered in front of them, or with its top left at the X Y given
+        let place = |numbers: &[&str], w: f32, h: f32| -> std::result::Result<Position, String> {
+            if numbers.is_empty() {
+                return Ok(Position { x: front.x + (front.w - w) / 2.0, y: front.y + (front.h - h) / 2.0, w, h })
+            }
+            Ok(Position { x: number(numbers.first())?, y: number(numbers.get(1))?, w, h })
+        };
+        match words.first().copied().unwrap_or("") {
+            "potion" => {
+                let named = words.get(1).and_then(|name| {
+                    POTION_SPAWN_WEIGHTS.iter().map(|(potion_type, _)| potion_type.clone()).find(|t| t.name().eq_ignore_ascii_case(name))
+                });
+                let numbers = if named.is_some() { &words[2..] } else { &words[1..] };
+                let pos = place(numbers, POTION_WIDTH, POTION_HEIGHT)?;
+                let potion = Potion::dropped(pos, named.unwrap_or_else(|| PotionType::random(rng)));
+                let text = format!("Spawned a {} potion", potion.potion_type.name());
+                self.potions.push(potion);
+                Ok(text)
+            },
+            "crate" => {
+                self.crates.push(Crate::new(place(&words[1..], CRATE_WIDTH, CRATE_HEIGHT)?));
+                Ok("Spawned a crate".to_string())
+            },
+            "god" => {
+                player.god_mode = !player.god_mode;
+                Ok(format!("Infinite HP and MP {}", if player.god_mode { "on" } else { "off" }))
+            },
+            "hp" => {
+                player.hp = (number(words.get(1))? as i64).clamp(0, PLAYER_MAX_HP);
+                Ok(format!("HP set to {}", player.hp))
+            },
+            "mp" => {
+                player.mp = (number(words.get(1))? as i64).clamp(0, PLAYER_MAX_MP);
+                Ok(format!("MP set to {}", player.mp))
+            },
+            "tp" => {
+                player.body.x = number(words.get(1))?;
+                player.body.y = number(words.get(2))?;
+                Ok(format!("Moved to {} {}", player.body.x, player.body.y))
+            },
+            _ => Err(format!("Unknown sandbox command {}", command)),
+        }
+    }
+
+    /// Puts loot on the ground for `item_lifetime` seconds.
+    #[cfg(feature = "server")]
+    fn drop_loot(&mut self, loot: Loot) {
+        match loot {
+            Loot::Potion(mut potion) => {
+                potion.lifetime = Some(self.item_lifetime);
+                self.potions.push(potion);
+            },
+            Loot::Equipment(mut drop) => {
+                drop.lifetime = Some(self.item_lifetime);
+                self.equipment.push(drop);
+            },
+        }
+    }
+
+    /// Carries out what the game's scripts asked for.
+    #[cfg(feature = "server")]
+    fn apply_script_actions(&mut self, actions: Vec<scripting::Action>) {
+        for action in actions {
+            match action {
+                scripting::Action::Heal(name, amount) => {
+                    if let Some(player) = self.players.iter_mut().find(|p| p.name == name && p.hp > 0) {
+                        player.hp = (player.hp + amount.max(0)).min(PLAYER_MAX_HP);
+                    }
+                },
+                scripting::Action::Hurt(name, amount) => {
+                    let slain = match self.players.iter_mut().find(|p| p.name == name && p.hp > 0) {
+                        Some(player) => {
+                            player.hp = (player.hp - amount.max(0)).max(0);
+                            player.hp == 0
+                        },
+                        None => false,
+                    };
+                    if slain {
+                        self.log_kill(format!("{} was slain", name));
+                    }
+                },
+                scripting::Action::GiveXp(name, amount) => {
+                    if let Some(player) = self.players.iter_mut().find(|p| p.name == name) {
+                        player.gain_xp(amount);
+                    }
+                },
+                scripting::Action::AddScore(name, points) => {
+                    // Scripts go by name, anything else being a team's
+                    let key = self.players.iter().find(|p| p.name == name).map_or(name, |p| p.score_key());
+                    *self.scores.entry(key).or_insert(0.0) += points;
+                },
+                scripting::Action::Announce(text) => self.log_event(text, true),
+            }
+        }
+    }
+
+    /// Candidate with the most votes, ties going to the one offered first.
+    #[cfg(feature = "server")]
+    fn tally_map_votes(&self) -> String {
+        let mut winner = self.map.clone();
+        let mut winner_votes = 0;
+        for candidate in self.map_candidates.iter() {
+            let votes = self.map_votes.values().filter(|vote| *vote == candidate).count();
+            if votes > winner_votes {
+                winner = candidate.clone();
+                winner_votes = votes;
+            }
+        }
+        winner
+    }
+}
+
+/// Runs whatever the command line asks for. `dedicated` is for the
+/// dedicated server, which only hosts, sends commands and lists games, as
+/// does anything built without the client.
+pub fn run(dedicated: bool) -> std::result::Result<(), String> {
+    let server_only = dedicated || cfg!(not(feature = "client"));
+
+    let matches = App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg("-h --host=[HOSTNAME:PORT] 'Set as server and assign hostname:port'")
+        .arg("-l --list=[HOSTNAME:PORT]... 'List all games on each server, nearest first'")
+        .arg("--listen 'Play on the server given with --host, in a new game made for you'")
+        .arg("--mode=[MODE] 'Mode: duel, koth, ctf or race. With --list, only games of it, with --listen, the game made'")
+        .arg("--map=[MAP] 'With --list, only games on or voting on this map'")
+        .arg("--open 'With --list, only games with room left'")
+        .arg("--page=[PAGE] 'With --list, which page of games to show, from 1'")
+        .arg("-p --player=[NAME] 'Player Name'")
+        .arg("-s --server=[HOSTNAME:PORT]... 'Host to connect to, the nearest if given more than one'")
+        .arg("-g --game=[GAMEID] 'GameID to join, else the first open one or a new one'")
+        .arg("--relay=[HOSTNAME:PORT] 'Standby server to move the game to if ours goes away mid-game'")
+        .arg("--practice 'Play against a bot, no server needed'")
+        .arg("--sandbox 'Practice with F8 spawning potions, F9 crates, F4 infinite HP/MP and /set in chat changing movement'")
+        .arg("--assets=[DIR] 'Directory to load textures from, instead of looking for one'")
+        .arg("--pack=[PATH] 'Asset pack, a directory or zip of textures, sounds and stats, to use over the defaults'")
+        .arg("--skin=[SHEET] 'Hero sprite sheet, eg. hero or hero_knight'")
+        .arg("--color=[COLOR] 'Hero color: plain, red, blue, green, gold or violet'")
+        .arg("--profile 'Collect a puffin profile into profile.puffin'")
+        .arg("--no-name-filter 'Host without turning away names with profanity in them'")
+        .arg("--set=[OVERRIDE]... 'Override a config.toml value, eg. --set top_accel=6.5'")
+        .arg("--daemon 'Host without the console, logging to a file, eg. under systemd, taking admin commands with --admin-key'")
+        .arg("--log=[FILE] 'File --daemon logs to, server.log by default'")
+        .arg("--send=[COMMAND] 'Send a console command to --server, eg. newgamekoth or gameinfo'")
+        .arg("--admin-key=[KEY] 'Send --send as an admin command, eg. ban, unban, bans, setmotd or setrules, with the admin_key of --server'")
+        .arg("--matchtrace 'Print where everyone went in --game on --server, as JSON'")
+        .arg("--websocket=[HOSTNAME:PORT] 'Also take requests over WebSocket, for browsers or where UDP is blocked'")
+        .arg("--observe=[HOSTNAME:PORT] 'Also serve live games read-only as JSON over HTTP, eg. for stream overlays'")
+        .arg("--record=[FILE] 'Host keeping every request, the answers to it and each game tick in FILE'")
+        .arg("--replay=[FILE] 'Play a session saved with --record through a fresh server, showing the answers that differ'")
+        .get_matches();
+
+    if let Some(path) = matches.value_of("pack") {
+        match pack::Pack::open(path::Path::new(path)) {
+            Ok(pack) => {
+                println!("Using asset pack {}", pack.manifest.name.as_deref().unwrap_or(path));
+                pack::init(pack);
+            },
+            Err(e) => return Err(format!("Invalid asset pack: {}", e)),
+        }
+    }
+    match config::Config::load(matches.values_of("set").into_iter().flatten()) {
+        Ok(config) => config::init(config),
+        Err(e) => return Err(format!("Invalid config: {}", e)),
+    }
+
+    // if hosting
+    let profile = matches.is_present("profile");
+    // Playing on our own server is a client that happens to host, below
+    if let Some(server) = matches.value_of("host").filter(|_| !matches.is_present("listen")) {
+        serve(&matches, server, profile)
+    } else if let Some(session) = matches.value_of("replay") {
+        replay(session)
+    } else if let Some(command) = matches.value_of("send") {
+        let host = matches.value_of("server").map(|s| s.to_string())
+            .unwrap_or_else(|| format!("localhost:{}", config::get().port));
+        let game_id = matches.value_of("game").unwrap_or("").to_string();
+        let player = matches.value_of("player").unwrap_or("").to_string();
+        let answer = match matches.value_of("admin-key") {
+            Some(key) => net::send_admin(host.clone(), key.to_string(), command),
+            None => net::send_command(host.clone(), game_id, player, &command.to_ascii_lowercase()),
+        };
+        match answer {
+            Some(result) => println!("{}", result),
+            None => println!("No answer from {}", host),
+        }
+        Ok(())
+    } else if matches.is_present("matchtrace") {
+        let host = matches.value_of("server").map(|s| s.to_string())
+            .unwrap_or_else(|| format!("localhost:{}", config::get().port));
+        let game_id = matches.value_of("game").ok_or("--matchtrace needs the --game to trace")?;
+        println!("{}", net::match_trace(host, game_id.to_string())?);
+        Ok(())
+    } else if let Some(list) = matches.values_of("list") {
+       let hosts: Vec<String> = list.map(|host| host.to_string()).collect();
+       let filter = GameFilter {
+           mode: matches.value_of("mode").map(|mode| mode.to_string()),
+           map: matches.value_of("map").map(|map| map.to_string()),
+           open: matches.is_present("open"),
+           page: matches.value_of("page").and_then(|page| page.parse::<usize>().ok()).unwrap_or(1).max(1) - 1,
+       };
+       for listing in servers::survey(&hosts, &filter) {
+           println!("{}", listing.describe());
+       }
+       Ok(())
+    } else if server_only {
+        Err("The server only hosts, start it with --host, --send or --list".to_string())
+    } else {
+        play(&matches, profile)
+    }
+}
+
+/// Hosts on `server`, taking commands typed into the console unless it's a
+/// daemon.
+#[cfg(feature = "server")]
+fn serve(matches: &clap::ArgMatches, server: &str, profile: bool) -> std::result::Result<(), String> {
+    let safe_server = server.to_string();
+    let filter_names = !matches.is_present("no-name-filter");
+    shutdown::catch_signals();
+    let daemon = matches.is_present("daemon");
+    if daemon {
+        let log = matches.value_of("log").map(path::PathBuf::from).unwrap_or_else(logging::log_path);
+        if let Err(e) = logging::log_to(&log) {
+            println!("Unable to log to {}: {}", log.display(), e);
+        }
+    }
+    let websocket_requests = matches.value_of("websocket").and_then(|address| {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        match websocket::listen(address, sender) {
+            Ok(()) => {
+                println!("Taking WebSocket requests on {}", address);
+                Some(receiver)
+            },
+            Err(e) => {
+                println!("Unable to listen for WebSockets on {}: {}", address, e);
+                None
+            },
+        }
+    });
+    let observer_requests = matches.value_of("observe").and_then(|address| {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        match observer::listen(address, sender) {
+            Ok(()) => {
+                println!("Serving live games over HTTP on {}", address);
+                Some(receiver)
+            },
+            Err(e) => {
+                println!("Unable to serve live games on {}: {}", address, e);
+                None
+            },
+        }
+    });
+    let recorder = match matches.value_of("record") {
+        Some(session) => Some(session::Recorder::create(path::Path::new(session))?),
+        None => None,
+    };
+    let run_server = move || {
+        let mut gameserver = GameServer::new(safe_server);
+        if let Some(recorder) = recorder {
+            gameserver.record(recorder);
+        }
+        gameserver.profiler = Profiler::start(profile);
+        gameserver.filter_names = filter_names;
+        gameserver.websocket_requests = websocket_requests;
+        gameserver.observer_requests = observer_requests;
+        gameserver.host()
+    };
+    // Without a console, admin commands come in with --send and the admin key
+    if daemon {
+        println!("Started Item Wars Server on {} as a daemon", server);
+        return run_server()
+    }
+    // The console waits on stdin, so it gets a thread of its own and goes
+    // when the server stops
+    let console_server = server.to_string();
+    std::thread::spawn(move || console(console_server));
+    println!("Started Item Wars Server on {}", server);
+    run_server()
+}
+
+/// Takes commands typed at a server's console, admin commands or requests
+/// sent to `server` as if from a player.
+#[cfg(feature = "server")]
+fn console(server: String) {
+    let mut player = "".to_string();
+    let mut game_id = "".to_string();
+    loop {
+        let mut server_input = "".to_string();
+        println!("\nITEM WARS ENTER COMMAND :> ");
+        let _ = io::stdin().read_line(&mut server_input);
+        if let Some(answer) = admin::run(&server_input) {
+            println!("{}", answer);
+            continue
+        }
+        server_input.retain(|c| !c.is_whitespace());
+
+        let command = server_input.to_ascii_lowercase().to_string();
+        let cloned_command = command.clone();
+        if command.len() >= 7 && command[0..7].to_string() == "setgame" {
+            game_id = command[7..].to_string();
+            println!("Game ID set to {}", game_id);
+        } else if command.len() >= 9 && command[0..9].to_string() == "setplayer" {
+            // A name to join with, or the ID of someone who already has
+            player = command[9..].to_string();
+            println!("Player set to {}", player);
+        } else if command == "exit" {
+            shutdown::request();
+            return
+        } else {
+            let result = match net::send_command(server.to_string(), game_id.clone(), player.to_string(), &command) {
+                Some(r) => r,
+                None => {
+                    println!("Command not found!");
+                    continue
+                }
+            };
+            println!("RESULT: {}", result);
+            if cloned_command.starts_with("newgame") {
+                game_id = result;
+                println!("Game ID set to {}", game_id);
+            } else if cloned_command == "joingame" {
+                let joined: serde_json::Value = serde_json::from_str(&result).unwrap_or_default();
+                if let Some(id) = joined["player_id"].as_str() {
+                    player = id.to_string();
+                    println!("Player ID set to {}", player);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "server"))]
+fn serve(_matches: &clap::ArgMatches, _server: &str, _profile: bool) -> std::result::Result<(), String> {
+    Err("Built without the server, so unable to host".to_string())
+}
+
+/// Replays the session recorded in `session`, failing if any request was
+/// answered differently.
+#[cfg(feature = "server")]
+fn replay(session: &str) -> std::result::Result<(), String> {
+    let entries = session::load(path::Path::new(session))?;
+    let requests = entries.iter().filter(|e| matches!(e, session::Entry::Request { .. })).count();
+    let mismatches = GameServer::replay(&entries);
+    for mismatch in mismatches.iter() {
+        println!("{}", mismatch.describe());
+    }
+    if mismatches.is_empty() {
+        println!("All {} requests answered as recorded", requests);
+        Ok(())
+    } else {
+        Err(format!("{} of {} requests answered differently", mismatches.len(), requests))
+    }
+}
+
+#[cfg(not(feature = "server"))]
+fn replay(_session: &str) -> std::result::Result<(), String> {
+    Err("Built without the server, so unable to replay".to_string())
+}
+
+/// The server and game when playing on a server of our own, with
+/// `--practice`, `--sandbox` or `--listen`.
+#[cfg(all(feature = "client", feature = "server"))]
+fn local_game(matches: &clap::ArgMatches, sandbox: bool) -> std::result::Result<Option<(String, String)>, String> {
+    if matches.is_present("practice") || sandbox {
+        Ok(Some(GameServer::practice(sandbox)))
+    } else if let (true, Some(server)) = (matches.is_present("listen"), matches.value_of("host")) {
+        let mode = match matches.value_of("mode") {
+            Some(name) => GameMode::from_name(name).ok_or_else(|| format!("Unknown mode {}", name))?,
+            None => GameMode::default(),
+        };
+        shutdown::catch_signals();
+        let (host, game_id) = GameServer::listen(server.to_string(), mode, !matches.is_present("no-name-filter"))?;
+        println!("Hosting game {} on {}, waiting for players", game_id, server);
+        Ok(Some((host, game_id)))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(all(feature = "client", not(feature = "server")))]
+fn local_game(matches: &clap::ArgMatches, sandbox: bool) -> std::result::Result<Option<(String, String)>, String> {
+    if matches.is_present("practice") || sandbox || matches.is_present("listen") {
+        return Err("Built without the server, so only able to play on someone else's".to_string())
+    }
+    Ok(None)
+}
+
+/// Finds or makes a game and opens the window to play it in.
+#[cfg(feature = "client")]
+fn play(matches: &clap::ArgMatches, profile: bool) -> std::result::Result<(), String> {
+    let player_name = matches.clone().value_of("player").unwrap_or("Player").to_string();
+    // The server has the final say, this just saves the trip
+    names::check(&player_name, None, false).map_err(|e| format!("Invalid player name: {}", e))?;
+    let mut skin = Skin::default();
+    if let Some(sheet) = matches.value_of("skin") {
+        skin.sheet = sheet.to_string();
+    }
+    if let Some(color) = matches.value_of("color") {
+        skin.color = skin::SKIN_COLORS.iter()
+            .position(|(name, _)| *name == color)
+            .ok_or_else(|| format!("Unknown color {}", color))?;
+    }
+    let sandbox = matches.is_present("sandbox");
+    let (host, game_id) = if let Some(local) = local_game(matches, sandbox)? {
+        local
+    } else {
+        let hosts: Vec<String> = matches.values_of("server").into_iter().flatten().map(|s| s.to_string()).collect();
+        let host = match hosts.len() {
+            0 => format!("localhost:{}", config::get().port),
+            1 => hosts[0].clone(),
+            _ => match servers::nearest(&hosts) {
+                Some(host) => host,
+                None => return Err(format!("None of {} answered", hosts.join(", "))),
+            },
+        };
+        let game_id = match matches.clone().value_of("game") {
+            Some(g ) => g.to_string(),
+            None => GameState::find_or_create_game(host.clone())
+                .map_err(|e| format!("Unable to find or create a game on {}: {}", host, e))?,
+        };
+        (host, game_id)
+    };
+    let check_world_game = GameState::get_world_state(host.clone(), player_name.clone(), game_id.clone())
+        .ok_or_else(|| format!("Unable to get game {} from {}", game_id, host))?;
+    if !check_world_game.started && check_world_game.players.iter().any(|player| player.name == player_name) {
+        return Err(format!("Game {} already has a player named {}", game_id, player_name))
+    }
+
+    if let Some(dir) = matches.value_of("assets") {
+        assets::set_textures_dir(path::PathBuf::from(dir));
+    }
+    let resource_dir = assets::textures_dir();
+
+    let settings = Settings::load();
+    let (window_width, window_height) = settings.window_size();
+    let fullscreen = if settings.fullscreen { FullscreenType::Desktop } else { FullscreenType::Windowed };
+    let (mut ctx, events_loop) = ggez::ContextBuilder::new("iterm wars", "Mitt Miles")
+        .window_setup(ggez::conf::WindowSetup::default().title("Item Wars!").vsync(settings.vsync))
+        .window_mode(ggez::conf::WindowMode::default()
+            .dimensions(window_width, window_height)
+            .fullscreen_type(fullscreen)
+            .resizable(true))
+        .add_resource_path(resource_dir)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let (drawable_width, drawable_height) = graphics::drawable_size(&ctx);
+    graphics::set_screen_coordinates(&mut ctx, camera::letterbox(SCREEN_SIZE.0, SCREEN_SIZE.1, drawable_width, drawable_height))
+        .map_err(|e| e.to_string())?;
+
+    // Load our textures, behind a loading screen
+    let assets = match Assets::load(&mut ctx) {
+        Ok(assets) => assets,
+        Err(e) => {
+            println!("{}", e);
+            return Err(e.to_string())
+        },
+    };
+
+    // Next we create a new instance of our GameState struct, which implements EventHandler
+    let mut state = GameState::new(player_name, host, game_id, skin, settings, assets)?;
+    state.profiler = Profiler::start(profile);
+    state.sandbox = sandbox;
+    state.relay = matches.value_of("relay").map(|relay| relay.to_string());
+    // And finally we actually run our game, passing in our context and state.
+    event::run(ctx, events_loop, state)
+}
+
+#[cfg(not(feature = "client"))]
+fn play(_matches: &clap::ArgMatches, _profile: bool) -> std::result::Result<(), String> {
+    unreachable!("Built without the client, so only ever the server")
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use game_server::Reply;
+    use proptest::prelude::*;
+    use rand::{Rng, SeedableRng};
+    use serde_json::json;
+
+    const ACTIONS: [&str; 26] = [
+        "sendposition", "newgame", "listgames", "ready", "getworld", "getopponent", "joingame",
+        "getopponentname", "votemap", "attack", "block", "placetrap", "setskin", "gameinfo",
+        "leavegame", "sendinputs", "sendchat", "getchat", "muteplayer", "getmap", "sandbox",
+        "serverinfo", "rejoingame", "importgame", "matchtrace", "admin",
+    ];
+
+    #[test]
+    fn actions_round_trip() {
+        for name in ACTIONS.iter() {
+            let action = NetActions::from_string(name.to_string());
+            assert_ne!(action, NetActions::Unknown, "{} isn't an action", name);
+            let number: usize = action.into();
+            assert_eq!(NetActions::from_usize(number), NetActions::from_string(name.to_string()), "{} is sent as {}", name, number);
+        }
+        assert_eq!(NetActions::from_string("nonsense".to_string()), NetActions::Unknown);
+        assert_eq!(NetActions::from_usize(0), NetActions::Unknown);
+    }
+
+    #[test]
+    fn spatial_hash_follows_what_moved() {
+        let at = |x| Position { x, y: 0.0, w: 10.0, h: 10.0 };
+        let mut hash = physics::SpatialHash::default();
+        hash.sync(vec![Some(at(0.0)), Some(at(200.0))]);
+        assert_eq!(hash.query(&at(5.0)), vec![0]);
+        hash.sync(vec![Some(at(195.0)), None]);
+        assert!(hash.query(&at(5.0)).is_empty());
+        assert_eq!(hash.query(&at(190.0)), vec![0]);
+        hash.sync(vec![]);
+        assert!(!hash.overlaps(&at(190.0)));
+    }
+
+    /// Everything the server answers `request` with.
+    fn ask(server: &mut GameServer, request: &str) -> Vec<String> {
+        let (sender, answers) = crossbeam_channel::unbounded();
+        server.handle_connection(request.to_string(), &Reply::WebSocket(sender), request.len());
+        answers.try_iter().collect()
+    }
+
+    #[test]
+    fn garbage_never_panics_the_server() {
+        // Seeded, so a failure happens again the same way
+        let mut rng = rand::rngs::StdRng::seed_from_u64(687);
+        let mut server = GameServer::new("localhost".to_string());
+        let newgame: usize = NetActions::Newgame.into();
+        let joingame: usize = NetActions::Joingame.into();
+        let game_id = ask(&mut server, &format!("::{}:", newgame)).remove(0);
+        let joined: serde_json::Value = serde_json::from_str(&ask(&mut server, &format!("{}:fuzzer:{}:", game_id, joingame))[0]).unwrap();
+        let player_id = joined["player_id"].as_str().unwrap().to_string();
+
+        for round in 0..3000 {
+            let bytes: Vec<u8> = (0..rng.gen_range(0, 64)).map(|_| rng.gen()).collect();
+            let noise = String::from_utf8_lossy(&bytes).to_string();
+            ask(&mut server, &noise);
+            // The same noise as the meta of an action from someone in the game,
+            // and numbers in the shape of a position update
+            let action = rng.gen_range(0, ACTIONS.len() + 5);
+            ask(&mut server, &format!("{}:{}:{}:{}", game_id, player_id, action, noise));
+            let numbers: Vec<f32> = (0..rng.gen_range(0, 12)).map(|_| rng.gen_range(-1000.0, 1000.0)).collect();
+            ask(&mut server, &format!("{}:{}:{}:{}", game_id, player_id, action, json!(numbers)));
+            if round % 10 == 0 {
+                server.tick(Duration::from_millis(16));
+            }
+        }
+    }
+
+    #[test]
+    fn recorded_sessions_replay_the_same() {
+        let dir = path::Path::new(env!("CARGO_MANIFEST_DIR")).join("sessions");
+        let mut replayed = 0;
+        for file in std::fs::read_dir(&dir).unwrap() {
+            let file = file.unwrap().path();
+            let entries = session::load(&file).unwrap();
+            let mismatches: Vec<String> = GameServer::replay(&entries).iter().map(|m| m.describe()).collect();
+            assert!(mismatches.is_empty(), "{} replayed differently:\n{}", file.display(), mismatches.join("\n"));
+            replayed += 1;
+        }
+        assert!(replayed > 0, "No sessions in {}", dir.display());
+    }
+
+    fn finite() -> impl Strategy<Value = f32> {
+        -1.0e6f32..1.0e6
+    }
+
+    prop_compose! {
+        fn filters()(mode in proptest::option::of(".*"), map in proptest::option::of(".*"), open: bool, page: usize) -> GameFilter {
+            GameFilter { mode, map, open, page }
+        }
+    }
+
+    prop_compose! {
+        fn rules()(
+            time_limit in finite(), score_limit in proptest::option::of(finite()), item_spawn_interval in finite(),
+            friendly_fire: bool, max_players in proptest::option::of(0..rules::MAX_PLAYERS_LIMIT), afk_timeout in finite(),
+            double_jump: bool, rollback: bool, scripts in prop::collection::vec("[a-z_]{1,12}", 0..3), all_chat: bool,
+        ) -> MatchRules {
+            MatchRules {
+                time_limit, score_limit, item_spawn_interval, friendly_fire, max_players, afk_timeout, double_jump, rollback,
+                scripts, all_chat,
+            }
+        }
+    }
+
+    prop_compose! {
+        fn games()(
+            game_id in "[0-9]{1,4}", maps in prop::collection::vec("[a-z_]{1,12}", 0..4),
+            mode in prop::sample::select(vec![GameMode::Duel, GameMode::KingOfTheHill, GameMode::CaptureTheFlag, GameMode::CollectionRace]),
+            rules in rules(), players in prop::collection::vec((".{0,16}", finite(), finite(), 0..=PLAYER_MAX_HP), 0..6),
+            clock in finite(), started: bool, completed: bool,
+        ) -> NetworkedGame {
+            let mut game = NetworkedGame::new(game_id, maps, mode, rules);
+            for (number, (name, x, y, hp)) in players.into_iter().enumerate() {
+                let mut player = PlayerState::new(name, Position { x, y, w: PLAYER_CELL_WIDTH, h: PLAYER_CELL_HEIGHT });
+                player.hp = hp;
+                player.number = number as u32 + 1;
+                game.scores.insert(player.score_key(), x);
+                game.players.push(player);
+            }
+            game.clock = clock;
+            game.started = started;
+            game.completed = completed;
+            game
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn requests_round_trip(game_id in "[^:]*", player in "[^:]*", action in 0..ACTIONS.len(), meta in ".*") {
+            let number: usize = NetActions::from_string(ACTIONS[action].to_string()).into();
+            let (request, number) = (net::request(&game_id, &player, number, &meta), number.to_string());
+            let parsed = net::parse_request(&request);
+            prop_assert_eq!(parsed, Some((game_id.as_str(), player.as_str(), number.as_str(), meta.as_str())));
+        }
+
+        #[test]
+        fn filters_round_trip(filter in filters()) {
+            let json = serde_json::to_string(&filter).unwrap();
+            prop_assert_eq!(serde_json::from_str::<GameFilter>(&json).unwrap(), filter);
+        }
+
+        #[test]
+        fn rules_round_trip(rules in rules()) {
+            let json = serde_json::to_string(&rules).unwrap();
+            prop_assert_eq!(serde_json::from_str::<MatchRules>(&json).unwrap(), rules);
+        }
+
+        /// Games have no `PartialEq`, so what they encode to is compared,
+        /// leaving out what's never sent
+        #[test]
+        fn games_round_trip(game in games()) {
+            let json = serde_json::to_string(&game).unwrap();
+            let decoded: NetworkedGame = serde_json::from_str(&json).unwrap();
+            prop_assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&game).unwrap());
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Output of a server run with `--daemon`, which has no terminal to print to.
+//! Everything printed goes to a log file instead, appended to across runs.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+
+/// Where a daemon logs to unless told otherwise.
+pub fn log_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("server.log");
+        path
+    } else {
+        path::PathBuf::from("./server.log")
+    }
+}
+
+/// Sends stdout and stderr to the end of `path` from now on.
+pub fn log_to(path: &path::Path) -> io::Result<()> {
+    let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    redirect(&file)
+}
+
+#[cfg(unix)]
+fn redirect(file: &fs::File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // The copies keep the file open once `file` is dropped
+    for stream in [libc::STDOUT_FILENO, libc::STDERR_FILENO].iter() {
+        if unsafe { libc::dup2(file.as_raw_fd(), *stream) } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn redirect(_file: &fs::File) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "logging to a file needs a unix system"))
+}
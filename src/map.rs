@@ -0,0 +1,661 @@
+//! Loading of maps exported from the Tiled editor (https://www.mapeditor.org/).
+//!
+//! Maps are exported with "File > Export As > JSON map files" into the `maps/`
+//! directory. Tile layers are drawn in order, and a single object layer places
+//! the walls, player spawn points, and potion spawn locations:
+//!
+//! * `wall` - rectangle the players can't walk through
+//! * `spawn` - player spawn point, handed out in join order
+//! * `potion_spawn` - location where the server may drop a potion
+//! * `hazard` - lava or spikes (by object name), draining the `damage` custom
+//!   property in HP per second from anyone standing in it
+//! * `terrain` - ice or mud (by object name) overriding the tiles below it
+//! * `crate` - breakable crate, maps without any get a few scattered randomly
+//!
+//! Ground under a player's feet can change how they move through the
+//! `friction` and `speed` custom properties, set either on tiles in the
+//! tileset or on `terrain` objects. Anything without them uses `Terrain::default()`.
+
+#[cfg(feature = "client")]
+use ggez::{graphics, Context, GameResult};
+#[cfg(feature = "client")]
+use graphics::spritebatch::SpriteBatch;
+#[cfg(feature = "client")]
+use graphics::{Mesh, Rect};
+#[cfg(feature = "client")]
+use glam::*;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path;
+
+#[cfg(feature = "server")]
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::physics::SpatialHash;
+use crate::Position;
+
+pub const DEFAULT_MAP: &str = "arena";
+
+const WALL_OBJECT: &str = "wall";
+const SPAWN_OBJECT: &str = "spawn";
+const POTION_SPAWN_OBJECT: &str = "potion_spawn";
+const HAZARD_OBJECT: &str = "hazard";
+const TERRAIN_OBJECT: &str = "terrain";
+const CRATE_OBJECT: &str = "crate";
+const ZONE_OBJECT: &str = "zone";
+const BASE_OBJECT: &str = "base";
+
+/// Size of the capture zone put in the middle of maps that don't have any.
+#[cfg(feature = "server")]
+const DEFAULT_ZONE_SIZE: f32 = 96.0;
+/// Size of the team bases put around the spawn points of maps without any.
+const DEFAULT_BASE_SIZE: f32 = 96.0;
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledLayer {
+    name: String,
+    #[serde(rename = "type")]
+    layer_type: String,
+    #[serde(default = "default_visible")]
+    visible: bool,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default)]
+    objects: Vec<TiledObject>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledObject {
+    #[serde(default)]
+    name: String,
+    // Tiled 1.9 renamed the object `type` to `class`
+    #[serde(default, rename = "type", alias = "class")]
+    object_type: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn property_f32(properties: &[TiledProperty], name: &str) -> Option<f32> {
+    properties.iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.value.as_f64())
+        .map(|v| v as f32)
+}
+
+/// `None` when neither `friction` nor `speed` is set.
+fn terrain_properties(properties: &[TiledProperty]) -> Option<Terrain> {
+    let friction = property_f32(properties, "friction");
+    let speed = property_f32(properties, "speed");
+    if friction.is_none() && speed.is_none() {
+        None
+    } else {
+        let default = Terrain::default();
+        Some(Terrain {
+            friction: friction.unwrap_or(default.friction),
+            speed: speed.unwrap_or(default.speed),
+        })
+    }
+}
+
+impl TiledObject {
+    fn property_f32(&self, name: &str) -> Option<f32> {
+        property_f32(&self.properties, name)
+    }
+}
+
+/// Movement modifiers of the ground a player stands on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terrain {
+    /// Multiplier of the acceleration lost per update once the player lets go.
+    pub friction: f32,
+    /// Multiplier of the walking speed.
+    pub speed: f32,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain {
+            friction: crate::config::tunables().friction,
+            speed: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TerrainArea {
+    #[cfg(feature = "client")]
+    pub name: String,
+    pub area: Position,
+    pub terrain: Terrain,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    image: String,
+    #[cfg(feature = "client")]
+    imagewidth: f32,
+    #[cfg(feature = "client")]
+    imageheight: f32,
+    #[cfg(feature = "client")]
+    tilewidth: f32,
+    #[cfg(feature = "client")]
+    tileheight: f32,
+    #[cfg(feature = "client")]
+    columns: u32,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    #[cfg(feature = "client")]
+    first_gid: u32,
+    #[cfg(feature = "client")]
+    image: String,
+    #[cfg(feature = "client")]
+    image_width: f32,
+    #[cfg(feature = "client")]
+    image_height: f32,
+    #[cfg(feature = "client")]
+    tile_width: f32,
+    #[cfg(feature = "client")]
+    tile_height: f32,
+    #[cfg(feature = "client")]
+    columns: u32,
+    /// Tiles with movement modifiers, by global tile id.
+    terrain: HashMap<u32, Terrain>,
+}
+
+impl Tileset {
+    /// Source rectangle of a tile inside the tileset image, in the 0.0 - 1.0
+    /// range ggez expects for `DrawParam::src`.
+    #[cfg(feature = "client")]
+    fn src(&self, gid: u32) -> Rect {
+        let index = gid - self.first_gid;
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+        Rect {
+            x: column * self.tile_width / self.image_width,
+            y: row * self.tile_height / self.image_height,
+            w: self.tile_width / self.image_width,
+            h: self.tile_height / self.image_height,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hazard {
+    pub name: String,
+    pub area: Position,
+    /// HP drained per second.
+    #[cfg(feature = "server")]
+    pub damage: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Map {
+    pub name: String,
+    width: usize,
+    height: usize,
+    tile_width: f32,
+    tile_height: f32,
+    /// Global tile ids of every visible tile layer, row by row. 0 is an empty tile.
+    layers: Vec<Vec<u32>>,
+    tileset: Tileset,
+    pub walls: Vec<Position>,
+    pub spawns: Vec<Position>,
+    #[cfg(feature = "server")]
+    pub potion_spawns: Vec<Position>,
+    pub hazards: Vec<Hazard>,
+    pub terrain: Vec<TerrainArea>,
+    #[cfg(feature = "server")]
+    pub crates: Vec<Position>,
+    /// Capture zones for King of the Hill, used in turn
+    #[cfg(feature = "server")]
+    pub zones: Vec<Position>,
+    /// Capture the Flag bases by team, from the object's `team` property
+    pub bases: Vec<(usize, Position)>,
+    /// The walls, hazards, terrain and spawns bucketed for collision checks,
+    /// built once with the map since none of them ever move
+    wall_hash: SpatialHash,
+    #[cfg(feature = "server")]
+    hazard_hash: SpatialHash,
+    terrain_hash: SpatialHash,
+    #[cfg(feature = "server")]
+    spawn_hash: SpatialHash,
+    /// Every tile of every layer, built on the first draw since the map never changes
+    #[cfg(feature = "client")]
+    tiles: Option<SpriteBatch>,
+    /// Terrain, hazards and walls in one mesh, built alongside the tiles
+    #[cfg(feature = "client")]
+    overlay: Option<Mesh>,
+}
+
+/// Copies of the maps the game ships with, for when the files are gone.
+#[cfg(feature = "embedded-assets")]
+const BUILT_IN_MAPS: [(&str, &str); 4] = [
+    ("arena", include_str!("../maps/arena.json")),
+    ("colosseum", include_str!("../maps/colosseum.json")),
+    ("crossroads", include_str!("../maps/crossroads.json")),
+    ("pillars", include_str!("../maps/pillars.json")),
+];
+#[cfg(not(feature = "embedded-assets"))]
+const BUILT_IN_MAPS: [(&str, &str); 0] = [];
+
+/// Directory the maps are read from, next to `textures/`.
+pub fn maps_dir() -> path::PathBuf {
+    crate::assets::textures_dir().with_file_name("maps")
+}
+
+/// Names of every map in the maps directory or built in, sorted.
+#[cfg(feature = "server")]
+pub fn available_maps() -> Vec<String> {
+    let mut maps: Vec<String> = match fs::read_dir(maps_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .collect(),
+        Err(_e) => vec![],
+    };
+    maps.extend(BUILT_IN_MAPS.iter().map(|(name, _)| name.to_string()));
+    maps.sort();
+    maps.dedup();
+    maps
+}
+
+/// Map names are file names without the extension, nothing that could reach
+/// outside the maps directory.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The JSON of `maps/<name>.json`, or of the built in copy if there's no such file.
+pub fn source(name: &str) -> io::Result<String> {
+    let mut path = maps_dir();
+    path.push(format!("{}.json", name));
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match BUILT_IN_MAPS.iter().find(|(built_in, _)| *built_in == name) {
+            Some((_, contents)) => Ok(contents.to_string()),
+            None => Err(e),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Keeps a map a server sent us in the maps directory, once it's known to load.
+#[cfg(feature = "client")]
+pub fn save(name: &str, contents: &str) -> io::Result<()> {
+    if !valid_name(name) {
+        return Err(invalid_map(name, "bad name"))
+    }
+    Map::from_json(name, contents)?;
+    fs::create_dir_all(maps_dir())?;
+    fs::write(maps_dir().join(format!("{}.json", name)), contents)
+}
+
+fn invalid_map(name: &str, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid map {}: {}", name, reason))
+}
+
+impl Map {
+
+    /// Loads `maps/<name>.json`, or the built in copy if there's no such file.
+    pub fn load(name: &str) -> io::Result<Map> {
+        Map::from_json(name, &source(name)?)
+    }
+
+    pub fn from_json(name: &str, contents: &str) -> io::Result<Map> {
+        let tiled: TiledMap = serde_json::from_str(contents).map_err(|e| invalid_map(name, &e.to_string()))?;
+        let tiled_tileset = match tiled.tilesets.first() {
+            Some(t) => t.clone(),
+            None => return Err(invalid_map(name, "no tileset")),
+        };
+        if tiled.tilesets.len() > 1 {
+            println!("Map {} has more than one tileset, only {} will be drawn", name, tiled_tileset.image);
+        }
+
+        let mut layers = vec![];
+        let mut walls = vec![];
+        let mut spawns = vec![];
+        let mut potion_spawns = vec![];
+        let mut hazards = vec![];
+        let mut terrain = vec![];
+        let mut crates = vec![];
+        let mut zones = vec![];
+        let mut bases = vec![];
+        for layer in tiled.layers.iter() {
+            if layer.layer_type == "tilelayer" {
+                if layer.data.len() != tiled.width * tiled.height {
+                    return Err(invalid_map(name, &format!("layer {} is not {}x{}", layer.name, tiled.width, tiled.height)));
+                }
+                if layer.visible {
+                    layers.push(layer.data.clone());
+                }
+            } else if layer.layer_type == "objectgroup" {
+                for object in layer.objects.iter() {
+                    let pos = Position { x: object.x, y: object.y, w: object.width, h: object.height };
+                    match object.object_type.as_str() {
+                        WALL_OBJECT => walls.push(pos),
+                        SPAWN_OBJECT => spawns.push(pos),
+                        POTION_SPAWN_OBJECT => potion_spawns.push(pos),
+                        HAZARD_OBJECT => hazards.push(Hazard {
+                            name: object.name.clone(),
+                            area: pos,
+                            #[cfg(feature = "server")]
+                            damage: object.property_f32("damage").unwrap_or(0.0),
+                        }),
+                        CRATE_OBJECT => crates.push(pos),
+                        ZONE_OBJECT => zones.push(pos),
+                        BASE_OBJECT => {
+                            let team = object.property_f32("team").map_or(bases.len(), |t| t as usize);
+                            bases.push((team, pos));
+                        },
+                        TERRAIN_OBJECT => terrain.push(TerrainArea {
+                            #[cfg(feature = "client")]
+                            name: object.name.clone(),
+                            area: pos,
+                            terrain: terrain_properties(&object.properties).unwrap_or_default(),
+                        }),
+                        _ => println!("Map {} ignoring object {} of type {:?}", name, object.name, object.object_type),
+                    }
+                }
+            }
+        }
+
+        let first_gid = tiled_tileset.firstgid;
+        let wall_hash = SpatialHash::build(walls.iter().copied());
+        #[cfg(feature = "server")]
+        let hazard_hash = SpatialHash::build(hazards.iter().map(|hazard| hazard.area));
+        let terrain_hash = SpatialHash::build(terrain.iter().map(|t| t.area));
+        #[cfg(feature = "server")]
+        let spawn_hash = SpatialHash::build(spawns.iter().copied());
+        Ok(Map {
+            name: name.to_string(),
+            width: tiled.width,
+            height: tiled.height,
+            tile_width: tiled.tilewidth,
+            tile_height: tiled.tileheight,
+            layers,
+            tileset: Tileset {
+                #[cfg(feature = "client")]
+                first_gid: tiled_tileset.firstgid,
+                #[cfg(feature = "client")]
+                image: tiled_tileset.image,
+                #[cfg(feature = "client")]
+                image_width: tiled_tileset.imagewidth,
+                #[cfg(feature = "client")]
+                image_height: tiled_tileset.imageheight,
+                #[cfg(feature = "client")]
+                tile_width: tiled_tileset.tilewidth,
+                #[cfg(feature = "client")]
+                tile_height: tiled_tileset.tileheight,
+                #[cfg(feature = "client")]
+                columns: tiled_tileset.columns.max(1),
+                terrain: tiled_tileset.tiles.iter()
+                    .filter_map(|tile| {
+                        terrain_properties(&tile.properties).map(|t| (first_gid + tile.id, t))
+                    })
+                    .collect(),
+            },
+            walls,
+            spawns,
+            #[cfg(feature = "server")]
+            potion_spawns,
+            hazards,
+            terrain,
+            #[cfg(feature = "server")]
+            crates,
+            #[cfg(feature = "server")]
+            zones,
+            bases,
+            wall_hash,
+            #[cfg(feature = "server")]
+            hazard_hash,
+            terrain_hash,
+            #[cfg(feature = "server")]
+            spawn_hash,
+            #[cfg(feature = "client")]
+            tiles: None,
+            #[cfg(feature = "client")]
+            overlay: None,
+        })
+    }
+
+    pub fn pixel_width(&self) -> f32 {
+        self.width as f32 * self.tile_width
+    }
+
+    pub fn pixel_height(&self) -> f32 {
+        self.height as f32 * self.tile_height
+    }
+
+    /// True when `pos` overlaps any wall.
+    pub fn is_blocked(&self, pos: &Position) -> bool {
+        self.wall_hash.overlaps(pos)
+    }
+
+    /// HP per second drained from someone standing at `pos`.
+    #[cfg(feature = "server")]
+    pub fn hazard_damage(&self, pos: &Position) -> f32 {
+        self.hazard_hash.query(pos).into_iter()
+            .map(|index| self.hazards[index].damage)
+            .sum()
+    }
+
+    /// True when `pos` is on spikes.
+    #[cfg(feature = "server")]
+    pub fn on_spikes(&self, pos: &Position) -> bool {
+        self.hazard_hash.query(pos).into_iter().any(|index| self.hazards[index].name == "spikes")
+    }
+
+    /// Ground under the feet of someone at `pos`. Terrain objects win over
+    /// tiles, and upper tile layers over lower ones.
+    pub fn terrain_at(&self, pos: &Position) -> Terrain {
+        let (feet_x, feet_y) = (pos.x + pos.w / 2.0, pos.y + pos.h);
+        let feet = Position { x: feet_x, y: feet_y, w: 0.0, h: 0.0 };
+        if let Some(index) = self.terrain_hash.query(&feet).pop() {
+            return self.terrain[index].terrain;
+        }
+        if feet_x < 0.0 || feet_y < 0.0 {
+            return Terrain::default();
+        }
+        let column = (feet_x / self.tile_width) as usize;
+        let row = (feet_y / self.tile_height) as usize;
+        if column >= self.width || row >= self.height {
+            return Terrain::default();
+        }
+        let index = row * self.width + column;
+        self.layers.iter().rev()
+            .filter_map(|layer| self.tileset.terrain.get(&layer[index]))
+            .next()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Random spot of the given size clear of walls, hazards, and spawn
+    /// points, or `None` if nothing free turned up after a few tries.
+    #[cfg(feature = "server")]
+    pub fn random_free_position(&self, w: f32, h: f32, rng: &mut impl Rng) -> Option<Position> {
+        for _ in 0..20 {
+            let pos = Position {
+                x: rng.gen_range(0.0, (self.pixel_width() - w).max(1.0)),
+                y: rng.gen_range(0.0, (self.pixel_height() - h).max(1.0)),
+                w,
+                h,
+            };
+            let taken = self.is_blocked(&pos)
+                || self.hazard_hash.overlaps(&pos)
+                || self.spawn_hash.overlaps(&pos);
+            if !taken {
+                return Some(pos);
+            }
+        }
+        None
+    }
+
+    /// Spawn point for the n-th player to join, wrapping around when the map
+    /// has fewer spawns than players.
+    pub fn spawn_point(&self, index: usize) -> Option<Position> {
+        if self.spawns.is_empty() {
+            None
+        } else {
+            Some(self.spawns[index % self.spawns.len()])
+        }
+    }
+
+    /// The n-th capture zone, wrapping around, or the middle of the map when
+    /// it has none.
+    #[cfg(feature = "server")]
+    pub fn zone(&self, index: usize) -> Position {
+        if self.zones.is_empty() {
+            Position {
+                x: (self.pixel_width() - DEFAULT_ZONE_SIZE) / 2.0,
+                y: (self.pixel_height() - DEFAULT_ZONE_SIZE) / 2.0,
+                w: DEFAULT_ZONE_SIZE,
+                h: DEFAULT_ZONE_SIZE,
+            }
+        } else {
+            self.zones[index % self.zones.len()]
+        }
+    }
+
+    /// Base of a Capture the Flag team, falling back to the area around the
+    /// team's spawn point.
+    pub fn base(&self, team: usize) -> Position {
+        if let Some((_, base)) = self.bases.iter().find(|(t, _)| *t == team) {
+            return *base
+        }
+        let spawn = self.spawn_point(team).unwrap_or(Position {
+            x: if team.is_multiple_of(2) { 0.0 } else { self.pixel_width() - DEFAULT_BASE_SIZE },
+            y: (self.pixel_height() - DEFAULT_BASE_SIZE) / 2.0,
+            w: DEFAULT_BASE_SIZE,
+            h: DEFAULT_BASE_SIZE,
+        });
+        Position {
+            x: spawn.x + (spawn.w - DEFAULT_BASE_SIZE) / 2.0,
+            y: spawn.y + (spawn.h - DEFAULT_BASE_SIZE) / 2.0,
+            w: DEFAULT_BASE_SIZE,
+            h: DEFAULT_BASE_SIZE,
+        }
+    }
+
+    /// File of the tileset image, inside `textures/`.
+    #[cfg(feature = "client")]
+    pub fn tileset_image(&self) -> &str {
+        &self.tileset.image
+    }
+
+    /// Rebuilds the tiles from the tileset image on the next draw.
+    #[cfg(feature = "client")]
+    pub fn reload_tiles(&mut self) {
+        self.tiles = None;
+    }
+
+    /// Draws the tiles and walls, moved and scaled by `param`. Both are
+    /// batched up once, the camera takes care of what's on screen.
+    #[cfg(feature = "client")]
+    pub fn draw(&mut self, ctx: &mut Context, param: graphics::DrawParam) -> GameResult<()> {
+        if self.tiles.is_none() {
+            self.tiles = Some(self.build_tiles(ctx)?);
+        }
+        if self.overlay.is_none() {
+            self.overlay = self.build_overlay(ctx)?;
+        }
+        if let Some(tiles) = &self.tiles {
+            graphics::draw(ctx, tiles, param)?;
+        }
+        if let Some(overlay) = &self.overlay {
+            graphics::draw(ctx, overlay, param)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "client")]
+    fn build_tiles(&self, ctx: &mut Context) -> GameResult<SpriteBatch> {
+        let texture = crate::assets::image(ctx, &self.tileset.image)?;
+        let mut tiles = SpriteBatch::new(texture);
+        for layer in self.layers.iter() {
+            for (index, gid) in layer.iter().enumerate() {
+                if *gid < self.tileset.first_gid {
+                    continue;
+                }
+                let x = (index % self.width) as f32 * self.tile_width;
+                let y = (index / self.width) as f32 * self.tile_height;
+                tiles.add(graphics::DrawParam::new()
+                    .src(self.tileset.src(*gid))
+                    .dest(Vec2::new(x, y))
+                    .scale(Vec2::new(self.tile_width / self.tileset.tile_width, self.tile_height / self.tileset.tile_height)));
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// `None` for maps without any terrain, hazards or walls.
+    #[cfg(feature = "client")]
+    fn build_overlay(&self, ctx: &mut Context) -> GameResult<Option<Mesh>> {
+        if self.terrain.is_empty() && self.hazards.is_empty() && self.walls.is_empty() {
+            return Ok(None)
+        }
+        let mut overlay = graphics::MeshBuilder::new();
+        for area in self.terrain.iter() {
+            let color = match area.name.as_str() {
+                "ice" => [0.75, 0.9, 1.0, 0.6],
+                "mud" => [0.4, 0.25, 0.1, 0.6],
+                _ => [1.0, 1.0, 1.0, 0.2],
+            };
+            overlay.rectangle(graphics::DrawMode::fill(), area.area.into(), color.into())?;
+        }
+        for hazard in self.hazards.iter() {
+            let color = if hazard.name == "spikes" {
+                [0.6, 0.6, 0.65, 0.8]
+            } else {
+                [1.0, 0.35, 0.0, 0.8]
+            };
+            overlay.rectangle(graphics::DrawMode::fill(), hazard.area.into(), color.into())?;
+        }
+        for wall in self.walls.iter() {
+            overlay.rectangle(graphics::DrawMode::fill(), (*wall).into(), [0.35, 0.3, 0.25, 1.0].into())?;
+        }
+        Ok(Some(overlay.build(ctx)?))
+    }
+}
@@ -0,0 +1,46 @@
+//! The pause menu, opened with Escape or by the window losing focus. The
+//! game carries on underneath, since the server doesn't wait for anyone.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuItem {
+    Resume,
+    Settings,
+    Quit,
+}
+
+/// Every item, in the order they're listed.
+pub const MENU_ITEMS: [MenuItem; 3] = [MenuItem::Resume, MenuItem::Settings, MenuItem::Quit];
+
+impl MenuItem {
+    pub fn title(self) -> &'static str {
+        match self {
+            MenuItem::Resume => "Resume",
+            MenuItem::Settings => "Settings",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+/// Where the pause menu is at while it's open.
+#[derive(Debug, Clone, Default)]
+pub struct PauseMenu {
+    /// Index into `MENU_ITEMS`
+    pub selected: usize,
+    /// Asking whether to really leave the game
+    pub confirming_quit: bool,
+}
+
+impl PauseMenu {
+
+    /// Opened straight onto the quit question, as closing the window does.
+    pub fn confirming_quit() -> PauseMenu {
+        PauseMenu {
+            selected: MENU_ITEMS.len() - 1,
+            confirming_quit: true,
+        }
+    }
+
+    pub fn item(&self) -> MenuItem {
+        MENU_ITEMS[self.selected]
+    }
+}
@@ -0,0 +1,145 @@
+//! Moving a game to another server when the one it's on goes away mid-game,
+//! like a player hosting with `--host` quitting. Every client keeps the last
+//! world snapshot, and once the server has been quiet long enough they take
+//! it to a standby relay given with `--relay`, or the first remote player
+//! starts a server of their own with it and the rest go there. Everyone then
+//! joins again with the player ID they had and carries on from the snapshot.
+//!
+//! The threads talking to the server keep the host, game and player ID they
+//! started with, so rather than restarting them all, every message is sent
+//! on to wherever its game moved.
+
+#[cfg(feature = "server")]
+use std::net::IpAddr;
+use std::sync::RwLock;
+#[cfg(feature = "client")]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+use crate::{net, transfer};
+#[cfg(feature = "native")]
+use crate::NetworkedGame;
+#[cfg(all(feature = "client", feature = "server"))]
+use crate::GameServer;
+
+/// How long the server can go without sending the world before the game is
+/// taken somewhere else.
+#[cfg(feature = "client")]
+pub const MIGRATION_TIMEOUT_MILLIS: u64 = 3000;
+/// Tries at joining the game where it went, while the server there comes up.
+#[cfg(feature = "client")]
+const REJOIN_ATTEMPTS: usize = 10;
+#[cfg(feature = "client")]
+const REJOIN_RETRY_MILLIS: u64 = 500;
+
+/// Who takes a game over if its server goes away, and where to find them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Successor {
+    pub name: String,
+    pub address: String,
+}
+
+/// Where a game went, and who we are there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moved {
+    pub host: String,
+    pub game_id: String,
+    pub player_id: String,
+}
+
+/// Games that moved, by the host and game they were on and our player ID
+/// there.
+static MOVED: RwLock<Vec<(String, String, String, Moved)>> = RwLock::new(Vec::new());
+
+/// Sends messages for game `game_id` on `host` on to where the game went,
+/// as `moved.player_id` instead of `player`.
+#[cfg(feature = "client")]
+pub fn record(host: &str, game_id: &str, player: &str, moved: Moved) {
+    if let Ok(mut all) = MOVED.write() {
+        all.push((host.to_string(), game_id.to_string(), player.to_string(), moved));
+    }
+}
+
+/// Where a message to `host` about game `game_id` from `player` goes, as
+/// `(host, game_id, player)`, following every move the game made.
+pub fn route(mut host: String, mut game_id: String, mut player: String) -> (String, String, String) {
+    let all = match MOVED.read() {
+        Ok(all) => all,
+        Err(_e) => return (host, game_id, player),
+    };
+    while let Some((_, _, old_player, moved)) = all.iter().find(|(h, g, _, _)| *h == host && *g == game_id) {
+        if *old_player == player {
+            player = moved.player_id.clone();
+        }
+        host = moved.host.clone();
+        game_id = moved.game_id.clone();
+    }
+    (host, game_id, player)
+}
+
+/// What game `game_id` from `host` is known by once it moves, the same for
+/// every client and unlikely to clash with a relay's own games.
+#[cfg(feature = "client")]
+pub fn session_id(host: &str, game_id: &str) -> String {
+    format!("{}-{:04x}", game_id, transfer::checksum(host.as_bytes()) & 0xffff)
+}
+
+/// Who starts a server for `game` when there's no relay: the first player
+/// that isn't a bot or on the old server's machine, which went down with it.
+#[cfg(feature = "server")]
+pub fn successor(game: &NetworkedGame) -> Option<Successor> {
+    game.players.iter().filter(|p| !p.bot).find_map(|p| {
+        let address = p.address.as_deref().and_then(|address| address.parse::<IpAddr>().ok())?;
+        (!address.is_loopback()).then(|| Successor { name: p.name.clone(), address: address.to_string() })
+    })
+}
+
+/// What everyone's told about `player_id`, which lets a server the game
+/// moves to check someone rejoining with it but not rejoin as them.
+#[cfg(feature = "server")]
+pub fn rejoin_check(player_id: &str) -> String {
+    if player_id.is_empty() {
+        return String::new()
+    }
+    sha1_smol::Sha1::from(player_id).digest().to_string()
+}
+
+/// The port of `host`, eg. `7878` for `example.com:7878`.
+#[cfg(feature = "client")]
+pub fn port(host: &str) -> Option<u16> {
+    host.rsplit(':').next().and_then(|port| port.parse().ok())
+}
+
+/// Starts a server of our own on `port` with `game`, as `game_id`, for
+/// when we're the successor. Gives back where to find it.
+#[cfg(all(feature = "client", feature = "server"))]
+pub fn host(port: u16, game_id: &str, game: NetworkedGame) -> Result<String, String> {
+    GameServer::resume(format!("0.0.0.0:{}", port), game_id.to_string(), game)?;
+    Ok(format!("127.0.0.1:{}", port))
+}
+
+#[cfg(all(feature = "client", not(feature = "server")))]
+pub fn host(_port: u16, _game_id: &str, _game: NetworkedGame) -> Result<String, String> {
+    Err("built without the server, so unable to host it".to_string())
+}
+
+/// Joins game `game_id` on `host` again as whoever was `player_id` before it
+/// moved, giving the server there a moment to come up. Gives back our new
+/// player ID.
+#[cfg(feature = "client")]
+pub fn rejoin(host: &str, game_id: &str, player_id: &str) -> Result<String, String> {
+    let mut error = format!("no answer from {}", host);
+    for _ in 0..REJOIN_ATTEMPTS {
+        if let Some(answer) = net::send_message(host.to_string(), game_id.to_string(), player_id.to_string(), "rejoingame".to_string(), "".to_string(), true) {
+            let answer: serde_json::Value = serde_json::from_str(&answer).unwrap_or_default();
+            if let Some(id) = answer["player_id"].as_str() {
+                return Ok(id.to_string())
+            }
+            error = answer["error"].as_str().unwrap_or("no player ID").to_string();
+        }
+        std::thread::sleep(Duration::from_millis(REJOIN_RETRY_MILLIS));
+    }
+    Err(error)
+}
@@ -0,0 +1,78 @@
+//! Game modes and their win conditions, picked when a game is created.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ctf;
+
+/// Points a King of the Hill player needs, one per second held.
+pub const KOTH_SCORE_LIMIT: f32 = 30.0;
+/// Seconds before the hill moves on maps with more than one zone.
+#[cfg(feature = "server")]
+pub const KOTH_ZONE_MOVE_SECONDS: f32 = 30.0;
+/// Items a collection race player needs.
+pub const RACE_SCORE_LIMIT: f32 = 10.0;
+/// Items dropped when killed in a collection race.
+#[cfg(feature = "server")]
+pub const RACE_DEATH_DROP: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Default)]
+pub enum GameMode {
+    /// Last one standing
+    #[default]
+    Duel,
+    /// Hold the capture zone alone to score
+    KingOfTheHill,
+    /// Two teams of two bringing the enemy flag home
+    CaptureTheFlag,
+    /// First to pick up enough items, dying drops some of them
+    CollectionRace,
+}
+
+
+impl GameMode {
+
+    /// Parses the name given to `newgame`, as returned by `name`.
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name {
+            "duel" => Some(GameMode::Duel),
+            "koth" => Some(GameMode::KingOfTheHill),
+            "ctf" => Some(GameMode::CaptureTheFlag),
+            "race" => Some(GameMode::CollectionRace),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Duel => "duel",
+            GameMode::KingOfTheHill => "koth",
+            GameMode::CaptureTheFlag => "ctf",
+            GameMode::CollectionRace => "race",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            GameMode::Duel => "Duel",
+            GameMode::KingOfTheHill => "King of the Hill",
+            GameMode::CaptureTheFlag => "Capture the Flag",
+            GameMode::CollectionRace => "Collection Race",
+        }
+    }
+
+    /// Score that wins the game, if the mode keeps score.
+    pub fn score_limit(&self) -> Option<f32> {
+        match self {
+            GameMode::Duel => None,
+            GameMode::KingOfTheHill => Some(KOTH_SCORE_LIMIT),
+            GameMode::CaptureTheFlag => Some(ctf::CTF_SCORE_LIMIT),
+            GameMode::CollectionRace => Some(RACE_SCORE_LIMIT),
+        }
+    }
+
+    /// Whether players are split into teams, scoring together.
+    pub fn has_teams(&self) -> bool {
+        *self == GameMode::CaptureTheFlag
+    }
+}
@@ -0,0 +1,38 @@
+//! Player names. Requests find their player by the ID the server handed out
+//! on joining, but that ID is kept from everyone else so nobody can act for
+//! them. Scores, map votes, rollback inputs and checksums, traps and flags go
+//! by the player's number instead, which everyone gets to see. Names are for
+//! show, in the kill feed and for mutes, and the server checks them before
+//! letting anyone join, which also keeps them unique within a game.
+
+/// Longest name allowed, in characters.
+pub const MAX_NAME_LENGTH: usize = 8;
+
+/// Turned away when the server filters names, anywhere in one. Kept to
+/// words that don't hide inside harmless names.
+const BLOCKED_WORDS: [&str; 12] = [
+    "asshole", "bitch", "cunt", "dildo", "faggot", "fuck", "nazi", "nigg", "penis", "shit", "slut", "whore",
+];
+
+/// Whether `name` is fit to play under, against the names already in the
+/// game. The error says what's wrong with it.
+pub fn check<'a>(name: &str, taken: impl IntoIterator<Item = &'a str>, filter_profanity: bool) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("name can't be empty".to_string())
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("name can't be longer than {} characters", MAX_NAME_LENGTH))
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("name can only have letters and numbers".to_string())
+    }
+    let lower = name.to_ascii_lowercase();
+    if filter_profanity && BLOCKED_WORDS.iter().any(|word| lower.contains(word)) {
+        return Err("name isn't allowed".to_string())
+    }
+    // Told apart without case too, so nobody can pass for someone else
+    if taken.into_iter().any(|other| other.eq_ignore_ascii_case(name)) {
+        return Err(format!("{} is already taken in this game", name))
+    }
+    Ok(())
+}
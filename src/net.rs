@@ -0,0 +1,84 @@
+//! The client's end of the protocol: a request is one UDP packet,
+//! `game:player:action:meta`, and the answer is the one that comes back.
+//! Anything can send them, a server being built in or not.
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{migration, transfer, NetActions};
+
+/// Sends a command as typed into the server console, waiting for the answer.
+pub fn send_command(host: String, game_id: String, player: String, command: &str) -> Option<String> {
+    // The game mode rides along with newgame, eg. newgamekoth
+    let (command, meta) = if let Some(mode) = command.strip_prefix("newgame") {
+        ("newgame".to_string(), mode.to_string())
+    } else {
+        (command.to_string(), "".to_string())
+    };
+    send_message(host, game_id, player, command, meta, true)
+}
+
+/// Sends an admin command as it'd be typed at the server's console, with the
+/// server's admin key.
+pub fn send_admin(host: String, key: String, command: &str) -> Option<String> {
+    send_message(host, "".to_string(), key, "admin".to_string(), command.to_string(), true)
+}
+
+/// A request as the server reads it, `game:player:action:meta`.
+pub fn request(game_id: &str, player: &str, action: usize, meta: &str) -> String {
+    format!("{}:{}:{}:{}", game_id, player, action, meta)
+}
+
+/// The game ID, player, action number and meta of `request`, if it has all
+/// four. Meta is last so it can hold JSON with colons of its own.
+#[cfg(feature = "server")]
+pub fn parse_request(request: &str) -> Option<(&str, &str, &str, &str)> {
+    let mut keys = request.splitn(4, ':');
+    Some((keys.next()?, keys.next()?, keys.next()?, keys.next()?))
+}
+
+pub fn send_message(host: String, game_id: String, player: String, msg: String, meta: String, block: bool) -> Option<String> {
+    puffin::profile_function!();
+    let (host, game_id, player) = migration::route(host, game_id, player);
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    socket.set_nonblocking(!block).unwrap();
+    let _ = socket.connect(host);
+
+    let msg = request(&game_id, &player, NetActions::from_string(msg).into(), &meta);
+
+    match socket.send(&Bytes::from(msg)) {
+        Ok(_) => (),
+        Err(_e) => {
+            return None
+        }
+    }
+
+    if !block {
+        return Some("".to_string())
+    }
+    match socket.set_read_timeout(Some(Duration::new(1, 0))) {
+        Ok(_) => (),
+        Err(_e) => {
+            return None
+        }
+    }
+    // Room for anything the server sends, which reads its requests the same
+    // way. Joining a full game of the most players rules allow is over 6KB.
+    let mut buf = [0; 65_000];
+    match socket.recv(&mut buf) {
+        Ok(size) => Some(String::from_utf8_lossy(&buf[0..size]).to_string()),
+        Err(_e) => {
+            None
+        }
+    }
+}
+
+/// Where everyone went in `game_id`, as the JSON the server keeps it in.
+pub fn match_trace(host: String, game_id: String) -> Result<String, String> {
+    transfer::download(|index| {
+        let answer = send_message(host.clone(), game_id.clone(), "".to_string(), "matchtrace".to_string(), index.to_string(), true)?;
+        serde_json::from_str(&answer).ok()
+    })
+}
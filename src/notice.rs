@@ -0,0 +1,54 @@
+//! A server's message of the day and house rules, from `motd` and
+//! `server_rules` in config.toml or changed with the `setmotd` and
+//! `setrules` admin commands. Both go out in `serverinfo`, which clients show
+//! in the lobby while they wait for a game.
+
+#[cfg(feature = "server")]
+use std::sync::RwLock;
+
+#[cfg(feature = "server")]
+use crate::config;
+
+/// Set by an admin command, standing in for the config's from then on.
+#[cfg(feature = "server")]
+static MOTD: RwLock<Option<String>> = RwLock::new(None);
+#[cfg(feature = "server")]
+static RULES: RwLock<Option<String>> = RwLock::new(None);
+
+#[cfg(feature = "server")]
+pub fn motd() -> String {
+    MOTD.read().ok().and_then(|motd| motd.clone()).unwrap_or_else(|| config::get().motd.clone())
+}
+
+#[cfg(feature = "server")]
+pub fn rules() -> String {
+    RULES.read().ok().and_then(|rules| rules.clone()).unwrap_or_else(|| config::get().server_rules.clone())
+}
+
+#[cfg(feature = "server")]
+pub fn set_motd(text: &str) {
+    if let Ok(mut motd) = MOTD.write() {
+        *motd = Some(text.trim().to_string());
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn set_rules(text: &str) {
+    if let Ok(mut rules) = RULES.write() {
+        *rules = Some(text.trim().to_string());
+    }
+}
+
+/// What the lobby shows from `info`, a `serverinfo` answer, leaving out
+/// whatever the server didn't set.
+#[cfg(feature = "client")]
+pub fn lobby_lines(info: &serde_json::Value) -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(motd) = info["motd"].as_str().filter(|motd| !motd.is_empty()) {
+        lines.push(motd.to_string());
+    }
+    if let Some(rules) = info["rules"].as_str().filter(|rules| !rules.is_empty()) {
+        lines.push(format!("Rules: {}", rules));
+    }
+    lines
+}
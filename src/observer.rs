@@ -0,0 +1,133 @@
+//! Read-only HTTP listener run by the server with `--observe`, so stream
+//! overlays and tournament dashboards can follow live games without
+//! speaking the UDP protocol. Everything is a GET answered with JSON:
+//!
+//! - `/games` lists every game on the server
+//! - `/games/<id>` has a game's players, scores and clock, scores going by
+//!   team name or by player `number`
+//!
+//! The answers are made by the server loop from its own games, the listener
+//! only handing paths over and answers back.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_json::{json, Value};
+
+use crate::NetworkedGame;
+
+/// A path asked for, with where to send what's there, `None` for nothing.
+pub type Request = (String, Sender<Option<Value>>);
+
+/// How long a request waits on the server loop before giving up.
+const ANSWER_MILLIS: u64 = 1000;
+/// How long a client gets to send its request.
+const READ_MILLIS: u64 = 2000;
+
+/// Takes HTTP requests on `address` from now on, each connection on its own
+/// thread, handing them to the server through `requests`.
+pub fn listen(address: &str, requests: Sender<Request>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let requests = requests.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(stream, requests) {
+                    println!("Observer request failed: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream, requests: Sender<Request>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_millis(READ_MILLIS)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers say nothing we need, but are read so the client isn't cut off
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some(path)) => {
+            let (sender, answer) = crossbeam_channel::bounded(1);
+            let path = path.split('?').next().unwrap_or(path).trim_end_matches('/').to_string();
+            if requests.send((path, sender)).is_err() {
+                ("503 Service Unavailable", json!({"error": "The server has stopped"}))
+            } else {
+                match answer.recv_timeout(Duration::from_millis(ANSWER_MILLIS)) {
+                    Ok(Some(found)) => ("200 OK", found),
+                    Ok(None) => ("404 Not Found", json!({"error": "Nothing there"})),
+                    Err(_e) => ("503 Service Unavailable", json!({"error": "The server is busy"})),
+                }
+            }
+        },
+        _ => ("405 Method Not Allowed", json!({"error": "Only GET is served"})),
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+    )?;
+    stream.flush()
+}
+
+/// What's at `path` among `games`, `None` if nothing is.
+pub fn answer(games: &[NetworkedGame], path: &str) -> Option<Value> {
+    if path == "/games" {
+        return Some(json!(games.iter().map(summary).collect::<Vec<Value>>()))
+    }
+    let id = path.strip_prefix("/games/")?;
+    games.iter().find(|g| g.session_id == id).map(details)
+}
+
+fn summary(game: &NetworkedGame) -> Value {
+    let players: Vec<&String> = game.players.iter().map(|p| &p.name).collect();
+    json!({
+        "session_id": game.session_id,
+        "mode": game.mode.name(),
+        "map": game.map,
+        "players": players,
+        "started": game.started,
+        "completed": game.completed,
+    })
+}
+
+fn details(game: &NetworkedGame) -> Value {
+    let players: Vec<Value> = game.players.iter().map(|p| json!({
+        "name": p.name,
+        "number": p.number,
+        "hp": p.hp,
+        "mp": p.mp,
+        "level": p.level,
+        "team": p.team,
+        "bot": p.bot,
+        "afk": p.afk,
+    })).collect();
+    json!({
+        "session_id": game.session_id,
+        "mode": game.mode.name(),
+        "map": game.map,
+        "players": players,
+        "scores": game.scores,
+        "score_limit": game.score_limit(),
+        "clock": game.clock,
+        "time_limit": game.rules.time_limit,
+        "remaining": (game.rules.time_limit - game.clock).max(0.0),
+        "sudden_death": game.sudden_death.is_some(),
+        "started": game.started,
+        "completed": game.completed,
+        "winner": game.winner,
+    })
+}
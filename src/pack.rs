@@ -0,0 +1,169 @@
+//! Asset packs picked with `--pack`, a directory or a zip of one laid out as
+//!
+//! ```text
+//! pack.toml        optional, see `Manifest`
+//! textures/*.png   in place of the files of the same name in `textures/`
+//! sounds/*.wav     in place of the synthesized sound of the same name
+//! ```
+//!
+//! Anything the pack leaves out is loaded as it would be without one.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+static PACK: OnceLock<Pack> = OnceLock::new();
+
+/// What `pack.toml` can hold.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub animation: Animation,
+    /// Config values, as `config.toml` has them, that `--set` can still override
+    pub stats: toml::value::Table,
+}
+
+/// How the hero sheets are laid out and played.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Animation {
+    /// Frames across a sheet, each row being a direction
+    pub frames: u32,
+    pub frame_millis: u64,
+}
+
+impl Default for Animation {
+    fn default() -> Animation {
+        Animation {
+            frames: 4,
+            frame_millis: 150,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Dir(path::PathBuf),
+    /// Every file in the zip, read up front since there are only a few
+    Zip(Vec<(String, Vec<u8>)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Pack {
+    source: Source,
+    pub manifest: Manifest,
+    /// Of every file in the pack, so players can tell they have the same one
+    pub checksum: u64,
+}
+
+impl Pack {
+
+    /// Opens the pack at `path`, a directory or a zip file.
+    pub fn open(path: &path::Path) -> Result<Pack, String> {
+        let source = if path.is_dir() {
+            Source::Dir(path.to_path_buf())
+        } else {
+            Source::Zip(read_zip(path).map_err(|e| format!("{}: {}", path.display(), e))?)
+        };
+        let mut pack = Pack { source, manifest: Manifest::default(), checksum: 0 };
+        if let Some(manifest) = pack.read("pack.toml") {
+            let manifest = String::from_utf8_lossy(&manifest).to_string();
+            pack.manifest = toml::from_str(&manifest).map_err(|e| format!("{} pack.toml: {}", path.display(), e))?;
+        }
+        if pack.manifest.animation.frames == 0 || pack.manifest.animation.frame_millis == 0 {
+            return Err(format!("{} pack.toml: animation frames and frame_millis must be positive", path.display()))
+        }
+        pack.checksum = pack.compute_checksum().map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(pack)
+    }
+
+    /// The contents of `file`, a path inside the pack like `textures/hero.png`.
+    pub fn read(&self, file: &str) -> Option<Vec<u8>> {
+        match &self.source {
+            Source::Dir(dir) => fs::read(dir.join(file)).ok(),
+            Source::Zip(files) => files.iter().find(|(name, _)| name == file).map(|(_, data)| data.clone()),
+        }
+    }
+
+    /// Names of the files in `dir` inside the pack, eg. `textures`.
+    #[cfg(feature = "client")]
+    pub fn files(&self, dir: &str) -> Vec<String> {
+        match &self.source {
+            Source::Dir(root) => match fs::read_dir(root.join(dir)) {
+                Ok(entries) => entries.flatten().map(|entry| entry.file_name().to_string_lossy().to_string()).collect(),
+                Err(_e) => vec![],
+            },
+            Source::Zip(files) => files.iter()
+                .filter_map(|(name, _)| name.strip_prefix(dir)?.strip_prefix('/').map(|file| file.to_string()))
+                .filter(|file| !file.is_empty() && !file.contains('/'))
+                .collect(),
+        }
+    }
+
+    fn compute_checksum(&self) -> io::Result<u64> {
+        let mut files = match &self.source {
+            Source::Dir(dir) => read_dir_files(dir, "")?,
+            Source::Zip(files) => files.clone(),
+        };
+        files.sort();
+        let mut all = vec![];
+        for (name, data) in files {
+            all.extend(name.bytes());
+            all.push(0);
+            all.extend(data);
+        }
+        Ok(crate::transfer::checksum(&all))
+    }
+}
+
+/// Every file under `dir`, named by their path from the pack's root like a
+/// zip names them.
+fn read_dir_files(dir: &path::Path, prefix: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            files.extend(read_dir_files(&entry.path(), &format!("{}/", name))?);
+        } else {
+            files.push((name, fs::read(entry.path())?));
+        }
+    }
+    Ok(files)
+}
+
+fn read_zip(path: &path::Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if file.is_dir() {
+            continue
+        }
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        files.push((file.name().to_string(), data));
+    }
+    Ok(files)
+}
+
+/// Uses `pack` from now on. Has to come before anything is loaded.
+pub fn init(pack: Pack) {
+    let _ = PACK.set(pack);
+}
+
+/// The pack picked with `--pack`, if any.
+pub fn get() -> Option<&'static Pack> {
+    PACK.get()
+}
+
+/// How hero sheets are played, the pack's or the usual.
+#[cfg(feature = "client")]
+pub fn animation() -> Animation {
+    get().map(|pack| pack.manifest.animation).unwrap_or_default()
+}
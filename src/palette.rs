@@ -0,0 +1,93 @@
+//! Colors that carry meaning, picked from a table by the color mode setting
+//! so they can be swapped for high contrast or colorblind-safe ones.
+
+use ggez::{graphics, Context};
+
+pub struct Palette {
+    pub name: &'static str,
+    pub hp: [f32; 4],
+    pub mp: [f32; 4],
+    pub stamina: [f32; 4],
+    /// Stamina while too winded to use it
+    pub winded: [f32; 4],
+    /// Behind the stat bars
+    pub bar_back: [f32; 4],
+    /// The part of a bar just lost, still draining
+    pub bar_drain: [f32; 4],
+    /// Ring under health, mana and speed potions, whose sprites are only
+    /// told apart by being red, blue and green
+    pub potion_rings: Option<[[f32; 4]; 3]>,
+    /// Dark edge around text, to stand out over the map
+    pub text_outline: Option<[f32; 4]>,
+}
+
+/// Every palette, in the order the setting cycles through them.
+pub const PALETTES: [Palette; 4] = [
+    Palette {
+        name: "Standard",
+        hp: [1.0, 0.2, 0.2, 1.0],
+        mp: [0.0, 0.4, 1.0, 1.0],
+        stamina: [0.4, 0.9, 0.3, 1.0],
+        winded: [0.5, 0.5, 0.5, 1.0],
+        bar_back: [0.15, 0.15, 0.15, 1.0],
+        bar_drain: [1.0, 0.9, 0.8, 0.8],
+        potion_rings: None,
+        text_outline: None,
+    },
+    Palette {
+        name: "High contrast",
+        hp: [1.0, 0.1, 0.1, 1.0],
+        mp: [0.2, 0.6, 1.0, 1.0],
+        stamina: [1.0, 1.0, 0.2, 1.0],
+        winded: [0.4, 0.4, 0.4, 1.0],
+        bar_back: [0.0, 0.0, 0.0, 1.0],
+        bar_drain: [1.0, 1.0, 1.0, 1.0],
+        potion_rings: Some([[1.0, 0.1, 0.1, 1.0], [0.2, 0.6, 1.0, 1.0], [1.0, 1.0, 0.2, 1.0]]),
+        text_outline: Some([0.0, 0.0, 0.0, 1.0]),
+    },
+    // Okabe-Ito colors, which stay apart without telling red from green
+    Palette {
+        name: "Red-green safe",
+        hp: [0.84, 0.37, 0.0, 1.0],
+        mp: [0.0, 0.45, 0.7, 1.0],
+        stamina: [0.94, 0.89, 0.26, 1.0],
+        winded: [0.5, 0.5, 0.5, 1.0],
+        bar_back: [0.1, 0.1, 0.1, 1.0],
+        bar_drain: [1.0, 1.0, 1.0, 0.8],
+        potion_rings: Some([[0.84, 0.37, 0.0, 1.0], [0.0, 0.45, 0.7, 1.0], [0.94, 0.89, 0.26, 1.0]]),
+        text_outline: Some([0.0, 0.0, 0.0, 1.0]),
+    },
+    // And ones that don't need blue told from yellow
+    Palette {
+        name: "Blue-yellow safe",
+        hp: [0.9, 0.1, 0.1, 1.0],
+        mp: [0.0, 0.62, 0.45, 1.0],
+        stamina: [0.8, 0.47, 0.65, 1.0],
+        winded: [0.5, 0.5, 0.5, 1.0],
+        bar_back: [0.1, 0.1, 0.1, 1.0],
+        bar_drain: [1.0, 1.0, 1.0, 0.8],
+        potion_rings: Some([[0.9, 0.1, 0.1, 1.0], [0.0, 0.62, 0.45, 1.0], [0.8, 0.47, 0.65, 1.0]]),
+        text_outline: Some([0.0, 0.0, 0.0, 1.0]),
+    },
+];
+
+/// The palette at `index`, wrapping around.
+pub fn palette(index: usize) -> &'static Palette {
+    &PALETTES[index % PALETTES.len()]
+}
+
+/// Queues `text` like `graphics::queue_text`, behind an outline if the
+/// palette has one.
+pub fn queue_text(ctx: &mut Context, text: &graphics::Text, dest: ggez::mint::Point2<f32>, palette: &Palette) {
+    if let Some(outline) = palette.text_outline {
+        let mut edge = text.clone();
+        for fragment in edge.fragments_mut() {
+            let alpha = fragment.color.map_or(1.0, |color| color.a);
+            fragment.color = Some(graphics::Color::new(outline[0], outline[1], outline[2], outline[3] * alpha));
+        }
+        for (x, y) in [(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)].iter() {
+            graphics::queue_text(ctx, &edge, ggez::mint::Point2 { x: dest.x + x, y: dest.y + y }, None);
+        }
+    }
+    graphics::queue_text(ctx, text, dest, None);
+}
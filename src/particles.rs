@@ -0,0 +1,103 @@
+//! Short lived sparks and puffs for pickups, hits, dashes, and deaths. Purely
+//! for show, so each client makes its own. They're entities moved and aged
+//! by the systems in `ecs`.
+
+use ggez::{graphics, Context, GameResult};
+use hecs::{Entity, World};
+use rand::Rng;
+
+use crate::ecs::{Lifetime, Position, Velocity};
+use crate::events::GameEvent;
+
+/// Oldest particles are dropped past this many.
+const MAX_PARTICLES: usize = 600;
+
+/// Drawn as a dot `size` across, fading out as it ages.
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    size: f32,
+    color: [f32; 4],
+}
+
+/// How a burst of particles looks.
+#[derive(Debug, Clone, Copy)]
+pub struct Burst {
+    pub count: usize,
+    /// Fastest a particle flies off, in pixels per second
+    pub speed: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+pub const PICKUP_SPARKLE: Burst = Burst { count: 14, speed: 60.0, lifetime: 0.6, size: 2.0, color: [1.0, 1.0, 0.6, 1.0] };
+pub const HIT_IMPACT: Burst = Burst { count: 10, speed: 120.0, lifetime: 0.3, size: 2.5, color: [1.0, 0.3, 0.2, 1.0] };
+pub const DASH_TRAIL: Burst = Burst { count: 2, speed: 15.0, lifetime: 0.35, size: 3.0, color: [0.6, 0.8, 1.0, 0.8] };
+pub const DEATH_BURST: Burst = Burst { count: 40, speed: 160.0, lifetime: 1.0, size: 3.5, color: [0.9, 0.1, 0.1, 1.0] };
+
+/// Spawns `burst` at `x`, `y`, flying off in every direction.
+pub fn spawn(world: &mut World, x: f32, y: f32, burst: Burst) {
+    let mut rng = rand::thread_rng();
+    for _ in 0..burst.count {
+        let angle = rng.gen_range(0.0, std::f32::consts::PI * 2.0);
+        let speed = rng.gen_range(0.2, 1.0) * burst.speed;
+        world.spawn((
+            Position { x, y },
+            Velocity { x: angle.cos() * speed, y: angle.sin() * speed },
+            Lifetime::new(burst.lifetime * rng.gen_range(0.6, 1.0)),
+            Particle { size: burst.size, color: burst.color },
+        ));
+    }
+    let mut particles: Vec<(Entity, f32)> = world.query_mut::<(&Lifetime, &Particle)>()
+        .into_iter()
+        .map(|(entity, (lifetime, _))| (entity, lifetime.age))
+        .collect();
+    if particles.len() > MAX_PARTICLES {
+        let excess = particles.len() - MAX_PARTICLES;
+        particles.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        for (entity, _) in particles.into_iter().take(excess) {
+            let _ = world.despawn(entity);
+        }
+    }
+}
+
+/// How many particles are alive.
+/// Bursts for hits, deaths and pickups.
+pub fn on_event(world: &mut World, event: &GameEvent) {
+    match event {
+        GameEvent::Hit { body, fatal, .. } => {
+            let burst = if *fatal { DEATH_BURST } else { HIT_IMPACT };
+            spawn(world, body.x + body.w / 2.0, body.y + body.h / 2.0, burst);
+        },
+        GameEvent::Pickup(pos) => spawn(world, pos.x + pos.w / 2.0, pos.y + pos.h / 2.0, PICKUP_SPARKLE),
+        _ => (),
+    }
+}
+
+pub fn count(world: &World) -> usize {
+    world.query::<&Particle>().iter().count()
+}
+
+/// Draws every particle, fading out as it ages.
+pub fn draw(ctx: &mut Context, world: &World) -> GameResult<()> {
+    let mut query = world.query::<(&Position, &Lifetime, &Particle)>();
+    let mut mesh = graphics::MeshBuilder::new();
+    let mut any = false;
+    for (_, (position, lifetime, particle)) in query.iter() {
+        let [r, g, b, a] = particle.color;
+        let fade = 1.0 - lifetime.progress();
+        mesh.circle(
+            graphics::DrawMode::fill(),
+            ggez::mint::Point2 { x: position.x, y: position.y },
+            particle.size,
+            0.5,
+            graphics::Color::new(r, g, b, a * fade),
+        )?;
+        any = true;
+    }
+    if !any {
+        return Ok(())
+    }
+    let mesh = mesh.build(ctx)?;
+    graphics::draw(ctx, &mesh, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))
+}
@@ -0,0 +1,107 @@
+//! Performance overlay, toggled with `controls::PERF_KEY`: frames per second,
+//! a graph of recent frame times, how long the last update took and how much
+//! is in the world.
+
+use ggez::{graphics, Context, GameResult};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Frames shown on the graph, one pixel wide each.
+const FRAMES_KEPT: usize = 120;
+/// Frame time at the top of the graph, in seconds.
+const GRAPH_MAX_SECONDS: f32 = 1.0 / 20.0;
+const GRAPH_HEIGHT: f32 = 40.0;
+/// Frames slower than these are drawn yellow and red.
+const SLOW_FRAME_SECONDS: f32 = 1.0 / 55.0;
+const DROPPED_FRAME_SECONDS: f32 = 1.0 / 30.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct Perf {
+    pub shown: bool,
+    /// Seconds each recent frame took, oldest first
+    frame_times: VecDeque<f32>,
+    /// How long game logic took on the last update
+    update_time: Duration,
+}
+
+impl Perf {
+
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+    }
+
+    pub fn record_frame(&mut self, frame: Duration) {
+        self.frame_times.push_back(frame.as_secs_f32());
+        if self.frame_times.len() > FRAMES_KEPT {
+            self.frame_times.pop_front();
+        }
+    }
+
+    pub fn record_update(&mut self, took: Duration) {
+        self.update_time = took;
+    }
+
+    /// Panel at `x`, `y` with the numbers, the frame time graph and `counts`
+    /// of things in the world by name.
+    pub fn draw(&self, ctx: &mut Context, x: f32, y: f32, fps: f64, counts: &[(&str, usize)]) -> GameResult<()> {
+        if !self.shown {
+            return Ok(())
+        }
+        let frame = self.frame_times.back().copied().unwrap_or(0.0);
+        let worst = self.frame_times.iter().copied().fold(0.0, f32::max);
+        let counted: Vec<String> = counts.iter().map(|(name, count)| format!("{} {}", name, count)).collect();
+        let lines = [
+            format!("{:.0} fps  frame {:.1} ms (worst {:.1})", fps, frame * 1000.0, worst * 1000.0),
+            format!("update {:.2} ms", self.update_time.as_secs_f32() * 1000.0),
+            counted.join("  "),
+        ];
+        let width = FRAMES_KEPT as f32 * 2.0 + 8.0;
+        let height = GRAPH_HEIGHT + lines.len() as f32 * 16.0 + 12.0;
+
+        let mut panel = graphics::MeshBuilder::new();
+        panel.rectangle(graphics::DrawMode::fill(), graphics::Rect::new(x, y, width, height), [0.0, 0.0, 0.0, 0.7].into())?;
+        let graph_bottom = y + 4.0 + GRAPH_HEIGHT;
+        for (index, seconds) in self.frame_times.iter().enumerate() {
+            let bar = (seconds / GRAPH_MAX_SECONDS).min(1.0) * GRAPH_HEIGHT;
+            let color = if *seconds > DROPPED_FRAME_SECONDS {
+                [1.0, 0.2, 0.2, 1.0]
+            } else if *seconds > SLOW_FRAME_SECONDS {
+                [1.0, 0.85, 0.2, 1.0]
+            } else {
+                [0.2, 1.0, 0.3, 1.0]
+            };
+            let column = graphics::Rect::new(x + 4.0 + index as f32 * 2.0, graph_bottom - bar, 2.0, bar.max(1.0));
+            panel.rectangle(graphics::DrawMode::fill(), column, color.into())?;
+        }
+        // Where a frame at 60 fps reaches
+        let target = graph_bottom - (1.0 / 60.0) / GRAPH_MAX_SECONDS * GRAPH_HEIGHT;
+        panel.line(
+            &[ggez::mint::Point2 { x: x + 4.0, y: target }, ggez::mint::Point2 { x: x + width - 4.0, y: target }],
+            1.0,
+            [1.0, 1.0, 1.0, 0.5].into(),
+        )?;
+        let panel = panel.build(ctx)?;
+        graphics::draw(ctx, &panel, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+
+        for (index, line) in lines.iter().enumerate() {
+            let text = graphics::Text::new(graphics::TextFragment {
+                    text: line.clone(),
+                    color: Some(graphics::Color::new(1.0, 1.0, 1.0, 1.0)),
+                    // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                    // `Font::default()` always exists and maps to DejaVuSerif.
+                    font: Some(graphics::Font::default()),
+                    scale: Some(graphics::PxScale { x: 14.0, y: 14.0 }),
+                });
+            graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: x + 4.0, y: graph_bottom + 4.0 + index as f32 * 16.0 }, None);
+        }
+        graphics::draw_queued_text(
+                ctx,
+                graphics::DrawParam::new()
+                    .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+                None,
+                graphics::FilterMode::Linear,
+            )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Collision queries against lots of boxes at once. Boxes are bucketed into a
+//! grid of cells, so asking what overlaps an area only looks at the few
+//! boxes in the cells it covers rather than at every one of them. Hashes of
+//! things that never move are built once; hashes of things that do are kept
+//! and only the boxes that changed are moved between cells.
+
+use std::collections::HashMap;
+
+use crate::Position;
+
+/// Side of a cell in pixels, a couple of player widths.
+const CELL_SIZE: f32 = 64.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Every box, indexed in the order they went in, `None` where one was
+    /// taken out
+    rects: Vec<Option<Position>>,
+}
+
+impl SpatialHash {
+
+    /// A hash of `rects`, each known by its index in the iterator so the
+    /// caller can find what it belongs to.
+    pub fn build(rects: impl IntoIterator<Item = Position>) -> SpatialHash {
+        let mut hash = SpatialHash::default();
+        for rect in rects {
+            hash.insert(rect);
+        }
+        hash
+    }
+
+    pub fn insert(&mut self, rect: Position) -> usize {
+        let index = self.rects.len();
+        self.update(index, Some(rect));
+        index
+    }
+
+    /// Moves box `index` to `rect`, or takes it out with `None`. Does
+    /// nothing when it's already there.
+    pub fn update(&mut self, index: usize, rect: Option<Position>) {
+        if index >= self.rects.len() {
+            self.rects.resize(index + 1, None);
+        }
+        let old = self.rects[index];
+        if same_box(old, rect) {
+            return;
+        }
+        if let Some(old) = old {
+            for cell in SpatialHash::cells_of(&old) {
+                if let Some(indexes) = self.cells.get_mut(&cell) {
+                    indexes.retain(|i| *i != index);
+                    if indexes.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                }
+            }
+        }
+        if let Some(rect) = rect {
+            for cell in SpatialHash::cells_of(&rect) {
+                self.cells.entry(cell).or_default().push(index);
+            }
+        }
+        self.rects[index] = rect;
+    }
+
+    /// Brings the hash in line with `rects`, the n-th of which is box n,
+    /// moving only the boxes that changed and dropping any past the end.
+    pub fn sync(&mut self, rects: impl IntoIterator<Item = Option<Position>>) {
+        let mut count = 0;
+        for (index, rect) in rects.into_iter().enumerate() {
+            self.update(index, rect);
+            count = index + 1;
+        }
+        for index in count..self.rects.len() {
+            self.update(index, None);
+        }
+        self.rects.truncate(count);
+    }
+
+    /// Indexes of the boxes overlapping `area`, lowest first.
+    pub fn query(&self, area: &Position) -> Vec<usize> {
+        let mut found: Vec<usize> = SpatialHash::cells_of(area)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|index| self.rects[*index].is_some_and(|rect| rect == *area))
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Whether anything at all overlaps `area`.
+    pub fn overlaps(&self, area: &Position) -> bool {
+        SpatialHash::cells_of(area)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .any(|index| self.rects[*index].is_some_and(|rect| rect == *area))
+    }
+
+    fn cells_of(rect: &Position) -> impl Iterator<Item = (i32, i32)> {
+        let (left, top) = ((rect.x / CELL_SIZE).floor() as i32, (rect.y / CELL_SIZE).floor() as i32);
+        let (right, bottom) = (((rect.x + rect.w) / CELL_SIZE).floor() as i32, ((rect.y + rect.h) / CELL_SIZE).floor() as i32);
+        (left..=right).flat_map(move |x| (top..=bottom).map(move |y| (x, y)))
+    }
+}
+
+/// Whether two boxes are exactly the same, where `==` on positions asks
+/// whether they overlap.
+fn same_box(a: Option<Position>, b: Option<Position>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.x == b.x && a.y == b.y && a.w == b.w && a.h == b.h,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Removes the items at `taken` from `items`, in any order and with no
+/// index repeated.
+#[cfg(feature = "server")]
+pub fn remove_taken<T>(items: &mut Vec<T>, mut taken: Vec<usize>) {
+    taken.sort_unstable();
+    for index in taken.into_iter().rev() {
+        items.remove(index);
+    }
+}
@@ -0,0 +1,81 @@
+//! Profiling with puffin, turned on with `--profile`. Update, draw,
+//! serialization and socket I/O are wrapped in scopes, collected frame by
+//! frame and saved to `profile.puffin` every few seconds, for opening in
+//! `puffin_viewer`. Without the flag the scopes cost next to nothing.
+
+use std::env;
+use std::fs;
+use std::path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often the collected frames are written out.
+const SAVE_SECONDS: u64 = 5;
+
+/// Where the profile is saved.
+pub fn profile_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("profile.puffin");
+        path
+    } else {
+        path::PathBuf::from("./profile.puffin")
+    }
+}
+
+/// Marks where frames start and saves them, for the client or the server
+/// whichever owns the process.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    /// Frames collected so far, only there when profiling
+    view: Option<Arc<puffin::GlobalFrameView>>,
+    last_save: Option<Instant>,
+}
+
+impl std::fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profiler").field("on", &self.view.is_some()).finish()
+    }
+}
+
+impl Profiler {
+
+    /// Starts collecting if `on`, otherwise does nothing at all.
+    pub fn start(on: bool) -> Profiler {
+        if !on {
+            return Profiler::default()
+        }
+        puffin::set_scopes_on(true);
+        println!("Profiling, saving to {} every {} seconds", profile_path().display(), SAVE_SECONDS);
+        Profiler {
+            view: Some(Arc::new(puffin::GlobalFrameView::default())),
+            last_save: Some(Instant::now()),
+        }
+    }
+
+    /// Ends the current frame and starts the next, saving now and then.
+    pub fn new_frame(&mut self) {
+        if self.view.is_none() {
+            return
+        }
+        puffin::GlobalProfiler::lock().new_frame();
+        if self.last_save.is_none_or(|saved| saved.elapsed() >= Duration::from_secs(SAVE_SECONDS)) {
+            self.save();
+            self.last_save = Some(Instant::now());
+        }
+    }
+
+    /// Writes out the frames collected so far, if profiling.
+    pub fn save(&self) {
+        let view = match &self.view {
+            Some(view) => view,
+            None => return,
+        };
+        let result = fs::File::create(profile_path())
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| view.lock().write(&mut file).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            println!("Unable to save profile: {}", e);
+        }
+    }
+}
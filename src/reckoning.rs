@@ -0,0 +1,57 @@
+//! Dead reckoning for the opponent, whose positions come in over the network
+//! at whatever pace the connection manages. Between them they're carried on
+//! at the speed they were last seen going, and when the real position turns
+//! up the difference is eased away rather than snapped to.
+
+use std::time::{Duration, Instant};
+
+/// Longest a position is carried on for without hearing anything, so a
+/// dropped connection doesn't send the opponent off across the map.
+const MAX_EXTRAPOLATION_MILLIS: u64 = 250;
+/// How long the drawn position takes to catch up with a real one.
+const BLEND_MILLIS: u64 = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct DeadReckoning {
+    /// Last position heard and when
+    last: Option<(f32, f32, Instant)>,
+    /// Pixels per second, from the last two positions
+    velocity: (f32, f32),
+    /// How far off the guess was when the last position came in, shrinking to
+    /// nothing over `BLEND_MILLIS`
+    correction: (f32, f32),
+}
+
+impl DeadReckoning {
+
+    /// Takes in a real position, with where the opponent was being shown at
+    /// the time. Moves faster than `max_speed` are teleports, respawning say,
+    /// and are snapped to.
+    pub fn receive(&mut self, x: f32, y: f32, shown: (f32, f32), max_speed: f32) {
+        let now = Instant::now();
+        let mut teleported = false;
+        if let Some((last_x, last_y, at)) = self.last {
+            let seconds = now.duration_since(at).as_secs_f32();
+            if seconds > 0.0 {
+                let velocity = ((x - last_x) / seconds, (y - last_y) / seconds);
+                teleported = velocity.0.hypot(velocity.1) > max_speed;
+                // Two arriving at once say nothing about speed, keep the last one
+                self.velocity = if teleported { (0.0, 0.0) } else { velocity };
+            }
+        }
+        self.correction = if teleported || self.last.is_none() { (0.0, 0.0) } else { (shown.0 - x, shown.1 - y) };
+        self.last = Some((x, y, now));
+    }
+
+    /// Where to show the opponent now, if anything has been heard yet.
+    pub fn position(&self) -> Option<(f32, f32)> {
+        let (x, y, at) = self.last?;
+        let age = at.elapsed();
+        let ahead = age.min(Duration::from_millis(MAX_EXTRAPOLATION_MILLIS)).as_secs_f32();
+        let blend = 1.0 - (age.as_millis() as f32 / BLEND_MILLIS as f32).min(1.0);
+        Some((
+            x + self.velocity.0 * ahead + self.correction.0 * blend,
+            y + self.velocity.1 * ahead + self.correction.1 * blend,
+        ))
+    }
+}
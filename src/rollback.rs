@@ -0,0 +1,183 @@
+//! Rollback for duels played with the `rollback` rule. Rather than waiting on
+//! the opponent's positions, each client simulates them from the inputs they
+//! send, guessing they keep doing what they last did until the real input for
+//! a frame turns up. A wrong guess rolls the opponent back to that frame and
+//! runs them forward again with what they actually pressed.
+//!
+//! Players don't collide with each other, so only the opponent ever needs
+//! rolling back, and the server still has the last word on hits and HP.
+//!
+//! Every so often each side sends a checksum of itself at a frame, which the
+//! other holds up against its simulation of them at that frame once it's
+//! confirmed. A mismatch means the two have drifted apart despite the same
+//! inputs, and the opponent gets put back where the server has them.
+
+#[cfg(feature = "client")]
+use std::collections::VecDeque;
+
+/// One frame of input, as bits.
+pub type FrameInput = u8;
+
+pub const UP: FrameInput = 1;
+pub const DOWN: FrameInput = 1 << 1;
+pub const LEFT: FrameInput = 1 << 2;
+pub const RIGHT: FrameInput = 1 << 3;
+pub const SPRINT: FrameInput = 1 << 4;
+/// Pressed on this frame, rather than held
+pub const JUMP: FrameInput = 1 << 5;
+pub const DASH: FrameInput = 1 << 6;
+
+/// Furthest back a late input can reach, older guesses are stuck with.
+#[cfg(feature = "client")]
+const MAX_ROLLBACK_FRAMES: usize = 30;
+/// Most inputs sent in one message, so they fit in a packet.
+pub const MAX_INPUTS_PER_MESSAGE: usize = 64;
+/// Frames between checksums, a second at the default rate.
+#[cfg(feature = "client")]
+pub const CHECKSUM_INTERVAL: u32 = 60;
+/// Checksums of the opponent kept to compare against theirs, which can be a
+/// few intervals behind.
+#[cfg(feature = "client")]
+const CHECKSUMS_KEPT: usize = 8;
+
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct Rollback<S> {
+    /// Frame the next step simulates
+    frame: u32,
+    /// Ours, from the first frame
+    local: Vec<FrameInput>,
+    /// The opponent's that have turned up, from the first frame
+    confirmed: Vec<FrameInput>,
+    /// For each frame from `pending_from`, the state before it and the input
+    /// it was simulated with
+    pending: VecDeque<(S, FrameInput)>,
+    pending_from: u32,
+    checksum: fn(&S) -> u64,
+    /// The opponent's checksum at every `CHECKSUM_INTERVAL`th frame that's
+    /// been confirmed, as `(frame, checksum)`
+    checksums: VecDeque<(u32, u64)>,
+}
+
+#[cfg(feature = "client")]
+impl<S: Clone> Rollback<S> {
+
+    /// `checksum` sums up a state, the same way for us and the opponent.
+    pub fn new(checksum: fn(&S) -> u64) -> Rollback<S> {
+        Rollback {
+            frame: 0,
+            local: vec![],
+            confirmed: vec![],
+            pending: VecDeque::new(),
+            pending_from: 0,
+            checksum,
+            checksums: VecDeque::new(),
+        }
+    }
+
+    /// Simulates the next frame, with `local` as ours and the opponent's
+    /// input if it's here or a guess at it if not.
+    pub fn advance(&mut self, state: &mut S, local: FrameInput, mut step: impl FnMut(&mut S, FrameInput)) {
+        self.local.push(local);
+        let input = self.input_for(self.frame);
+        if self.pending.is_empty() {
+            self.pending_from = self.frame;
+        }
+        // Known inputs leave nothing to go back to
+        if (self.frame as usize) >= self.confirmed.len() {
+            self.pending.push_back((state.clone(), input));
+            if self.pending.len() > MAX_ROLLBACK_FRAMES {
+                self.pending.pop_front();
+                self.pending_from += 1;
+            }
+        } else {
+            self.record_checksum(self.frame, state);
+        }
+        step(state, input);
+        self.frame += 1;
+    }
+
+    /// Takes in the opponent's inputs starting at frame `from`, rolling back
+    /// and simulating again if any of them went differently than guessed.
+    pub fn receive(&mut self, from: u32, inputs: &[FrameInput], state: &mut S, mut step: impl FnMut(&mut S, FrameInput)) {
+        let known = self.confirmed.len();
+        if from as usize > known {
+            return
+        }
+        self.confirmed.extend(inputs.iter().skip(known - from as usize));
+        let wrong = self.pending.iter().enumerate()
+            .take_while(|(offset, _)| (self.pending_from as usize + offset) < self.confirmed.len())
+            .find(|(offset, (_, guess))| self.confirmed[self.pending_from as usize + offset] != *guess)
+            .map(|(offset, _)| offset);
+        if let Some(wrong) = wrong {
+            *state = self.pending[wrong].0.clone();
+            for offset in wrong..self.pending.len() {
+                let input = self.input_for(self.pending_from + offset as u32);
+                self.pending[offset] = (state.clone(), input);
+                step(state, input);
+            }
+        }
+        // Confirmed frames can't be wrong any more
+        while (self.pending_from as usize) < self.confirmed.len() {
+            let (state, _) = match self.pending.pop_front() {
+                Some(pending) => pending,
+                None => break,
+            };
+            self.record_checksum(self.pending_from, &state);
+            self.pending_from += 1;
+        }
+    }
+
+    /// Gives up on rolling back before now, for when the state was set from
+    /// somewhere else.
+    pub fn resync(&mut self) {
+        self.pending.clear();
+        self.pending_from = self.frame;
+        self.checksums.clear();
+    }
+
+    /// Frame the next step simulates.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Whether the opponent's `checksum` at `frame` matches ours for them,
+    /// `None` if that frame isn't confirmed here yet or is too long ago.
+    pub fn matches(&self, frame: u32, checksum: u64) -> Option<bool> {
+        self.checksums.iter().find(|(checked, _)| *checked == frame).map(|(_, ours)| *ours == checksum)
+    }
+
+    /// Our inputs from frame `from` on, as many as fit in a message.
+    pub fn local_since(&self, from: u32) -> &[FrameInput] {
+        let from = (from as usize).min(self.local.len());
+        let to = (from + MAX_INPUTS_PER_MESSAGE).min(self.local.len());
+        &self.local[from..to]
+    }
+
+    /// How many of the opponent's inputs have turned up, the frame they're
+    /// wanted from next.
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed.len() as u32
+    }
+
+    /// Keeps the checksum of `state`, the opponent before `frame` is
+    /// simulated, if it's one that gets compared.
+    fn record_checksum(&mut self, frame: u32, state: &S) {
+        if !frame.is_multiple_of(CHECKSUM_INTERVAL) {
+            return
+        }
+        self.checksums.push_back((frame, (self.checksum)(state)));
+        if self.checksums.len() > CHECKSUMS_KEPT {
+            self.checksums.pop_front();
+        }
+    }
+
+    /// The opponent's input for `frame`, or what they last did if it's not here.
+    fn input_for(&self, frame: u32) -> FrameInput {
+        match self.confirmed.get(frame as usize) {
+            Some(input) => *input,
+            // Presses only happen once, held keys are what carry on
+            None => self.confirmed.last().map_or(0, |last| last & !(JUMP | DASH)),
+        }
+    }
+}
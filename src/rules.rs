@@ -0,0 +1,124 @@
+//! Match rules picked when a game is created, so joiners know what they're
+//! getting into.
+
+use serde::{Deserialize, Serialize};
+
+use crate::mode::GameMode;
+
+/// Games never take more players than this, whatever the rules say.
+pub const MAX_PLAYERS_LIMIT: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    /// Seconds before the match is decided
+    pub time_limit: f32,
+    /// Score that wins, the mode's own when `None`
+    pub score_limit: Option<f32>,
+    /// Seconds between item spawns
+    pub item_spawn_interval: f32,
+    /// Whether attacks hurt teammates
+    pub friendly_fire: bool,
+    /// The mode's own when `None`
+    pub max_players: Option<usize>,
+    /// Seconds without input before a player is marked AFK
+    pub afk_timeout: f32,
+    /// Whether players can jump once more in the air
+    pub double_jump: bool,
+    /// Duels only, clients swap inputs and simulate each other, see `rollback`
+    pub rollback: bool,
+    /// Scripts from the server's `scripts` directory run alongside the mode,
+    /// see `scripting`
+    pub scripts: Vec<String>,
+    /// Whether players see what spectators say, not just other spectators
+    pub all_chat: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> MatchRules {
+        MatchRules {
+            time_limit: crate::MATCH_SECONDS,
+            score_limit: None,
+            item_spawn_interval: crate::ITEM_SPAWN_INTERVAL_SECONDS,
+            friendly_fire: false,
+            max_players: None,
+            afk_timeout: crate::AFK_TIMEOUT_SECONDS,
+            double_jump: true,
+            rollback: false,
+            scripts: vec![],
+            all_chat: false,
+        }
+    }
+}
+
+impl MatchRules {
+
+    /// Parses the `newgame` meta, either a bare mode name like `koth` or a
+    /// JSON object with an optional `mode` and any rules to change, eg.
+    /// `{"mode":"ctf","time_limit":300,"friendly_fire":true}`.
+    pub fn from_meta(meta: &str) -> Result<(GameMode, MatchRules), String> {
+        if meta.is_empty() {
+            return Ok((GameMode::default(), MatchRules::default()))
+        }
+        if !meta.starts_with('{') {
+            return GameMode::from_name(meta)
+                .map(|mode| (mode, MatchRules::default()))
+                .ok_or_else(|| format!("Unknown game mode {}", meta))
+        }
+        let value: serde_json::Value = serde_json::from_str(meta).map_err(|e| e.to_string())?;
+        let mode = match value["mode"].as_str() {
+            Some(name) => GameMode::from_name(name).ok_or_else(|| format!("Unknown game mode {}", name))?,
+            None => GameMode::default(),
+        };
+        let rules: MatchRules = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        rules.validate()?;
+        if rules.rollback && mode != GameMode::Duel {
+            return Err("rollback is only for duels".to_string())
+        }
+        Ok((mode, rules))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.time_limit <= 0.0 {
+            return Err("time_limit must be positive".to_string())
+        }
+        if self.score_limit.is_some_and(|limit| limit <= 0.0) {
+            return Err("score_limit must be positive".to_string())
+        }
+        if self.item_spawn_interval <= 0.0 {
+            return Err("item_spawn_interval must be positive".to_string())
+        }
+        if self.afk_timeout <= 0.0 {
+            return Err("afk_timeout must be positive".to_string())
+        }
+        if self.max_players.is_some_and(|max| !(2..=MAX_PLAYERS_LIMIT).contains(&max)) {
+            return Err(format!("max_players must be between 2 and {}", MAX_PLAYERS_LIMIT))
+        }
+        if let Some(name) = self.scripts.iter().find(|name| !crate::scripting::valid_name(name)) {
+            return Err(format!("Invalid script name {}", name))
+        }
+        Ok(())
+    }
+
+    /// One line summary for the lobby.
+    pub fn describe(&self, mode: GameMode) -> String {
+        let score = match self.score_limit.or_else(|| mode.score_limit()) {
+            Some(limit) => format!("  Score: {}", limit),
+            None => "".to_string(),
+        };
+        let rollback = if self.rollback { "  Rollback" } else { "" };
+        let all_chat = if self.all_chat { "  All chat" } else { "" };
+        let scripts = if self.scripts.is_empty() { "".to_string() } else { format!("  Scripts: {}", self.scripts.join(", ")) };
+        format!(
+            "Time: {}s{}  Items: {}s  FF: {}  Double jump: {}{}{}{}",
+            self.time_limit,
+            score,
+            self.item_spawn_interval,
+            if self.friendly_fire { "on" } else { "off" },
+            if self.double_jump { "on" } else { "off" },
+            rollback,
+            all_chat,
+            scripts,
+        )
+    }
+}
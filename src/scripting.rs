@@ -0,0 +1,161 @@
+//! Game rules in Rhai scripts, so modes and mutators can be tried out without
+//! building a new server. A game runs the scripts its rules name, each read
+//! from `scripts/<name>.rhai` next to the maps, and any of them can define
+//!
+//! ```text
+//! fn on_tick(clock, players)          every server tick, clock in seconds
+//! fn on_pickup(player, item)          after a potion or gear is picked up
+//! fn on_hit(attacker, target, damage) before damage is dealt, returning a
+//!                                     number replaces it
+//! ```
+//!
+//! where players are names, and `players` is an array of maps with `name`,
+//! `hp`, `mp`, `level` and `score`. Scripts change the game by calling
+//! `heal`, `hurt`, `give_xp` and `add_score` with a player name and an
+//! amount, or `announce` with some text.
+
+#[cfg(feature = "server")]
+use std::cell::RefCell;
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::fs;
+#[cfg(feature = "server")]
+use std::path;
+#[cfg(feature = "server")]
+use std::rc::Rc;
+
+#[cfg(feature = "server")]
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Scope, AST, FLOAT, INT};
+
+/// Enough for any sensible hook, so a script stuck in a loop can't stall the
+/// server for everyone.
+#[cfg(feature = "server")]
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// What a script asked to be done to the game, carried out once it returns.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Heal(String, i64),
+    Hurt(String, i64),
+    GiveXp(String, u32),
+    AddScore(String, f32),
+    Announce(String),
+}
+
+#[cfg(feature = "server")]
+pub struct Scripting {
+    engine: Engine,
+    /// Script name to the compiled script
+    scripts: HashMap<String, AST>,
+    /// Filled in by the functions scripts call, during a hook
+    actions: Rc<RefCell<Vec<Action>>>,
+}
+
+#[cfg(feature = "server")]
+pub fn scripts_dir() -> path::PathBuf {
+    crate::assets::textures_dir().with_file_name("scripts")
+}
+
+/// Script names are file names without the extension, nothing that could
+/// reach outside the scripts directory.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(feature = "server")]
+impl Default for Scripting {
+    fn default() -> Scripting {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let actions = Rc::new(RefCell::new(vec![]));
+        let queue = actions.clone();
+        engine.register_fn("heal", move |name: &str, amount: INT| queue.borrow_mut().push(Action::Heal(name.to_string(), amount)));
+        let queue = actions.clone();
+        engine.register_fn("hurt", move |name: &str, amount: INT| queue.borrow_mut().push(Action::Hurt(name.to_string(), amount)));
+        let queue = actions.clone();
+        engine.register_fn("give_xp", move |name: &str, amount: INT| {
+            queue.borrow_mut().push(Action::GiveXp(name.to_string(), amount.max(0) as u32));
+        });
+        let queue = actions.clone();
+        engine.register_fn("add_score", move |name: &str, points: FLOAT| {
+            queue.borrow_mut().push(Action::AddScore(name.to_string(), points as f32));
+        });
+        // Whole points are written without a decimal point
+        let queue = actions.clone();
+        engine.register_fn("add_score", move |name: &str, points: INT| {
+            queue.borrow_mut().push(Action::AddScore(name.to_string(), points as f32));
+        });
+        let queue = actions.clone();
+        engine.register_fn("announce", move |text: &str| queue.borrow_mut().push(Action::Announce(text.to_string())));
+        Scripting {
+            engine,
+            scripts: HashMap::new(),
+            actions,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl Scripting {
+
+    /// Compiles the scripts called `names`, again if they were before so a
+    /// changed file is picked up by the next game to use it.
+    pub fn load(&mut self, names: &[String]) -> Result<(), String> {
+        for name in names {
+            let path = scripts_dir().join(format!("{}.rhai", name));
+            let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let script = self.engine.compile(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+            self.scripts.insert(name.clone(), script);
+        }
+        Ok(())
+    }
+
+    pub fn on_tick(&self, names: &[String], clock: f32, players: Array) -> Vec<Action> {
+        for name in names {
+            self.call(name, "on_tick", (clock as FLOAT, players.clone()));
+        }
+        self.take_actions()
+    }
+
+    pub fn on_pickup(&self, names: &[String], player: &str, item: &str) -> Vec<Action> {
+        for name in names {
+            self.call(name, "on_pickup", (player.to_string(), item.to_string()));
+        }
+        self.take_actions()
+    }
+
+    /// The damage to deal, as the last script to return a number says.
+    pub fn on_hit(&self, names: &[String], attacker: &str, target: &str, mut damage: i64) -> (i64, Vec<Action>) {
+        for name in names {
+            let returned = self.call(name, "on_hit", (attacker.to_string(), target.to_string(), damage as INT));
+            if let Some(changed) = returned.and_then(|value| value.as_int().ok()) {
+                damage = changed.max(0);
+            }
+        }
+        (damage, self.take_actions())
+    }
+
+    /// Runs `hook` in the script `name` if it has one. Errors only get a
+    /// mention, a broken script shouldn't take the game down with it.
+    fn call(&self, name: &str, hook: &str, args: impl rhai::FuncArgs) -> Option<Dynamic> {
+        let script = self.scripts.get(name)?;
+        if !script.iter_functions().any(|f| f.name == hook) {
+            return None
+        }
+        // Only the hook is run, not the script's top level again
+        let options = CallFnOptions::new().eval_ast(false);
+        match self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), script, hook, args) {
+            Ok(returned) => Some(returned),
+            Err(e) => {
+                println!("Script {} failed in {}: {}", name, hook, e);
+                None
+            },
+        }
+    }
+
+    fn take_actions(&self) -> Vec<Action> {
+        self.actions.borrow_mut().drain(..).collect()
+    }
+}
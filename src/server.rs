@@ -0,0 +1,7 @@
+// The dedicated server: the same code as the game, but it only ever hosts.
+// Built without the `client` feature it leaves out ggez and everything
+// windowing, graphics and sound with it, for headless boxes.
+
+fn main() -> Result<(), String> {
+    item_wars::run(true)
+}
@@ -0,0 +1,159 @@
+//! Choosing between servers by how far away they are. Each one is asked for
+//! its games, all at once, and the time the answer takes is its ping. Its
+//! version, uptime, message of the day and how full it is come along too,
+//! from `serverinfo`.
+//!
+//! Games are listed a page at a time so the answer fits in a packet however
+//! many a server has, and can be narrowed down by mode, map and whether
+//! there's room left.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::net;
+
+/// Most games in one `listgames` answer.
+#[cfg(feature = "server")]
+pub const GAMES_PER_PAGE: usize = 20;
+
+/// Which open games `listgames` answers with, sent as its meta.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameFilter {
+    /// Only games of this mode, by its `GameMode::name`
+    pub mode: Option<String>,
+    /// Only games on this map or voting on it
+    pub map: Option<String>,
+    /// Only games with room left
+    pub open: bool,
+    /// Which `GAMES_PER_PAGE` of the games that match, from 0
+    pub page: usize,
+}
+
+impl GameFilter {
+
+    /// Whether a game of `mode` on one of `maps`, with `players` of
+    /// `max_players` in it, should be listed.
+    #[cfg(feature = "server")]
+    pub fn matches(&self, mode: &str, maps: &[&str], players: usize, max_players: usize) -> bool {
+        self.mode.as_ref().is_none_or(|wanted| wanted.eq_ignore_ascii_case(mode))
+            && self.map.as_ref().is_none_or(|wanted| maps.iter().any(|map| wanted.eq_ignore_ascii_case(map)))
+            && (!self.open || players < max_players)
+    }
+}
+
+/// A page of `listgames`' answer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GamePage {
+    /// Each is the game ID, player count, mode, most players allowed and map
+    pub games: Vec<Vec<String>>,
+    pub page: usize,
+    /// How many pages there are of games matching the filter, at least 1
+    pub pages: usize,
+}
+
+impl GamePage {
+
+    /// Page `page` of `games`, which can be past the last and come out empty.
+    #[cfg(feature = "server")]
+    pub fn of(games: Vec<Vec<String>>, page: usize) -> GamePage {
+        GamePage {
+            pages: games.len().div_ceil(GAMES_PER_PAGE).max(1),
+            games: games.into_iter().skip(page * GAMES_PER_PAGE).take(GAMES_PER_PAGE).collect(),
+            page,
+        }
+    }
+}
+
+/// The open games on `host` that `filter` lets through, `None` if it
+/// didn't answer.
+pub fn list_games(host: &str, filter: &GameFilter) -> Option<GamePage> {
+    let meta = serde_json::to_string(filter).unwrap_or_default();
+    let listed = net::send_message(host.to_string(), "".to_string(), "".to_string(), "listgames".to_string(), meta, true)?;
+    Some(serde_json::from_str(&listed).unwrap_or_default())
+}
+
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub host: String,
+    /// `None` when the server didn't answer
+    pub ping: Option<Duration>,
+    /// A page of the server's open games, as `listgames` gives them
+    pub games: GamePage,
+    /// The server's version, uptime, message of the day and how much of its
+    /// caps it's using, as `serverinfo` gives them, `None` from servers too
+    /// old to say
+    pub info: Option<serde_json::Value>,
+}
+
+impl Listing {
+
+    /// One line for the list, eg. `localhost:7878  12ms  v0.1.0 (protocol 1),
+    /// up 3h 12m, 3/64 games, 5/128 players  [["1", "1", "duel", "2", "arena"]]
+    /// page 1/1  Welcome!`.
+    pub fn describe(&self) -> String {
+        match self.ping {
+            Some(ping) => format!(
+                "{}  {}ms  {}{:?}  page {}/{}{}",
+                self.host, ping.as_millis(), self.summary(), self.games.games, self.games.page + 1, self.games.pages, self.motd(),
+            ),
+            None => format!("{}  no answer", self.host),
+        }
+    }
+
+    /// Version, uptime, and games and players against the server's caps.
+    fn summary(&self) -> String {
+        let info = match &self.info {
+            Some(info) => info,
+            None => return String::new(),
+        };
+        let uptime = info["uptime_seconds"].as_u64().unwrap_or(0);
+        let full = if info["full"].as_bool().unwrap_or(false) { ", full" } else { "" };
+        format!(
+            "v{} (protocol {}), up {}h {}m, {}/{} games, {}/{} players{}  ",
+            info["version"].as_str().unwrap_or("?"), info["protocol"], uptime / 3600, uptime % 3600 / 60,
+            info["games"], info["max_games"], info["players"], info["max_players"], full,
+        )
+    }
+
+    fn motd(&self) -> String {
+        match self.info.as_ref().and_then(|info| info["motd"].as_str()) {
+            Some(motd) if !motd.is_empty() => format!("  {}", motd),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Asks every one of `hosts` for its games that `filter` lets through,
+/// nearest first and the ones that didn't answer last.
+pub fn survey(hosts: &[String], filter: &GameFilter) -> Vec<Listing> {
+    let mut listings: Vec<Listing> = std::thread::scope(|scope| {
+        let asked: Vec<_> = hosts.iter().map(|host| scope.spawn(move || {
+            let sent = Instant::now();
+            let games = list_games(host, filter);
+            let ping = games.as_ref().map(|_| sent.elapsed());
+            let info = ping.and_then(|_| net::send_message(host.clone(), "".to_string(), "".to_string(), "serverinfo".to_string(), "".to_string(), true))
+                .and_then(|info| serde_json::from_str(&info).ok());
+            Listing {
+                host: host.clone(),
+                ping,
+                games: games.unwrap_or_default(),
+                info,
+            }
+        })).collect();
+        asked.into_iter().filter_map(|listing| listing.join().ok()).collect()
+    });
+    listings.sort_by_key(|listing| listing.ping.unwrap_or(Duration::MAX));
+    listings
+}
+
+/// The nearest of `hosts` that answers, for when a player gives a few.
+#[cfg(feature = "client")]
+pub fn nearest(hosts: &[String]) -> Option<String> {
+    let listings = survey(hosts, &GameFilter::default());
+    for listing in listings.iter() {
+        println!("{}", listing.describe());
+    }
+    listings.into_iter().find(|listing| listing.ping.is_some()).map(|listing| listing.host)
+}
@@ -0,0 +1,86 @@
+//! Sessions recorded off a running server with `--record`, to be played
+//! back through a fresh one with `--replay`. A session is one JSON entry a
+//! line: each request as it came in with the answers the server gave it,
+//! and each game tick with how long it ran for, so reworking how requests
+//! are handled can be checked against real games.
+//!
+//! A session starts with the seed the server's random numbers came from, so
+//! player IDs, map shuffles, crits and item drops come out the same on a
+//! replay. The rules of play go by the time each entry was recorded at,
+//! see `clock`, so only answers holding the real time, like the uptime, are
+//! still going to differ. The sessions in `sessions/` are replayed by the
+//! tests.
+
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// Requests and ticks are stamped with when they happened, `at` nanoseconds
+/// into the session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Entry {
+    /// A request, `game:player:action:meta`, where it came from and what was
+    /// sent back
+    Request { at: u64, request: String, address: Option<String>, answers: Vec<String> },
+    /// A game tick running for `nanos` nanoseconds
+    Tick { at: u64, nanos: u64 },
+    /// What the server's random numbers were seeded with, first in a session
+    Seed { seed: u64 },
+}
+
+/// Writes a session as it happens.
+pub struct Recorder {
+    file: BufWriter<fs::File>,
+    started: Instant,
+}
+
+impl Recorder {
+
+    /// Records into `path`, replacing whatever session was there.
+    pub fn create(path: &path::Path) -> Result<Recorder, String> {
+        let file = fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Recorder { file: BufWriter::new(file), started: Instant::now() })
+    }
+
+    /// How far into the session `now` is, for stamping entries with.
+    pub fn at(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started).as_nanos() as u64
+    }
+
+    pub fn record(&mut self, entry: &Entry) {
+        if let Ok(line) = serde_json::to_string(entry) {
+            let _ = writeln!(self.file, "{}", line);
+            // Kept whole on disk, the server only stopping when killed
+            let _ = self.file.flush();
+        }
+    }
+}
+
+/// Reads back a session written by a `Recorder`.
+pub fn load(path: &path::Path) -> Result<Vec<Entry>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e)))
+        .collect()
+}
+
+/// A request answered differently on a replay than when it was recorded.
+pub struct Mismatch {
+    /// Which entry of the session, from 1
+    pub entry: usize,
+    pub request: String,
+    pub recorded: Vec<String>,
+    pub replayed: Vec<String>,
+}
+
+impl Mismatch {
+    pub fn describe(&self) -> String {
+        format!("Entry {}, {}\n  recorded: {:?}\n  replayed: {:?}", self.entry, self.request, self.recorded, self.replayed)
+    }
+}
@@ -0,0 +1,336 @@
+//! Graphics, audio and input options, saved to `settings.json` next to `textures/`
+//! and changed on the settings screen along with the controls.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::palette::{self, Palette, PALETTES};
+
+/// Window sizes offered, the first being what the game is laid out for.
+pub const RESOLUTIONS: [(f32, f32); 5] = [
+    (640.0, 480.0),
+    (800.0, 600.0),
+    (1024.0, 768.0),
+    (1280.0, 720.0),
+    (1920.0, 1080.0),
+];
+
+/// How much a volume changes per key press.
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Fullscreen,
+    Vsync,
+    Resolution,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    ShowPing,
+    ScreenShake,
+    HitStop,
+    LowHpFlash,
+    KillCam,
+    NamePlates,
+    NameBox,
+    Hud,
+    CasterHud,
+    ColorMode,
+    ToggleSprint,
+    AutoJump,
+    DoubleTapDash,
+    TouchControls,
+}
+
+/// Every setting, in the order the settings screen lists them.
+pub const SETTINGS: [Setting; 20] = [
+    Setting::Fullscreen,
+    Setting::Vsync,
+    Setting::Resolution,
+    Setting::MasterVolume,
+    Setting::MusicVolume,
+    Setting::SfxVolume,
+    Setting::ShowPing,
+    Setting::ScreenShake,
+    Setting::HitStop,
+    Setting::LowHpFlash,
+    Setting::KillCam,
+    Setting::NamePlates,
+    Setting::NameBox,
+    Setting::Hud,
+    Setting::CasterHud,
+    Setting::ColorMode,
+    Setting::ToggleSprint,
+    Setting::AutoJump,
+    Setting::DoubleTapDash,
+    Setting::TouchControls,
+];
+
+impl Setting {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Setting::Fullscreen => "Fullscreen",
+            Setting::Vsync => "Vsync",
+            Setting::Resolution => "Resolution",
+            Setting::MasterVolume => "Master volume",
+            Setting::MusicVolume => "Music volume",
+            Setting::SfxVolume => "SFX volume",
+            Setting::ShowPing => "Show ping",
+            Setting::ScreenShake => "Screen shake",
+            Setting::HitStop => "Hit-stop",
+            Setting::LowHpFlash => "Low HP flash",
+            Setting::KillCam => "Kill cam",
+            Setting::NamePlates => "Name plates",
+            Setting::NameBox => "Name box",
+            Setting::Hud => "HUD bars",
+            Setting::CasterHud => "Caster HUD",
+            Setting::ColorMode => "Colors",
+            Setting::ToggleSprint => "Sprint",
+            Setting::AutoJump => "Hold to jump",
+            Setting::DoubleTapDash => "Double tap dash",
+            Setting::TouchControls => "Touch controls",
+        }
+    }
+}
+
+/// Where the settings are saved.
+pub fn settings_path() -> path::PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("settings.json");
+        path
+    } else {
+        path::PathBuf::from("./settings.json")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub fullscreen: bool,
+    /// Only takes effect on the next start
+    pub vsync: bool,
+    /// Index into `RESOLUTIONS`
+    pub resolution: usize,
+    /// 0.0 - 1.0, scaling the music and SFX volumes
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub show_ping: bool,
+    /// Shake the camera when we get hit
+    pub screen_shake: bool,
+    /// Freeze for a moment when our swing lands
+    pub hit_stop: bool,
+    /// Pulse the screen edges red when low on HP
+    pub low_hp_flash: bool,
+    /// Replay the killing blow in slow motion
+    pub kill_cam: bool,
+    /// Names and levels above players
+    pub name_plates: bool,
+    /// The black box behind the name plates
+    pub name_box: bool,
+    /// The bars along the top and bottom of the screen with our stats
+    pub hud: bool,
+    /// Both players' bars along the top with the clock and score between
+    /// them and no chat, for capturing the window in a stream
+    pub caster_hud: bool,
+    /// Index into `palette::PALETTES`
+    pub color_mode: usize,
+    /// Sprint key switches sprint on and off instead of being held
+    pub toggle_sprint: bool,
+    /// Holding jump jumps again on landing
+    pub auto_jump: bool,
+    /// Tapping a direction twice dashes that way
+    pub double_tap_dash: bool,
+    /// The on-screen stick and buttons
+    pub touch_controls: bool,
+    /// Players whose chat we don't want to see, by name
+    pub muted: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            fullscreen: false,
+            vsync: true,
+            resolution: 0,
+            master_volume: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 0.8,
+            show_ping: false,
+            screen_shake: true,
+            hit_stop: true,
+            low_hp_flash: true,
+            kill_cam: true,
+            name_plates: true,
+            name_box: true,
+            hud: true,
+            caster_hud: false,
+            color_mode: 0,
+            toggle_sprint: false,
+            auto_jump: false,
+            double_tap_dash: false,
+            touch_controls: false,
+            muted: vec![],
+        }
+    }
+}
+
+impl Settings {
+
+    /// Reads `settings.json`, with defaults for anything missing or broken.
+    pub fn load() -> Settings {
+        let contents = match fs::read_to_string(settings_path()) {
+            Ok(contents) => contents,
+            Err(_e) => return Settings::default(),
+        };
+        match serde_json::from_str::<Settings>(&contents) {
+            Ok(mut settings) => {
+                settings.resolution = settings.resolution.min(RESOLUTIONS.len() - 1);
+                settings.color_mode = settings.color_mode.min(PALETTES.len() - 1);
+                settings
+            },
+            Err(e) => {
+                println!("Ignoring {}: {}", settings_path().display(), e);
+                Settings::default()
+            },
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(settings_path(), contents)
+    }
+
+    pub fn is_muted(&self, name: &str) -> bool {
+        self.muted.iter().any(|muted| muted.eq_ignore_ascii_case(name))
+    }
+
+    /// Mutes `name`, or unmutes them if they were. Returns whether they're
+    /// muted now.
+    pub fn toggle_mute(&mut self, name: &str) -> bool {
+        if self.is_muted(name) {
+            self.muted.retain(|muted| !muted.eq_ignore_ascii_case(name));
+            false
+        } else {
+            self.muted.push(name.to_string());
+            true
+        }
+    }
+
+    pub fn window_size(&self) -> (f32, f32) {
+        RESOLUTIONS[self.resolution % RESOLUTIONS.len()]
+    }
+
+    pub fn palette(&self) -> &'static Palette {
+        palette::palette(self.color_mode)
+    }
+
+    /// Flips a toggle, or steps a value up or down.
+    pub fn change(&mut self, setting: Setting, up: bool) {
+        let step = if up { VOLUME_STEP } else { -VOLUME_STEP };
+        match setting {
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::Resolution => {
+                let count = RESOLUTIONS.len();
+                self.resolution = if up { (self.resolution + 1) % count } else { (self.resolution + count - 1) % count };
+            },
+            Setting::MasterVolume => self.master_volume = (self.master_volume + step).clamp(0.0, 1.0),
+            Setting::MusicVolume => self.music_volume = (self.music_volume + step).clamp(0.0, 1.0),
+            Setting::SfxVolume => self.sfx_volume = (self.sfx_volume + step).clamp(0.0, 1.0),
+            Setting::ShowPing => self.show_ping = !self.show_ping,
+            Setting::ScreenShake => self.screen_shake = !self.screen_shake,
+            Setting::HitStop => self.hit_stop = !self.hit_stop,
+            Setting::LowHpFlash => self.low_hp_flash = !self.low_hp_flash,
+            Setting::KillCam => self.kill_cam = !self.kill_cam,
+            Setting::NamePlates => self.name_plates = !self.name_plates,
+            Setting::NameBox => self.name_box = !self.name_box,
+            Setting::Hud => self.hud = !self.hud,
+            Setting::CasterHud => self.caster_hud = !self.caster_hud,
+            Setting::ColorMode => {
+                let count = PALETTES.len();
+                self.color_mode = if up { (self.color_mode + 1) % count } else { (self.color_mode + count - 1) % count };
+            },
+            Setting::ToggleSprint => self.toggle_sprint = !self.toggle_sprint,
+            Setting::AutoJump => self.auto_jump = !self.auto_jump,
+            Setting::DoubleTapDash => self.double_tap_dash = !self.double_tap_dash,
+            Setting::TouchControls => self.touch_controls = !self.touch_controls,
+        }
+    }
+
+    /// The setting's value as shown on the settings screen.
+    pub fn describe(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let percent = |volume: f32| format!("{}%", (volume * 100.0).round());
+        match setting {
+            Setting::Fullscreen => on_off(self.fullscreen),
+            Setting::Vsync => format!("{} (on restart)", on_off(self.vsync)),
+            Setting::Resolution => {
+                let (width, height) = self.window_size();
+                format!("{}x{}", width, height)
+            },
+            Setting::MasterVolume => percent(self.master_volume),
+            Setting::MusicVolume => percent(self.music_volume),
+            Setting::SfxVolume => percent(self.sfx_volume),
+            Setting::ShowPing => on_off(self.show_ping),
+            Setting::ScreenShake => on_off(self.screen_shake),
+            Setting::HitStop => on_off(self.hit_stop),
+            Setting::LowHpFlash => on_off(self.low_hp_flash),
+            Setting::KillCam => on_off(self.kill_cam),
+            Setting::NamePlates => on_off(self.name_plates),
+            Setting::NameBox => on_off(self.name_box),
+            Setting::Hud => on_off(self.hud),
+            Setting::CasterHud => on_off(self.caster_hud),
+            Setting::ColorMode => self.palette().name.to_string(),
+            Setting::ToggleSprint => if self.toggle_sprint { "toggle" } else { "hold" }.to_string(),
+            Setting::AutoJump => on_off(self.auto_jump),
+            Setting::DoubleTapDash => on_off(self.double_tap_dash),
+            Setting::TouchControls => on_off(self.touch_controls),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingsPage {
+    Options,
+    Controls,
+}
+
+/// Where the settings screen is at while it's open.
+#[derive(Debug, Clone)]
+pub struct SettingsScreen {
+    pub page: SettingsPage,
+    /// Index into `SETTINGS` or `controls::ACTIONS`, depending on the page
+    pub selected: usize,
+    /// Waiting for the new key of the selected action
+    pub rebinding: bool,
+    /// Why the last rebind was refused
+    pub message: Option<String>,
+}
+
+impl Default for SettingsScreen {
+    fn default() -> SettingsScreen {
+        SettingsScreen {
+            page: SettingsPage::Options,
+            selected: 0,
+            rebinding: false,
+            message: None,
+        }
+    }
+}
+
+impl SettingsScreen {
+    /// Rows on the current page.
+    pub fn rows(&self) -> usize {
+        match self.page {
+            SettingsPage::Options => SETTINGS.len(),
+            SettingsPage::Controls => crate::controls::ACTIONS.len(),
+        }
+    }
+}
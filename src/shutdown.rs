@@ -0,0 +1,34 @@
+//! Ctrl-C and SIGTERM on a hosting server. They're caught rather than left to
+//! kill the process, so the server can tell the games it's going, keep
+//! answering long enough for their players to find out, and save what it has.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches the signals from now on.
+pub fn catch_signals() {
+    let handler = on_signal as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    }
+}
+
+/// Asks for a shutdown, or exits on the spot if one was already asked for so
+/// a stuck server can still be stopped.
+extern "C" fn on_signal(_signal: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) }
+    }
+}
+
+/// Asks for a shutdown as a signal would, eg. from the console's `exit`.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether a signal has asked the server to shut down.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
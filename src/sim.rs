@@ -0,0 +1,702 @@
+//! The rules of play, run the same way by the server's ticks and by the
+//! client predicting its own player and replaying the opponent's inputs, so
+//! the two can't drift apart: movement and collision, swings and hits, and
+//! items appearing and being picked up.
+//!
+//! Hits and pickups are only ever worked out by the server, clients wait to
+//! hear about them, but they live here with the movement so everything a
+//! tick does is in one place.
+
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[cfg(feature = "server")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "server")]
+use rand::Rng;
+
+use crate::map::{Map, Terrain};
+#[cfg(feature = "server")]
+use crate::physics;
+use crate::physics::SpatialHash;
+#[cfg(feature = "server")]
+use crate::scripting::Scripting;
+use crate::rollback;
+use crate::status::StatusKind;
+#[cfg(feature = "server")]
+use crate::status::StatusEffect;
+use crate::{clock, config, ctf, Crate, Direction, PlayerState, Position, Potion, Trap};
+#[cfg(feature = "server")]
+use crate::{GameMode, ItemSpawn, Loot, NetworkedGame, PotionType};
+use crate::{
+    ATTACK_COOLDOWN_MILLIS, ATTACK_REACH, DASH_COOLDOWN_MILLIS, DASH_MP_COST, DASH_SPEED, DASH_UPDATES,
+    JUMP_STAMINA_COST, PLAYER_CELL_HEIGHT, PLAYER_CELL_WIDTH, PLAYER_MAX_STAMINA, SPRINT_SPEED_MULTIPLIER,
+    SPRINT_STAMINA_PER_SECOND, STAMINA_REGEN_PER_SECOND, STAMINA_WINDED_RECOVERY,
+};
+#[cfg(feature = "server")]
+use crate::{
+    BLOCK_DAMAGE_FACTOR, COMBO_DAMAGE_BONUS, COMBO_MAX_BONUS_HITS, COMBO_WINDOW_MILLIS, CRATE_EQUIPMENT_CHANCE,
+    CRIT_CHANCE, CRIT_MULTIPLIER, DAMAGE_PER_STRENGTH, DEFENSE_SCALE, ITEM_SPAWN_WARNING_SECONDS, JUMP_DODGE_HEIGHT,
+    JUMP_STAMINA_SLACK, MAX_ITEMS, MOVE_VALIDATION_SLACK_TICKS, PARRY_WINDOW_MILLIS, PLAYER_MAX_HP, PLAYER_MAX_MP, POTION_BUFF_SECONDS, POTION_HEALTH_RESTORE,
+    POTION_HEIGHT, POTION_INVISIBILITY_SECONDS, POTION_MANA_RESTORE, POTION_SPEED_BOOST, POTION_STRENGTH_BOOST,
+    POTION_WIDTH, SPAWN_EQUIPMENT_CHANCE, STAGGER_MILLIS, TRAP_DAMAGE, TRAP_POISON, TRAP_POISON_SECONDS,
+    XP_PER_KILL, XP_PER_PICKUP,
+};
+
+/// Brings `obstacles`, what players can't walk through besides the walls,
+/// in line with the crates still standing.
+#[cfg(feature = "native")]
+pub fn sync_obstacles(obstacles: &mut SpatialHash, crates: &[Crate]) {
+    obstacles.sync(crates.iter().map(|c| if c.broken { None } else { Some(c.pos) }));
+}
+
+/// Everything in a game that moves or comes and goes, kept hashed from one
+/// tick to the next for swings, pickups and captures to look things up in.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Default)]
+pub struct Collisions {
+    pub players: SpatialHash,
+    /// The crates still standing, which are also the obstacles
+    pub crates: SpatialHash,
+    pub potions: SpatialHash,
+    pub equipment: SpatialHash,
+    pub traps: SpatialHash,
+    pub flags: SpatialHash,
+}
+
+#[cfg(feature = "server")]
+impl NetworkedGame {
+
+    /// Moves whatever moved, broke, dropped or was taken since last time.
+    pub fn sync_collisions(&mut self) {
+        let collisions = &mut self.collisions;
+        collisions.players.sync(self.players.iter().map(|p| Some(p.body)));
+        sync_obstacles(&mut collisions.crates, &self.crates);
+        collisions.potions.sync(self.potions.iter().map(|potion| Some(potion.pos)));
+        collisions.equipment.sync(self.equipment.iter().map(|drop| Some(drop.pos)));
+        collisions.traps.sync(self.traps.iter().map(|trap| Some(trap.pos)));
+        collisions.flags.sync(self.flags.iter().map(|flag| Some(flag.pos)));
+    }
+}
+
+// Movement and collision
+impl PlayerState {
+
+    pub fn reset_last_dir(&mut self) {
+        self.last_dir.left = false;
+        self.last_dir.right = false;
+        self.last_dir.up = false;
+        self.last_dir.down = false;
+    }
+
+    /// Moves the body by the given offset unless that would put it inside a
+    /// wall or one of the `obstacles`.
+    pub fn step(&mut self, x: f32, y: f32, map: &Map, obstacles: &SpatialHash) -> bool {
+        let mut next = self.body;
+        next.x += x;
+        next.y += y;
+        if map.is_blocked(&next) || obstacles.overlaps(&next) {
+            false
+        } else {
+            self.body = next;
+            true
+        }
+    }
+
+    /// Farthest anyone can move along an axis in one update on `terrain`.
+    pub fn max_step(terrain: Terrain, dashing: bool) -> f32 {
+        if dashing {
+            DASH_SPEED * terrain.speed
+        } else {
+            (config::tunables().move_speed + config::tunables().top_accel) * terrain.speed
+        }
+    }
+
+    pub fn can_dash(&self) -> bool {
+        let cooled_down = match self.since_dash {
+            Some(since_dash) => since_dash >= Duration::from_millis(DASH_COOLDOWN_MILLIS),
+            None => true,
+        };
+        cooled_down && self.mp >= DASH_MP_COST
+    }
+
+    /// True while a dash the server agreed to is still running, with the same
+    /// again as leeway for late position packets.
+    #[cfg(feature = "server")]
+    pub fn dash_active(&self) -> bool {
+        match self.since_dash {
+            Some(since_dash) => since_dash < Duration::from_millis(config::tunables().millis_per_update() * DASH_UPDATES as u64 * 2),
+            None => false,
+        }
+    }
+
+    pub fn start_dash(&mut self) {
+        let facing = if self.is_moving() { &self.dir } else { &self.last_dir };
+        if !self.can_dash() || !(facing.up || facing.down || facing.left || facing.right) {
+            return
+        }
+        self.last_dir = facing.clone();
+        self.dash_updates = DASH_UPDATES;
+        self.dashing = true;
+        self.since_dash = Some(Duration::ZERO);
+        self.mp -= DASH_MP_COST;
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.sprint_held && !self.winded && self.is_moving()
+    }
+
+    /// Does what was pressed on a frame, the opponent's as they sent it or
+    /// ours in the browser.
+    pub fn apply_input(&mut self, input: rollback::FrameInput, double_jump: bool) {
+        self.dir = Direction {
+            up: input & rollback::UP != 0,
+            down: input & rollback::DOWN != 0,
+            left: input & rollback::LEFT != 0,
+            right: input & rollback::RIGHT != 0,
+        };
+        self.throttle = 1.0;
+        self.sprint_held = input & rollback::SPRINT != 0;
+        if input & rollback::JUMP != 0 {
+            self.jump(double_jump);
+        }
+        if input & rollback::DASH != 0 {
+            self.start_dash();
+        }
+    }
+
+    /// High enough off the ground that swings and traps go under.
+    #[cfg(feature = "server")]
+    pub fn airborne(&self) -> bool {
+        self.jumping && self.jump_offset >= config::tunables().jump_height() * JUMP_DODGE_HEIGHT
+    }
+
+    /// Takes a client's word that it's jumping only as far as the rules go:
+    /// leaving the ground costs stamina, a jump stays up no longer than its
+    /// arc, once more with `double_jump`, and never goes above its peak.
+    #[cfg(feature = "server")]
+    pub fn claim_jump(&mut self, jumping: bool, offset: f32, double_jump: bool) {
+        let arc = Duration::from_millis(
+            ((config::tunables().jump_updates() + MOVE_VALIDATION_SLACK_TICKS) * config::tunables().millis_per_update() as f32) as u64,
+        );
+        let landed = self.jump_started.is_some_and(|started| clock::since(started) >= arc);
+        let leaving = self.jump_started.is_none() || (landed && double_jump && !self.air_jumped);
+        let can_afford = self.stamina + JUMP_STAMINA_SLACK >= JUMP_STAMINA_COST;
+        if !jumping || (landed && !leaving) || (leaving && !can_afford) {
+            self.jumping = false;
+            self.jump_offset = 0.0;
+            self.air_jumped = false;
+            self.jump_started = None;
+            return
+        }
+        if leaving {
+            self.air_jumped = self.jump_started.is_some();
+            self.jump_started = Some(clock::now());
+            self.stamina = (self.stamina - JUMP_STAMINA_COST).max(0.0);
+        }
+        self.jumping = true;
+        self.jump_offset = offset.clamp(0.0, config::tunables().jump_height());
+    }
+
+    /// Jumps off the ground, or once more in the air if `double_jump` is
+    /// allowed. Presses that can't jump are held on to for a moment in case
+    /// we're about to land.
+    pub fn jump(&mut self, double_jump: bool) {
+        let can_leave = !self.jumping || (double_jump && !self.air_jumped);
+        if can_leave && !self.winded && self.stamina >= JUMP_STAMINA_COST {
+            self.air_jumped = self.jumping;
+            self.jumping = true;
+            self.jump_velocity = config::tunables().jump_velocity;
+            self.jump_buffered = None;
+            self.stamina -= JUMP_STAMINA_COST;
+        } else if self.jumping {
+            self.jump_buffered = Some(Duration::ZERO);
+        }
+    }
+
+    /// Rises and falls along an arc, landing when back at the ground.
+    pub fn update_jump(&mut self, ticks: f32) {
+        if !self.jumping {
+            self.jump_offset = 0.0;
+            return
+        }
+        // Everyone else's jumps are only known from `jumping`, so start them here
+        if self.jump_offset <= 0.0 && self.jump_velocity <= 0.0 {
+            self.jump_velocity = config::tunables().jump_velocity;
+        }
+        self.jump_offset += self.jump_velocity * ticks;
+        self.jump_velocity -= config::tunables().jump_gravity * ticks;
+        if self.jump_offset <= 0.0 {
+            self.jumping = false;
+            self.jump_offset = 0.0;
+            self.jump_velocity = 0.0;
+            self.air_jumped = false;
+            let buffered = self.jump_buffered.take();
+            // Pressed just before landing, it goes off as soon as we touch down
+            if buffered.is_some_and(|since| since < Duration::from_millis(config::get().input_buffer_millis)) {
+                self.jump(false);
+            }
+        }
+    }
+
+    /// Moves the dash cooldown and jump buffer on by `elapsed` seconds.
+    pub fn tick_timers(&mut self, elapsed: f32) {
+        let elapsed = Duration::from_secs_f32(elapsed);
+        if let Some(since_dash) = self.since_dash.as_mut() {
+            *since_dash += elapsed;
+        }
+        if let Some(since_press) = self.jump_buffered.as_mut() {
+            *since_press += elapsed;
+        }
+    }
+
+    /// Sprinting wears stamina down, walking or standing about gets it back.
+    /// Running out leaves the player winded for a while.
+    pub fn update_stamina(&mut self, elapsed: f32) {
+        if self.is_sprinting() {
+            self.stamina -= SPRINT_STAMINA_PER_SECOND * elapsed;
+            if self.stamina <= 0.0 {
+                self.stamina = 0.0;
+                self.winded = true;
+            }
+        } else if !self.jumping {
+            self.stamina = (self.stamina + STAMINA_REGEN_PER_SECOND * elapsed).min(PLAYER_MAX_STAMINA);
+        }
+        if self.winded && self.stamina >= STAMINA_WINDED_RECOVERY {
+            self.winded = false;
+        }
+    }
+
+    /// Diagonals cover the same ground per update as straight lines.
+    pub fn diagonal_factor(dir: &Direction) -> f32 {
+        if dir.is_diagonal() {
+            std::f32::consts::FRAC_1_SQRT_2
+        } else {
+            1.0
+        }
+    }
+
+    /// Burst along the facing direction, ignoring acceleration.
+    pub fn move_dash(&mut self, ticks: f32, map: &Map, obstacles: &SpatialHash) {
+        let speed = DASH_SPEED * map.terrain_at(&self.body).speed * PlayerState::diagonal_factor(&self.last_dir) * ticks;
+        if self.last_dir.up && self.body.y > PLAYER_CELL_HEIGHT {
+            self.step(0.0, -speed, map, obstacles);
+        }
+        if self.last_dir.down && self.body.y < map.pixel_height() - (PLAYER_CELL_HEIGHT * 2.0) {
+            self.step(0.0, speed, map, obstacles);
+        }
+        if self.last_dir.left && self.body.x > 0.0 {
+            self.step(-speed, 0.0, map, obstacles);
+        }
+        if self.last_dir.right && self.body.x < map.pixel_width() - PLAYER_CELL_WIDTH {
+            self.step(speed, 0.0, map, obstacles);
+        }
+        self.dash_updates -= 1;
+        self.dashing = self.dash_updates > 0;
+        self.current_accel = config::tunables().top_accel;
+    }
+
+    pub fn move_direction(&mut self, ticks: f32, map: &Map, obstacles: &SpatialHash) {
+        self.reset_last_dir();
+        if self.current_accel < config::tunables().top_accel {
+            self.current_accel = (self.current_accel + config::tunables().accel * ticks).min(config::tunables().top_accel);
+        }
+        let terrain = map.terrain_at(&self.body);
+        let sprint = if self.is_sprinting() { SPRINT_SPEED_MULTIPLIER } else { 1.0 };
+        let speed = (config::tunables().move_speed + self.current_accel) * terrain.speed * self.speed_multiplier() * sprint
+            * self.throttle * PlayerState::diagonal_factor(&self.dir) * ticks;
+        if self.dir.up && self.body.y > PLAYER_CELL_HEIGHT {
+            self.last_dir.up = self.step(0.0, -speed, map, obstacles);
+        }
+        if self.dir.down && self.body.y < map.pixel_height() - (PLAYER_CELL_HEIGHT * 2.0) {
+            self.last_dir.down = self.step(0.0, speed, map, obstacles);
+        }
+        if self.dir.left && self.body.x > 0.0 {
+            self.last_dir.left = self.step(-speed, 0.0, map, obstacles);
+        }
+        if self.dir.right && self.body.x < map.pixel_width() - PLAYER_CELL_WIDTH {
+            self.last_dir.right = self.step(speed, 0.0, map, obstacles);
+        }
+    }
+
+    pub fn move_direction_cooldown(&mut self, ticks: f32, map: &Map, obstacles: &SpatialHash) {
+        let terrain = map.terrain_at(&self.body);
+        let speed = (config::tunables().move_speed + self.current_accel) * terrain.speed * self.speed_multiplier()
+            * self.throttle * PlayerState::diagonal_factor(&self.last_dir) * ticks;
+        if self.last_dir.up && self.body.y > PLAYER_CELL_HEIGHT {
+            self.step(0.0, -speed, map, obstacles);
+        }
+        if self.last_dir.down && self.body.y < map.pixel_height() - (PLAYER_CELL_HEIGHT * 2.0) {
+            self.step(0.0, speed, map, obstacles);
+        }
+        if self.last_dir.left && self.body.x > 0.0 {
+            self.step(-speed, 0.0, map, obstacles);
+        }
+        if self.last_dir.right && self.body.x < map.pixel_width() - PLAYER_CELL_WIDTH {
+            self.step(speed, 0.0, map, obstacles);
+        }
+        if self.current_accel > 0.0 {
+            self.current_accel -= config::tunables().accel * terrain.friction * ticks;
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.dir.up || self.dir.down || self.dir.left || self.dir.right
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        let gear: f32 = self.equipment.iter().map(|e| e.speed_multiplier()).product();
+        let flag = if self.carrying_flag.is_some() { ctf::FLAG_CARRIER_SPEED } else { 1.0 };
+        self.statuses.iter().map(|s| s.speed_multiplier()).product::<f32>() * gear * flag
+    }
+
+    /// Moves the player on by `elapsed` seconds. Speeds are given per update,
+    /// so they're scaled by how many updates' worth of time that is.
+    pub fn update(&mut self, elapsed: f32, do_move: bool, map: &Map, obstacles: &SpatialHash) {
+        let ticks = elapsed * config::tunables().updates_per_second;
+        self.tick_timers(elapsed);
+        self.update_jump(ticks);
+        self.update_stamina(elapsed);
+        if do_move {
+            if self.dash_updates > 0 {
+                self.move_dash(ticks, map, obstacles)
+            } else if self.is_moving() {
+                self.move_direction(ticks, map, obstacles)
+            } else if self.current_accel > config::tunables().starting_accel {
+                self.move_direction_cooldown(ticks, map, obstacles)
+            }
+        }
+        //if self.eats(food) && !self.jumping {
+        //    self.ate = Some(food.clone());
+        //} else {
+        //    self.ate = None
+        //}
+    }
+}
+
+// Swings, hits and guarding
+impl PlayerState {
+
+    /// Area in front of the player hit by a swing.
+    pub fn attack_reach(&self) -> Position {
+        self.reach_towards(&self.last_dir)
+    }
+
+    /// Area a swing would hit facing `dir`.
+    pub fn reach_towards(&self, dir: &Direction) -> Position {
+        let mut reach = self.body;
+        if dir.up {
+            reach.y -= ATTACK_REACH;
+            reach.h = ATTACK_REACH;
+        } else if dir.left {
+            reach.x -= ATTACK_REACH;
+            reach.w = ATTACK_REACH;
+        } else if dir.right {
+            reach.x += self.body.w;
+            reach.w = ATTACK_REACH;
+        } else {
+            reach.y += self.body.h;
+            reach.h = ATTACK_REACH;
+        }
+        reach
+    }
+
+    pub fn can_attack(&self) -> bool {
+        let cooled_down = match self.last_attack {
+            Some(last_attack) => clock::since(last_attack) >= Duration::from_millis(ATTACK_COOLDOWN_MILLIS),
+            None => true,
+        };
+        cooled_down && !self.staggered && !self.blocking
+    }
+
+    /// Raising the guard just before a hit lands parries it.
+    #[cfg(feature = "server")]
+    pub fn parrying(&self) -> bool {
+        match self.block_started {
+            Some(block_started) => self.blocking && clock::since(block_started) < Duration::from_millis(PARRY_WINDOW_MILLIS),
+            None => false,
+        }
+    }
+
+    /// Whether the last hit landed recently enough for the next to add to the combo.
+    #[cfg(feature = "server")]
+    pub fn combo_alive(&self) -> bool {
+        self.combo_at.is_some_and(|at| clock::since(at) < Duration::from_millis(COMBO_WINDOW_MILLIS))
+    }
+
+    /// STR including any strength buff and weapon.
+    pub fn strength(&self) -> i64 {
+        let bonus: f32 = self.statuses.iter()
+            .filter(|s| s.kind == StatusKind::Strength)
+            .map(|s| s.magnitude)
+            .sum();
+        let gear: i64 = self.equipment.iter().map(|e| e.strength_bonus()).sum();
+        self.str + bonus as i64 + gear
+    }
+
+    /// Armor from equipment, see `hit_damage`.
+    #[cfg(feature = "server")]
+    pub fn defense(&self) -> i64 {
+        self.equipment.iter().map(|e| e.defense_bonus()).sum()
+    }
+
+    /// What a hit with `strength` behind it takes off someone with `defense`.
+    /// Each point of defense cuts a bit less than the last, so armor always
+    /// lets a scratch through.
+    #[cfg(feature = "server")]
+    pub fn hit_damage(strength: i64, defense: i64, crit: bool) -> i64 {
+        let raw = strength as f32 * DAMAGE_PER_STRENGTH;
+        let raw = if crit { raw * CRIT_MULTIPLIER } else { raw };
+        let damage = raw * DEFENSE_SCALE / (DEFENSE_SCALE + defense.max(0) as f32);
+        (damage.round() as i64).max(1)
+    }
+
+    pub fn set_blocking(&mut self, blocking: bool) {
+        if blocking && !self.blocking {
+            self.block_started = Some(clock::now());
+        }
+        self.blocking = blocking;
+    }
+}
+
+/// Resolves a swing against the crates and players in front of the attacker.
+#[cfg(feature = "server")]
+pub fn attack(game: &mut NetworkedGame, scripting: &Scripting, attacker_id: &str, rng: &mut impl Rng) {
+    let (attacker_name, reach, damage, team, combo) = match game.players.iter_mut().find(|p| p.id == attacker_id) {
+        Some(attacker) => {
+            if !attacker.can_attack() {
+                return
+            }
+            attacker.last_attack = Some(clock::now());
+            attacker.attack_count += 1;
+            let combo = if attacker.combo_alive() { attacker.combo } else { 0 };
+            (attacker.name.clone(), attacker.attack_reach(), attacker.strength(), attacker.team, combo)
+        },
+        None => return,
+    };
+    game.sync_collisions();
+    let mut loot = vec![];
+    for index in game.collisions.crates.query(&reach) {
+        if let Some(dropped) = game.crates[index].hit(damage, rng) {
+            loot.push(dropped);
+        }
+    }
+    for dropped in loot {
+        game.drop_loot(dropped);
+    }
+    let crit = rng.gen_bool(CRIT_CHANCE);
+    let combo_bonus = 1.0 + COMBO_DAMAGE_BONUS * combo.min(COMBO_MAX_BONUS_HITS) as f32;
+    let mut hit = false;
+    let mut parried = false;
+    let mut slain = vec![];
+    let mut script_actions = vec![];
+    let friendly_fire = game.rules.friendly_fire;
+    let teammate = |p: &PlayerState| !friendly_fire && team.is_some() && p.team == team;
+    let in_reach = game.collisions.players.query(&reach);
+    let targets = game.players.iter_mut().enumerate()
+        .filter(|(index, _)| in_reach.contains(index))
+        .map(|(_, p)| p)
+        .filter(|p| p.id != attacker_id && !teammate(p) && !p.airborne());
+    for target in targets {
+        let damage = (PlayerState::hit_damage(damage, target.defense(), crit) as f32 * combo_bonus).round() as i64;
+        let (damage, actions) = scripting.on_hit(&game.rules.scripts, &attacker_name, &target.name, damage);
+        script_actions.extend(actions);
+        let was_alive = target.hp > 0;
+        if !target.parrying() {
+            hit = true;
+            target.hits_taken += 1;
+            if crit {
+                target.crits_taken += 1;
+            }
+            // Getting hit breaks whatever combo they had going
+            target.combo = 0;
+            target.combo_at = None;
+        }
+        if target.parrying() {
+            parried = true;
+        } else if game.sudden_death.is_some() {
+            target.hp = 0;
+        } else if target.blocking {
+            target.hp = (target.hp - (damage as f32 * BLOCK_DAMAGE_FACTOR).round() as i64).max(0);
+        } else {
+            target.hp = (target.hp - damage).max(0);
+        }
+        if target.god_mode {
+            target.hp = PLAYER_MAX_HP;
+        }
+        if was_alive && target.hp == 0 {
+            slain.push(target.name.clone());
+        }
+    }
+    for victim in slain.iter() {
+        game.log_kill(format!("{} slew {}", attacker_name, victim));
+    }
+    game.apply_script_actions(script_actions);
+    if let Some(attacker) = game.players.iter_mut().find(|p| p.id == attacker_id) {
+        // A miss or a parry ends the combo
+        if hit && !parried {
+            attacker.combo = combo + 1;
+            attacker.combo_at = Some(clock::now());
+        } else {
+            attacker.combo = 0;
+            attacker.combo_at = None;
+        }
+        if parried {
+            attacker.stagger_until = Some(clock::now() + Duration::from_millis(STAGGER_MILLIS));
+            attacker.staggered = true;
+        }
+        attacker.gain_xp(slain.len() as u32 * XP_PER_KILL);
+    }
+}
+
+impl Potion {
+
+    #[cfg(feature = "server")]
+    pub fn apply(&self, player: &mut PlayerState) {
+        match self.potion_type {
+            PotionType::Health => player.hp = (player.hp + POTION_HEALTH_RESTORE).min(PLAYER_MAX_HP),
+            PotionType::Mana => player.mp = (player.mp + POTION_MANA_RESTORE).min(PLAYER_MAX_MP),
+            PotionType::Speed => player.add_status(StatusEffect::new(StatusKind::Haste, POTION_SPEED_BOOST, POTION_BUFF_SECONDS)),
+            PotionType::Strength => player.add_status(StatusEffect::new(StatusKind::Strength, POTION_STRENGTH_BOOST, POTION_BUFF_SECONDS)),
+            PotionType::Invisibility => player.add_status(StatusEffect::new(StatusKind::Invisible, 1.0, POTION_INVISIBILITY_SECONDS)),
+            PotionType::Trap => player.traps += 1,
+        }
+    }
+}
+
+impl Trap {
+
+    #[cfg(feature = "server")]
+    pub fn spring(&self, victim: &mut PlayerState) {
+        victim.hp = (victim.hp - TRAP_DAMAGE).max(0);
+        victim.hits_taken += 1;
+        victim.add_status(StatusEffect::new(StatusKind::Poison, TRAP_POISON, TRAP_POISON_SECONDS));
+    }
+}
+
+impl Crate {
+
+    /// Takes a hit, returning the loot when that breaks it.
+    #[cfg(feature = "server")]
+    pub fn hit(&mut self, damage: i64, rng: &mut impl Rng) -> Option<Loot> {
+        if self.broken {
+            return None
+        }
+        self.hp -= damage;
+        if self.hp > 0 {
+            return None
+        }
+        self.broken = true;
+        Some(Loot::random(&self.pos, CRATE_EQUIPMENT_CHANCE, rng))
+    }
+}
+
+/// Ages out old items and spawns new ones after a warning shimmer.
+#[cfg(feature = "server")]
+pub fn tick_items(game: &mut NetworkedGame, map: &Map, elapsed: f32, rng: &mut impl Rng) {
+    for potion in game.potions.iter_mut() {
+        potion.age += elapsed;
+    }
+    game.potions.retain(|potion| !potion.expired());
+    for drop in game.equipment.iter_mut() {
+        drop.age += elapsed;
+    }
+    game.equipment.retain(|drop| !drop.expired());
+
+    let arrived = match game.item_spawn.as_mut() {
+        Some(item_spawn) => {
+            item_spawn.remaining -= elapsed;
+            item_spawn.remaining <= 0.0
+        },
+        None => false,
+    };
+    if arrived {
+        if let Some(item_spawn) = game.item_spawn.take() {
+            game.drop_loot(Loot::random(&item_spawn.pos, SPAWN_EQUIPMENT_CHANCE, rng));
+        }
+        game.item_spawn_timer = 0.0;
+    }
+
+    game.item_spawn_timer += elapsed;
+    if game.item_spawn.is_none() && game.item_spawn_timer >= game.rules.item_spawn_interval
+        && game.potions.len() + game.equipment.len() < MAX_ITEMS {
+        game.sync_collisions();
+        let collisions = &game.collisions;
+        let free: Vec<&Position> = map.potion_spawns.iter()
+            .filter(|spawn| !collisions.potions.overlaps(spawn) && !collisions.equipment.overlaps(spawn))
+            .collect();
+        if let Some(spawn) = free.choose(rng) {
+            game.item_spawn = Some(ItemSpawn {
+                pos: Position { x: spawn.x, y: spawn.y, w: POTION_WIDTH, h: POTION_HEIGHT },
+                remaining: ITEM_SPAWN_WARNING_SECONDS,
+            });
+        }
+        game.item_spawn_timer = 0.0;
+    }
+}
+
+/// Hands whatever each player walked over to them, and springs the traps
+/// they walked into. Potions are picked up by walking over them, not by
+/// jumping over them.
+#[cfg(feature = "server")]
+pub fn pick_up(game: &mut NetworkedGame, scripting: &Scripting) {
+    let mut feed = vec![];
+    let mut kills = vec![];
+    let mut script_actions = vec![];
+    game.sync_collisions();
+    let Collisions { potions, equipment, traps, .. } = &game.collisions;
+    let (mut taken_potions, mut taken_equipment, mut sprung_traps) = (vec![], vec![], vec![]);
+    // Who set each trap, for the kill feed, looked up while the players are
+    // being gone through
+    let names: HashMap<u32, String> = game.players.iter().map(|p| (p.number, p.name.clone())).collect();
+    for player in game.players.iter_mut() {
+        // Anyone off the ground can't pick things up, but only those high
+        // enough clear the traps
+        let grounded = !player.jumping;
+        if let Some(index) = potions.query(&player.body).into_iter().find(|index| grounded && !taken_potions.contains(index)) {
+            taken_potions.push(index);
+            let potion = &game.potions[index];
+            potion.apply(player);
+            feed.push(format!("{} picked up {}!", player.name, potion.potion_type.name()));
+            script_actions.extend(scripting.on_pickup(&game.rules.scripts, &player.name, potion.potion_type.name()));
+            player.gain_xp(XP_PER_PICKUP);
+            if game.mode == GameMode::CollectionRace {
+                *game.scores.entry(player.score_key()).or_insert(0.0) += 1.0;
+            }
+        }
+        if let Some(index) = equipment.query(&player.body).into_iter().find(|index| grounded && !taken_equipment.contains(index)) {
+            taken_equipment.push(index);
+            let drop = &game.equipment[index];
+            player.equip(drop.kind);
+            feed.push(format!("{} picked up {}!", player.name, drop.kind.name()));
+            script_actions.extend(scripting.on_pickup(&game.rules.scripts, &player.name, drop.kind.name()));
+            player.gain_xp(XP_PER_PICKUP);
+            if game.mode == GameMode::CollectionRace {
+                *game.scores.entry(player.score_key()).or_insert(0.0) += 1.0;
+            }
+        }
+        let placed = &game.traps;
+        let trap = traps.query(&player.body).into_iter()
+            .find(|index| !sprung_traps.contains(index) && placed[*index].owner != player.number && !player.airborne());
+        if let Some(index) = trap {
+            sprung_traps.push(index);
+            let trap = &game.traps[index];
+            let was_alive = player.hp > 0;
+            trap.spring(player);
+            if was_alive && player.hp == 0 {
+                kills.push(format!("{} slew {} with a trap", names.get(&trap.owner).map_or("Someone", String::as_str), player.name));
+            }
+        }
+    }
+    physics::remove_taken(&mut game.potions, taken_potions);
+    physics::remove_taken(&mut game.equipment, taken_equipment);
+    physics::remove_taken(&mut game.traps, sprung_traps);
+    for text in feed {
+        game.log_event(text, false);
+    }
+    for text in kills {
+        game.log_kill(text);
+    }
+    game.apply_script_actions(script_actions);
+}
@@ -0,0 +1,87 @@
+//! How a hero looks, picked in the lobby so the two players can tell each
+//! other apart.
+
+#[cfg(feature = "client")]
+use std::collections::HashMap;
+
+#[cfg(feature = "client")]
+use ggez::graphics::{GlBackendSpec, ImageGeneric};
+use serde::{Deserialize, Serialize};
+
+/// Sprite sheet every client has. Variants are named `hero_<variant>.png`.
+pub const DEFAULT_SHEET: &str = "hero";
+
+/// Tints offered in the lobby, the first one leaving the sprite as drawn.
+#[cfg(feature = "native")]
+pub const SKIN_COLORS: [(&str, [f32; 3]); 6] = [
+    ("plain", [1.0, 1.0, 1.0]),
+    ("red", [1.0, 0.55, 0.55]),
+    ("blue", [0.55, 0.7, 1.0]),
+    ("green", [0.6, 1.0, 0.6]),
+    ("gold", [1.0, 0.9, 0.45]),
+    ("violet", [0.85, 0.6, 1.0]),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Skin {
+    pub sheet: String,
+    /// Index into `SKIN_COLORS`
+    pub color: usize,
+}
+
+impl Default for Skin {
+    fn default() -> Skin {
+        Skin {
+            sheet: DEFAULT_SHEET.to_string(),
+            color: 0,
+        }
+    }
+}
+
+impl Skin {
+
+    /// Parses the `sheet,color` meta sent with `joingame` and `setskin`,
+    /// falling back to the default look for anything missing.
+    #[cfg(feature = "server")]
+    pub fn from_meta(meta: &str) -> Skin {
+        let mut parts = meta.split(',');
+        let sheet = match parts.next() {
+            Some(sheet) if !sheet.is_empty() && sheet.chars().all(|c| c.is_alphanumeric() || c == '_') => sheet.to_string(),
+            _ => DEFAULT_SHEET.to_string(),
+        };
+        let color = parts.next()
+            .and_then(|color| color.parse::<usize>().ok())
+            .unwrap_or(0) % SKIN_COLORS.len();
+        Skin { sheet, color }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn to_meta(&self) -> String {
+        format!("{},{}", self.sheet, self.color)
+    }
+
+    #[cfg(feature = "native")]
+    pub fn next_color(&mut self) {
+        self.color = (self.color + 1) % SKIN_COLORS.len();
+    }
+
+    #[cfg(feature = "client")]
+    pub fn color_name(&self) -> &'static str {
+        SKIN_COLORS[self.color % SKIN_COLORS.len()].0
+    }
+
+    /// Sprite sheet and tint to draw with. Sheets this client doesn't have
+    /// fall back to the default one, tinted so the heroes still differ.
+    #[cfg(feature = "client")]
+    pub fn texture(&self, textures: &HashMap<String, ImageGeneric<GlBackendSpec>>) -> (Option<ImageGeneric<GlBackendSpec>>, [f32; 3]) {
+        let (texture, color) = match textures.get(&self.sheet) {
+            Some(texture) => (Some(texture.clone()), self.color),
+            None if self.color == 0 => {
+                let stand_in = 1 + self.sheet.len() % (SKIN_COLORS.len() - 1);
+                (textures.get(DEFAULT_SHEET).cloned(), stand_in)
+            },
+            None => (textures.get(DEFAULT_SHEET).cloned(), self.color),
+        };
+        (texture, SKIN_COLORS[color % SKIN_COLORS.len()].1)
+    }
+}
@@ -0,0 +1,98 @@
+//! Sound effects. There are no sound files shipped yet, so each one is
+//! synthesized into WAV data when the game starts, unless the asset pack has
+//! its own under `sounds/`.
+
+use ggez::audio::{self, SoundSource};
+use ggez::Context;
+
+use crate::events::GameEvent;
+
+const SAMPLE_RATE: u32 = 22_050;
+
+#[derive(Debug, Clone)]
+pub struct Sounds {
+    /// Two rising notes for a critical hit
+    crit: audio::SoundData,
+}
+
+impl Default for Sounds {
+    fn default() -> Self {
+        Sounds {
+            crit: from_pack("crit.wav").unwrap_or_else(|| audio::SoundData::from_bytes(&wav(&crit_sting()))),
+        }
+    }
+}
+
+impl Sounds {
+
+    /// Plays the critical hit sting at `volume`, 0.0 to 1.0.
+    pub fn play_crit(&self, ctx: &mut Context, volume: f32) {
+        play(ctx, &self.crit, volume);
+    }
+
+    /// Plays whatever goes with `event`, so far just the sting for crits.
+    pub fn on_event(&self, ctx: &mut Context, event: &GameEvent, volume: f32) {
+        if let GameEvent::Hit { crit: true, .. } = event {
+            self.play_crit(ctx, volume);
+        }
+    }
+}
+
+fn from_pack(file: &str) -> Option<audio::SoundData> {
+    let bytes = crate::pack::get()?.read(&format!("sounds/{}", file))?;
+    Some(audio::SoundData::from_bytes(&bytes))
+}
+
+/// Sounds are a nicety, so a missing audio device only gets a mention.
+fn play(ctx: &mut Context, data: &audio::SoundData, volume: f32) {
+    if volume <= 0.0 {
+        return
+    }
+    let played = audio::Source::from_data(ctx, data.clone()).and_then(|mut source| {
+        source.set_volume(volume);
+        source.play_detached(ctx)
+    });
+    if let Err(e) = played {
+        println!("Unable to play sound: {}", e);
+    }
+}
+
+/// A short square wave note at `frequency` that dies away over `seconds`.
+fn note(frequency: f32, seconds: f32) -> Vec<i16> {
+    let samples = (SAMPLE_RATE as f32 * seconds) as usize;
+    (0..samples).map(|i| {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        let wave = if (t * frequency).fract() < 0.5 { 1.0 } else { -1.0 };
+        let envelope = 1.0 - i as f32 / samples as f32;
+        (wave * envelope * envelope * i16::MAX as f32 * 0.3) as i16
+    }).collect()
+}
+
+fn crit_sting() -> Vec<i16> {
+    let mut samples = note(880.0, 0.07);
+    samples.extend(note(1320.0, 0.18));
+    samples
+}
+
+/// 16 bit mono PCM wrapped in a WAV header.
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
@@ -0,0 +1,100 @@
+//! Timed effects on a player, ticked by the server and replicated in the
+//! world snapshot.
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds between the periodic ticks of poison and regen.
+#[cfg(feature = "server")]
+pub const STATUS_TICK_SECONDS: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Loses `magnitude` HP every tick
+    Poison,
+    /// Moves `magnitude` (0.0 - 1.0) slower
+    Slow,
+    /// Moves `magnitude` faster
+    Haste,
+    /// Gains `magnitude` HP every tick
+    Regen,
+    /// Hits `magnitude` harder
+    Strength,
+    /// Hidden from the opponent
+    Invisible,
+}
+
+impl StatusKind {
+    /// Letter shown on the HUD icon.
+    #[cfg(feature = "client")]
+    pub fn letter(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "P",
+            StatusKind::Slow => "S",
+            StatusKind::Haste => "H",
+            StatusKind::Regen => "R",
+            StatusKind::Strength => "+",
+            StatusKind::Invisible => "I",
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn color(&self) -> [f32; 4] {
+        match self {
+            StatusKind::Poison => [0.5, 0.8, 0.1, 1.0],
+            StatusKind::Slow => [0.4, 0.4, 0.8, 1.0],
+            StatusKind::Haste => [1.0, 0.8, 0.1, 1.0],
+            StatusKind::Regen => [0.9, 0.3, 0.5, 1.0],
+            StatusKind::Strength => [1.0, 0.3, 0.1, 1.0],
+            StatusKind::Invisible => [0.8, 0.8, 0.8, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: f32,
+    /// Seconds left before it wears off
+    pub remaining: f32,
+    /// Seconds left until the next periodic tick
+    next_tick: f32,
+}
+
+impl StatusEffect {
+
+    #[cfg(feature = "server")]
+    pub fn new(kind: StatusKind, magnitude: f32, duration: f32) -> StatusEffect {
+        StatusEffect {
+            kind,
+            magnitude,
+            remaining: duration,
+            next_tick: STATUS_TICK_SECONDS,
+        }
+    }
+
+    /// Runs the clock, returning how many periodic ticks went off.
+    #[cfg(feature = "server")]
+    pub fn advance(&mut self, elapsed: f32) -> u32 {
+        let mut ticks = 0;
+        self.remaining -= elapsed;
+        self.next_tick -= elapsed;
+        while self.next_tick <= 0.0 {
+            ticks += 1;
+            self.next_tick += STATUS_TICK_SECONDS;
+        }
+        ticks
+    }
+
+    #[cfg(feature = "server")]
+    pub fn expired(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        match self.kind {
+            StatusKind::Slow => (1.0 - self.magnitude).max(0.0),
+            StatusKind::Haste => 1.0 + self.magnitude,
+            _ => 1.0,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! An on-screen stick and buttons, so the game can be played on a touchscreen
+//! laptop without a keyboard, and later on phones. ggez only hands touches
+//! over as the mouse, so it's one finger at a time: hold the stick to move,
+//! and lifting off it lets the player glide to a stop like a gamepad stick.
+
+use ggez::{graphics, Context, GameResult};
+
+use crate::controls::Action;
+
+/// Middle of the stick, bottom left just above the HUD bar.
+const STICK_CENTER: (f32, f32) = (72.0, 370.0);
+const STICK_RADIUS: f32 = 44.0;
+const KNOB_RADIUS: f32 = 18.0;
+/// Presses this far outside the stick still grab it, fingers being fat.
+const STICK_SLACK: f32 = 16.0;
+const BUTTON_RADIUS: f32 = 22.0;
+/// Buttons bottom right, where they are and what's written on them.
+const BUTTONS: [(Action, f32, f32, &str); 4] = [
+    (Action::Attack, 596.0, 384.0, "ATK"),
+    (Action::Jump, 544.0, 404.0, "JMP"),
+    (Action::Dash, 596.0, 332.0, "DSH"),
+    (Action::Block, 544.0, 352.0, "BLK"),
+];
+
+/// What a press or release was on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Touched {
+    /// The stick, pushed this far each way, -1.0 - 1.0 with up positive like
+    /// a gamepad's
+    Stick(f32, f32),
+    Button(Action),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TouchControls {
+    /// How far the stick is pushed while it's held
+    stick: Option<(f32, f32)>,
+    /// The button held down
+    button: Option<Action>,
+}
+
+fn push(x: f32, y: f32) -> (f32, f32) {
+    let (dx, dy) = ((x - STICK_CENTER.0) / STICK_RADIUS, (STICK_CENTER.1 - y) / STICK_RADIUS);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length > 1.0 {
+        (dx / length, dy / length)
+    } else {
+        (dx, dy)
+    }
+}
+
+impl TouchControls {
+
+    /// Takes a press at `x`, `y` in screen coordinates, returning what it's on.
+    pub fn press(&mut self, x: f32, y: f32) -> Option<Touched> {
+        let (dx, dy) = (x - STICK_CENTER.0, y - STICK_CENTER.1);
+        if (dx * dx + dy * dy).sqrt() < STICK_RADIUS + STICK_SLACK {
+            let (px, py) = push(x, y);
+            self.stick = Some((px, py));
+            return Some(Touched::Stick(px, py))
+        }
+        let (action, _, _, _) = BUTTONS.iter().find(|(_, bx, by, _)| {
+            ((x - bx) * (x - bx) + (y - by) * (y - by)).sqrt() < BUTTON_RADIUS
+        })?;
+        self.button = Some(*action);
+        Some(Touched::Button(*action))
+    }
+
+    /// Moves a held stick to follow the finger, returning the new push.
+    pub fn drag(&mut self, x: f32, y: f32) -> Option<(f32, f32)> {
+        self.stick?;
+        self.stick = Some(push(x, y));
+        self.stick
+    }
+
+    /// Lets go of whatever was pressed, returning what that was.
+    pub fn release(&mut self) -> Option<Touched> {
+        if self.stick.take().is_some() {
+            return Some(Touched::Stick(0.0, 0.0))
+        }
+        self.button.take().map(Touched::Button)
+    }
+}
+
+pub fn draw(ctx: &mut Context, controls: &TouchControls) -> GameResult<()> {
+    let faint = graphics::Color::new(1.0, 1.0, 1.0, 0.2);
+    let held = graphics::Color::new(1.0, 1.0, 1.0, 0.5);
+    let (px, py) = controls.stick.unwrap_or((0.0, 0.0));
+    let knob = ggez::mint::Point2 { x: STICK_CENTER.0 + px * STICK_RADIUS, y: STICK_CENTER.1 - py * STICK_RADIUS };
+    let mut mesh = graphics::MeshBuilder::new();
+    mesh.circle(graphics::DrawMode::stroke(2.0), ggez::mint::Point2 { x: STICK_CENTER.0, y: STICK_CENTER.1 }, STICK_RADIUS, 0.5, faint)?;
+    mesh.circle(graphics::DrawMode::fill(), knob, KNOB_RADIUS, 0.5, if controls.stick.is_some() { held } else { faint })?;
+    for (action, x, y, label) in BUTTONS.iter() {
+        let color = if controls.button == Some(*action) { held } else { faint };
+        mesh.circle(graphics::DrawMode::fill(), ggez::mint::Point2 { x: *x, y: *y }, BUTTON_RADIUS, 0.5, color)?;
+        let text = graphics::Text::new(graphics::TextFragment {
+                text: label.to_string(),
+                color: Some(graphics::Color::new(1.0, 1.0, 1.0, 0.8)),
+                // `Font` is a handle to a loaded TTF, stored inside the `Context`.
+                // `Font::default()` always exists and maps to DejaVuSerif.
+                font: Some(graphics::Font::default()),
+                scale: Some(graphics::PxScale { x: 11.0, y: 11.0 }),
+            });
+        let (width, height) = (text.width(ctx), text.height(ctx));
+        graphics::queue_text(ctx, &text, ggez::mint::Point2 { x: x - width / 2.0, y: y - height / 2.0 }, None);
+    }
+    let mesh = mesh.build(ctx)?;
+    graphics::draw(ctx, &mesh, (ggez::mint::Point2 { x: 0.0, y: 0.0 },))?;
+    graphics::draw_queued_text(
+            ctx,
+            graphics::DrawParam::new()
+                .dest(ggez::mint::Point2 { x: 0.0, y: 0.0}),
+            None,
+            graphics::FilterMode::Linear,
+        )
+}
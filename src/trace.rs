@@ -0,0 +1,93 @@
+//! Where everyone went over a match, and where they died, for heatmaps and
+//! replays after the game. The server samples each player's spot a few times
+//! a second, and once a trail gets long halves it and samples half as often,
+//! so a match of any length fits in the same room. Asked for with
+//! `matchtrace`, in chunks the way maps are sent.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use crate::PlayerState;
+
+/// Seconds between samples at the start of a match.
+const SAMPLE_SECONDS: f32 = 0.25;
+/// Samples kept per player before the trail is thinned out.
+#[cfg(feature = "server")]
+const MAX_SAMPLES: usize = 1200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trace {
+    /// Seconds between samples, growing as the match goes on
+    pub interval: f32,
+    /// Player name to where their middle was, as `[clock, x, y]`
+    pub players: HashMap<String, Vec<[f32; 3]>>,
+    /// Where each death happened, as `[clock, x, y]`
+    #[serde(default)]
+    pub deaths: Vec<[f32; 3]>,
+    #[serde(skip)]
+    #[cfg(feature = "server")]
+    since_sample: f32,
+    /// Players down at the last tick, so each death counts once
+    #[serde(skip)]
+    #[cfg(feature = "server")]
+    down: Vec<String>,
+}
+
+impl Default for Trace {
+    fn default() -> Trace {
+        Trace {
+            interval: SAMPLE_SECONDS,
+            players: HashMap::new(),
+            deaths: vec![],
+            #[cfg(feature = "server")]
+            since_sample: SAMPLE_SECONDS,
+            #[cfg(feature = "server")]
+            down: vec![],
+        }
+    }
+}
+
+impl Trace {
+
+    /// Samples `players` if it's been long enough, `clock` being the seconds
+    /// since the game started.
+    #[cfg(feature = "server")]
+    pub fn record(&mut self, elapsed: f32, clock: f32, players: &[PlayerState]) {
+        for player in players.iter() {
+            let was_down = self.down.contains(&player.name);
+            if player.hp <= 0 && !was_down {
+                let (x, y) = Trace::middle(player);
+                self.deaths.push([clock, x, y]);
+                self.down.push(player.name.clone());
+            } else if player.hp > 0 && was_down {
+                self.down.retain(|name| *name != player.name);
+            }
+        }
+        self.since_sample += elapsed;
+        if self.since_sample < self.interval {
+            return
+        }
+        self.since_sample = 0.0;
+        for player in players.iter().filter(|p| p.hp > 0) {
+            let (x, y) = Trace::middle(player);
+            self.players.entry(player.name.clone()).or_default().push([clock, x, y]);
+        }
+        if self.players.values().any(|trail| trail.len() > MAX_SAMPLES) {
+            for trail in self.players.values_mut() {
+                let mut index = 0;
+                trail.retain(|_| {
+                    index += 1;
+                    index % 2 == 1
+                });
+            }
+            self.interval *= 2.0;
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn middle(player: &PlayerState) -> (f32, f32) {
+        (player.body.x + player.body.w / 2.0, player.body.y + player.body.h / 2.0)
+    }
+}
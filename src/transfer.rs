@@ -0,0 +1,52 @@
+//! Sending map files to clients that don't have them, a chunk per request so
+//! each answer fits in a packet. Chunks lost on the way are asked for again,
+//! and the whole file is checked against the server's checksum at the end.
+
+/// Characters per chunk, leaving room in a packet for them all needing
+/// escaping in the JSON answer.
+#[cfg(feature = "server")]
+pub const CHUNK_CHARS: usize = 1200;
+/// Times a chunk is asked for before giving up on the file.
+const CHUNK_ATTEMPTS: usize = 4;
+
+/// FNV-1a, enough to catch a chunk that went missing or got garbled.
+pub fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(feature = "server")]
+pub fn chunk_count(contents: &str) -> usize {
+    contents.chars().count().div_ceil(CHUNK_CHARS).max(1)
+}
+
+/// Chunk `index` of `contents`, cut on characters so it stays valid UTF-8.
+#[cfg(feature = "server")]
+pub fn chunk(contents: &str, index: usize) -> Option<String> {
+    if index >= chunk_count(contents) {
+        return None
+    }
+    Some(contents.chars().skip(index * CHUNK_CHARS).take(CHUNK_CHARS).collect())
+}
+
+/// Puts a file back together from the answers `fetch` gets for each chunk,
+/// `{"chunks": 3, "checksum": 123, "data": "..."}`.
+pub fn download(mut fetch: impl FnMut(usize) -> Option<serde_json::Value>) -> Result<String, String> {
+    let mut contents = String::new();
+    let (mut chunks, mut expected) = (1, 0);
+    let mut index = 0;
+    while index < chunks {
+        let answer = (0..CHUNK_ATTEMPTS).find_map(|_| fetch(index))
+            .ok_or_else(|| format!("no answer for chunk {}", index))?;
+        if let Some(e) = answer["error"].as_str() {
+            return Err(e.to_string())
+        }
+        chunks = answer["chunks"].as_u64().unwrap_or(0) as usize;
+        expected = answer["checksum"].as_u64().unwrap_or(0);
+        contents.push_str(answer["data"].as_str().unwrap_or(""));
+        index += 1;
+    }
+    if checksum(contents.as_bytes()) != expected {
+        return Err("checksum doesn't match".to_string())
+    }
+    Ok(contents)
+}
@@ -0,0 +1,71 @@
+//! WebSocket listener run by the server alongside its UDP socket, for
+//! browsers and for networks where UDP is blocked. A text message holds one
+//! request as the UDP protocol has it, `game:player:action:meta`, and each
+//! answer comes back as a text message, so the requests go through the same
+//! `handle_connection` as everyone else's. The browser client in `web/` plays
+//! through it.
+
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use tungstenite::{Message, WebSocket};
+
+/// A request, with where to send the answers to it.
+pub type Request = (String, Sender<String>);
+
+/// How long a connection waits on its browser before passing on answers.
+const POLL_MILLIS: u64 = 5;
+
+/// Accepts WebSocket clients on `address` from now on, each on its own
+/// thread, handing their requests to the server through `requests`.
+pub fn listen(address: &str, requests: Sender<Request>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let requests = requests.clone();
+            thread::spawn(move || {
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                if let Err(e) = serve(stream, requests) {
+                    println!("WebSocket {} closed: {}", peer, e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: TcpStream, requests: Sender<Request>) -> Result<(), String> {
+    let mut websocket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+    websocket.get_ref().set_read_timeout(Some(Duration::from_millis(POLL_MILLIS))).map_err(|e| e.to_string())?;
+    let (answer_sender, answers): (Sender<String>, Receiver<String>) = crossbeam_channel::unbounded();
+    loop {
+        match next_request(&mut websocket)? {
+            Some(Some(request)) => {
+                if requests.send((request, answer_sender.clone())).is_err() {
+                    // The server has stopped
+                    return Ok(())
+                }
+            },
+            Some(None) => (),
+            None => return Ok(()),
+        }
+        for answer in answers.try_iter() {
+            websocket.write_message(Message::Text(answer)).map_err(|e| e.to_string())?;
+        }
+    }
+}
+
+/// The next request from the browser if one came in, none at all once the
+/// WebSocket has been closed.
+fn next_request(websocket: &mut WebSocket<TcpStream>) -> Result<Option<Option<String>>, String> {
+    match websocket.read_message() {
+        Ok(Message::Text(text)) => Ok(Some(Some(text))),
+        Ok(Message::Binary(data)) => Ok(Some(Some(String::from_utf8_lossy(&data).to_string()))),
+        Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => Ok(None),
+        Ok(_) => Ok(Some(None)),
+        Err(tungstenite::Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => Ok(Some(None)),
+        Err(e) => Err(e.to_string()),
+    }
+}
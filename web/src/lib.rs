@@ -0,0 +1,452 @@
+//! The game in a browser. It talks the same protocol as the native client,
+//! one request per WebSocket message to a server started with
+//! `--websocket=HOST:PORT`, and plays against whoever is on the UDP side.
+//!
+//! The page is opened as `index.html?server=ws://HOST:PORT`, with `name=` to
+//! play under, `game=` to join a particular game and `mode=` for the mode of
+//! one made when none is waiting. Arrows or WASD move, space attacks and the
+//! number keys vote for a map. Everyone is drawn as a box, the native
+//! client's sprites and sounds stay with it. We move by the game's own
+//! rules, built without its client and server for the simulation and maps.
+
+mod protocol;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent, MessageEvent, UrlSearchParams, WebSocket};
+
+use item_wars::map::Map;
+use item_wars::physics::SpatialHash;
+use item_wars::{config, net, rollback, transfer, NetActions, PlayerState, Position, PLAYER_MAX_HP};
+use protocol::{Answer, World};
+
+/// Milliseconds between asking for the world.
+const POLL_MILLIS: f64 = 50.0;
+/// Milliseconds before asking again when the world never came.
+const POLL_TIMEOUT_MILLIS: f64 = 1000.0;
+const TEAM_COLORS: [&str; 4] = ["#d04040", "#4060d0", "#40a040", "#d0b040"];
+
+/// Draws a frame, given the time, and asks for the next one.
+type FrameCallback = Closure<dyn FnMut(f64)>;
+
+#[derive(Default)]
+struct Keys {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+/// A map on its way from the server, a chunk at a time.
+struct Download {
+    name: String,
+    contents: String,
+    next: usize,
+}
+
+struct App {
+    socket: WebSocket,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    name: String,
+    mode: String,
+    game_id: String,
+    /// What the server knows us by once we've joined
+    player_id: Option<String>,
+    world: Option<World>,
+    map: Option<Map>,
+    download: Option<Download>,
+    /// Ourselves as we've moved, sent to the server as we go
+    player: Option<PlayerState>,
+    /// The crates still standing, which we can't walk through
+    obstacles: SpatialHash,
+    keys: Keys,
+    last_frame: Option<f64>,
+    last_poll: f64,
+    /// Asked for the world and still waiting for it
+    polling: bool,
+    status: String,
+}
+
+impl App {
+
+    fn send(&self, action: NetActions, meta: &str) {
+        let player = self.player_id.as_deref().unwrap_or(&self.name);
+        let _ = self.socket.send_with_str(&net::request(&self.game_id, player, action.into(), meta));
+    }
+
+    fn connected(&mut self) {
+        if self.game_id.is_empty() {
+            self.status = "Looking for a game".to_string();
+            self.send(NetActions::Listgames, r#"{"open":true}"#);
+        } else {
+            self.join();
+        }
+    }
+
+    fn join(&mut self) {
+        self.status = format!("Joining game {}", self.game_id);
+        self.send(NetActions::Joingame, "");
+    }
+
+    fn answer(&mut self, answer: Answer) {
+        match answer {
+            Answer::Games(games) => match games.first() {
+                Some(game_id) => {
+                    self.game_id = game_id.clone();
+                    self.join();
+                },
+                None => {
+                    self.status = "Making a game".to_string();
+                    let mode = self.mode.clone();
+                    self.send(NetActions::Newgame, &mode);
+                },
+            },
+            Answer::Created(game_id) => {
+                self.game_id = game_id;
+                self.join();
+            },
+            Answer::Joined { player_id, world, tunables } => {
+                if let Err(e) = tunables.validate() {
+                    self.status = format!("Bad movement values from the server: {}", e);
+                    return
+                }
+                config::use_server_tunables(tunables);
+                self.player_id = Some(player_id);
+                self.status = "Waiting for players".to_string();
+                self.send(NetActions::Ready, "");
+                self.update_world(*world);
+            },
+            Answer::World(world) => {
+                self.polling = false;
+                self.update_world(*world);
+            },
+            Answer::MapChunk { chunks, checksum, data } => self.map_chunk(chunks, checksum, &data),
+            Answer::Ready | Answer::Voted(true) => (),
+            Answer::Voted(false) => self.status = "That map isn't up for a vote".to_string(),
+            Answer::Refused(reason) => {
+                self.polling = false;
+                self.status = reason;
+            },
+        }
+    }
+
+    fn update_world(&mut self, world: World) {
+        let downloading = self.download.as_ref().map(|download| download.name.as_str());
+        let have = self.map.as_ref().map(|map| map.name.as_str());
+        if have != Some(world.map.as_str()) && downloading != Some(world.map.as_str()) {
+            self.download = Some(Download { name: world.map.clone(), contents: String::new(), next: 0 });
+            self.send(NetActions::Getmap, &format!("{}:0", world.map));
+        }
+        if let Some(me) = world.players.iter().find(|p| p.name == self.name) {
+            let name = &self.name;
+            self.player.get_or_insert_with(|| PlayerState::new(name.clone(), me.body))
+                .follow_corrections(&me.body, me.corrections);
+        }
+        self.obstacles.sync(world.crates.iter().map(|c| if c.broken { None } else { Some(c.pos) }));
+        if world.completed {
+            self.status = match &world.winner {
+                Some(winner) => format!("{} won!", winner),
+                None => "Nobody won".to_string(),
+            };
+        } else if world.started {
+            self.status.clear();
+        } else if world.players.iter().all(|p| p.ready) && !world.map_candidates.is_empty() {
+            let candidates: Vec<String> = world.map_candidates.iter().enumerate()
+                .map(|(i, map)| format!("{} {}", i + 1, map))
+                .collect();
+            self.status = format!("Vote for a map: {}", candidates.join("  "));
+        }
+        self.world = Some(world);
+    }
+
+    fn map_chunk(&mut self, chunks: usize, checksum: u64, data: &str) {
+        let download = match self.download.as_mut() {
+            Some(download) => download,
+            None => return,
+        };
+        download.contents.push_str(data);
+        download.next += 1;
+        if download.next < chunks {
+            let meta = format!("{}:{}", download.name, download.next);
+            self.send(NetActions::Getmap, &meta);
+            return
+        }
+        if let Some(download) = self.download.take() {
+            if transfer::checksum(download.contents.as_bytes()) != checksum {
+                self.status = format!("Map {} came garbled", download.name);
+                return
+            }
+            match Map::from_json(&download.name, &download.contents) {
+                Ok(map) => {
+                    self.canvas.set_width(map.pixel_width() as u32);
+                    self.canvas.set_height(map.pixel_height() as u32);
+                    self.map = Some(map);
+                },
+                Err(e) => self.status = e.to_string(),
+            }
+        }
+    }
+
+    fn key(&mut self, key: &str, down: bool) {
+        match key {
+            "ArrowUp" | "w" => self.keys.up = down,
+            "ArrowDown" | "s" => self.keys.down = down,
+            "ArrowLeft" | "a" => self.keys.left = down,
+            "ArrowRight" | "d" => self.keys.right = down,
+            " " if down => self.send(NetActions::Attack, ""),
+            _ if down => {
+                let voting = self.world.as_ref().filter(|world| !world.started);
+                let vote = key.parse::<usize>().ok()
+                    .and_then(|n| voting.and_then(|world| world.map_candidates.get(n.wrapping_sub(1))))
+                    .cloned();
+                if let Some(map) = vote {
+                    self.send(NetActions::Votemap, &map);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn frame(&mut self, now: f64) {
+        let elapsed = self.last_frame.map_or(0.0, |last| (now - last) as f32 / 1000.0);
+        self.last_frame = Some(now);
+        let due = if self.polling { POLL_TIMEOUT_MILLIS } else { POLL_MILLIS };
+        if self.player_id.is_some() && now - self.last_poll >= due {
+            self.send(NetActions::Getworld, "");
+            self.polling = true;
+            self.last_poll = now;
+        }
+        self.walk(elapsed);
+        self.draw();
+    }
+
+    /// Moves us by the keys held, the same as the native client would, and
+    /// tells the server where we got to.
+    fn walk(&mut self, elapsed: f32) {
+        let (world, map, player) = match (&self.world, &self.map, self.player.as_mut()) {
+            (Some(world), Some(map), Some(player)) => (world, map, player),
+            _ => return,
+        };
+        let name = &self.name;
+        let alive = world.players.iter().any(|p| p.name == *name && p.hp > 0);
+        if !world.started || world.completed || !alive {
+            return
+        }
+        let keys = &self.keys;
+        let held = [(keys.up, rollback::UP), (keys.down, rollback::DOWN), (keys.left, rollback::LEFT), (keys.right, rollback::RIGHT)];
+        let input = held.iter().filter(|(down, _)| *down).fold(0, |input, (_, bit)| input | bit);
+        let before = player.body();
+        player.apply_input(input, false);
+        player.update(elapsed, true, map, &self.obstacles);
+        let body = player.body();
+        if input != 0 || body.x != before.x || body.y != before.y {
+            let meta = player.position_meta();
+            self.send(NetActions::Sendposition, &meta);
+        }
+    }
+
+    fn draw(&self) {
+        let context = &self.context;
+        let (width, height) = (self.canvas.width() as f64, self.canvas.height() as f64);
+        context.set_fill_style_str("#202028");
+        context.fill_rect(0.0, 0.0, width, height);
+        if let Some(map) = &self.map {
+            context.set_fill_style_str("rgba(200, 40, 40, 0.4)");
+            for hazard in map.hazards.iter() {
+                fill(context, &hazard.area);
+            }
+            context.set_fill_style_str("#707078");
+            for wall in map.walls.iter() {
+                fill(context, wall);
+            }
+        }
+        if let Some(world) = &self.world {
+            self.draw_world(world);
+        }
+        context.set_fill_style_str("#ffffff");
+        context.set_font("16px sans-serif");
+        let _ = context.fill_text(&self.status, 8.0, 20.0);
+    }
+
+    fn draw_world(&self, world: &World) {
+        let context = &self.context;
+        if let Some(zone) = &world.zone {
+            context.set_stroke_style_str("#e0e040");
+            context.stroke_rect(zone.x as f64, zone.y as f64, zone.w as f64, zone.h as f64);
+        }
+        context.set_fill_style_str("#8a5a2b");
+        for standing in world.crates.iter().filter(|c| !c.broken) {
+            fill(context, &standing.pos);
+        }
+        context.set_fill_style_str("#e060c0");
+        for potion in world.potions.iter() {
+            fill(context, &potion.pos);
+        }
+        context.set_fill_style_str("#40d0d0");
+        for drop in world.equipment.iter() {
+            fill(context, &drop.pos);
+        }
+        // Traps are hidden from everyone but whoever set them
+        context.set_fill_style_str("#a0a0a0");
+        let number = world.players.iter().find(|p| p.name == self.name).map(|p| p.number);
+        for trap in world.traps.iter().filter(|trap| Some(trap.owner) == number) {
+            fill(context, &trap.pos);
+        }
+        for flag in world.flags.iter() {
+            context.set_fill_style_str(TEAM_COLORS[flag.team % TEAM_COLORS.len()]);
+            fill(context, &flag.pos);
+        }
+        context.set_font("12px sans-serif");
+        for player in world.players.iter().filter(|p| p.hp > 0) {
+            let me = player.name == self.name;
+            if player.is_invisible() && !me {
+                continue
+            }
+            let mut body = match &self.player {
+                Some(ours) if me => ours.body(),
+                _ => player.body,
+            };
+            body.y -= player.jump_offset;
+            let color = match player.team {
+                Some(team) => TEAM_COLORS[team % TEAM_COLORS.len()],
+                None if me => "#40c040",
+                None => "#d04040",
+            };
+            context.set_fill_style_str(color);
+            fill(context, &body);
+            let health = player.hp.clamp(0, PLAYER_MAX_HP) as f32 / PLAYER_MAX_HP as f32;
+            context.set_fill_style_str("#40e040");
+            fill(context, &Position { x: body.x, y: body.y - 6.0, w: body.w * health, h: 3.0 });
+            context.set_fill_style_str("#ffffff");
+            let _ = context.fill_text(&player.name, body.x as f64, (body.y - 10.0) as f64);
+        }
+        let height = self.canvas.height() as f64;
+        for (i, event) in world.events.iter().rev().take(4).enumerate() {
+            let _ = context.fill_text(&event.text, 8.0, height - 8.0 - 16.0 * i as f64);
+        }
+    }
+}
+
+fn fill(context: &CanvasRenderingContext2d, pos: &Position) {
+    context.fill_rect(pos.x as f64, pos.y as f64, pos.w as f64, pos.h as f64);
+}
+
+/// Asks for the next frame to be drawn by `callback`.
+fn request_frame(callback: &FrameCallback) {
+    if let Some(window) = web_sys::window() {
+        let _ = window.request_animation_frame(callback.as_ref().unchecked_ref());
+    }
+}
+
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let document = window.document().ok_or("no document")?;
+    let params = UrlSearchParams::new_with_str(&window.location().search()?)?;
+    let server = params.get("server").ok_or("no ?server=ws://HOST:PORT to play on")?;
+    let canvas: HtmlCanvasElement = document.get_element_by_id("game").ok_or("no canvas")?.dyn_into()?;
+    let context: CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or("no 2d canvas")?.dyn_into()?;
+    let socket = WebSocket::new(&server)?;
+    let app = Rc::new(RefCell::new(App {
+        socket: socket.clone(),
+        canvas,
+        context,
+        name: params.get("name").unwrap_or_else(|| "Browser".to_string()),
+        mode: params.get("mode").unwrap_or_default(),
+        game_id: params.get("game").unwrap_or_default(),
+        player_id: None,
+        world: None,
+        map: None,
+        download: None,
+        player: None,
+        obstacles: SpatialHash::default(),
+        keys: Keys::default(),
+        last_frame: None,
+        last_poll: 0.0,
+        polling: false,
+        status: format!("Connecting to {}", server),
+    }));
+
+    let opened = app.clone();
+    let on_open = Closure::<dyn FnMut()>::new(move || opened.borrow_mut().connected());
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    on_open.forget();
+
+    let answered = app.clone();
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        if let Some(answer) = event.data().as_string().and_then(|text| Answer::parse(&text)) {
+            answered.borrow_mut().answer(answer);
+        }
+    });
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+
+    let closed = app.clone();
+    let on_close = Closure::<dyn FnMut()>::new(move || closed.borrow_mut().status = "Lost the server".to_string());
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    on_close.forget();
+
+    for (event, down) in [("keydown", true), ("keyup", false)].iter() {
+        let pressed = app.clone();
+        let down = *down;
+        let on_key = Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+            if !event.repeat() || !down {
+                pressed.borrow_mut().key(&event.key(), down);
+            }
+        });
+        document.add_event_listener_with_callback(event, on_key.as_ref().unchecked_ref())?;
+        on_key.forget();
+    }
+
+    // Each frame asks for the next, so the closure has to know itself
+    let frame: Rc<RefCell<Option<FrameCallback>>> = Rc::new(RefCell::new(None));
+    let next = frame.clone();
+    *frame.borrow_mut() = Some(Closure::new(move |now: f64| {
+        app.borrow_mut().frame(now);
+        if let Some(callback) = next.borrow().as_ref() {
+            request_frame(callback);
+        }
+    }));
+    if let Some(callback) = frame.borrow().as_ref() {
+        request_frame(callback);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the server answered the recorded sessions with, to whatever
+    /// this client asks too, has to make sense to it.
+    #[test]
+    fn recorded_answers_parse() {
+        let asked: Vec<usize> = vec![
+            NetActions::Newgame, NetActions::Listgames, NetActions::Ready, NetActions::Getworld,
+            NetActions::Joingame, NetActions::Votemap, NetActions::Getmap,
+        ].into_iter().map(usize::from).collect();
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../sessions");
+        let mut parsed = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let contents = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for line in contents.lines() {
+                let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                let request = &entry["request"];
+                let action = request["request"].as_str().and_then(|r| r.split(':').nth(2)).and_then(|a| a.parse().ok());
+                if !action.is_some_and(|action: usize| asked.contains(&action)) {
+                    continue
+                }
+                for answer in request["answers"].as_array().into_iter().flatten() {
+                    let text = answer.as_str().unwrap();
+                    assert!(Answer::parse(text).is_some(), "can't make out {}", text);
+                    parsed += 1;
+                }
+            }
+        }
+        assert!(parsed > 0);
+    }
+}
@@ -0,0 +1,156 @@
+//! The answers that come back to the game's requests. The world is the part
+//! of the server's `NetworkedGame` JSON that gets drawn, read into types of
+//! its own since the game's keep their fields to themselves.
+
+use item_wars::config::Tunables;
+use item_wars::Position;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Player {
+    pub name: String,
+    /// What traps and flags know the player by
+    #[serde(default)]
+    pub number: u32,
+    pub body: Position,
+    pub hp: i64,
+    #[serde(default)]
+    pub jump_offset: f32,
+    #[serde(default)]
+    pub ready: bool,
+    #[serde(default)]
+    pub team: Option<usize>,
+    /// Bumped whenever the server puts the player somewhere, see
+    /// `PlayerState::follow_corrections`
+    #[serde(default)]
+    pub corrections: u32,
+    #[serde(default)]
+    statuses: Vec<Status>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Status {
+    kind: String,
+}
+
+impl Player {
+
+    pub fn is_invisible(&self) -> bool {
+        self.statuses.iter().any(|s| s.kind == "Invisible")
+    }
+}
+
+/// Anything lying on the ground, potions, gear and traps alike.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Item {
+    pub pos: Position,
+    /// Number of whoever set it, for traps
+    #[serde(default)]
+    pub owner: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Crate {
+    pub pos: Position,
+    pub broken: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Flag {
+    pub team: usize,
+    pub pos: Position,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedEvent {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct World {
+    pub players: Vec<Player>,
+    pub map: String,
+    #[serde(default)]
+    pub map_candidates: Vec<String>,
+    #[serde(default)]
+    pub crates: Vec<Crate>,
+    #[serde(default)]
+    pub potions: Vec<Item>,
+    #[serde(default)]
+    pub equipment: Vec<Item>,
+    #[serde(default)]
+    pub traps: Vec<Item>,
+    #[serde(default)]
+    pub zone: Option<Position>,
+    #[serde(default)]
+    pub flags: Vec<Flag>,
+    #[serde(default)]
+    pub events: Vec<FeedEvent>,
+    pub winner: Option<String>,
+    pub started: bool,
+    pub completed: bool,
+}
+
+/// Something the server sent back, told apart by its shape since answers
+/// don't say which request they're for.
+#[derive(Debug)]
+pub enum Answer {
+    /// IDs of the games waiting for players
+    Games(Vec<String>),
+    /// ID of the game we asked for
+    Created(String),
+    /// What we play as, and the movement values to play with
+    Joined { player_id: String, world: Box<World>, tunables: Tunables },
+    World(Box<World>),
+    /// A chunk of a map file, `chunks` long in all
+    MapChunk { chunks: usize, checksum: u64, data: String },
+    /// Told we're ready
+    Ready,
+    /// Whether the vote counted
+    Voted(bool),
+    /// Why the server said no
+    Refused(String),
+}
+
+impl Answer {
+
+    pub fn parse(text: &str) -> Option<Answer> {
+        // New games are the only answers that aren't JSON objects, their
+        // ID or why there isn't one
+        if !text.starts_with('{') {
+            return Some(if text.contains(' ') { Answer::Refused(text.to_string()) } else { Answer::Created(text.to_string()) })
+        }
+        let mut value: serde_json::Value = serde_json::from_str(text).ok()?;
+        if let Some(error) = value["error"].as_str() {
+            return Some(Answer::Refused(error.to_string()))
+        }
+        if let Some(player_id) = value["player_id"].as_str() {
+            return Some(Answer::Joined {
+                player_id: player_id.to_string(),
+                world: serde_json::from_value(value["world"].take()).ok()?,
+                tunables: serde_json::from_value(value["tunables"].take()).ok()?,
+            })
+        }
+        if let Some(games) = value["games"].as_array() {
+            let ids = games.iter().filter_map(|game| game[0].as_str()).map(|id| id.to_string()).collect();
+            return Some(Answer::Games(ids))
+        }
+        if let Some(chunks) = value["chunks"].as_u64() {
+            return Some(Answer::MapChunk {
+                chunks: chunks as usize,
+                checksum: value["checksum"].as_u64()?,
+                data: value["data"].as_str()?.to_string(),
+            })
+        }
+        if value["ready"].is_boolean() {
+            return Some(Answer::Ready)
+        }
+        if let Some(voted) = value["voted"].as_bool() {
+            return Some(Answer::Voted(voted))
+        }
+        if value["players"].is_array() {
+            return serde_json::from_value(value).ok().map(|world| Answer::World(Box::new(world)))
+        }
+        None
+    }
+}